@@ -2,13 +2,25 @@ pub mod button;
 pub mod container;
 pub mod rule;
 pub mod scrollable;
+pub mod syntax;
 pub mod text;
 pub mod text_input;
+pub mod theme;
 
 use iced::{Border, Color};
 
+pub use syntax::SyntaxTheme;
+pub use theme::{ThemeError, Variant};
+
+/**
+ * The colours of the editor, read from a theme file. See [`theme`] for the format.
+ */
 #[derive(Debug, Clone)]
 pub struct LaurelTheme {
+    /// The name the theme was loaded by, e.g. `one-dark`.
+    pub name: String,
+    pub variant: Variant,
+
     pub text: Color,
     pub subtle: Color,
     pub muted: Color,
@@ -23,37 +35,25 @@ pub struct LaurelTheme {
     pub highlight_high: Color,
 
     pub accent: Color,
+    pub cursor: Color,
+    pub selection: Color,
+
+    pub syntax: SyntaxTheme,
+}
+
+impl LaurelTheme {
+    /**
+     * Gets the colour of a highlight capture, falling back to the text colour when neither the
+     * capture nor any of its parents has one.
+     */
+    pub fn syntax_color(&self, capture_name: &str) -> Color {
+        self.syntax.get(capture_name).unwrap_or(self.text)
+    }
 }
 
 impl Default for LaurelTheme {
     fn default() -> Self {
-        Self {
-            text: Color::from_rgb8(87, 82, 121),
-            subtle: Color::from_rgb8(121, 117, 147),
-            muted: Color::from_rgb8(152, 147, 165),
-            base: Color::from_rgb8(250, 244, 237),
-            surface: Color::from_rgb8(255, 250, 243),
-            overlay: Color::from_rgb8(242, 233, 225),
-            border_low: Border {
-                radius: 4.0.into(),
-                width: 1.0,
-                color: Color::from_rgb8(244, 237, 232),
-            },
-            border_med: Border {
-                radius: 6.0.into(),
-                width: 1.5,
-                color: Color::from_rgb8(223, 218, 217),
-            },
-            border_hi: Border {
-                radius: 8.0.into(),
-                width: 2.0,
-                color: Color::from_rgb8(244, 237, 232),
-            },
-            highlight_low: Color::from_rgb8(244, 237, 232),
-            highlight_med: Color::from_rgb8(223, 218, 217),
-            highlight_high: Color::from_rgb8(244, 237, 232),
-            accent: Color::from_rgb8(144, 122, 169),
-        }
+        theme::bundled(theme::DEFAULT_THEME).expect("the default theme is bundled")
     }
 }
 
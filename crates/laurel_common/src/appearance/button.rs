@@ -17,14 +17,14 @@ impl Catalog for LaurelTheme {
     }
 }
 
-pub fn menu(_theme: &LaurelTheme, status: Status) -> Style {
+pub fn menu(theme: &LaurelTheme, status: Status) -> Style {
     Style {
         background: Some(Background::from(if status == Status::Hovered {
-            Color::from_rgb8(70, 77, 89)
+            theme.highlight_med
         } else {
-            Color::from_rgb8(25, 31, 43)
+            theme.overlay
         })),
-        text_color: Color::WHITE,
+        text_color: theme.text,
         ..Default::default()
     }
 }
@@ -57,3 +57,18 @@ pub fn transparent(theme: &LaurelTheme, status: Status) -> Style {
         ..Default::default()
     }
 }
+
+/**
+ * A row of a list that is selected, like the current completion.
+ */
+pub fn selected(theme: &LaurelTheme, _status: Status) -> Style {
+    Style {
+        background: Some(theme.highlight_med.into()),
+        text_color: theme.text,
+        border: Border {
+            radius: 4.0.into(),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
@@ -27,7 +27,7 @@ pub fn normal(theme: &LaurelTheme) -> Style {
 pub fn menu(theme: &LaurelTheme) -> Style {
     Style {
         background: Some(theme.overlay.into()),
-        text_color: Some(Color::WHITE),
+        text_color: Some(theme.text),
         ..Default::default()
     }
 }
@@ -40,10 +40,10 @@ pub fn floating(theme: &LaurelTheme) -> Style {
     }
 }
 
-pub fn saved(_theme: &LaurelTheme) -> Style {
+pub fn saved(theme: &LaurelTheme) -> Style {
     Style {
-        background: Some(Background::from(Color::from_rgba8(25, 31, 43, 1.0))),
-        text_color: Some(Color::from_rgb8(130, 130, 130)),
+        background: Some(Background::from(theme.overlay)),
+        text_color: Some(theme.muted),
         ..Default::default()
     }
 }
@@ -55,3 +55,12 @@ pub fn transparent(theme: &LaurelTheme) -> Style {
         ..Default::default()
     }
 }
+
+pub fn code(theme: &LaurelTheme) -> Style {
+    Style {
+        background: Some(theme.base.into()),
+        border: theme.border_low,
+        text_color: Some(theme.text),
+        ..Default::default()
+    }
+}
@@ -1,5 +1,5 @@
 use iced::{
-    Border, Color,
+    Border,
     widget::scrollable::{Catalog, Rail, Scroller, Status, Style, StyleFn},
 };
 
@@ -27,7 +27,7 @@ pub fn default(theme: &LaurelTheme, _status: Status) -> Style {
             background: None,
             border: Border::default(),
             scroller: Scroller {
-                color: Color::BLACK,
+                color: theme.highlight_high,
                 border: Border::default(),
             },
         },
@@ -35,7 +35,7 @@ pub fn default(theme: &LaurelTheme, _status: Status) -> Style {
             background: None,
             border: Border::default(),
             scroller: Scroller {
-                color: Color::BLACK,
+                color: theme.highlight_high,
                 border: Border::default(),
             },
         },
@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+use iced::Color;
+
+/**
+ * The colours of highlight captures.
+ *
+ * Scopes are dotted like the captures, and a capture without a colour of its own takes the colour
+ * of its closest parent, so `function.method` falls back to `function`.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct SyntaxTheme {
+    scopes: HashMap<String, Color>,
+}
+
+impl SyntaxTheme {
+    pub fn new(scopes: HashMap<String, Color>) -> Self {
+        Self { scopes }
+    }
+
+    pub fn get(&self, capture_name: &str) -> Option<Color> {
+        let mut scope = capture_name;
+        loop {
+            if let Some(color) = self.scopes.get(scope) {
+                return Some(*color);
+            }
+            scope = scope.rsplit_once('.')?.0;
+        }
+    }
+
+    pub fn insert(&mut self, scope: String, color: Color) {
+        self.scopes.insert(scope, color);
+    }
+}
+
+#[cfg(test)]
+mod syntax_tests {
+    use super::SyntaxTheme;
+    use iced::Color;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_scopes_fall_back_to_parent() {
+        let mut syntax = SyntaxTheme::default();
+        syntax.insert(String::from("function"), Color::WHITE);
+        syntax.insert(String::from("function.macro"), Color::BLACK);
+
+        assert_eq!(syntax.get("function.method"), Some(Color::WHITE));
+        assert_eq!(syntax.get("function.macro.builtin"), Some(Color::BLACK));
+        assert_eq!(syntax.get("function"), Some(Color::WHITE));
+        assert_eq!(syntax.get("keyword"), None);
+    }
+}
@@ -1,5 +1,5 @@
+use iced::Border;
 use iced::widget::text_input::{Catalog, Status, Style, StyleFn};
-use iced::{Border, Color};
 
 use crate::LaurelTheme;
 
@@ -21,7 +21,7 @@ pub fn default(theme: &LaurelTheme, _status: Status) -> Style {
         value: theme.text,
         icon: theme.text,
         border: Border::default(),
-        placeholder: Color::BLACK,
-        selection: Color::WHITE,
+        placeholder: theme.muted,
+        selection: theme.selection,
     }
 }
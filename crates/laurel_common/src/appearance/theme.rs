@@ -0,0 +1,393 @@
+//! Themes are TOML files with a `[ui]` table for the editor's colours and a `[syntax]` table for
+//! the colours of highlight captures:
+//!
+//! ```toml
+//! variant = "dark"
+//!
+//! [palette]
+//! blue = "#61afef"
+//!
+//! [ui]
+//! base = "#282c34"
+//! accent = "blue"
+//!
+//! [syntax]
+//! function = "blue"
+//! "function.macro" = "#c678dd"
+//! ```
+//!
+//! Colours are `#rgb`, `#rrggbb` or `#rrggbbaa`, or the name of a colour in the `[palette]`.
+//! UI colours left out come from the bundled theme of the same variant, and captures without a
+//! colour fall back to their parent scope, then to the text colour. Files without any of these
+//! tables are imported as Helix themes, see [`import`].
+
+use std::collections::HashMap;
+
+use iced::{Border, Color};
+use toml::{Table, Value};
+
+use super::{LaurelTheme, SyntaxTheme};
+
+pub mod import;
+
+pub const DEFAULT_THEME: &str = "one-dark";
+const DEFAULT_LIGHT_THEME: &str = "one-light";
+
+pub const BUNDLED_THEMES: &[(&str, &str)] = &[
+    (
+        "one-dark",
+        include_str!("../../../../data/themes/one-dark.toml"),
+    ),
+    (
+        "one-light",
+        include_str!("../../../../data/themes/one-light.toml"),
+    ),
+    (
+        "rose-pine",
+        include_str!("../../../../data/themes/rose-pine.toml"),
+    ),
+    (
+        "rose-pine-dawn",
+        include_str!("../../../../data/themes/rose-pine-dawn.toml"),
+    ),
+];
+
+/// The keys of the `[ui]` table.
+const UI_COLORS: &[&str] = &[
+    "text",
+    "subtle",
+    "muted",
+    "base",
+    "surface",
+    "overlay",
+    "border_low",
+    "border_med",
+    "border_hi",
+    "highlight_low",
+    "highlight_med",
+    "highlight_high",
+    "accent",
+    "cursor",
+    "selection",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    Light,
+    Dark,
+}
+
+impl Variant {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "light" => Some(Variant::Light),
+            "dark" => Some(Variant::Dark),
+            _ => None,
+        }
+    }
+
+    /// Guesses the variant of an imported theme from its background.
+    fn from_background(background: Color) -> Self {
+        let luminance = 0.299 * background.r + 0.587 * background.g + 0.114 * background.b;
+        if luminance > 0.5 {
+            Variant::Light
+        } else {
+            Variant::Dark
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ThemeError {
+    #[error("Invalid TOML: `{0}`")]
+    Toml(#[from] toml::de::Error),
+    #[error("Invalid JSON: `{0}`")]
+    Json(#[from] serde_json::Error),
+    #[error("Couldn't read the theme: `{0}`")]
+    Io(#[from] std::io::Error),
+    #[error("No theme named `{0}`")]
+    NotFound(String),
+    #[error("Unknown variant `{0}`, expected `light` or `dark`")]
+    UnknownVariant(String),
+    #[error("`{0}` should be a table")]
+    ExpectedTable(String),
+    #[error("Unknown UI colour `{0}`")]
+    UnknownColor(String),
+    #[error("Missing UI colour `{0}`")]
+    MissingColor(String),
+    #[error("Invalid colour `{0}`")]
+    InvalidColor(String),
+}
+
+/**
+ * The colours read from a theme file, before the missing ones are filled in.
+ */
+#[derive(Default)]
+struct ThemeColors {
+    variant: Option<Variant>,
+    ui: HashMap<String, Color>,
+    syntax: HashMap<String, Color>,
+}
+
+impl LaurelTheme {
+    /**
+     * Reads a theme in Laurel's own format, or imports it as a Helix theme when it has none of
+     * Laurel's tables.
+     */
+    pub fn from_toml(name: &str, source: &str) -> Result<Self, ThemeError> {
+        let table: Table = source.parse()?;
+        let is_laurel = ["variant", "ui", "syntax"]
+            .iter()
+            .any(|key| table.contains_key(*key));
+        if !is_laurel {
+            return import::helix_table(name, &table);
+        }
+
+        let colors = parse(&table)?;
+        let fallback = fallback(colors.variant.unwrap_or(Variant::Dark));
+        build(name, colors, Some(&fallback))
+    }
+}
+
+/**
+ * Gets one of the themes shipped with the editor.
+ */
+pub fn bundled(name: &str) -> Option<LaurelTheme> {
+    let (name, source) = BUNDLED_THEMES
+        .iter()
+        .find(|(bundled, _)| *bundled == name)?;
+    let theme = source
+        .parse()
+        .map_err(ThemeError::from)
+        .and_then(|table| parse(&table))
+        .and_then(|colors| build(name, colors, None));
+    Some(theme.expect("bundled themes are complete"))
+}
+
+fn fallback(variant: Variant) -> LaurelTheme {
+    let name = match variant {
+        Variant::Light => DEFAULT_LIGHT_THEME,
+        Variant::Dark => DEFAULT_THEME,
+    };
+    bundled(name).expect("the fallback themes are bundled")
+}
+
+fn parse(table: &Table) -> Result<ThemeColors, ThemeError> {
+    let variant = match table.get("variant") {
+        Some(variant) => {
+            let name = variant.as_str().unwrap_or_default();
+            Some(Variant::from_name(name).ok_or_else(|| ThemeError::UnknownVariant(name.into()))?)
+        }
+        None => None,
+    };
+    let palette = section(table, "palette")?;
+
+    let ui = colors(table, "ui", palette)?;
+    if let Some(key) = ui.keys().find(|key| !UI_COLORS.contains(&key.as_str())) {
+        return Err(ThemeError::UnknownColor(key.clone()));
+    }
+
+    Ok(ThemeColors {
+        variant,
+        ui,
+        syntax: colors(table, "syntax", palette)?,
+    })
+}
+
+fn section<'a>(table: &'a Table, key: &str) -> Result<Option<&'a Table>, ThemeError> {
+    table
+        .get(key)
+        .map(|value| {
+            value
+                .as_table()
+                .ok_or_else(|| ThemeError::ExpectedTable(key.to_owned()))
+        })
+        .transpose()
+}
+
+fn colors(
+    table: &Table,
+    key: &str,
+    palette: Option<&Table>,
+) -> Result<HashMap<String, Color>, ThemeError> {
+    let Some(section) = section(table, key)? else {
+        return Ok(HashMap::new());
+    };
+    section
+        .iter()
+        .map(|(scope, value)| {
+            let value = value
+                .as_str()
+                .ok_or_else(|| ThemeError::InvalidColor(value.to_string()))?;
+            Ok((scope.clone(), resolve(value, palette)?))
+        })
+        .collect()
+}
+
+/**
+ * Parses a colour, looking it up in the palette first.
+ */
+fn resolve(value: &str, palette: Option<&Table>) -> Result<Color, ThemeError> {
+    let value = palette
+        .and_then(|palette| palette.get(value))
+        .and_then(Value::as_str)
+        .unwrap_or(value);
+    parse_color(value).ok_or_else(|| ThemeError::InvalidColor(value.to_owned()))
+}
+
+/**
+ * Parses a `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa` colour.
+ */
+pub fn parse_color(value: &str) -> Option<Color> {
+    let hex = value.strip_prefix('#')?;
+    if !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channels: Vec<u8> = match hex.len() {
+        3 | 4 => hex
+            .chars()
+            .filter_map(|digit| digit.to_digit(16))
+            .map(|digit| digit as u8 * 17)
+            .collect(),
+        6 | 8 => (0..hex.len())
+            .step_by(2)
+            .filter_map(|index| u8::from_str_radix(&hex[index..index + 2], 16).ok())
+            .collect(),
+        _ => return None,
+    };
+
+    let alpha = channels.get(3).map_or(1.0, |alpha| *alpha as f32 / 255.0);
+    Some(Color::from_rgba8(
+        channels[0],
+        channels[1],
+        channels[2],
+        alpha,
+    ))
+}
+
+/**
+ * Fills in the colours the theme left out from `fallback`. Without a fallback every UI colour is
+ * required.
+ */
+fn build(
+    name: &str,
+    colors: ThemeColors,
+    fallback: Option<&LaurelTheme>,
+) -> Result<LaurelTheme, ThemeError> {
+    let ui = |key: &str, get: fn(&LaurelTheme) -> Color| -> Result<Color, ThemeError> {
+        colors
+            .ui
+            .get(key)
+            .copied()
+            .or_else(|| fallback.map(get))
+            .ok_or_else(|| ThemeError::MissingColor(key.to_owned()))
+    };
+    let border = |radius: f32, width: f32, color: Color| Border {
+        radius: radius.into(),
+        width,
+        color,
+    };
+
+    Ok(LaurelTheme {
+        name: name.to_owned(),
+        variant: colors
+            .variant
+            .or(fallback.map(|theme| theme.variant))
+            .unwrap_or(Variant::Dark),
+        text: ui("text", |theme| theme.text)?,
+        subtle: ui("subtle", |theme| theme.subtle)?,
+        muted: ui("muted", |theme| theme.muted)?,
+        base: ui("base", |theme| theme.base)?,
+        surface: ui("surface", |theme| theme.surface)?,
+        overlay: ui("overlay", |theme| theme.overlay)?,
+        border_low: border(4.0, 1.0, ui("border_low", |theme| theme.border_low.color)?),
+        border_med: border(6.0, 1.5, ui("border_med", |theme| theme.border_med.color)?),
+        border_hi: border(8.0, 2.0, ui("border_hi", |theme| theme.border_hi.color)?),
+        highlight_low: ui("highlight_low", |theme| theme.highlight_low)?,
+        highlight_med: ui("highlight_med", |theme| theme.highlight_med)?,
+        highlight_high: ui("highlight_high", |theme| theme.highlight_high)?,
+        accent: ui("accent", |theme| theme.accent)?,
+        cursor: ui("cursor", |theme| theme.cursor)?,
+        selection: ui("selection", |theme| theme.selection)?,
+        syntax: SyntaxTheme::new(colors.syntax),
+    })
+}
+
+#[cfg(test)]
+mod theme_tests {
+    use super::{BUNDLED_THEMES, Variant, bundled, parse_color};
+    use crate::appearance::{LaurelTheme, ThemeError};
+    use iced::Color;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_bundled_themes_are_complete() {
+        for (name, _) in BUNDLED_THEMES {
+            let theme = bundled(name).unwrap();
+            assert_eq!(theme.name, *name);
+        }
+        assert_eq!(bundled("one-light").unwrap().variant, Variant::Light);
+        assert!(bundled("missing").is_none());
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#ff8000"), Some(Color::from_rgb8(255, 128, 0)));
+        assert_eq!(parse_color("#f80"), Some(Color::from_rgb8(255, 136, 0)));
+        assert_eq!(
+            parse_color("#ff800080"),
+            Some(Color::from_rgba8(255, 128, 0, 128.0 / 255.0))
+        );
+        assert_eq!(parse_color("ff8000"), None);
+        assert_eq!(
+            parse_color("#ff80"),
+            Some(Color::from_rgba8(255, 255, 136, 0.0))
+        );
+        assert_eq!(parse_color("#+f8000"), None);
+    }
+
+    #[test]
+    fn test_missing_colors_fall_back_to_variant() {
+        let source = r##"
+            variant = "light"
+
+            [palette]
+            ink = "#101010"
+
+            [ui]
+            text = "ink"
+
+            [syntax]
+            function = "#0000ff"
+        "##;
+        let theme = LaurelTheme::from_toml("ink", source).unwrap();
+        let light = bundled("one-light").unwrap();
+
+        assert_eq!(theme.name, "ink");
+        assert_eq!(theme.text, Color::from_rgb8(16, 16, 16));
+        assert_eq!(theme.base, light.base);
+        assert_eq!(
+            theme.syntax_color("function.method"),
+            Color::from_rgb8(0, 0, 255)
+        );
+        assert_eq!(theme.syntax_color("keyword"), theme.text);
+    }
+
+    #[test]
+    fn test_invalid_themes() {
+        let error = |source| LaurelTheme::from_toml("broken", source).unwrap_err();
+
+        assert!(
+            matches!(error("[ui]\ntxt = \"#fff\""), ThemeError::UnknownColor(key) if key == "txt")
+        );
+        assert!(matches!(
+            error("[ui]\ntext = \"blue\""),
+            ThemeError::InvalidColor(_)
+        ));
+        assert!(matches!(
+            error("variant = \"dim\""),
+            ThemeError::UnknownVariant(_)
+        ));
+        assert!(matches!(error("ui = 1"), ThemeError::ExpectedTable(_)));
+    }
+}
@@ -0,0 +1,437 @@
+//! Imports Helix and VS Code themes, mapping their colours onto Laurel's. Colours without a
+//! counterpart are left out and fall back like in any other theme.
+
+use std::collections::HashMap;
+
+use iced::Color;
+use serde_json::Value as Json;
+use toml::{Table, Value};
+
+use super::{ThemeColors, ThemeError, Variant, build, fallback, parse_color, resolve};
+use crate::LaurelTheme;
+
+/// Helix scopes and the `fg` or `bg` colours each UI colour is taken from, in order of preference.
+const HELIX_UI: &[(&str, &[(&str, &str)])] = &[
+    ("text", &[("ui.text", "fg")]),
+    (
+        "subtle",
+        &[("ui.text.inactive", "fg"), ("ui.linenr.selected", "fg")],
+    ),
+    ("muted", &[("ui.linenr", "fg"), ("comment", "fg")]),
+    ("base", &[("ui.background", "bg")]),
+    (
+        "surface",
+        &[
+            ("ui.popup", "bg"),
+            ("ui.menu", "bg"),
+            ("ui.statusline", "bg"),
+        ],
+    ),
+    ("overlay", &[("ui.menu", "bg"), ("ui.statusline", "bg")]),
+    (
+        "border_low",
+        &[("ui.cursorline.primary", "bg"), ("ui.cursorline", "bg")],
+    ),
+    ("border_med", &[("ui.window", "fg"), ("ui.selection", "bg")]),
+    ("border_hi", &[("ui.window", "fg"), ("ui.linenr", "fg")]),
+    (
+        "highlight_low",
+        &[("ui.cursorline.primary", "bg"), ("ui.cursorline", "bg")],
+    ),
+    (
+        "highlight_med",
+        &[("ui.selection", "bg"), ("ui.menu.selected", "bg")],
+    ),
+    (
+        "highlight_high",
+        &[("ui.menu.selected", "bg"), ("ui.selection", "bg")],
+    ),
+    ("accent", &[("ui.text.focus", "fg"), ("function", "fg")]),
+    (
+        "cursor",
+        &[("ui.cursor.primary", "bg"), ("ui.cursor", "bg")],
+    ),
+    (
+        "selection",
+        &[("ui.selection.primary", "bg"), ("ui.selection", "bg")],
+    ),
+];
+
+/// Helix scopes named differently from the captures of Laurel's queries.
+const HELIX_SCOPES: &[(&str, &str)] = &[
+    ("markup.heading", "text.title"),
+    ("markup.bold", "text.strong"),
+    ("markup.italic", "text.emphasis"),
+    ("markup.raw", "text.literal"),
+    ("markup.link.url", "text.uri"),
+    ("markup.link.text", "text.reference"),
+    ("constant.character.escape", "string.escape"),
+    ("constant.numeric", "number"),
+];
+
+/// VS Code workbench colours each UI colour is taken from, in order of preference.
+const VSCODE_UI: &[(&str, &[&str])] = &[
+    ("text", &["editor.foreground", "foreground"]),
+    (
+        "subtle",
+        &["descriptionForeground", "editorLineNumber.activeForeground"],
+    ),
+    (
+        "muted",
+        &["editorLineNumber.foreground", "disabledForeground"],
+    ),
+    ("base", &["editor.background"]),
+    (
+        "surface",
+        &["editorWidget.background", "sideBar.background"],
+    ),
+    (
+        "overlay",
+        &["titleBar.activeBackground", "sideBar.background"],
+    ),
+    ("border_low", &["editorGroup.border", "panel.border"]),
+    ("border_med", &["editorWidget.border", "panel.border"]),
+    ("border_hi", &["focusBorder"]),
+    (
+        "highlight_low",
+        &["editor.lineHighlightBackground", "list.hoverBackground"],
+    ),
+    (
+        "highlight_med",
+        &["list.hoverBackground", "editor.selectionBackground"],
+    ),
+    (
+        "highlight_high",
+        &[
+            "list.activeSelectionBackground",
+            "editor.selectionBackground",
+        ],
+    ),
+    ("accent", &["button.background", "focusBorder"]),
+    ("cursor", &["editorCursor.foreground"]),
+    ("selection", &["editor.selectionBackground"]),
+];
+
+/// TextMate scopes and the captures they colour.
+const TEXTMATE_SCOPES: &[(&str, &str)] = &[
+    ("comment", "comment"),
+    ("string", "string"),
+    ("string.regexp", "string.special"),
+    ("constant", "constant"),
+    ("constant.language", "constant.builtin"),
+    ("constant.numeric", "number"),
+    ("constant.character.escape", "string.escape"),
+    ("keyword", "keyword"),
+    ("keyword.operator", "operator"),
+    ("entity.name.function", "function"),
+    ("entity.name.function.macro", "function.macro"),
+    ("support.function", "function.builtin"),
+    ("entity.name.type", "type"),
+    ("support.type", "type.builtin"),
+    ("entity.other.attribute-name", "attribute"),
+    ("entity.name.label", "label"),
+    ("variable", "variable"),
+    ("variable.parameter", "variable.parameter"),
+    ("variable.language", "variable.builtin"),
+    ("variable.other.property", "property"),
+    ("punctuation", "punctuation"),
+    ("markup.heading", "text.title"),
+    ("markup.bold", "text.strong"),
+    ("markup.italic", "text.emphasis"),
+    ("markup.inline.raw", "text.literal"),
+    ("markup.underline.link", "text.uri"),
+];
+
+/**
+ * Imports a Helix theme. Themes it inherits from aren't followed.
+ */
+pub fn helix(name: &str, source: &str) -> Result<LaurelTheme, ThemeError> {
+    helix_table(name, &source.parse()?)
+}
+
+pub(super) fn helix_table(name: &str, table: &Table) -> Result<LaurelTheme, ThemeError> {
+    let palette = table.get("palette").and_then(Value::as_table);
+
+    // A scope is either its foreground colour or a table with `fg`, `bg` and `modifiers`
+    let color = |scope: &str, key: &str| {
+        let value = match table.get(scope)? {
+            Value::String(value) if key == "fg" => value.as_str(),
+            Value::Table(style) => style.get(key)?.as_str()?,
+            _ => return None,
+        };
+        resolve(value, palette).ok()
+    };
+
+    let mut colors = ThemeColors::default();
+    for (key, sources) in HELIX_UI {
+        let found = sources.iter().find_map(|(scope, key)| color(scope, key));
+        if let Some(found) = found {
+            colors.ui.insert((*key).to_owned(), found);
+        }
+    }
+
+    let is_syntax = |scope: &&String| {
+        !scope.starts_with("ui.")
+            && !scope.starts_with("diagnostic")
+            && !["palette", "inherits", "warning", "error", "info", "hint"]
+                .contains(&scope.as_str())
+    };
+    for scope in table.keys().filter(is_syntax) {
+        let Some(found) = color(scope, "fg") else {
+            continue;
+        };
+        let capture = HELIX_SCOPES
+            .iter()
+            .find(|(helix, _)| helix == scope)
+            .map_or(scope.as_str(), |(_, capture)| capture);
+        colors.syntax.insert(capture.to_owned(), found);
+    }
+
+    colors.variant = colors.ui.get("base").copied().map(Variant::from_background);
+    import(name, colors)
+}
+
+/**
+ * Imports a VS Code colour theme, which can have comments and trailing commas.
+ */
+pub fn vscode(name: &str, source: &str) -> Result<LaurelTheme, ThemeError> {
+    let theme: Json = serde_json::from_str(&strip_jsonc(source))?;
+    let color = |key: &str| {
+        theme
+            .get("colors")?
+            .get(key)?
+            .as_str()
+            .and_then(parse_color)
+    };
+
+    let mut colors = ThemeColors::default();
+    for (key, sources) in VSCODE_UI {
+        if let Some(found) = sources.iter().find_map(|source| color(source)) {
+            colors.ui.insert((*key).to_owned(), found);
+        }
+    }
+    colors.syntax = token_colors(theme.get("tokenColors"));
+
+    colors.variant = match theme.get("type").and_then(Json::as_str) {
+        Some("light" | "hc-light") => Some(Variant::Light),
+        Some("dark" | "hc-black") => Some(Variant::Dark),
+        _ => colors.ui.get("base").copied().map(Variant::from_background),
+    };
+    import(name, colors)
+}
+
+fn import(name: &str, colors: ThemeColors) -> Result<LaurelTheme, ThemeError> {
+    let fallback = fallback(colors.variant.unwrap_or(Variant::Dark));
+    build(name, colors, Some(&fallback))
+}
+
+/**
+ * Picks the colour of each capture from the TextMate rules. Like in TextMate the most specific
+ * selector wins, and the later rule wins between equally specific ones.
+ */
+fn token_colors(rules: Option<&Json>) -> HashMap<String, Color> {
+    let rules = rules
+        .and_then(Json::as_array)
+        .map_or(&[][..], Vec::as_slice);
+
+    // The specificity and index of the rule each capture's colour came from
+    let mut chosen: HashMap<&str, ((usize, usize), Color)> = HashMap::new();
+    for (index, rule) in rules.iter().enumerate() {
+        let Some(color) = rule
+            .pointer("/settings/foreground")
+            .and_then(Json::as_str)
+            .and_then(parse_color)
+        else {
+            continue;
+        };
+
+        for selector in selectors(rule.get("scope")) {
+            for (textmate, capture) in TEXTMATE_SCOPES {
+                let applies = *textmate == selector
+                    || textmate
+                        .strip_prefix(selector)
+                        .is_some_and(|rest| rest.starts_with('.'));
+                if !applies {
+                    continue;
+                }
+
+                let rank = (selector.split('.').count(), index);
+                if chosen.get(capture).is_none_or(|(best, _)| rank >= *best) {
+                    chosen.insert(capture, (rank, color));
+                }
+            }
+        }
+    }
+
+    chosen
+        .into_iter()
+        .map(|(capture, (_, color))| (capture.to_owned(), color))
+        .collect()
+}
+
+/**
+ * Splits the `scope` of a rule into selectors, keeping only the last scope of descendant
+ * selectors like `source.rust keyword`.
+ */
+fn selectors(scope: Option<&Json>) -> Vec<&str> {
+    let scopes: Vec<&str> = match scope {
+        Some(Json::String(scope)) => scope.split(',').collect(),
+        Some(Json::Array(scopes)) => scopes.iter().filter_map(Json::as_str).collect(),
+        _ => Vec::new(),
+    };
+    scopes
+        .into_iter()
+        .filter_map(|scope| scope.split_whitespace().last())
+        .collect()
+}
+
+/**
+ * Removes the comments and trailing commas JSON doesn't allow.
+ */
+fn strip_jsonc(source: &str) -> String {
+    let mut json = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            json.push(c);
+            match c {
+                '\\' => json.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                json.push(c);
+            }
+            ('/', Some('/')) => while chars.next_if(|c| *c != '\n').is_some() {},
+            ('/', Some('*')) => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+            }
+            ('}' | ']', _) => {
+                let trimmed = json.trim_end().len();
+                if json[..trimmed].ends_with(',') {
+                    json.truncate(trimmed - 1);
+                }
+                json.push(c);
+            }
+            _ => json.push(c),
+        }
+    }
+    json
+}
+
+#[cfg(test)]
+mod import_tests {
+    use super::{helix, strip_jsonc, vscode};
+    use crate::{
+        LaurelTheme,
+        appearance::theme::{Variant, bundled},
+    };
+    use iced::Color;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_strip_jsonc() {
+        let source =
+            "{\n  // A comment\n  \"a\": \"// not a comment\", /* block */\n  \"b\": [1, 2,],\n}";
+        assert_eq!(
+            strip_jsonc(source),
+            "{\n  \n  \"a\": \"// not a comment\", \n  \"b\": [1, 2]}"
+        );
+    }
+
+    #[test]
+    fn test_helix_import() {
+        let source = r##"
+            "ui.background" = { bg = "white" }
+            "ui.text" = "black"
+            "keyword" = { fg = "purple", modifiers = ["bold"] }
+            "markup.heading" = "purple"
+            "diagnostic.error" = { underline = { color = "purple" } }
+
+            [palette]
+            white = "#fafafa"
+            black = "#101010"
+            purple = "#a626a4"
+        "##;
+        let theme = helix("paper", source).unwrap();
+        let detected = LaurelTheme::from_toml("paper", source).unwrap();
+        assert_eq!(detected.base, theme.base);
+
+        assert_eq!(theme.variant, Variant::Light);
+        assert_eq!(theme.base, Color::from_rgb8(250, 250, 250));
+        assert_eq!(theme.text, Color::from_rgb8(16, 16, 16));
+        assert_eq!(
+            theme.syntax_color("keyword.control"),
+            Color::from_rgb8(166, 38, 164)
+        );
+        assert_eq!(
+            theme.syntax_color("text.title"),
+            Color::from_rgb8(166, 38, 164)
+        );
+        // Left out colours come from the light fallback
+        assert_eq!(theme.cursor, bundled("one-light").unwrap().cursor);
+    }
+
+    #[test]
+    fn test_vscode_import() {
+        let source = r##"{
+            "type": "dark",
+            "colors": {
+                "editor.background": "#1e1e1e",
+                "editor.selectionBackground": "#264f78",
+            },
+            "tokenColors": [
+                { "scope": "keyword", "settings": { "foreground": "#569cd6" } },
+                {
+                    "scope": ["keyword.operator", "source.rust punctuation"],
+                    "settings": { "foreground": "#d4d4d4" }
+                },
+                {
+                    "scope": "constant.numeric, entity.name.function",
+                    "settings": { "foreground": "#b5cea8" }
+                },
+                // Less specific than `keyword.operator`, so the operator keeps its colour
+                { "scope": "keyword", "settings": { "foreground": "#c586c0" } },
+            ],
+        }"##;
+        let theme = vscode("dark-plus", source).unwrap();
+
+        assert_eq!(theme.variant, Variant::Dark);
+        assert_eq!(theme.selection, Color::from_rgb8(38, 79, 120));
+        assert_eq!(
+            theme.syntax_color("keyword"),
+            Color::from_rgb8(197, 134, 192)
+        );
+        assert_eq!(
+            theme.syntax_color("operator"),
+            Color::from_rgb8(212, 212, 212)
+        );
+        assert_eq!(
+            theme.syntax_color("punctuation.bracket"),
+            Color::from_rgb8(212, 212, 212)
+        );
+        assert_eq!(
+            theme.syntax_color("number"),
+            Color::from_rgb8(181, 206, 168)
+        );
+        assert_eq!(
+            theme.syntax_color("function.method"),
+            Color::from_rgb8(181, 206, 168)
+        );
+        assert_eq!(theme.syntax_color("string"), theme.text);
+    }
+}
@@ -3,6 +3,10 @@ use async_lsp::lsp_types;
 pub trait CursorMessage {
     fn from_cursor_position(pos: Position) -> Self;
     fn from_selection_move(pos: Position) -> Self;
+    /// The text under the mouse, `None` when the mouse left it
+    fn from_mouse_hover(pos: Option<Position>) -> Self;
+    /// The inlay hint under the mouse by its index, `None` when the mouse left it
+    fn from_inlay_hint_hover(hint: Option<usize>) -> Self;
 }
 
 #[derive(Debug, Clone, Copy, Default, Eq)]
@@ -0,0 +1,19 @@
+(pair
+  key: (string) @property)
+
+(string) @string
+(escape_sequence) @escape
+(number) @constant
+[
+  (true)
+  (false)
+  (null)
+] @constant.builtin
+
+"," @punctuation.delimiter
+":" @punctuation.delimiter
+
+"[" @punctuation.bracket
+"]" @punctuation.bracket
+"{" @punctuation.bracket
+"}" @punctuation.bracket
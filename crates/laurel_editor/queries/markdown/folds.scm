@@ -0,0 +1,5 @@
+[
+  (fenced_code_block)
+  (list)
+  (block_quote)
+] @fold
@@ -0,0 +1,34 @@
+[
+  (atx_heading)
+  (setext_heading)
+] @text.title
+
+(code_fence_content) @none
+
+[
+  (indented_code_block)
+  (fenced_code_block)
+  (code_span)
+] @text.literal
+
+
+(emphasis) @text.emphasis
+
+(strong_emphasis) @text.strong
+
+(link_destination) @text.uri
+
+(link_label) @text.reference
+
+[
+  (list_marker_plus)
+  (list_marker_minus)
+  (list_marker_star)
+  (list_marker_dot)
+  (list_marker_parenthesis)
+] @punctuation.special
+
+[
+  (backslash_escape)
+  (hard_line_break)
+] @string.escape
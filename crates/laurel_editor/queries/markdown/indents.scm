@@ -0,0 +1,4 @@
+[
+  (list_item)
+  (block_quote)
+] @indent
@@ -0,0 +1,11 @@
+(fenced_code_block
+  (info_string
+    (language) @injection.language)
+  (code_fence_content) @injection.content)
+
+; A code block without a language is in the code language of the layer, e.g. Rust in a doc
+; comment, and isn't highlighted otherwise
+(fenced_code_block
+  (fenced_code_block_delimiter)
+  .
+  (code_fence_content) @injection.content)
@@ -0,0 +1,15 @@
+[
+  (block)
+  (argument_list)
+  (parameters)
+  (list)
+  (dictionary)
+  (set)
+  (tuple)
+] @indent
+
+[
+  ")"
+  "]"
+  "}"
+] @outdent
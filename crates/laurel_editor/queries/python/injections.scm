@@ -0,0 +1,16 @@
+; re.compile("..."), re.match(r"...", text)
+(call
+  function: (attribute
+    object: (identifier) @_re
+    attribute: (identifier) @_function)
+  arguments: (argument_list
+    .
+    (string
+      (string_content) @injection.content))
+  (#eq? @_re "re")
+  (#match? @_function "^(compile|search|match|fullmatch|split|findall|finditer|sub|subn)$")
+  (#set! injection.language "regex"))
+
+((string_content) @injection.content
+  (#match? @injection.content "^\\s*(SELECT|INSERT|UPDATE|DELETE|CREATE|ALTER|DROP|WITH) ")
+  (#set! injection.language "sql"))
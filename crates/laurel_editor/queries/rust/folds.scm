@@ -0,0 +1,9 @@
+[
+  (block)
+  (declaration_list)
+  (field_declaration_list)
+  (enum_variant_list)
+  (match_block)
+  (use_list)
+  (block_comment)
+] @fold
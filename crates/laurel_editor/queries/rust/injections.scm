@@ -0,0 +1,66 @@
+; Doc comments are Markdown, so the code examples in them are highlighted too. The offset leaves
+; out the `///` and keeps the newline, since the comment doesn't include it. Like rustdoc, the
+; examples without a language are Rust.
+((line_comment) @injection.content
+  (#match? @injection.content "^//[/!]")
+  (#offset! @injection.content 0 3 0 1)
+  (#set! injection.language "markdown")
+  (#set! injection.code-language "rust")
+  (#set! injection.combined))
+
+; Regex::new("..."), RegexBuilder::new("...")
+(call_expression
+  function: (scoped_identifier
+    path: (identifier) @_regex
+    name: (identifier) @_new)
+  arguments: (arguments
+    .
+    (string_literal) @injection.content)
+  (#match? @_regex "^(Regex|RegexBuilder|RegexSet)$")
+  (#eq? @_new "new")
+  (#offset! @injection.content 0 1 0 -1)
+  (#set! injection.language "regex"))
+
+(call_expression
+  function: (scoped_identifier
+    path: (identifier) @_regex
+    name: (identifier) @_new)
+  arguments: (arguments
+    .
+    (raw_string_literal) @injection.content)
+  (#match? @_regex "^(Regex|RegexBuilder|RegexSet)$")
+  (#eq? @_new "new")
+  (#match? @injection.content "^r\"")
+  (#offset! @injection.content 0 2 0 -1)
+  (#set! injection.language "regex"))
+
+(call_expression
+  function: (scoped_identifier
+    path: (identifier) @_regex
+    name: (identifier) @_new)
+  arguments: (arguments
+    .
+    (raw_string_literal) @injection.content)
+  (#match? @_regex "^(Regex|RegexBuilder|RegexSet)$")
+  (#eq? @_new "new")
+  (#match? @injection.content "^r#\"")
+  (#offset! @injection.content 0 3 0 -2)
+  (#set! injection.language "regex"))
+
+; sqlx::query!("..."), query_as!(User, "...")
+(macro_invocation
+  macro: [
+    (identifier) @_macro
+    (scoped_identifier
+      name: (identifier) @_macro)
+  ]
+  (token_tree
+    (string_literal) @injection.content)
+  (#match? @_macro "^query(_as|_scalar)?(_unchecked)?$")
+  (#offset! @injection.content 0 1 0 -1)
+  (#set! injection.language "sql"))
+
+((string_literal) @injection.content
+  (#match? @injection.content "^\"\\s*(SELECT|INSERT|UPDATE|DELETE|CREATE|ALTER|DROP|WITH) ")
+  (#offset! @injection.content 0 1 0 -1)
+  (#set! injection.language "sql"))
@@ -0,0 +1,66 @@
+(comment) @comment
+
+(literal) @string
+
+(invocation
+  (object_reference
+    name: (identifier) @function))
+
+(object_reference
+  name: (identifier) @type)
+
+(field
+  name: (identifier) @property)
+
+[
+  (keyword_null)
+  (keyword_true)
+  (keyword_false)
+] @constant.builtin
+
+[
+  (keyword_select)
+  (keyword_insert)
+  (keyword_update)
+  (keyword_delete)
+  (keyword_create)
+  (keyword_table)
+  (keyword_into)
+  (keyword_values)
+  (keyword_set)
+  (keyword_from)
+  (keyword_where)
+  (keyword_join)
+  (keyword_on)
+  (keyword_as)
+  (keyword_order)
+  (keyword_group)
+  (keyword_by)
+  (keyword_limit)
+  (keyword_distinct)
+  (keyword_and)
+  (keyword_or)
+  (keyword_not)
+  (keyword_in)
+  (keyword_is)
+] @keyword
+
+[
+  "="
+  "<"
+  ">"
+  "+"
+  "-"
+  "*"
+] @operator
+
+[
+  "("
+  ")"
+] @punctuation.bracket
+
+[
+  ","
+  "."
+  ";"
+] @punctuation.delimiter
@@ -0,0 +1,5 @@
+[
+  (table)
+  (table_array_element)
+  (array)
+] @fold
@@ -21,7 +21,7 @@ pub fn main() -> iced::Result {
         .init();
 
     iced::application(|| Editor::new(()), Editor::update, Editor::view)
-        .title("Laurel LSP test")
+        .title(Editor::title)
         .theme(Editor::theme)
         // .scale_factor(Editor::scale_factor)
         .subscription(Editor::subscription)
@@ -2,29 +2,45 @@ use crate::{
     highlighter::{Highlighter, HighlighterConfig},
     Message,
 };
+use async_lsp::lsp_types::{
+    Diagnostic, SemanticTokens, SemanticTokensDelta, SemanticTokensLegend, TextEdit,
+};
 use iced::{clipboard, widget::text, Task};
-use laurel_lsp::diagnostics::{ClientDiagnostics, Issue};
+use laurel_lsp::{
+    diagnostics::{ClientDiagnostics, Issue},
+    TextChange,
+};
 use ropey::iter::Lines;
+use std::cmp::Reverse;
 
 use super::{
-    document::{ByteRange, Document},
+    completion,
+    document::Document,
     document_change::DocumentChange,
+    formatting,
+    history::{History, Revert},
+    snippet::{Snippet, SnippetSession},
     window::{MoveDirectionX, MoveDirectionY, VirtualWindow},
 };
 
-use laurel_common::text::{Cursor, Position, Range, Selection};
+use laurel_common::text::{Cursor, Position, Selection};
 
 pub struct Buffer {
     document: Document,
-    highlighter: HighlighterConfig,
+    highlighter: Option<HighlighterConfig>,
     pub selection: Selection,
     pub cursor: Cursor,
     pub window: VirtualWindow,
     diagnostics: Option<ClientDiagnostics>,
+    /// The changes the language server hasn't been told about yet.
+    unsynced: Vec<TextChange>,
+    /// The snippet whose tab stops are being filled in
+    snippet: Option<SnippetSession>,
+    history: History,
 }
 
 impl Buffer {
-    pub fn new(document: Document, highlighter: HighlighterConfig) -> Self {
+    pub fn new(document: Document, highlighter: Option<HighlighterConfig>) -> Self {
         let height = text::LineHeight::default().to_absolute(iced::Pixels(14.0));
         Self {
             document,
@@ -33,6 +49,9 @@ impl Buffer {
             window: VirtualWindow::new().set_lineheight(height.0),
             selection: Selection::default(),
             diagnostics: None,
+            unsynced: Vec::new(),
+            snippet: None,
+            history: History::default(),
         }
     }
 
@@ -44,12 +63,11 @@ impl Buffer {
     }
 
     /**
-     * Adds the diagnostic to the buffer if the paths match.
+     * Shows the diagnostics of the document, the ones of other files are left out.
      */
-    pub fn add_diagnostics(&mut self, diagonostic: ClientDiagnostics) {
-        if self.document.uri() == &diagonostic.uri {
-            self.diagnostics = Some(diagonostic)
-        }
+    pub fn set_diagnostics(&mut self, diagnostics: Option<ClientDiagnostics>) {
+        self.diagnostics =
+            diagnostics.filter(|diagnostics| self.document.uri() == &diagnostics.uri);
     }
 
     /**
@@ -70,6 +88,21 @@ impl Buffer {
         None
     }
 
+    /**
+     * The diagnostics that touch the lines from `start` to `end`, as the server sent them.
+     */
+    pub fn diagnostics_on_lines(&self, start: usize, end: usize) -> Vec<Diagnostic> {
+        let Some(diagnostics) = &self.diagnostics else {
+            return Vec::new();
+        };
+        diagnostics
+            .issues
+            .iter()
+            .filter(|issue| issue.range.start().line() <= end && issue.range.end().line() >= start)
+            .map(|issue| issue.diagnostic.clone())
+            .collect()
+    }
+
     pub fn filename(&self) -> &String {
         self.document.filename()
     }
@@ -114,117 +147,399 @@ impl Buffer {
 
     pub fn get_highlighter(&self) -> Option<Highlighter> {
         let start_line = self.window.padded_start_line();
-        let start_line = self.document.get_line_bytes(start_line);
-
         let end_line = self.window.padded_end_line();
-        let end_line = self.document.get_line_bytes(end_line);
 
-        Highlighter::new(&self.highlighter, start_line..end_line, self.document())
+        Highlighter::new(
+            self.highlighter.as_ref()?,
+            start_line..end_line,
+            &self.document.slice_all(),
+        )
+    }
+
+    /**
+     * Reloads the queries, or sets up highlighting when a grammar was added since the
+     * document was opened.
+     */
+    pub fn reload_queries(&mut self) {
+        // Every layer is rebuilt, since the injections can change along with the queries
+        self.highlighter = self.document.language().and_then(|language| {
+            HighlighterConfig::for_language(language, &self.document.slice_all())
+        });
+    }
+
+    pub fn set_semantic_tokens(&mut self, legend: &SemanticTokensLegend, tokens: SemanticTokens) {
+        if let Some(highlighter) = &mut self.highlighter {
+            let content = self.document.slice_all();
+            highlighter.set_semantic_tokens(legend, tokens.result_id, tokens.data, &content);
+        }
+    }
+
+    /**
+     * Applies a semantic tokens delta, returning `false` when the full tokens have to be
+     * requested again.
+     */
+    pub fn apply_semantic_delta(
+        &mut self,
+        previous_result_id: &str,
+        delta: SemanticTokensDelta,
+    ) -> bool {
+        match &mut self.highlighter {
+            Some(highlighter) => highlighter.apply_semantic_delta(
+                previous_result_id,
+                delta.result_id,
+                delta.edits,
+                &self.document.slice_all(),
+            ),
+            None => true,
+        }
+    }
+
+    pub fn semantic_result_id(&self) -> Option<&str> {
+        self.highlighter.as_ref()?.semantic_result_id()
     }
 
     pub fn lines(&self) -> Lines<'_> {
         self.document.lines()
     }
 
-    pub fn replace(&mut self, start_idx: &Position, end_idx: &Position, character: String) {
-        self.document.replace(start_idx, end_idx, character);
+    pub fn replace(
+        &mut self,
+        start_idx: &Position,
+        end_idx: &Position,
+        character: String,
+    ) -> Option<DocumentChange> {
+        let change = self.document.replace(start_idx, end_idx, character);
+        self.apply_change(change)
     }
 
-    pub fn delete(&mut self) {
-        let cursor_pos = self.get_position();
-
+    pub fn delete(&mut self) -> Option<DocumentChange> {
         // If the selection is available
-        if !self.selection.is_empty() {
+        let change = if !self.selection.is_empty() {
             let selection = self.selection.correct_position();
             self.selection.clear(&mut self.cursor);
-            let bytes = self.document.delete(selection.start(), selection.end());
-            if let Some(bytes) = bytes {
-                self.character_deleted(bytes.start, bytes.end, *selection.start(), *selection.end())
-            }
+            self.document.delete(selection.start(), selection.end())
         } else {
-            let start = self.document.delete(
+            self.document.delete(
                 &self.cursor.0,
                 &Position::new(self.cursor.0.line(), self.cursor.0.character() + 1),
-            );
-            if let Some(value) = &start {
-                self.character_deleted(value.start, value.end, cursor_pos, cursor_pos)
+            )
+        };
+        let change = self.apply_change(change);
+        self.mirror_snippet();
+        change
+    }
+
+    /**
+     * Keeps the syntax tree in step with a change that was made to the document, and keeps the
+     * change for the language server.
+     */
+    fn apply_change(&mut self, change: Option<DocumentChange>) -> Option<DocumentChange> {
+        let change = change?;
+        if let Some(highlighter) = &mut self.highlighter {
+            highlighter.edit(&change, &self.document.slice_all());
+        }
+        self.unsynced
+            .push(TextChange::new(change.lsp_range, change.text.clone()));
+        let start = self.document.get_character_pos(&change.range.start());
+        self.history.record(Revert {
+            start,
+            end: start + change.text.chars().count(),
+            text: change.old_text.clone(),
+        });
+        if let Some(snippet) = &mut self.snippet {
+            let range = &change.byte_range;
+            if !snippet.apply_change(range.start, range.end, change.text.len()) {
+                self.snippet = None;
             }
         }
+        Some(change)
     }
 
-    fn character_deleted(
-        &mut self,
-        start_byte: usize,
-        end_byte: usize,
-        start_pos: Position,
-        end_pos: Position,
-    ) {
-        self.highlighter.delete(
-            start_byte,
-            end_byte,
-            start_pos,
-            end_pos,
-            &self.document.slice_all(),
-        )
+    /**
+     * Closes the group of changes that are undone together.
+     */
+    pub fn close_undo_group(&mut self) {
+        self.history.close_group();
     }
 
-    fn character_inputed(
-        &mut self,
-        start_byte: usize,
-        end_byte: usize,
-        start_pos: Position,
-        end_pos: Position,
-        length: usize,
-    ) {
-        self.highlighter.insert(
-            start_byte,
-            end_byte,
-            start_pos,
-            end_pos,
-            length,
-            &self.document.slice_all(),
-        )
+    /**
+     * Undoes the last group of changes, returning `false` when there's nothing to undo.
+     */
+    pub fn undo(&mut self) -> bool {
+        let Some(reverts) = self.history.undo() else {
+            return false;
+        };
+        self.revert(reverts);
+        self.history.finish_undo();
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let Some(reverts) = self.history.redo() else {
+            return false;
+        };
+        self.revert(reverts);
+        self.history.finish_redo();
+        true
+    }
+
+    /**
+     * Makes the reverts of an undo or a redo, leaving the cursor after the text of the last one.
+     */
+    fn revert(&mut self, reverts: Vec<Revert>) {
+        // The tab stops don't follow the text back
+        self.snippet = None;
+        let mut cursor = None;
+        for revert in reverts {
+            let start = self.document.char_to_position(revert.start);
+            let end = self.document.char_to_position(revert.end);
+            let inserted = revert.text.chars().count();
+            let change = self.document.replace(&start, &end, revert.text);
+            if self.apply_change(change).is_some() {
+                cursor = Some(revert.start + inserted);
+            }
+        }
+        self.selection.clear(&mut self.cursor);
+        if let Some(cursor) = cursor {
+            self.cursor.0 = self.document.char_to_position(cursor);
+        }
+    }
+
+    /**
+     * The value of a snippet variable like `TM_FILENAME`, `None` when it isn't known.
+     */
+    fn snippet_variable(&self, name: &str) -> Option<String> {
+        let path = std::path::Path::new(self.filename());
+        let line = self.cursor.0.line();
+        let line_text = || {
+            let text = self.document.get_line(line)?.to_string();
+            Some(text.trim_end_matches(['\r', '\n']).to_owned())
+        };
+        match name {
+            "TM_SELECTED_TEXT" => Some(self.get_selected_text()),
+            "TM_CURRENT_LINE" => line_text(),
+            "TM_CURRENT_WORD" => {
+                let text = line_text()?;
+                let character = self.cursor.0.character();
+                let start = completion::word_start(&text, character);
+                completion::typed_word(&text, start, character)
+            }
+            "TM_LINE_INDEX" => Some(line.to_string()),
+            "TM_LINE_NUMBER" => Some((line + 1).to_string()),
+            "TM_FILENAME" => Some(path.file_name()?.to_string_lossy().into_owned()),
+            "TM_FILENAME_BASE" => Some(path.file_stem()?.to_string_lossy().into_owned()),
+            "TM_DIRECTORY" => Some(path.parent()?.to_string_lossy().into_owned()),
+            "TM_FILEPATH" => Some(self.filename().clone()),
+            _ => None,
+        }
+    }
+
+    /**
+     * Expands a snippet to be inserted at the cursor, indented like the cursor's line.
+     */
+    pub fn expand_snippet(&self, source: &str) -> Snippet {
+        let mut snippet = Snippet::parse(source, &|name| self.snippet_variable(name));
+        let indent: String = self
+            .document
+            .get_line(self.cursor.0.line())
+            .map(|line| {
+                line.chars()
+                    .take_while(|c| *c == ' ' || *c == '\t')
+                    .collect()
+            })
+            .unwrap_or_default();
+        snippet.reindent(&indent, &" ".repeat(4));
+        snippet
+    }
+
+    /**
+     * Starts filling in a snippet whose text was just inserted before the cursor, selecting its
+     * first tab stop.
+     */
+    pub fn start_snippet(&mut self, snippet: Snippet) {
+        let end = self.document.position_to_byte(&self.cursor.0);
+        let start = end.saturating_sub(snippet.text.len());
+        self.snippet = Some(SnippetSession::new(snippet, start));
+        self.select_tab_stop();
+    }
+
+    pub fn snippet(&self) -> Option<&SnippetSession> {
+        self.snippet.as_ref()
+    }
+
+    /**
+     * Moves to the next tab stop of the snippet, returning `false` when there's no snippet.
+     */
+    pub fn next_tab_stop(&mut self) -> bool {
+        let Some(snippet) = &mut self.snippet else {
+            return false;
+        };
+        snippet.next();
+        self.select_tab_stop();
+        true
+    }
+
+    pub fn previous_tab_stop(&mut self) -> bool {
+        let Some(snippet) = &mut self.snippet else {
+            return false;
+        };
+        snippet.previous();
+        self.select_tab_stop();
+        true
+    }
+
+    pub fn end_snippet(&mut self) {
+        self.snippet = None;
+    }
+
+    /**
+     * Selects the placeholder of the current tab stop, the snippet is done once the cursor
+     * reaches the final one.
+     */
+    fn select_tab_stop(&mut self) {
+        let Some(snippet) = &self.snippet else {
+            return;
+        };
+        let Some(range) = snippet.current().ranges.first() else {
+            return;
+        };
+        let start = self.document.byte_to_position(range.start);
+        let end = self.document.byte_to_position(range.end);
+        if snippet.is_finished() {
+            self.snippet = None;
+        }
+        self.selection.set_start(start);
+        self.selection.set_end(end);
+        self.cursor.0 = end;
+    }
+
+    /**
+     * Repeats what was typed into the current tab stop in its mirrors.
+     */
+    fn mirror_snippet(&mut self) {
+        while let Some((range, text)) = self.snippet.as_ref().and_then(|snippet| {
+            snippet.unmirrored(|range| self.document.byte_slice(range.start, range.end).to_string())
+        }) {
+            let start = self.document.byte_to_position(range.start);
+            let end = self.document.byte_to_position(range.end);
+            if self.replace(&start, &end, text).is_none() {
+                break;
+            }
+        }
+    }
+
+    /**
+     * Takes the changes made since the last call, in the order they were made.
+     */
+    pub fn take_changes(&mut self) -> Vec<TextChange> {
+        std::mem::take(&mut self.unsynced)
     }
 
     pub fn insert(&mut self, content: String) -> Option<DocumentChange> {
-        let cursor_pos = self.get_position();
         // If the selection is available
-        if !self.selection.is_empty() {
+        let change = if !self.selection.is_empty() {
             let selection = self.selection.correct_position();
             self.selection.clear(&mut self.cursor);
-
-            let string_len = content.len();
-            let bytes =
-                self.document
-                    .replace(selection.start(), selection.end(), content.clone())?;
-            self.character_inputed(
-                bytes.start,
-                bytes.end,
-                *selection.start(),
-                *selection.end(),
-                string_len,
-            );
-            self.cursor.move_to_end_of_insert(content.clone());
-
-            Some(DocumentChange::new(
-                selection.into(),
-                bytes,
-                content,
-                self.document.filename().clone(),
-            ))
+            self.document
+                .replace(selection.start(), selection.end(), content.clone())
         // Input from the cursor positions
         } else {
-            let string_len = content.len();
-            let start = self.document.insert(&self.cursor.0, content.clone());
-            self.character_inputed(start, start, cursor_pos, cursor_pos, string_len);
-            self.cursor.move_to_end_of_insert(content.clone());
-            Some(DocumentChange::new(
-                Range::new(cursor_pos, cursor_pos),
-                ByteRange { start, end: start },
-                content,
-                self.document.filename().clone(),
-            ))
+            self.document.insert(&self.cursor.0, content.clone())
+        };
+
+        let change = self.apply_change(change)?;
+        self.cursor.move_to_end_of_insert(content);
+        self.mirror_snippet();
+        Some(change)
+    }
+
+    /**
+     * Applies edits from the language server, whose ranges refer to the document before any of
+     * them and count columns in UTF-16 code units.
+     *
+     * The cursor ends up after the text of the edit at `anchor`, or stays on the text it was on.
+     */
+    pub fn apply_text_edits(&mut self, edits: Vec<TextEdit>, anchor: Option<usize>) {
+        let edits = self.char_edits(edits);
+        let mut cursor = self.document.get_character_pos(&self.cursor.0);
+        for (index, start, end, new_text) in edits {
+            let inserted = new_text.chars().count();
+            let start_position = self.document.char_to_position(start);
+            let end_position = self.document.char_to_position(end);
+            let change = self
+                .document
+                .replace(&start_position, &end_position, new_text);
+            let changed = self.apply_change(change).is_some();
+            if anchor == Some(index) || (changed && start < cursor && cursor < end) {
+                cursor = start + inserted;
+            } else if changed && end <= cursor {
+                cursor = cursor + inserted - (end - start);
+            }
         }
+
+        self.selection.clear(&mut self.cursor);
+        self.cursor.0 = self.document.char_to_position(cursor);
+        self.mirror_snippet();
+    }
+
+    /**
+     * Applies the edits of a formatter, keeping the cursor and the selection on the code they
+     * were on rather than clearing the selection.
+     */
+    pub fn apply_formatting(&mut self, edits: Vec<TextEdit>) {
+        let edits = self.char_edits(edits);
+        let mut offsets = [
+            self.document.get_character_pos(&self.cursor.0),
+            self.document.get_character_pos(self.selection.start()),
+            self.document.get_character_pos(self.selection.end()),
+        ];
+        for (_, start, end, new_text) in edits {
+            let start_position = self.document.char_to_position(start);
+            let end_position = self.document.char_to_position(end);
+            let change = self
+                .document
+                .replace(&start_position, &end_position, new_text);
+            let Some(change) = self.apply_change(change) else {
+                continue;
+            };
+            let inserted = change.text.chars().count();
+            for offset in &mut offsets {
+                if *offset >= end {
+                    *offset = *offset - (end - start) + inserted;
+                } else if *offset > start {
+                    *offset = start
+                        + formatting::map_offset(*offset - start, &change.old_text, &change.text);
+                }
+            }
+        }
+
+        let [cursor, selection_start, selection_end] =
+            offsets.map(|offset| self.document.char_to_position(offset));
+        self.cursor.0 = cursor;
+        self.selection.set_start(selection_start);
+        self.selection.set_end(selection_end);
+        self.mirror_snippet();
+    }
+
+    /**
+     * The edits of the language server as ranges of characters, along with their index, sorted
+     * from the end of the document so the ranges that are left stay valid as they're applied.
+     * Inserts at the same place keep their order.
+     */
+    fn char_edits(&self, edits: Vec<TextEdit>) -> Vec<(usize, usize, usize, String)> {
+        let mut edits: Vec<(usize, usize, usize, String)> = edits
+            .into_iter()
+            .enumerate()
+            .map(|(index, edit)| {
+                let start = self.document.utf16_to_position(&edit.range.start.into());
+                let end = self.document.utf16_to_position(&edit.range.end.into());
+                let start = self.document.get_character_pos(&start);
+                let end = self.document.get_character_pos(&end).max(start);
+                (index, start, end, edit.new_text)
+            })
+            .collect();
+        edits.sort_by_key(|(index, start, ..)| (Reverse(*start), Reverse(*index)));
+        edits
     }
 
     fn get_selected_text(&self) -> String {
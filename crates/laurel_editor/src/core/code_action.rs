@@ -0,0 +1,173 @@
+use async_lsp::lsp_types::{CodeActionKind, CodeActionOrCommand};
+
+/**
+ * The title of a code action as it's listed.
+ */
+pub fn title(action: &CodeActionOrCommand) -> &str {
+    match action {
+        CodeActionOrCommand::Command(command) => &command.title,
+        CodeActionOrCommand::CodeAction(action) => &action.title,
+    }
+}
+
+/**
+ * Where an action is listed: the fixes the server prefers come first, then the other quick
+ * fixes, then everything else like refactorings.
+ */
+fn rank(action: &CodeActionOrCommand) -> u8 {
+    let CodeActionOrCommand::CodeAction(action) = action else {
+        return 2;
+    };
+    let is_quickfix = action
+        .kind
+        .as_ref()
+        .is_some_and(|kind| kind.as_str().starts_with(CodeActionKind::QUICKFIX.as_str()));
+    match (action.is_preferred, is_quickfix) {
+        (Some(true), _) => 0,
+        (_, true) => 1,
+        _ => 2,
+    }
+}
+
+/**
+ * The code actions at the cursor as they're listed in the menu, along with the one that's
+ * selected.
+ */
+#[derive(Debug, Clone)]
+pub struct CodeActions {
+    actions: Vec<CodeActionOrCommand>,
+    selected: usize,
+}
+
+impl CodeActions {
+    pub fn new(mut actions: Vec<CodeActionOrCommand>) -> Self {
+        // The order of the server is kept within a rank
+        actions.sort_by_key(rank);
+        Self {
+            actions,
+            selected: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    pub fn actions(&self) -> &[CodeActionOrCommand] {
+        &self.actions
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected(&self) -> Option<&CodeActionOrCommand> {
+        self.actions.get(self.selected)
+    }
+
+    pub fn select(&mut self, index: usize) {
+        if index < self.actions.len() {
+            self.selected = index;
+        }
+    }
+
+    /**
+     * Selects the next action, or the first one after the last.
+     */
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.actions.len().max(1);
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = match self.selected {
+            0 => self.actions.len().saturating_sub(1),
+            selected => selected - 1,
+        };
+    }
+
+    /**
+     * Takes the selected action out, e.g. once it's picked.
+     */
+    pub fn take_selected(mut self) -> Option<CodeActionOrCommand> {
+        (self.selected < self.actions.len()).then(|| self.actions.swap_remove(self.selected))
+    }
+}
+
+#[cfg(test)]
+mod code_action_tests {
+    use async_lsp::lsp_types::{CodeAction, CodeActionKind, CodeActionOrCommand, Command};
+    use pretty_assertions::assert_eq;
+
+    use super::{title, CodeActions};
+
+    fn action(title: &str, kind: CodeActionKind, is_preferred: bool) -> CodeActionOrCommand {
+        CodeActionOrCommand::CodeAction(CodeAction {
+            title: title.to_owned(),
+            kind: Some(kind),
+            is_preferred: is_preferred.then_some(true),
+            ..CodeAction::default()
+        })
+    }
+
+    fn titles(actions: &CodeActions) -> Vec<&str> {
+        actions.actions().iter().map(title).collect()
+    }
+
+    #[test]
+    fn test_preferred_fixes_first() {
+        let actions = CodeActions::new(vec![
+            action(
+                "Extract into function",
+                CodeActionKind::REFACTOR_EXTRACT,
+                false,
+            ),
+            CodeActionOrCommand::Command(Command::new(
+                String::from("Run test"),
+                String::from("run"),
+                None,
+            )),
+            action("Add missing match arms", CodeActionKind::QUICKFIX, false),
+            action("Import `HashMap`", CodeActionKind::QUICKFIX, true),
+            action(
+                "Remove unused",
+                CodeActionKind::from("quickfix.unused"),
+                false,
+            ),
+        ]);
+        assert_eq!(
+            titles(&actions),
+            vec![
+                "Import `HashMap`",
+                "Add missing match arms",
+                "Remove unused",
+                "Extract into function",
+                "Run test",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_selection_wraps() {
+        let mut actions = CodeActions::new(vec![
+            action("a", CodeActionKind::QUICKFIX, false),
+            action("b", CodeActionKind::QUICKFIX, false),
+        ]);
+        actions.select_previous();
+        assert_eq!(actions.selected_index(), 1);
+        actions.select_next();
+        assert_eq!(actions.selected_index(), 0);
+        actions.select(5);
+        assert_eq!(actions.selected_index(), 0);
+        actions.select(1);
+        assert_eq!(actions.take_selected().as_ref().map(title), Some("b"));
+
+        let mut none = CodeActions::new(Vec::new());
+        none.select_next();
+        none.select_previous();
+        assert_eq!(none.selected(), None);
+    }
+}
@@ -0,0 +1,395 @@
+use std::cmp::Reverse;
+
+use async_lsp::lsp_types::{self, CompletionItem, CompletionTextEdit, TextEdit};
+use laurel_common::text::Position;
+
+/**
+ * Whether a character continues an identifier, so typing it filters the completions rather than
+ * asking for new ones.
+ */
+pub fn is_identifier(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/**
+ * The column where the identifier that ends at `character` starts, in characters.
+ */
+pub fn word_start(line: &str, character: usize) -> usize {
+    let chars: Vec<char> = line.chars().take(character).collect();
+    chars
+        .iter()
+        .rposition(|c| !is_identifier(*c))
+        .map_or(0, |index| index + 1)
+}
+
+/**
+ * The identifier typed from column `start` up to `character`, `None` once the cursor left it.
+ */
+pub fn typed_word(line: &str, start: usize, character: usize) -> Option<String> {
+    let word: String = line
+        .chars()
+        .skip(start)
+        .take(character.checked_sub(start)?)
+        .collect();
+    word.chars().all(is_identifier).then_some(word)
+}
+
+/**
+ * Scores how well `candidate` matches the typed `query`, `None` when it doesn't contain the
+ * characters of the query in order. Matches at the start, at word boundaries and in a row score
+ * higher, and so do shorter candidates.
+ */
+pub fn score(query: &str, candidate: &str) -> Option<i32> {
+    // Everything matches, in the order of the server
+    if query.is_empty() {
+        return Some(0);
+    }
+    let candidate: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous: Option<usize> = None;
+    for c in query.chars() {
+        let found = (next..candidate.len())
+            .find(|&index| candidate[index].to_lowercase().eq(c.to_lowercase()))?;
+        let before = found.checked_sub(1).map(|index| candidate[index]);
+        score += match before {
+            None => 8,
+            Some(_) if previous == Some(found - 1) => 5,
+            Some(before) if !is_identifier(before) => 3,
+            Some(before) if before.is_lowercase() && candidate[found].is_uppercase() => 3,
+            Some(_) => 1,
+        };
+        if candidate[found] == c {
+            score += 1;
+        }
+        previous = Some(found);
+        next = found + 1;
+    }
+    let extra = candidate
+        .len()
+        .saturating_sub(query.chars().count())
+        .min(99) as i32;
+    Some(score * 100 - extra)
+}
+
+/**
+ * The completions of the language server for the word at the cursor, filtered by what was typed
+ * since they were requested.
+ */
+#[derive(Debug, Clone)]
+pub struct Completion {
+    items: Vec<CompletionItem>,
+    /// Whether the server has to be asked again when the query changes
+    is_incomplete: bool,
+    /// Where the word that's being completed starts, in characters
+    start: Position,
+    query: String,
+    /// The indices of the items that match the query, best first
+    matches: Vec<usize>,
+    /// The index into `matches` of the selected item
+    selected: usize,
+    /// Whether each item was resolved
+    resolved: Vec<bool>,
+}
+
+impl Completion {
+    pub fn new(items: Vec<CompletionItem>, is_incomplete: bool, start: Position) -> Self {
+        let mut completion = Self {
+            resolved: vec![false; items.len()],
+            items,
+            is_incomplete,
+            start,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+        };
+        completion.filter("");
+        completion
+    }
+
+    /**
+     * Completions that don't come from the language server, like the choices of a snippet, so
+     * there's nothing to resolve.
+     */
+    pub fn local(items: Vec<CompletionItem>, start: Position) -> Self {
+        Self::new(Vec::new(), false, start).with_local(items)
+    }
+
+    /**
+     * Adds completions that don't come from the language server, like the user's snippets.
+     */
+    pub fn with_local(mut self, items: Vec<CompletionItem>) -> Self {
+        self.resolved.extend(items.iter().map(|_| true));
+        self.items.extend(items);
+        let query = std::mem::take(&mut self.query);
+        self.filter(&query);
+        self
+    }
+
+    pub fn start(&self) -> Position {
+        self.start
+    }
+
+    pub fn is_incomplete(&self) -> bool {
+        self.is_incomplete
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /**
+     * Keeps the items that match `query`, best first, and selects the first of them.
+     */
+    pub fn filter(&mut self, query: &str) {
+        let mut matches: Vec<(i32, usize)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                let text = item.filter_text.as_deref().unwrap_or(&item.label);
+                Some((score(query, text)?, index))
+            })
+            .collect();
+        matches.sort_by_key(|(score, index)| {
+            let item = &self.items[*index];
+            let sort_text = item.sort_text.as_deref().unwrap_or(&item.label);
+            (Reverse(*score), sort_text, &item.label)
+        });
+
+        self.query = query.to_owned();
+        self.matches = matches.into_iter().map(|(_, index)| index).collect();
+        self.selected = self
+            .matches
+            .iter()
+            .position(|index| self.items[*index].preselect == Some(true))
+            .unwrap_or(0);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matches.is_empty()
+    }
+
+    /**
+     * The items that match the query, best first.
+     */
+    pub fn matches(&self) -> impl Iterator<Item = &CompletionItem> {
+        self.matches.iter().map(|index| &self.items[*index])
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected(&self) -> Option<&CompletionItem> {
+        Some(&self.items[*self.matches.get(self.selected)?])
+    }
+
+    pub fn select(&mut self, index: usize) {
+        if index < self.matches.len() {
+            self.selected = index;
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected + 1) % self.matches.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected + self.matches.len() - 1) % self.matches.len();
+        }
+    }
+
+    /**
+     * The selected item when its details still have to be resolved.
+     */
+    pub fn unresolved(&self) -> Option<&CompletionItem> {
+        let index = *self.matches.get(self.selected)?;
+        (!self.resolved[index]).then(|| &self.items[index])
+    }
+
+    /**
+     * Replaces the item a resolved item came from, preferring the selected one when several
+     * share its label.
+     */
+    pub fn resolve(&mut self, item: CompletionItem) {
+        let selected = self.matches.get(self.selected).copied();
+        let index = selected
+            .into_iter()
+            .chain(0..self.items.len())
+            .find(|index| !self.resolved[*index] && self.items[*index].label == item.label);
+        if let Some(index) = index {
+            self.items[index] = item;
+            self.resolved[index] = true;
+        }
+    }
+}
+
+/**
+ * The edits that insert `item`, with columns in UTF-16 code units: its own edit, which reaches
+ * to the cursor as the query was typed after it was requested, then its additional edits,
+ * like imports.
+ */
+pub fn text_edits(
+    item: &CompletionItem,
+    start: lsp_types::Position,
+    cursor: lsp_types::Position,
+) -> Vec<TextEdit> {
+    let on_cursor_line = |position: lsp_types::Position| {
+        position.line == cursor.line && position.character <= cursor.character
+    };
+    let (range_start, new_text) = match &item.text_edit {
+        Some(CompletionTextEdit::Edit(edit)) => (edit.range.start, edit.new_text.clone()),
+        Some(CompletionTextEdit::InsertAndReplace(edit)) => {
+            (edit.insert.start, edit.new_text.clone())
+        }
+        None => (
+            start,
+            item.insert_text
+                .clone()
+                .unwrap_or_else(|| item.label.clone()),
+        ),
+    };
+    let range_start = if on_cursor_line(range_start) {
+        range_start
+    } else {
+        start
+    };
+
+    let mut edits = vec![TextEdit {
+        range: lsp_types::Range {
+            start: range_start,
+            end: cursor,
+        },
+        new_text,
+    }];
+    edits.extend(item.additional_text_edits.iter().flatten().cloned());
+    edits
+}
+
+#[cfg(test)]
+mod completion_tests {
+    use super::{score, text_edits, typed_word, word_start, Completion};
+    use async_lsp::lsp_types::{
+        CompletionItem, CompletionTextEdit, Position as LspPosition, Range, TextEdit,
+    };
+    use laurel_common::text::Position;
+    use pretty_assertions::assert_eq;
+
+    fn item(label: &str) -> CompletionItem {
+        CompletionItem {
+            label: label.to_owned(),
+            ..CompletionItem::default()
+        }
+    }
+
+    fn labels(completion: &Completion) -> Vec<&str> {
+        completion
+            .matches()
+            .map(|item| item.label.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_word_start() {
+        assert_eq!(word_start("    let my_var", 14), 8);
+        assert_eq!(word_start("self.len", 8), 5);
+        assert_eq!(word_start("ab", 0), 0);
+    }
+
+    #[test]
+    fn test_typed_word() {
+        assert_eq!(typed_word("self.len", 5, 7), Some("le".to_owned()));
+        assert_eq!(typed_word("self.len", 5, 5), Some(String::new()));
+        assert_eq!(typed_word("self.len(", 5, 9), None);
+        assert_eq!(typed_word("self.len", 5, 4), None);
+    }
+
+    #[test]
+    fn test_score() {
+        assert_eq!(score("xyz", "len"), None);
+        assert!(score("le", "len") > score("le", "is_empty_len"));
+        assert!(score("ie", "is_empty") > score("ie", "pixel"));
+        assert!(score("tS", "to_String") > score("tS", "tests"));
+    }
+
+    #[test]
+    fn test_filter_and_select() {
+        let mut sorted = item("zzz_last");
+        sorted.sort_text = Some("0".to_owned());
+        let items = vec![item("push"), item("pop"), item("len"), sorted];
+        let mut completion = Completion::new(items, false, Position::new(0, 4));
+
+        // Without a query the sort text decides
+        assert_eq!(completion.selected().unwrap().label, "zzz_last");
+        completion.filter("p");
+        assert_eq!(labels(&completion), vec!["pop", "push"]);
+        completion.select_previous();
+        assert_eq!(completion.selected().unwrap().label, "push");
+        completion.select_next();
+        assert_eq!(completion.selected().unwrap().label, "pop");
+        completion.filter("q");
+        assert!(completion.is_empty());
+        assert!(completion.selected().is_none());
+    }
+
+    #[test]
+    fn test_local_items_are_resolved() {
+        let server = vec![item("push")];
+        let mut completion =
+            Completion::new(server, false, Position::new(0, 0)).with_local(vec![item("println")]);
+        assert_eq!(labels(&completion), vec!["println", "push"]);
+        completion.filter("pri");
+        assert!(completion.unresolved().is_none());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut completion =
+            Completion::new(vec![item("a"), item("b")], false, Position::new(0, 0));
+        completion.filter("b");
+        assert_eq!(completion.unresolved().unwrap().label, "b");
+
+        let mut resolved = item("b");
+        resolved.detail = Some("fn b()".to_owned());
+        completion.resolve(resolved);
+        assert!(completion.unresolved().is_none());
+        assert_eq!(
+            completion.selected().unwrap().detail.as_deref(),
+            Some("fn b()")
+        );
+    }
+
+    #[test]
+    fn test_edits_reach_the_cursor() {
+        let position = |character| LspPosition::new(2, character);
+        let mut import = item("HashMap");
+        import.text_edit = Some(CompletionTextEdit::Edit(TextEdit::new(
+            Range::new(position(4), position(6)),
+            "HashMap".to_owned(),
+        )));
+        let use_edit = TextEdit::new(
+            Range::new(LspPosition::new(0, 0), LspPosition::new(0, 0)),
+            "use std::collections::HashMap;\n".to_owned(),
+        );
+        import.additional_text_edits = Some(vec![use_edit.clone()]);
+
+        // `Ha` was requested, then `sh` was typed
+        let edits = text_edits(&import, position(4), position(8));
+        assert_eq!(
+            edits,
+            vec![
+                TextEdit::new(Range::new(position(4), position(8)), "HashMap".to_owned()),
+                use_edit,
+            ]
+        );
+
+        let plain = item("len");
+        let edits = text_edits(&plain, position(4), position(6));
+        assert_eq!(edits[0].new_text, "len");
+        assert_eq!(edits[0].range, Range::new(position(4), position(6)));
+    }
+}
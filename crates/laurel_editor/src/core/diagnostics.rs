@@ -0,0 +1,392 @@
+use std::{cmp::Ordering, collections::HashMap, path::Path};
+
+use async_lsp::lsp_types::{self, Location, Url};
+use laurel_lsp::{
+    diagnostics::{ClientDiagnostics, Issue, Severity},
+    ServerId,
+};
+
+/**
+ * The diagnostics the language servers published, for every file rather than just the open
+ * one, with how the problems panel filters and sorts them.
+ */
+#[derive(Debug, Default)]
+pub struct WorkspaceDiagnostics {
+    files: HashMap<Url, FileDiagnostics>,
+    /// Only the problems with the text in their message, file or source are listed, whatever the
+    /// case
+    filter: String,
+    hidden: Vec<Severity>,
+    sort: DiagnosticSort,
+}
+
+#[derive(Debug)]
+struct FileDiagnostics {
+    /// The server that published them, they're dropped when it stops
+    server: ServerId,
+    issues: Vec<Issue>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticSort {
+    /// By file, then where they are in it
+    #[default]
+    File,
+    /// Errors first, then by file
+    Severity,
+}
+
+/**
+ * A diagnostic as it's listed in the problems panel.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct Problem<'a> {
+    pub uri: &'a Url,
+    /// The root of the workspace of the server that published it
+    pub root: &'a Path,
+    pub issue: &'a Issue,
+}
+
+impl DiagnosticSort {
+    pub const ALL: [DiagnosticSort; 2] = [DiagnosticSort::File, DiagnosticSort::Severity];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DiagnosticSort::File => "By File",
+            DiagnosticSort::Severity => "By Severity",
+        }
+    }
+}
+
+/**
+ * What the severity is called in the problems panel.
+ */
+pub fn severity_label(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "Errors",
+        Severity::Warning => "Warnings",
+        Severity::Info => "Info",
+        Severity::Hint => "Hints",
+    }
+}
+
+impl Problem<'_> {
+    pub fn location(&self) -> Location {
+        Location {
+            uri: self.uri.clone(),
+            range: self.issue.diagnostic.range,
+        }
+    }
+
+    /**
+     * The file of the problem, relative to the root of the workspace when it's in it.
+     */
+    pub fn path(&self) -> String {
+        let Ok(path) = self.uri.to_file_path() else {
+            return self.uri.to_string();
+        };
+        path.strip_prefix(self.root)
+            .unwrap_or(&path)
+            .display()
+            .to_string()
+    }
+
+    /**
+     * Orders the problems by file, then where they start in it.
+     */
+    fn cmp_position(&self, other: &Problem) -> Ordering {
+        let start = self.issue.diagnostic.range.start;
+        let other_start = other.issue.diagnostic.range.start;
+        self.uri
+            .as_str()
+            .cmp(other.uri.as_str())
+            .then((start.line, start.character).cmp(&(other_start.line, other_start.character)))
+    }
+}
+
+impl WorkspaceDiagnostics {
+    /**
+     * Replaces the diagnostics of the file, which has none anymore when the server sent none.
+     */
+    pub fn publish(&mut self, server: ServerId, diagnostics: ClientDiagnostics) {
+        if diagnostics.issues.is_empty() {
+            self.files.remove(&diagnostics.uri);
+            return;
+        }
+        let file = FileDiagnostics {
+            server,
+            issues: diagnostics.issues,
+        };
+        self.files.insert(diagnostics.uri, file);
+    }
+
+    /**
+     * Drops the diagnostics of a server that stopped, it publishes them anew once it's started
+     * again.
+     */
+    pub fn clear_server(&mut self, server: &ServerId) {
+        self.files.retain(|_, file| &file.server != server);
+    }
+
+    /**
+     * The diagnostics of the file, as the buffer shows them.
+     */
+    pub fn get(&self, uri: &Url) -> Option<ClientDiagnostics> {
+        let file = self.files.get(uri)?;
+        Some(ClientDiagnostics {
+            issues: file.issues.clone(),
+            uri: uri.clone(),
+        })
+    }
+
+    /**
+     * How many errors and warnings there are in all the files.
+     */
+    pub fn counts(&self) -> (usize, usize) {
+        let issues = self.files.values().flat_map(|file| &file.issues);
+        issues.fold((0, 0), |(errors, warnings), issue| match issue.severity {
+            Severity::Error => (errors + 1, warnings),
+            Severity::Warning => (errors, warnings + 1),
+            _ => (errors, warnings),
+        })
+    }
+
+    fn all(&self) -> impl Iterator<Item = Problem<'_>> {
+        self.files.iter().flat_map(|(uri, file)| {
+            file.issues.iter().map(move |issue| Problem {
+                uri,
+                root: &file.server.root,
+                issue,
+            })
+        })
+    }
+
+    /**
+     * The problems of the severities that are shown, by file.
+     */
+    fn shown(&self) -> Vec<Problem<'_>> {
+        let mut problems: Vec<Problem> = self
+            .all()
+            .filter(|problem| self.is_shown(problem.issue.severity))
+            .collect();
+        problems.sort_by(Problem::cmp_position);
+        problems
+    }
+
+    /**
+     * The problems listed in the panel: the ones that are shown and have the text of the filter,
+     * in the order of the sort.
+     */
+    pub fn problems(&self) -> Vec<Problem<'_>> {
+        let filter = self.filter.to_lowercase();
+        let mut problems = self.shown();
+        problems.retain(|problem| {
+            let diagnostic = &problem.issue.diagnostic;
+            filter.is_empty()
+                || problem.issue.message.to_lowercase().contains(&filter)
+                || problem.path().to_lowercase().contains(&filter)
+                || diagnostic
+                    .source
+                    .as_ref()
+                    .is_some_and(|source| source.to_lowercase().contains(&filter))
+        });
+        if self.sort == DiagnosticSort::Severity {
+            // The sort is stable, so they stay by file within a severity
+            problems.sort_by_key(|problem| problem.issue.severity);
+        }
+        problems
+    }
+
+    /**
+     * The next problem after `position` in the file, or the previous one before it, going on to
+     * the other files and around. The severities that are hidden in the panel are skipped.
+     */
+    pub fn next(
+        &self,
+        uri: &Url,
+        position: lsp_types::Position,
+        forward: bool,
+    ) -> Option<Location> {
+        let problems = self.shown();
+        let here = |problem: &Problem| {
+            let start = problem.issue.diagnostic.range.start;
+            problem
+                .uri
+                .as_str()
+                .cmp(uri.as_str())
+                .then((start.line, start.character).cmp(&(position.line, position.character)))
+        };
+        let problem = if forward {
+            problems
+                .iter()
+                .find(|problem| here(problem) == Ordering::Greater)
+                .or(problems.first())
+        } else {
+            problems
+                .iter()
+                .rev()
+                .find(|problem| here(problem) == Ordering::Less)
+                .or(problems.last())
+        };
+        problem.map(Problem::location)
+    }
+
+    /**
+     * Shows the problems of the severity when they're hidden, hides them otherwise.
+     */
+    pub fn toggle(&mut self, severity: Severity) {
+        match self.hidden.iter().position(|hidden| *hidden == severity) {
+            Some(index) => {
+                self.hidden.remove(index);
+            }
+            None => self.hidden.push(severity),
+        }
+    }
+
+    pub fn is_shown(&self, severity: Severity) -> bool {
+        !self.hidden.contains(&severity)
+    }
+
+    pub fn set_filter(&mut self, filter: String) {
+        self.filter = filter;
+    }
+
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    pub fn set_sort(&mut self, sort: DiagnosticSort) {
+        self.sort = sort;
+    }
+
+    pub fn sort(&self) -> DiagnosticSort {
+        self.sort
+    }
+}
+
+#[cfg(test)]
+mod diagnostics_tests {
+    use super::{DiagnosticSort, WorkspaceDiagnostics};
+    use async_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Location, Position, Range, Url};
+    use laurel_lsp::{
+        diagnostics::{ClientDiagnostics, Issue, Severity},
+        ServerId,
+    };
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    fn server() -> ServerId {
+        ServerId {
+            language: String::from("rust"),
+            root: PathBuf::from("/project"),
+        }
+    }
+
+    fn uri(file: &str) -> Url {
+        Url::parse(&format!("file:///project/src/{file}")).unwrap()
+    }
+
+    fn issue(line: u32, severity: DiagnosticSeverity, message: &str) -> Issue {
+        let start = Position::new(line, 4);
+        Issue::from(Diagnostic {
+            range: Range::new(start, start),
+            severity: Some(severity),
+            message: message.to_owned(),
+            ..Diagnostic::default()
+        })
+    }
+
+    fn diagnostics() -> WorkspaceDiagnostics {
+        let mut diagnostics = WorkspaceDiagnostics::default();
+        diagnostics.publish(
+            server(),
+            ClientDiagnostics {
+                uri: uri("main.rs"),
+                issues: vec![
+                    issue(9, DiagnosticSeverity::WARNING, "unused variable"),
+                    issue(2, DiagnosticSeverity::ERROR, "mismatched types"),
+                ],
+            },
+        );
+        diagnostics.publish(
+            server(),
+            ClientDiagnostics {
+                uri: uri("lib.rs"),
+                issues: vec![issue(5, DiagnosticSeverity::ERROR, "cannot find value")],
+            },
+        );
+        diagnostics
+    }
+
+    fn listed(diagnostics: &WorkspaceDiagnostics) -> Vec<String> {
+        diagnostics
+            .problems()
+            .iter()
+            .map(|problem| {
+                let line = problem.issue.diagnostic.range.start.line + 1;
+                format!("{}:{line} {}", problem.path(), problem.issue.message)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_problems_are_filtered_and_sorted() {
+        let mut diagnostics = diagnostics();
+        assert_eq!(diagnostics.counts(), (2, 1));
+        assert_eq!(
+            listed(&diagnostics),
+            vec![
+                "src/lib.rs:6 cannot find value",
+                "src/main.rs:3 mismatched types",
+                "src/main.rs:10 unused variable",
+            ]
+        );
+
+        diagnostics.set_sort(DiagnosticSort::Severity);
+        diagnostics.toggle(Severity::Error);
+        assert_eq!(listed(&diagnostics), vec!["src/main.rs:10 unused variable"]);
+        diagnostics.toggle(Severity::Error);
+        diagnostics.set_filter(String::from("MAIN"));
+        assert_eq!(
+            listed(&diagnostics),
+            vec![
+                "src/main.rs:3 mismatched types",
+                "src/main.rs:10 unused variable",
+            ]
+        );
+
+        // A file without diagnostics anymore is dropped
+        diagnostics.publish(
+            server(),
+            ClientDiagnostics {
+                uri: uri("main.rs"),
+                issues: Vec::new(),
+            },
+        );
+        assert_eq!(diagnostics.counts(), (1, 0));
+        assert!(diagnostics.get(&uri("main.rs")).is_none());
+        diagnostics.clear_server(&server());
+        assert_eq!(diagnostics.counts(), (0, 0));
+    }
+
+    #[test]
+    fn test_next_goes_across_files() {
+        let diagnostics = diagnostics();
+        let next = |file: &str, line: u32, forward: bool| {
+            diagnostics
+                .next(&uri(file), Position::new(line, 4), forward)
+                .map(|Location { uri, range }| (uri, range.start.line))
+        };
+
+        assert_eq!(next("main.rs", 2, true), Some((uri("main.rs"), 9)));
+        // From the last one of a file to the first one of the next, and around
+        assert_eq!(next("lib.rs", 5, true), Some((uri("main.rs"), 2)));
+        assert_eq!(next("main.rs", 9, true), Some((uri("lib.rs"), 5)));
+        assert_eq!(next("main.rs", 2, false), Some((uri("lib.rs"), 5)));
+        assert_eq!(next("lib.rs", 0, false), Some((uri("main.rs"), 9)));
+        assert_eq!(
+            WorkspaceDiagnostics::default().next(&uri("lib.rs"), Position::new(0, 0), true),
+            None
+        );
+    }
+}
@@ -5,9 +5,15 @@ use ropey::iter::Lines;
 use ropey::{Rope, RopeSlice};
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use tree_sitter::Point;
 use unicode_segmentation::UnicodeSegmentation;
 
-use laurel_common::text::Position;
+use laurel_common::text::{Position, Range};
+
+use crate::language::{self, LanguageConfig};
+
+use super::document_change::DocumentChange;
 
 pub struct ByteRange {
     pub start: usize,
@@ -22,7 +28,10 @@ pub struct FileData {
 pub struct Document {
     rope: Rope,
     file_data: FileData,
+    language: Option<&'static LanguageConfig>,
     is_saved: bool,
+    /// Goes up with every change, so the language server can tell the changes apart.
+    version: i32,
 }
 
 impl ToString for Document {
@@ -40,6 +49,13 @@ impl Document {
         &self.file_data.name
     }
 
+    /**
+     * The language detected when the document was created, `None` for plain text.
+     */
+    pub fn language(&self) -> Option<&'static LanguageConfig> {
+        self.language
+    }
+
     pub fn slice_all(&self) -> RopeSlice {
         self.rope.slice(..)
     }
@@ -48,16 +64,29 @@ impl Document {
         let file = File::open(filename)?;
         let rope = Rope::from_reader(BufReader::new(file))?;
 
+        Ok(Self::from_rope(rope, filename))
+    }
+
+    /**
+     * Creates a document from text that isn't on disk yet, `filename` has to be an absolute path.
+     *
+     * The language is picked from the filename, or from the `#!` line when that doesn't match.
+     */
+    pub fn from_rope(rope: Rope, filename: &str) -> Self {
         let uri = Url::from_file_path(filename).expect("File path conversion to Url failed");
         let file_data = FileData {
             name: filename.to_owned(),
             uri,
         };
-        Ok(Self {
+        let first_line = rope.get_line(0).map(|line| line.to_string());
+        let language = language::detect(Path::new(filename), first_line.as_deref());
+        Self {
             rope,
             file_data,
+            language,
             is_saved: true,
-        })
+            version: 0,
+        }
     }
 
     pub fn save(&mut self) -> Result<(), Error> {
@@ -78,6 +107,76 @@ impl Document {
         self.rope.line_to_char(position.line()) + position.character()
     }
 
+    pub fn char_to_position(&self, char_idx: usize) -> Position {
+        let line = self.rope.char_to_line(char_idx);
+        Position::new(line, char_idx - self.rope.line_to_char(line))
+    }
+
+    /**
+     * Converts a character offset to a position whose column is counted in UTF-16 code units, as
+     * language servers count them.
+     */
+    pub fn char_to_utf16_position(&self, char_idx: usize) -> Position {
+        let line = self.rope.char_to_line(char_idx);
+        let line_start = self.rope.char_to_utf16_cu(self.rope.line_to_char(line));
+        Position::new(line, self.rope.char_to_utf16_cu(char_idx) - line_start)
+    }
+
+    /**
+     * Moves a position that's past the end of its line, or of the document, back to the end.
+     */
+    pub fn clamp_position(&self, position: &Position) -> Position {
+        let line = position.line().min(self.rope.len_lines().saturating_sub(1));
+        let character = position.character().min(self.rope.line(line).len_chars());
+        Position::new(line, character)
+    }
+
+    /**
+     * Converts a position to one whose column is counted in UTF-16 code units, clamping it to
+     * the document, e.g. when the mouse is past the end of a line.
+     */
+    pub fn position_to_utf16(&self, position: &Position) -> Position {
+        let position = self.clamp_position(position);
+        self.char_to_utf16_position(self.get_character_pos(&position))
+    }
+
+    /**
+     * Converts a position whose column is counted in UTF-16 code units, as a language server
+     * sends it, back to one counted in characters, clamping it to the document.
+     */
+    pub fn utf16_to_position(&self, position: &Position) -> Position {
+        let line = position.line().min(self.rope.len_lines().saturating_sub(1));
+        let line_start = self.rope.line_to_char(line);
+        let line_end = line_start + self.rope.line(line).len_chars();
+        let utf16_start = self.rope.char_to_utf16_cu(line_start);
+        let utf16_end = self.rope.char_to_utf16_cu(line_end);
+        let utf16 = (utf16_start + position.character()).min(utf16_end);
+        Position::new(line, self.rope.utf16_cu_to_char(utf16) - line_start)
+    }
+
+    /**
+     * Converts a byte offset to a tree-sitter point, where the column is counted in bytes.
+     */
+    pub fn byte_to_point(&self, byte_idx: usize) -> Point {
+        let row = self.rope.byte_to_line(byte_idx);
+        Point::new(row, byte_idx - self.rope.line_to_byte(row))
+    }
+
+    /**
+     * Converts a byte offset to a position whose column is counted in characters.
+     */
+    pub fn byte_to_position(&self, byte_idx: usize) -> Position {
+        self.char_to_position(self.rope.byte_to_char(byte_idx))
+    }
+
+    pub fn position_to_byte(&self, position: &Position) -> usize {
+        self.rope.char_to_byte(self.get_character_pos(position))
+    }
+
+    pub fn byte_slice(&self, start: usize, end: usize) -> RopeSlice<'_> {
+        self.rope.byte_slice(start..end)
+    }
+
     pub fn str_from_range(&self, start: usize, end: usize) -> RopeSlice<'_> {
         self.rope.slice(start..end)
     }
@@ -93,6 +192,10 @@ impl Document {
         self.is_saved
     }
 
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
     /**
      * Get's the length of characters within the line
      */
@@ -126,44 +229,65 @@ impl Document {
     }
 
     /**
-     * Replaces the strings within the range of the position with the character inputted
+     * Replaces the strings within the range of the position with the character inputted.
+     *
+     * Every edit of the document goes through here, returning `None` when the range is out of
+     * bounds or nothing would change.
      */
     pub fn replace(
         &mut self,
         start_idx: &Position,
         end_idx: &Position,
         character: String,
-    ) -> Option<ByteRange> {
-        let result = self.delete(start_idx, end_idx);
-        self.insert(start_idx, character);
-        result
-    }
+    ) -> Option<DocumentChange> {
+        let start_char = self.get_character_pos(start_idx);
+        let end_char = self.get_character_pos(end_idx);
+        if start_char > end_char
+            || end_char > self.rope.len_chars()
+            || (start_char == end_char && character.is_empty())
+        {
+            return None;
+        }
 
-    /**
-     * Returns the byte that was the starting position of the insert
-     */
-    pub fn insert(&mut self, position: &Position, character: String) -> usize {
-        let start_idx = self.get_character_pos(position);
-        self.rope.insert(start_idx, &character.to_string());
+        // Everything is measured before the rope changes
+        let range = Range::new(
+            self.char_to_position(start_char),
+            self.char_to_position(end_char),
+        );
+        let lsp_range = Range::new(
+            self.char_to_utf16_position(start_char),
+            self.char_to_utf16_position(end_char),
+        );
+        let byte_range = ByteRange {
+            start: self.rope.char_to_byte(start_char),
+            end: self.rope.char_to_byte(end_char),
+        };
+        let start_point = self.byte_to_point(byte_range.start);
+        let old_end_point = self.byte_to_point(byte_range.end);
+        let old_text = self.rope.slice(start_char..end_char).to_string();
+
+        self.rope.remove(start_char..end_char);
+        self.rope.insert(start_char, &character);
         self.is_saved = false;
-        start_idx
+        self.version += 1;
+
+        Some(DocumentChange::new(
+            range,
+            lsp_range,
+            byte_range,
+            start_point,
+            old_end_point,
+            character,
+            old_text,
+            self.filename().clone(),
+        ))
     }
 
-    /**
-     * Returns the byte that was the starting position of the insert
-     */
-    pub fn delete(&mut self, start_idx: &Position, end_idx: &Position) -> Option<ByteRange> {
-        let start_line = self.rope.get_line(start_idx.line()).unwrap();
-        let start_idx = self.get_character_pos(start_idx);
-        let end_idx = self.get_character_pos(end_idx);
-        if start_line.len_chars() != 0 && end_idx <= self.rope.len_bytes() {
-            self.rope.remove(start_idx..end_idx);
-            self.is_saved = false;
-            return Some(ByteRange {
-                start: start_idx,
-                end: end_idx,
-            });
-        }
-        None
+    pub fn insert(&mut self, position: &Position, character: String) -> Option<DocumentChange> {
+        self.replace(position, position, character)
+    }
+
+    pub fn delete(&mut self, start_idx: &Position, end_idx: &Position) -> Option<DocumentChange> {
+        self.replace(start_idx, end_idx, String::new())
     }
 }
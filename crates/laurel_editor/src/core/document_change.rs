@@ -1,20 +1,146 @@
 use super::document::ByteRange;
 use laurel_common::text::Range;
+use tree_sitter::{InputEdit, Point};
 
+/**
+ * A single replacement within the document: `range` of the old text was replaced with `text`.
+ *
+ * The points are tree-sitter points, so their columns are counted in bytes.
+ */
 pub struct DocumentChange {
     pub range: Range,
+    /// The same range with its columns counted in UTF-16 code units, for the language server.
+    pub lsp_range: Range,
     pub byte_range: ByteRange,
+    pub start_point: Point,
+    pub old_end_point: Point,
     pub text: String,
+    /// The text that was replaced, to undo the change
+    pub old_text: String,
     pub file: String,
 }
 
 impl DocumentChange {
-    pub fn new(range: Range, byte_range: ByteRange, text: String, file: String) -> Self {
+    pub fn new(
+        range: Range,
+        lsp_range: Range,
+        byte_range: ByteRange,
+        start_point: Point,
+        old_end_point: Point,
+        text: String,
+        old_text: String,
+        file: String,
+    ) -> Self {
         Self {
             range,
+            lsp_range,
             byte_range,
+            start_point,
+            old_end_point,
             text,
+            old_text,
             file,
         }
     }
+
+    pub fn new_end_byte(&self) -> usize {
+        self.byte_range.start + self.text.len()
+    }
+
+    /**
+     * The point right after the inserted text, which can span several lines.
+     */
+    pub fn new_end_point(&self) -> Point {
+        match self.text.rfind('\n') {
+            Some(last_newline) => Point::new(
+                self.start_point.row + self.text.matches('\n').count(),
+                self.text.len() - last_newline - 1,
+            ),
+            None => Point::new(
+                self.start_point.row,
+                self.start_point.column + self.text.len(),
+            ),
+        }
+    }
+
+    pub fn input_edit(&self) -> InputEdit {
+        InputEdit {
+            start_byte: self.byte_range.start,
+            old_end_byte: self.byte_range.end,
+            new_end_byte: self.new_end_byte(),
+            start_position: self.start_point,
+            old_end_position: self.old_end_point,
+            new_end_position: self.new_end_point(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod document_change_tests {
+    use crate::core::document::Document;
+    use laurel_common::text::Position;
+    use pretty_assertions::assert_eq;
+    use ropey::Rope;
+    use tree_sitter::{InputEdit, Point};
+
+    fn document() -> Document {
+        Document::from_rope(Rope::from_str("let é = 1;\nlet b = 2;\n"), "/tmp/main.rs")
+    }
+
+    #[test]
+    fn test_multi_line_paste() {
+        let mut document = document();
+        let change = document
+            .insert(&Position::new(0, 7), String::from("(\n    0,\n  )"))
+            .unwrap();
+
+        // `é` is two bytes long, so the byte column is one ahead of the character
+        assert_eq!(
+            change.input_edit(),
+            InputEdit {
+                start_byte: 8,
+                old_end_byte: 8,
+                new_end_byte: 20,
+                start_position: Point::new(0, 8),
+                old_end_position: Point::new(0, 8),
+                new_end_position: Point::new(2, 3),
+            }
+        );
+    }
+
+    #[test]
+    fn test_multi_line_delete() {
+        let mut document = document();
+        let change = document
+            .delete(&Position::new(0, 4), &Position::new(1, 4))
+            .unwrap();
+
+        assert_eq!(
+            change.input_edit(),
+            InputEdit {
+                start_byte: 4,
+                old_end_byte: 16,
+                new_end_byte: 4,
+                start_position: Point::new(0, 4),
+                old_end_position: Point::new(1, 4),
+                new_end_position: Point::new(0, 4),
+            }
+        );
+        assert_eq!(document.to_string(), "let b = 2;\n");
+        assert_eq!(change.old_text, "é = 1;\nlet ");
+    }
+
+    #[test]
+    fn test_lsp_range_counts_utf16() {
+        let mut document = Document::from_rope(Rope::from_str("a𝕏é = 1;\n"), "/tmp/main.rs");
+        let change = document
+            .delete(&Position::new(0, 1), &Position::new(0, 3))
+            .unwrap();
+
+        // `𝕏` takes two UTF-16 code units and `é` takes one
+        assert_eq!(change.range.end(), Position::new(0, 3));
+        assert_eq!(change.lsp_range.start(), Position::new(0, 1));
+        assert_eq!(change.lsp_range.end(), Position::new(0, 4));
+        assert_eq!(document.version(), 1);
+    }
 }
@@ -0,0 +1,103 @@
+use async_lsp::lsp_types::FormattingOptions;
+
+/**
+ * How the language server is told to indent, the same as the editor indents.
+ */
+pub fn options() -> FormattingOptions {
+    FormattingOptions {
+        tab_size: 4,
+        insert_spaces: true,
+        ..FormattingOptions::default()
+    }
+}
+
+/**
+ * Where an offset into `old` ends up once a formatter replaced it with `new`, both in characters.
+ *
+ * Formatting mostly moves whitespace around, so the offset stays before the same code, or after
+ * it when it was followed by whitespace. In the indentation of a later line it stays on the same
+ * line after the code.
+ */
+pub fn map_offset(offset: usize, old: &str, new: &str) -> usize {
+    let before: Vec<char> = old.chars().take(offset).collect();
+    let before_code = old.chars().nth(offset).is_some_and(|c| !c.is_whitespace());
+    let code = before.iter().filter(|c| !c.is_whitespace()).count();
+    let line_breaks = before
+        .iter()
+        .rev()
+        .take_while(|c| c.is_whitespace())
+        .filter(|c| **c == '\n')
+        .count();
+
+    let mut chars = new.chars().peekable();
+    let mut index = 0;
+    let mut seen = 0;
+    while seen < code {
+        let Some(c) = chars.next() else {
+            return index;
+        };
+        index += 1;
+        if !c.is_whitespace() {
+            seen += 1;
+        }
+    }
+    if before_code {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {
+            index += 1;
+        }
+        return index;
+    }
+    if line_breaks == 0 {
+        return index;
+    }
+
+    let mut seen = 0;
+    while let Some(c) = chars.next_if(|c| c.is_whitespace()) {
+        index += 1;
+        if c == '\n' {
+            seen += 1;
+            if seen == line_breaks {
+                break;
+            }
+        }
+    }
+    // Past the indentation of the line
+    while chars.next_if(|c| c.is_whitespace() && *c != '\n').is_some() {
+        index += 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod formatting_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::map_offset;
+
+    #[test]
+    fn test_stays_on_code() {
+        let old = "fn main(){let a=1;}";
+        let new = "fn main() {\n    let a = 1;\n}";
+        // Before `a`
+        assert_eq!(map_offset(14, old, new), 20);
+        // Before `1`
+        assert_eq!(map_offset(16, old, new), 24);
+        // Before `}`, on the next line now
+        assert_eq!(map_offset(18, old, new), 27);
+        // The end stays at the end
+        assert_eq!(map_offset(old.len(), old, new), new.len());
+        assert_eq!(map_offset(0, old, new), 0);
+    }
+
+    #[test]
+    fn test_stays_in_indentation() {
+        let old = "{\n  \n  a  \n}";
+        let new = "{\n\n    a\n}";
+        // On the empty line
+        assert_eq!(map_offset(4, old, new), 2);
+        // Before `a`
+        assert_eq!(map_offset(7, old, new), 7);
+        // After the trailing whitespace that was removed
+        assert_eq!(map_offset(10, old, new), 8);
+    }
+}
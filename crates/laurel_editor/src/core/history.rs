@@ -0,0 +1,161 @@
+use std::mem;
+
+/// How many groups of changes can be undone.
+const CAPACITY: usize = 200;
+
+/**
+ * What undoes a change: the characters from `start` to `end` go back to `text`.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revert {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/**
+ * The changes of a buffer that can be undone and redone. The changes recorded until a group is
+ * closed, e.g. those of a keystroke or of a rename, are undone together.
+ */
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Vec<Revert>>,
+    redo: Vec<Vec<Revert>>,
+    /// The changes since the last group was closed
+    open: Vec<Revert>,
+}
+
+impl History {
+    pub fn record(&mut self, revert: Revert) {
+        self.open.push(revert);
+    }
+
+    /**
+     * Closes the group of the changes recorded since the last one, which forgets what was undone
+     * before them.
+     */
+    pub fn close_group(&mut self) {
+        if self.open.is_empty() {
+            return;
+        }
+        self.undo.push(mem::take(&mut self.open));
+        self.redo.clear();
+        if self.undo.len() > CAPACITY {
+            self.undo.remove(0);
+        }
+    }
+
+    /**
+     * The reverts of the last group, in the order they have to be made. The changes they make
+     * are recorded, and [`History::finish_undo`] keeps them to redo the group.
+     */
+    pub fn undo(&mut self) -> Option<Vec<Revert>> {
+        self.close_group();
+        let group = self.undo.pop()?;
+        Some(group.into_iter().rev().collect())
+    }
+
+    pub fn finish_undo(&mut self) {
+        let group = mem::take(&mut self.open);
+        if !group.is_empty() {
+            self.redo.push(group);
+        }
+    }
+
+    /**
+     * The reverts of the last group that was undone, which [`History::finish_redo`] keeps to undo
+     * it again.
+     */
+    pub fn redo(&mut self) -> Option<Vec<Revert>> {
+        self.close_group();
+        let group = self.redo.pop()?;
+        Some(group.into_iter().rev().collect())
+    }
+
+    pub fn finish_redo(&mut self) {
+        let group = mem::take(&mut self.open);
+        if !group.is_empty() {
+            self.undo.push(group);
+        }
+    }
+}
+
+#[cfg(test)]
+mod history_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::{History, Revert};
+
+    fn revert(start: usize, end: usize, text: &str) -> Revert {
+        Revert {
+            start,
+            end,
+            text: text.to_owned(),
+        }
+    }
+
+    /**
+     * Makes the reverts on `text`, recording what undoes them like the buffer does.
+     */
+    fn apply(history: &mut History, text: &mut String, reverts: Vec<Revert>) {
+        for made in reverts {
+            let (start, end) = (made.start, made.end);
+            let old: String = text.chars().skip(start).take(end - start).collect();
+            let mut chars: Vec<char> = text.chars().collect();
+            chars.splice(start..end, made.text.chars());
+            *text = chars.into_iter().collect();
+            history.record(revert(start, start + made.text.chars().count(), &old));
+        }
+    }
+
+    #[test]
+    fn test_undo_and_redo_groups() {
+        let mut history = History::default();
+        let mut text = String::new();
+        apply(&mut history, &mut text, vec![revert(0, 0, "ab")]);
+        history.close_group();
+        // Two changes in one group, e.g. a rename
+        apply(
+            &mut history,
+            &mut text,
+            vec![revert(0, 1, "xy"), revert(3, 3, "é")],
+        );
+        assert_eq!(text, "xybé");
+
+        let reverts = history.undo().unwrap();
+        apply(&mut history, &mut text, reverts);
+        history.finish_undo();
+        assert_eq!(text, "ab");
+
+        let reverts = history.redo().unwrap();
+        apply(&mut history, &mut text, reverts);
+        history.finish_redo();
+        assert_eq!(text, "xybé");
+
+        for _ in 0..2 {
+            let reverts = history.undo().unwrap();
+            apply(&mut history, &mut text, reverts);
+            history.finish_undo();
+        }
+        assert_eq!(text, "");
+        assert_eq!(history.undo(), None);
+    }
+
+    #[test]
+    fn test_changing_forgets_redo() {
+        let mut history = History::default();
+        let mut text = String::new();
+        apply(&mut history, &mut text, vec![revert(0, 0, "a")]);
+        let reverts = history.undo().unwrap();
+        apply(&mut history, &mut text, reverts);
+        history.finish_undo();
+
+        apply(&mut history, &mut text, vec![revert(0, 0, "b")]);
+        assert_eq!(history.redo(), None);
+        // The open group is undone first
+        let reverts = history.undo().unwrap();
+        apply(&mut history, &mut text, reverts);
+        history.finish_undo();
+        assert_eq!(text, "");
+    }
+}
@@ -0,0 +1,333 @@
+use async_lsp::lsp_types::{
+    InlayHint, InlayHintKind, InlayHintLabel, InlayHintLabelPartTooltip, InlayHintTooltip,
+    MarkupContent, MarkupKind, Range,
+};
+
+use super::references::shift;
+
+/**
+ * The text of a hint as it's drawn, with a space on the sides it's padded on.
+ */
+pub fn label(hint: &InlayHint) -> String {
+    let label = match &hint.label {
+        InlayHintLabel::String(label) => label.clone(),
+        InlayHintLabel::LabelParts(parts) => parts.iter().map(|part| part.value.as_str()).collect(),
+    };
+    let left = if hint.padding_left == Some(true) {
+        " "
+    } else {
+        ""
+    };
+    let right = if hint.padding_right == Some(true) {
+        " "
+    } else {
+        ""
+    };
+    format!("{left}{label}{right}")
+}
+
+fn plain_text(value: &str) -> MarkupContent {
+    MarkupContent {
+        kind: MarkupKind::PlainText,
+        value: value.to_owned(),
+    }
+}
+
+/**
+ * The tooltip of a hint followed by the tooltips of the parts of its label, none when it has no
+ * tooltip at all.
+ */
+pub fn tooltips(hint: &InlayHint) -> Vec<MarkupContent> {
+    let mut tooltips = Vec::new();
+    match &hint.tooltip {
+        Some(InlayHintTooltip::String(value)) => tooltips.push(plain_text(value)),
+        Some(InlayHintTooltip::MarkupContent(content)) => tooltips.push(content.clone()),
+        None => {}
+    }
+    if let InlayHintLabel::LabelParts(parts) = &hint.label {
+        for tooltip in parts.iter().filter_map(|part| part.tooltip.as_ref()) {
+            match tooltip {
+                InlayHintLabelPartTooltip::String(value) => tooltips.push(plain_text(value)),
+                InlayHintLabelPartTooltip::MarkupContent(content) => tooltips.push(content.clone()),
+            }
+        }
+    }
+    tooltips
+}
+
+/**
+ * The inlay hints of the open document sorted by position, which is in UTF-16 code units like
+ * the positions of the language server. They aren't part of the document, only drawn along with
+ * it.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct InlayHints {
+    hints: Vec<InlayHint>,
+}
+
+impl InlayHints {
+    pub fn new(mut hints: Vec<InlayHint>) -> Self {
+        // The order of the server is kept for hints at the same position
+        hints.sort_by_key(|hint| hint.position);
+        Self { hints }
+    }
+
+    pub fn len(&self) -> usize {
+        self.hints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hints.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&InlayHint> {
+        self.hints.get(index)
+    }
+
+    /**
+     * The hints on a line, along with their index.
+     */
+    pub fn on_line(&self, line: u32) -> impl Iterator<Item = (usize, &InlayHint)> {
+        let start = self.hints.partition_point(|hint| hint.position.line < line);
+        self.hints[start..]
+            .iter()
+            .take_while(move |hint| hint.position.line == line)
+            .enumerate()
+            .map(move |(index, hint)| (start + index, hint))
+    }
+
+    /**
+     * Puts the hint the server resolved in place of the one it was resolved from, unless the
+     * hints changed since.
+     */
+    pub fn resolve(&mut self, index: usize, resolved: InlayHint) -> bool {
+        match self.hints.get_mut(index) {
+            Some(hint) if hint.position == resolved.position => {
+                *hint = resolved;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /**
+     * Moves the hints along with a change of the text. A change around a hint drops it, while
+     * text typed right where a hint is goes before it, except for parameter names which stay
+     * before the argument. `None` replaces the whole text, which drops every hint.
+     */
+    pub fn apply_change(&mut self, change: Option<&Range>, text: &str) {
+        let Some(change) = change else {
+            self.hints.clear();
+            return;
+        };
+        self.hints.retain_mut(|hint| {
+            let stays_before = hint.kind == Some(InlayHintKind::PARAMETER);
+            let position = hint.position;
+            if position < change.start
+                || (position == change.start && (change.start != change.end || stays_before))
+            {
+                true
+            } else if position >= change.end {
+                hint.position = shift(position, change, text);
+                true
+            } else {
+                false
+            }
+        });
+    }
+}
+
+/**
+ * A hint of a line as it's drawn, at a character of the line.
+ */
+#[derive(Debug, Clone)]
+pub struct LineHint {
+    /// The index of the hint in [`InlayHints`]
+    pub index: usize,
+    /// The character the hint is drawn before
+    pub character: usize,
+    pub label: String,
+}
+
+impl LineHint {
+    fn width(&self) -> usize {
+        self.label.chars().count()
+    }
+}
+
+/**
+ * The hints of a line, which move the characters after them to later columns of the monospace
+ * grid.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct LineHints {
+    hints: Vec<LineHint>,
+}
+
+impl LineHints {
+    pub fn new(mut hints: Vec<LineHint>) -> Self {
+        hints.sort_by_key(|hint| hint.character);
+        Self { hints }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hints.is_empty()
+    }
+
+    /**
+     * The column of a character. The cursor at a character goes before the hints there, while
+     * the character itself goes `after_hints`.
+     */
+    pub fn column(&self, character: usize, after_hints: bool) -> usize {
+        let before: usize = self
+            .hints
+            .iter()
+            .take_while(|hint| {
+                hint.character < character || (after_hints && hint.character == character)
+            })
+            .map(LineHint::width)
+            .sum();
+        character + before
+    }
+
+    /**
+     * The hints along with the column each of them starts at.
+     */
+    pub fn placed(&self) -> impl Iterator<Item = (usize, &LineHint)> {
+        let mut before = 0;
+        self.hints.iter().map(move |hint| {
+            let column = hint.character + before;
+            before += hint.width();
+            (column, hint)
+        })
+    }
+
+    /**
+     * The character at a column, and the index of the hint when the column is on one. A column
+     * on a hint is at the character the hint is drawn before.
+     */
+    pub fn hit(&self, column: usize) -> (usize, Option<usize>) {
+        let mut before = 0;
+        for (start, hint) in self.placed() {
+            if column < start {
+                break;
+            }
+            if column < start + hint.width() {
+                return (hint.character, Some(hint.index));
+            }
+            before += hint.width();
+        }
+        (column - before, None)
+    }
+}
+
+#[cfg(test)]
+mod inlay_hints_tests {
+    use async_lsp::lsp_types::{InlayHint, InlayHintKind, InlayHintLabel, Position, Range};
+    use pretty_assertions::assert_eq;
+
+    use super::{label, InlayHints, LineHint, LineHints};
+
+    fn hint(line: u32, character: u32, text: &str, kind: InlayHintKind) -> InlayHint {
+        InlayHint {
+            position: Position::new(line, character),
+            label: InlayHintLabel::String(text.to_owned()),
+            kind: Some(kind),
+            text_edits: None,
+            tooltip: None,
+            padding_left: None,
+            padding_right: Some(kind == InlayHintKind::PARAMETER),
+            data: None,
+        }
+    }
+
+    fn positions(hints: &InlayHints) -> Vec<(u32, u32)> {
+        (0..hints.len())
+            .filter_map(|index| hints.get(index))
+            .map(|hint| (hint.position.line, hint.position.character))
+            .collect()
+    }
+
+    #[test]
+    fn test_follows_edits() {
+        // let a = foo(1, 2);
+        let mut hints = InlayHints::new(vec![
+            hint(0, 12, "x:", InlayHintKind::PARAMETER),
+            hint(0, 5, ": i32", InlayHintKind::TYPE),
+            hint(0, 15, "y:", InlayHintKind::PARAMETER),
+        ]);
+        assert_eq!(positions(&hints), vec![(0, 5), (0, 12), (0, 15)]);
+        assert_eq!(label(hints.get(1).unwrap()), "x: ");
+
+        // Typing at the end of the name moves its type along, but not before an argument
+        hints.apply_change(
+            Some(&Range::new(Position::new(0, 5), Position::new(0, 5))),
+            "b",
+        );
+        hints.apply_change(
+            Some(&Range::new(Position::new(0, 13), Position::new(0, 13))),
+            "3",
+        );
+        assert_eq!(positions(&hints), vec![(0, 6), (0, 13), (0, 17)]);
+
+        // Deleting around a hint drops it
+        hints.apply_change(
+            Some(&Range::new(Position::new(0, 12), Position::new(0, 16))),
+            "",
+        );
+        assert_eq!(positions(&hints), vec![(0, 6), (0, 13)]);
+        hints.apply_change(
+            Some(&Range::new(Position::new(0, 10), Position::new(0, 10))),
+            "\n",
+        );
+        assert_eq!(positions(&hints), vec![(0, 6), (1, 3)]);
+        assert_eq!(
+            hints.on_line(0).map(|(index, _)| index).collect::<Vec<_>>(),
+            vec![0]
+        );
+        assert_eq!(
+            hints.on_line(1).map(|(index, _)| index).collect::<Vec<_>>(),
+            vec![1]
+        );
+
+        hints.apply_change(None, "");
+        assert!(hints.is_empty());
+    }
+
+    #[test]
+    fn test_columns() {
+        // let a = foo(1);
+        let hints = LineHints::new(vec![
+            LineHint {
+                index: 1,
+                character: 12,
+                label: String::from("x: "),
+            },
+            LineHint {
+                index: 0,
+                character: 5,
+                label: String::from(": i32"),
+            },
+        ]);
+        assert_eq!(hints.column(4, true), 4);
+        // The cursor goes before the type, the space after it
+        assert_eq!(hints.column(5, false), 5);
+        assert_eq!(hints.column(5, true), 10);
+        assert_eq!(hints.column(12, false), 17);
+        assert_eq!(hints.column(12, true), 20);
+        assert_eq!(
+            hints
+                .placed()
+                .map(|(column, hint)| (column, hint.index))
+                .collect::<Vec<_>>(),
+            vec![(5, 0), (17, 1)]
+        );
+
+        assert_eq!(hints.hit(4), (4, None));
+        assert_eq!(hints.hit(7), (5, Some(0)));
+        assert_eq!(hints.hit(10), (5, None));
+        assert_eq!(hints.hit(19), (12, Some(1)));
+        assert_eq!(hints.hit(20), (12, None));
+        assert_eq!(hints.hit(25), (17, None));
+    }
+}
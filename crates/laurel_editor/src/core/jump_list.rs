@@ -0,0 +1,124 @@
+use async_lsp::lsp_types::Url;
+use laurel_common::text::Position;
+
+/// How many places are remembered to go back to.
+const CAPACITY: usize = 100;
+
+/**
+ * A place the cursor jumped from, with its column in characters.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Jump {
+    pub uri: Url,
+    pub position: Position,
+}
+
+/**
+ * The places the cursor jumped from, to go back to them and forward again like in a browser.
+ */
+#[derive(Debug, Default)]
+pub struct JumpList {
+    back: Vec<Jump>,
+    forward: Vec<Jump>,
+}
+
+impl JumpList {
+    /**
+     * Remembers where a jump started, which forgets the places that were gone back from.
+     */
+    pub fn push(&mut self, from: Jump) {
+        self.forward.clear();
+        if self.back.last() != Some(&from) {
+            self.back.push(from);
+        }
+        if self.back.len() > CAPACITY {
+            self.back.remove(0);
+        }
+    }
+
+    /**
+     * The place that going back, or forward, leads to, without moving there.
+     */
+    pub fn peek(&self, forward: bool) -> Option<&Jump> {
+        if forward {
+            self.forward.last()
+        } else {
+            self.back.last()
+        }
+    }
+
+    /**
+     * The place to go back to from `current`, which is remembered to go forward to again.
+     */
+    pub fn back(&mut self, current: Jump) -> Option<Jump> {
+        let jump = self.back.pop()?;
+        self.forward.push(current);
+        Some(jump)
+    }
+
+    pub fn forward(&mut self, current: Jump) -> Option<Jump> {
+        let jump = self.forward.pop()?;
+        self.back.push(current);
+        Some(jump)
+    }
+}
+
+#[cfg(test)]
+mod jump_list_tests {
+    use async_lsp::lsp_types::Url;
+    use laurel_common::text::Position;
+    use pretty_assertions::assert_eq;
+
+    use super::{Jump, JumpList};
+
+    fn jump(file: &str, line: usize) -> Jump {
+        Jump {
+            uri: Url::parse(&format!("file:///{file}")).unwrap(),
+            position: Position::new(line, 0),
+        }
+    }
+
+    #[test]
+    fn test_back_and_forward() {
+        let mut jumps = JumpList::default();
+        assert_eq!(jumps.back(jump("a.rs", 1)), None);
+
+        jumps.push(jump("a.rs", 1));
+        jumps.push(jump("b.rs", 2));
+        // At c.rs:3 after two jumps
+        assert_eq!(jumps.back(jump("c.rs", 3)), Some(jump("b.rs", 2)));
+        assert_eq!(jumps.back(jump("b.rs", 2)), Some(jump("a.rs", 1)));
+        assert_eq!(jumps.back(jump("a.rs", 1)), None);
+        assert_eq!(jumps.forward(jump("a.rs", 1)), Some(jump("b.rs", 2)));
+        assert_eq!(jumps.forward(jump("b.rs", 2)), Some(jump("c.rs", 3)));
+        assert_eq!(jumps.forward(jump("c.rs", 3)), None);
+    }
+
+    #[test]
+    fn test_jumping_forgets_forward() {
+        let mut jumps = JumpList::default();
+        jumps.push(jump("a.rs", 1));
+        jumps.back(jump("b.rs", 2));
+        jumps.push(jump("a.rs", 1));
+        // The same place twice in a row is kept once
+        jumps.push(jump("a.rs", 1));
+        assert_eq!(jumps.forward(jump("c.rs", 3)), None);
+        assert_eq!(jumps.back(jump("c.rs", 3)), Some(jump("a.rs", 1)));
+        assert_eq!(jumps.back(jump("a.rs", 1)), None);
+    }
+
+    #[test]
+    fn test_peek_keeps_the_history() {
+        let mut jumps = JumpList::default();
+        assert_eq!(jumps.peek(false), None);
+
+        jumps.push(jump("a.rs", 1));
+        assert_eq!(jumps.peek(false), Some(&jump("a.rs", 1)));
+        assert_eq!(jumps.peek(false), Some(&jump("a.rs", 1)));
+        assert_eq!(jumps.peek(true), None);
+
+        jumps.back(jump("b.rs", 2));
+        assert_eq!(jumps.peek(false), None);
+        assert_eq!(jumps.peek(true), Some(&jump("b.rs", 2)));
+    }
+}
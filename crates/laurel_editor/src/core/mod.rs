@@ -1,4 +1,19 @@
 pub mod buffer;
+pub mod code_action;
+pub mod completion;
+pub mod diagnostics;
 pub mod document;
 pub mod document_change;
+pub mod formatting;
+pub mod history;
+pub mod inlay_hints;
+pub mod jump_list;
+pub mod navigation;
+pub mod output;
+pub mod progress;
+pub mod references;
+pub mod requests;
+pub mod signature_help;
+pub mod snippet;
 pub mod window;
+pub mod workspace_edit;
@@ -0,0 +1,15 @@
+use async_lsp::lsp_types::Location;
+
+/**
+ * Where the editor was asked to go, kept while the unsaved changes of the open document are
+ * saved or discarded.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Navigation {
+    /// A file picked by the user
+    File(String),
+    /// A location of the language server, e.g. a definition or a reference
+    Location(Location),
+    /// Back to where the cursor jumped from, or forward again
+    Jump { forward: bool },
+}
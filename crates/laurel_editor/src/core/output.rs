@@ -0,0 +1,318 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use laurel_lsp::{ServerId, ServerOutput, TraceKind, TraceMessage};
+
+/**
+ * How many entries are kept, the oldest ones are dropped past it.
+ */
+const MAX_ENTRIES: usize = 5000;
+
+/**
+ * What the language servers said besides their answers, and the trace of the messages that went
+ * between them and the editor while it's on, oldest first.
+ */
+#[derive(Debug)]
+pub struct OutputLog {
+    entries: VecDeque<OutputEntry>,
+    /// The requests of the trace waiting for a response, by server, sender and id, with when they
+    /// were sent and their method
+    pending: HashMap<(ServerId, bool, String), (Instant, String)>,
+    /// Only the entries with the text are shown, whatever the case
+    filter: String,
+    hidden: Vec<OutputKind>,
+    /// The times of the entries are counted from it
+    started: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputKind {
+    Stderr,
+    Log,
+    Message,
+    Trace,
+}
+
+/**
+ * A line of the output, as it's shown and copied.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputEntry {
+    pub kind: OutputKind,
+    pub text: String,
+}
+
+impl OutputKind {
+    pub const ALL: [OutputKind; 4] = [
+        OutputKind::Stderr,
+        OutputKind::Log,
+        OutputKind::Message,
+        OutputKind::Trace,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OutputKind::Stderr => "Stderr",
+            OutputKind::Log => "Log",
+            OutputKind::Message => "Messages",
+            OutputKind::Trace => "Trace",
+        }
+    }
+}
+
+impl Default for OutputLog {
+    fn default() -> Self {
+        Self::new(Instant::now())
+    }
+}
+
+impl OutputLog {
+    pub fn new(started: Instant) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            pending: HashMap::new(),
+            filter: String::new(),
+            hidden: Vec::new(),
+            started,
+        }
+    }
+
+    /**
+     * Adds what the server said, at `at` unless it's a message of the trace, which knows when it
+     * went over the pipe.
+     */
+    pub fn push(&mut self, server: &ServerId, output: ServerOutput, at: Instant) {
+        let (kind, at, text) = match output {
+            ServerOutput::Stderr(line) => (OutputKind::Stderr, at, line),
+            ServerOutput::Log(typ, message) => (OutputKind::Log, at, format!("{typ:?}: {message}")),
+            ServerOutput::Message(typ, message) => {
+                (OutputKind::Message, at, format!("{typ:?}: {message}"))
+            }
+            ServerOutput::Trace(message) => {
+                let at = message.at;
+                (OutputKind::Trace, at, self.trace_text(server, message))
+            }
+        };
+        let time = at.saturating_duration_since(self.started).as_secs_f64();
+        self.entries.push_back(OutputEntry {
+            kind,
+            text: format!("[{time:.3}] {} {}: {text}", server.language, kind.label()),
+        });
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+    }
+
+    /**
+     * The line of a message of the trace, e.g. `<- response 3 textDocument/hover (12 ms) {..}`.
+     * A response is paired with its request to tell what it answers and how long it took.
+     */
+    fn trace_text(&mut self, server: &ServerId, message: TraceMessage) -> String {
+        let arrow = if message.sent { "->" } else { "<-" };
+        let id = message.id.unwrap_or_default();
+        match message.kind {
+            TraceKind::Request => {
+                let method = message.method.unwrap_or_default();
+                let text = format!("{arrow} request {id} {method} {}", message.json);
+                // The requests that are never answered would pile up
+                if self.pending.len() >= MAX_ENTRIES {
+                    self.pending.clear();
+                }
+                self.pending
+                    .insert((server.clone(), message.sent, id), (message.at, method));
+                text
+            }
+            TraceKind::Response => {
+                // The response goes the other way than its request
+                let request = self
+                    .pending
+                    .remove(&(server.clone(), !message.sent, id.clone()));
+                match request {
+                    Some((sent, method)) => {
+                        let elapsed = message.at.saturating_duration_since(sent);
+                        format!(
+                            "{arrow} response {id} {method} ({}) {}",
+                            format_elapsed(elapsed),
+                            message.json
+                        )
+                    }
+                    None => format!("{arrow} response {id} {}", message.json),
+                }
+            }
+            TraceKind::Notification => format!(
+                "{arrow} notification {} {}",
+                message.method.unwrap_or_default(),
+                message.json
+            ),
+        }
+    }
+
+    /**
+     * Forgets the requests of a server that stopped, they're never answered.
+     */
+    pub fn clear_server(&mut self, server: &ServerId) {
+        self.pending.retain(|(pending, ..), _| pending != server);
+    }
+
+    /**
+     * The entries that aren't hidden and have the text of the filter.
+     */
+    pub fn visible(&self) -> impl DoubleEndedIterator<Item = (usize, &OutputEntry)> {
+        let filter = self.filter.to_lowercase();
+        self.entries.iter().enumerate().filter(move |(_, entry)| {
+            !self.hidden.contains(&entry.kind)
+                && (filter.is_empty() || entry.text.to_lowercase().contains(&filter))
+        })
+    }
+
+    pub fn entry(&self, index: usize) -> Option<&OutputEntry> {
+        self.entries.get(index)
+    }
+
+    /**
+     * The visible entries, one per line.
+     */
+    pub fn copy_text(&self) -> String {
+        let lines: Vec<&str> = self
+            .visible()
+            .map(|(_, entry)| entry.text.as_str())
+            .collect();
+        lines.join("\n")
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /**
+     * Shows the entries of the kind when they're hidden, hides them otherwise.
+     */
+    pub fn toggle(&mut self, kind: OutputKind) {
+        match self.hidden.iter().position(|hidden| *hidden == kind) {
+            Some(index) => {
+                self.hidden.remove(index);
+            }
+            None => self.hidden.push(kind),
+        }
+    }
+
+    pub fn is_shown(&self, kind: OutputKind) -> bool {
+        !self.hidden.contains(&kind)
+    }
+
+    pub fn set_filter(&mut self, filter: String) {
+        self.filter = filter;
+    }
+
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    if elapsed < Duration::from_secs(1) {
+        format!("{} ms", elapsed.as_millis())
+    } else {
+        format!("{:.2} s", elapsed.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod output_tests {
+    use super::{OutputKind, OutputLog};
+    use async_lsp::lsp_types::MessageType;
+    use laurel_lsp::{ServerId, ServerOutput, TraceMessage};
+    use pretty_assertions::assert_eq;
+    use std::{
+        path::PathBuf,
+        time::{Duration, Instant},
+    };
+
+    fn server() -> ServerId {
+        ServerId {
+            language: String::from("rust"),
+            root: PathBuf::from("/project"),
+        }
+    }
+
+    fn trace(sent: bool, at: Instant, json: &str) -> ServerOutput {
+        ServerOutput::Trace(TraceMessage::parse(sent, at, json.to_owned()).unwrap())
+    }
+
+    fn lines(log: &OutputLog) -> Vec<String> {
+        log.visible().map(|(_, entry)| entry.text.clone()).collect()
+    }
+
+    #[test]
+    fn test_response_is_paired_with_request() {
+        let started = Instant::now();
+        let mut log = OutputLog::new(started);
+        let request = r#"{"jsonrpc":"2.0","id":3,"method":"textDocument/hover"}"#;
+        let response = r#"{"jsonrpc":"2.0","id":3,"result":null}"#;
+        let notification = r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#;
+        log.push(&server(), trace(true, started, request), started);
+        // The server's request with the same id is another one
+        log.push(&server(), trace(false, started, request), started);
+        log.push(
+            &server(),
+            trace(false, started + Duration::from_millis(12), response),
+            started,
+        );
+        log.push(&server(), trace(true, started, notification), started);
+
+        assert_eq!(
+            lines(&log),
+            vec![
+                format!("[0.000] rust Trace: -> request 3 textDocument/hover {request}"),
+                format!("[0.000] rust Trace: <- request 3 textDocument/hover {request}"),
+                format!("[0.012] rust Trace: <- response 3 textDocument/hover (12 ms) {response}"),
+                format!("[0.000] rust Trace: -> notification initialized {notification}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter_and_hidden_kinds() {
+        let started = Instant::now();
+        let mut log = OutputLog::new(started);
+        let at = started + Duration::from_millis(1500);
+        log.push(
+            &server(),
+            ServerOutput::Stderr(String::from("panicked")),
+            at,
+        );
+        log.push(
+            &server(),
+            ServerOutput::Log(MessageType::WARNING, String::from("Fetching crates")),
+            at,
+        );
+        log.push(
+            &server(),
+            ServerOutput::Message(MessageType::ERROR, String::from("Failed to fetch")),
+            at,
+        );
+
+        log.set_filter(String::from("FETCH"));
+        assert_eq!(
+            lines(&log),
+            vec![
+                "[1.500] rust Log: Warning: Fetching crates",
+                "[1.500] rust Messages: Error: Failed to fetch",
+            ]
+        );
+
+        log.toggle(OutputKind::Log);
+        assert_eq!(
+            log.copy_text(),
+            "[1.500] rust Messages: Error: Failed to fetch"
+        );
+        log.toggle(OutputKind::Log);
+        log.set_filter(String::new());
+        assert_eq!(lines(&log).len(), 3);
+
+        log.clear();
+        assert!(lines(&log).is_empty());
+    }
+}
@@ -0,0 +1,191 @@
+use std::fmt::Write as _;
+
+use async_lsp::lsp_types::{NumberOrString, WorkDoneProgress};
+use laurel_lsp::ServerId;
+
+/**
+ * The work the language servers reported progress on and haven't ended yet, like indexing, in
+ * the order it began.
+ */
+#[derive(Debug, Default)]
+pub struct Progress {
+    works: Vec<Work>,
+}
+
+/**
+ * Work a language server began, as it was last reported.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Work {
+    server: ServerId,
+    /// Tells the works of the server apart
+    token: NumberOrString,
+    pub title: String,
+    pub message: Option<String>,
+    /// How much of the work is done out of 100, `None` when the server can't tell
+    pub percentage: Option<u32>,
+}
+
+impl Progress {
+    /**
+     * Begins, reports on or ends a work of the server.
+     */
+    pub fn update(&mut self, server: ServerId, token: NumberOrString, progress: WorkDoneProgress) {
+        let index = self
+            .works
+            .iter()
+            .position(|work| work.server == server && work.token == token);
+        match progress {
+            WorkDoneProgress::Begin(begin) => {
+                let work = Work {
+                    server,
+                    token,
+                    title: begin.title,
+                    message: begin.message,
+                    percentage: begin.percentage,
+                };
+                match index {
+                    Some(index) => self.works[index] = work,
+                    None => self.works.push(work),
+                }
+            }
+            WorkDoneProgress::Report(report) => {
+                // A report of work that never began has nothing to go with
+                let Some(work) = index.map(|index| &mut self.works[index]) else {
+                    return;
+                };
+                // What's left out is still as it was
+                if report.message.is_some() {
+                    work.message = report.message;
+                }
+                if report.percentage.is_some() {
+                    work.percentage = report.percentage;
+                }
+            }
+            WorkDoneProgress::End(_) => {
+                if let Some(index) = index {
+                    self.works.remove(index);
+                }
+            }
+        }
+    }
+
+    /**
+     * Drops the works of a server that stopped or was started again, as they'll never end.
+     */
+    pub fn clear_server(&mut self, server: &ServerId) {
+        self.works.retain(|work| &work.server != server);
+    }
+
+    pub fn works(&self) -> &[Work] {
+        &self.works
+    }
+}
+
+impl Work {
+    /**
+     * How the work is shown in the status bar, e.g. `rust: Indexing 40% 4/10 (core)`.
+     */
+    pub fn label(&self) -> String {
+        let mut label = format!("{}: {}", self.server.language, self.title);
+        if let Some(percentage) = self.percentage {
+            let _ = write!(label, " {}%", percentage.min(100));
+        }
+        if let Some(message) = &self.message {
+            label.push(' ');
+            label.push_str(message);
+        }
+        label
+    }
+}
+
+#[cfg(test)]
+mod progress_tests {
+    use super::Progress;
+    use async_lsp::lsp_types::{
+        NumberOrString, WorkDoneProgress, WorkDoneProgressBegin, WorkDoneProgressEnd,
+        WorkDoneProgressReport,
+    };
+    use laurel_lsp::ServerId;
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    fn server(language: &str) -> ServerId {
+        ServerId {
+            language: language.to_owned(),
+            root: PathBuf::from("/project"),
+        }
+    }
+
+    fn labels(progress: &Progress) -> Vec<String> {
+        progress.works().iter().map(|work| work.label()).collect()
+    }
+
+    #[test]
+    fn test_work_is_reported() {
+        let mut progress = Progress::default();
+        let token = NumberOrString::String(String::from("indexing"));
+        progress.update(
+            server("rust"),
+            token.clone(),
+            WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title: String::from("Indexing"),
+                message: Some(String::from("0/10")),
+                percentage: Some(0),
+                ..WorkDoneProgressBegin::default()
+            }),
+        );
+        progress.update(
+            server("rust"),
+            token.clone(),
+            WorkDoneProgress::Report(WorkDoneProgressReport {
+                percentage: Some(40),
+                ..WorkDoneProgressReport::default()
+            }),
+        );
+        // The same token of another server is another work
+        progress.update(
+            server("python"),
+            token.clone(),
+            WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title: String::from("Linting"),
+                ..WorkDoneProgressBegin::default()
+            }),
+        );
+        assert_eq!(
+            labels(&progress),
+            vec!["rust: Indexing 40% 0/10", "python: Linting"]
+        );
+
+        progress.update(
+            server("rust"),
+            token.clone(),
+            WorkDoneProgress::End(WorkDoneProgressEnd::default()),
+        );
+        assert_eq!(labels(&progress), vec!["python: Linting"]);
+    }
+
+    #[test]
+    fn test_stopped_server_is_cleared() {
+        let mut progress = Progress::default();
+        for (language, token) in [("rust", 1), ("rust", 2), ("python", 1)] {
+            progress.update(
+                server(language),
+                NumberOrString::Number(token),
+                WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                    title: format!("Work {token}"),
+                    ..WorkDoneProgressBegin::default()
+                }),
+            );
+        }
+        // A report of work that never began is dropped
+        progress.update(
+            server("python"),
+            NumberOrString::Number(2),
+            WorkDoneProgress::Report(WorkDoneProgressReport::default()),
+        );
+
+        progress.clear_server(&server("rust"));
+        assert_eq!(labels(&progress), vec!["python: Work 1"]);
+    }
+}
@@ -0,0 +1,303 @@
+use std::ops;
+
+use async_lsp::lsp_types::{Location, Position, Range, Url};
+
+/**
+ * Where a position after `range` ends up once `range` is replaced with `text`, in UTF-16 code
+ * units like the positions of the language server.
+ */
+pub fn shift(position: Position, range: &Range, text: &str) -> Position {
+    let newlines = text.matches('\n').count() as u32;
+    let last_line = text.rsplit('\n').next().unwrap_or_default();
+    let last_len = last_line.encode_utf16().count() as u32;
+    let end = match newlines {
+        0 => Position::new(range.start.line, range.start.character + last_len),
+        _ => Position::new(range.start.line + newlines, last_len),
+    };
+
+    if position.line == range.end.line {
+        Position::new(
+            end.line,
+            end.character + position.character - range.end.character,
+        )
+    } else {
+        Position::new(
+            position.line - range.end.line + end.line,
+            position.character,
+        )
+    }
+}
+
+/**
+ * The references of a symbol sorted by file and position, along with the line each of them
+ * starts on, and the one that was last gone to.
+ */
+#[derive(Debug, Clone)]
+pub struct References {
+    locations: Vec<Location>,
+    previews: Vec<String>,
+    current: Option<usize>,
+}
+
+impl References {
+    /**
+     * `preview` gives the line a location starts on, `None` when it can't be read.
+     */
+    pub fn new(
+        mut locations: Vec<Location>,
+        preview: impl Fn(&Location) -> Option<String>,
+    ) -> Self {
+        locations
+            .sort_by(|a, b| (a.uri.as_str(), a.range.start).cmp(&(b.uri.as_str(), b.range.start)));
+        locations.dedup();
+        let previews = locations
+            .iter()
+            .map(|location| preview(location).unwrap_or_default().trim().to_owned())
+            .collect();
+        Self {
+            locations,
+            previews,
+            current: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    pub fn locations(&self) -> &[Location] {
+        &self.locations
+    }
+
+    pub fn preview(&self, index: usize) -> &str {
+        &self.previews[index]
+    }
+
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /**
+     * The files in order, with the indices of their references.
+     */
+    pub fn files(&self) -> Vec<(&Url, ops::Range<usize>)> {
+        let mut files: Vec<(&Url, ops::Range<usize>)> = Vec::new();
+        for (index, location) in self.locations.iter().enumerate() {
+            match files.last_mut() {
+                Some((uri, range)) if *uri == &location.uri => range.end = index + 1,
+                _ => files.push((&location.uri, index..index + 1)),
+            }
+        }
+        files
+    }
+
+    /**
+     * The ranges of the references in a file.
+     */
+    pub fn in_file<'a>(&'a self, uri: &'a Url) -> impl Iterator<Item = &'a Range> + 'a {
+        self.locations
+            .iter()
+            .filter(move |location| &location.uri == uri)
+            .map(|location| &location.range)
+    }
+
+    pub fn select(&mut self, index: usize) -> Option<&Location> {
+        let location = self.locations.get(index)?;
+        self.current = Some(index);
+        Some(location)
+    }
+
+    /**
+     * Goes to the next reference, or to the first one after the last.
+     */
+    pub fn next(&mut self) -> Option<&Location> {
+        let next = self.current.map_or(0, |current| current + 1);
+        self.select(next % self.len().max(1))
+    }
+
+    pub fn previous(&mut self) -> Option<&Location> {
+        let previous = match self.current {
+            Some(current) if current > 0 => current - 1,
+            _ => self.len().checked_sub(1)?,
+        };
+        self.select(previous)
+    }
+
+    /**
+     * Moves the references of a file along with a change of its text. A change that cuts
+     * through a reference drops it, while one inside of it keeps it, e.g. while it's renamed.
+     * `None` replaces the whole text, which drops every reference in the file.
+     */
+    pub fn apply_change(&mut self, uri: &Url, change: Option<&Range>, text: &str) {
+        let mut index = 0;
+        while index < self.locations.len() {
+            let location = &mut self.locations[index];
+            let kept = match change {
+                _ if &location.uri != uri => true,
+                None => false,
+                Some(change) if location.range.end <= change.start => true,
+                Some(change) if location.range.start >= change.end => {
+                    location.range.start = shift(location.range.start, change, text);
+                    location.range.end = shift(location.range.end, change, text);
+                    true
+                }
+                Some(change)
+                    if location.range.start <= change.start && change.end <= location.range.end =>
+                {
+                    location.range.end = shift(location.range.end, change, text);
+                    true
+                }
+                Some(_) => false,
+            };
+
+            if kept {
+                index += 1;
+                continue;
+            }
+            self.locations.remove(index);
+            self.previews.remove(index);
+            self.current = match self.current {
+                Some(current) if current > index => Some(current - 1),
+                Some(current) if current == index => None,
+                current => current,
+            };
+        }
+    }
+
+    /**
+     * Reads the lines of the references of a file again, after its text changed.
+     */
+    pub fn refresh_previews(&mut self, uri: &Url, preview: impl Fn(&Location) -> Option<String>) {
+        for (location, line) in self.locations.iter().zip(self.previews.iter_mut()) {
+            if &location.uri == uri {
+                *line = preview(location).unwrap_or_default().trim().to_owned();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod references_tests {
+    use async_lsp::lsp_types::{Location, Position, Range, Url};
+    use pretty_assertions::assert_eq;
+
+    use super::References;
+
+    fn location(file: &str, line: u32, start: u32, end: u32) -> Location {
+        Location::new(
+            Url::parse(&format!("file:///{file}")).unwrap(),
+            Range::new(Position::new(line, start), Position::new(line, end)),
+        )
+    }
+
+    fn found(locations: Vec<Location>) -> References {
+        References::new(locations, |location| {
+            Some(format!("  line {}  ", location.range.start.line))
+        })
+    }
+
+    fn starts(references: &References) -> Vec<(u32, u32, u32)> {
+        references
+            .locations()
+            .iter()
+            .map(|location| {
+                let range = location.range;
+                (range.start.line, range.start.character, range.end.character)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sorted_by_file() {
+        let references = found(vec![
+            location("b.rs", 4, 0, 3),
+            location("a.rs", 9, 2, 5),
+            location("b.rs", 1, 0, 3),
+            location("a.rs", 9, 2, 5),
+        ]);
+        assert_eq!(references.len(), 3);
+        assert_eq!(references.preview(0), "line 9");
+
+        let files: Vec<(String, std::ops::Range<usize>)> = references
+            .files()
+            .into_iter()
+            .map(|(uri, range)| (uri.path().to_owned(), range))
+            .collect();
+        assert_eq!(
+            files,
+            vec![(String::from("/a.rs"), 0..1), (String::from("/b.rs"), 1..3)]
+        );
+    }
+
+    #[test]
+    fn test_next_and_previous() {
+        let mut references = found(vec![
+            location("a.rs", 1, 0, 3),
+            location("a.rs", 2, 0, 3),
+            location("b.rs", 1, 0, 3),
+        ]);
+        assert_eq!(references.previous(), Some(&location("b.rs", 1, 0, 3)));
+        assert_eq!(references.next(), Some(&location("a.rs", 1, 0, 3)));
+        assert_eq!(references.next(), Some(&location("a.rs", 2, 0, 3)));
+        assert_eq!(references.previous(), Some(&location("a.rs", 1, 0, 3)));
+        assert_eq!(references.current(), Some(0));
+
+        let mut none = found(Vec::new());
+        assert_eq!(none.next(), None);
+        assert_eq!(none.previous(), None);
+    }
+
+    #[test]
+    fn test_follows_changes() {
+        let uri = Url::parse("file:///a.rs").unwrap();
+        let mut references = found(vec![
+            location("a.rs", 1, 4, 7),
+            location("a.rs", 1, 12, 15),
+            location("a.rs", 3, 0, 3),
+            location("b.rs", 1, 4, 7),
+        ]);
+        references.select(2);
+
+        // Typed before the first reference
+        let typed = Range::new(Position::new(1, 0), Position::new(1, 0));
+        references.apply_change(&uri, Some(&typed), "ab");
+        assert_eq!(
+            starts(&references),
+            vec![(1, 6, 9), (1, 14, 17), (3, 0, 3), (1, 4, 7)]
+        );
+
+        // A line break between the two references on the same line
+        let newline = Range::new(Position::new(1, 10), Position::new(1, 10));
+        references.apply_change(&uri, Some(&newline), "\n  ");
+        assert_eq!(
+            starts(&references),
+            vec![(1, 6, 9), (2, 6, 9), (4, 0, 3), (1, 4, 7)]
+        );
+
+        // Renaming inside of a reference keeps it
+        let renamed = Range::new(Position::new(2, 6), Position::new(2, 9));
+        references.apply_change(&uri, Some(&renamed), "renamed");
+        assert_eq!(
+            starts(&references),
+            vec![(1, 6, 9), (2, 6, 13), (4, 0, 3), (1, 4, 7)]
+        );
+
+        // Deleting across a reference drops it
+        let deleted = Range::new(Position::new(1, 0), Position::new(1, 8));
+        references.apply_change(&uri, Some(&deleted), "");
+        assert_eq!(starts(&references), vec![(2, 6, 13), (4, 0, 3), (1, 4, 7)]);
+        assert_eq!(references.current(), Some(1));
+
+        references.refresh_previews(&uri, |_| Some(String::from("changed")));
+        assert_eq!(references.preview(1), "changed");
+        assert_eq!(references.preview(2), "line 1");
+
+        references.apply_change(&uri, None, "");
+        assert_eq!(starts(&references), vec![(1, 4, 7)]);
+        assert_eq!(references.current(), None);
+    }
+}
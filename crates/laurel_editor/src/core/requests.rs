@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+
+use laurel_common::text::Position;
+use laurel_lsp::RequestId;
+
+/**
+ * What a request that's waiting for a response of the language server was sent for, with what
+ * its response is handled with.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PendingRequest {
+    SemanticTokens,
+    /// Where the hover was asked for, in characters
+    Hover(Position),
+    Completion,
+    /// Whether the item is inserted once it's resolved
+    ResolveCompletion {
+        accept: bool,
+    },
+    /// The cursor the signatures were asked at
+    SignatureHelp(Position),
+    /// A definition, declaration, etc.
+    Goto,
+    References,
+    /// The prepare rename or rename request
+    Rename,
+    /// Whether the menu opens with the actions
+    CodeActions {
+        invoked: bool,
+    },
+    /// The version of the document it formats, and whether the document is saved once it's
+    /// formatted
+    Formatting {
+        version: i32,
+        save: bool,
+    },
+    InlayHints,
+    /// The index of the hint that's resolved
+    ResolveInlayHint(usize),
+}
+
+impl PendingRequest {
+    fn same_kind(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+/**
+ * The requests that are waiting for a response of the language server, only the last one of each
+ * kind as an older one would be out of date by the time it's answered.
+ */
+#[derive(Debug, Default)]
+pub struct PendingRequests {
+    requests: HashMap<RequestId, PendingRequest>,
+}
+
+impl PendingRequests {
+    /**
+     * Waits for the response of a request, in place of the request of the same kind, which is
+     * returned to be cancelled.
+     */
+    pub fn insert(&mut self, id: RequestId, request: PendingRequest) -> Option<RequestId> {
+        let previous = self.remove(|pending| pending.same_kind(&request));
+        self.requests.insert(id, request);
+        previous
+    }
+
+    pub fn get(&self, id: RequestId) -> Option<&PendingRequest> {
+        self.requests.get(&id)
+    }
+
+    /**
+     * The request a response or a failure is for, which isn't waited for anymore. `None` when it
+     * was replaced or cancelled since, or when its response doesn't matter.
+     */
+    pub fn take(&mut self, id: RequestId) -> Option<PendingRequest> {
+        self.requests.remove(&id)
+    }
+
+    /**
+     * Whether a request of a kind is waiting for its response.
+     */
+    pub fn contains(&self, kind: impl Fn(&PendingRequest) -> bool) -> bool {
+        self.requests.values().any(kind)
+    }
+
+    /**
+     * Stops waiting for the request of a kind, which is returned to be cancelled.
+     */
+    pub fn remove(&mut self, kind: impl Fn(&PendingRequest) -> bool) -> Option<RequestId> {
+        let id = self
+            .requests
+            .iter()
+            .find_map(|(id, request)| kind(request).then_some(*id))?;
+        self.requests.remove(&id);
+        Some(id)
+    }
+
+    /**
+     * Stops waiting for the requests of some kinds, e.g. the ones of a document that was closed.
+     */
+    pub fn forget(&mut self, kinds: impl Fn(&PendingRequest) -> bool) {
+        self.requests.retain(|_, request| !kinds(request));
+    }
+}
+
+#[cfg(test)]
+mod requests_tests {
+    use laurel_common::text::Position;
+    use laurel_lsp::{LspConnection, LspRequest, RequestId, ServerId};
+    use pretty_assertions::assert_eq;
+    use std::{path::PathBuf, sync::Arc};
+    use tokio::sync::mpsc;
+
+    use super::{PendingRequest, PendingRequests};
+
+    /// The ids of requests to a server that's gone
+    fn ids<const N: usize>() -> [RequestId; N] {
+        let (sender, _) = mpsc::unbounded_channel();
+        let server = ServerId {
+            language: String::from("rust"),
+            root: PathBuf::from("/tmp"),
+        };
+        let mut lsp = LspConnection::new(server, sender, Arc::default(), Arc::default());
+        [(); N].map(|()| lsp.request(LspRequest::Shutdown))
+    }
+
+    #[test]
+    fn test_request_replaces_the_same_kind() {
+        let [first, second, third] = ids();
+        let mut requests = PendingRequests::default();
+        assert_eq!(
+            requests.insert(first, PendingRequest::Hover(Position::new(0, 1))),
+            None
+        );
+        assert_eq!(requests.insert(second, PendingRequest::Goto), None);
+        assert_eq!(
+            requests.insert(third, PendingRequest::Hover(Position::new(2, 3))),
+            Some(first)
+        );
+
+        // The response of the replaced request is out of date
+        assert_eq!(requests.take(first), None);
+        assert_eq!(
+            requests.take(third),
+            Some(PendingRequest::Hover(Position::new(2, 3)))
+        );
+        assert_eq!(requests.take(third), None);
+        assert!(requests.contains(|request| *request == PendingRequest::Goto));
+    }
+
+    #[test]
+    fn test_remove_and_forget() {
+        let [hints, resolve, completion, format] = ids();
+        let mut requests = PendingRequests::default();
+        requests.insert(hints, PendingRequest::InlayHints);
+        requests.insert(resolve, PendingRequest::ResolveInlayHint(4));
+        requests.insert(completion, PendingRequest::Completion);
+        let formatting = PendingRequest::Formatting {
+            version: 2,
+            save: true,
+        };
+        requests.insert(format, formatting);
+
+        let is_completion = |request: &PendingRequest| *request == PendingRequest::Completion;
+        assert_eq!(requests.remove(is_completion), Some(completion));
+        assert_eq!(requests.remove(is_completion), None);
+        requests.forget(|request| {
+            matches!(
+                request,
+                PendingRequest::InlayHints | PendingRequest::ResolveInlayHint(_)
+            )
+        });
+        assert_eq!(requests.get(hints), None);
+        assert_eq!(requests.get(resolve), None);
+        assert_eq!(requests.get(format), Some(&formatting));
+    }
+}
@@ -0,0 +1,286 @@
+use std::ops::Range;
+
+use async_lsp::lsp_types::{
+    ParameterInformation, ParameterLabel, SignatureHelp, SignatureInformation,
+};
+use laurel_common::text::Position;
+
+/**
+ * The byte offset of the `(` of the call whose arguments `text` ends in, `None` when it doesn't
+ * end in a call.
+ *
+ * Brackets are matched without knowing the language, so one in a string or a comment throws it
+ * off. A `;` or an unclosed `{` means the text ends in a statement or a block instead.
+ */
+pub fn enclosing_call(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    for (index, c) in text.char_indices().rev() {
+        match c {
+            ')' | ']' | '}' => depth += 1,
+            '(' if depth == 0 => return Some(index),
+            '{' | ';' if depth == 0 => return None,
+            // An unclosed `[` is an argument, e.g. `f([1, 2`
+            '[' if depth == 0 => {}
+            '(' | '[' | '{' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/**
+ * The byte offset of a column counted in UTF-16 code units, as the offsets of parameter labels
+ * are.
+ */
+fn utf16_to_byte(text: &str, offset: u32) -> usize {
+    let mut utf16 = 0;
+    for (index, c) in text.char_indices() {
+        if utf16 >= offset as usize {
+            return index;
+        }
+        utf16 += c.len_utf16();
+    }
+    text.len()
+}
+
+/**
+ * The signatures of the call the cursor is in, along with the overload that's shown.
+ */
+#[derive(Debug, Clone)]
+pub struct Signatures {
+    help: SignatureHelp,
+    active: usize,
+    /// Where the `(` of the call is, the signatures are closed once the cursor leaves the call
+    call: Position,
+    /// Where the cursor was when they were requested
+    cursor: Position,
+}
+
+impl Signatures {
+    pub fn new(help: SignatureHelp, call: Position, cursor: Position) -> Self {
+        let last = help.signatures.len().saturating_sub(1);
+        let active = help.active_signature.map_or(0, |active| active as usize);
+        Self {
+            active: active.min(last),
+            help,
+            call,
+            cursor,
+        }
+    }
+
+    pub fn call(&self) -> Position {
+        self.call
+    }
+
+    pub fn cursor(&self) -> Position {
+        self.cursor
+    }
+
+    pub fn len(&self) -> usize {
+        self.help.signatures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.help.signatures.is_empty()
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    pub fn active(&self) -> Option<&SignatureInformation> {
+        self.help.signatures.get(self.active)
+    }
+
+    /**
+     * The signatures as they're shown, so the server knows which overload was picked when it's
+     * asked about the call again.
+     */
+    pub fn help(&self) -> SignatureHelp {
+        SignatureHelp {
+            active_signature: Some(self.active as u32),
+            ..self.help.clone()
+        }
+    }
+
+    fn active_parameter_index(&self) -> Option<usize> {
+        let signature = self.active()?;
+        // The parameter of the signature wins over the one of the whole help
+        let index = signature.active_parameter.or(self.help.active_parameter)?;
+        Some(index as usize)
+    }
+
+    pub fn active_parameter(&self) -> Option<&ParameterInformation> {
+        let parameters = self.active()?.parameters.as_ref()?;
+        parameters.get(self.active_parameter_index()?)
+    }
+
+    /**
+     * The bytes of the label of the active signature that are the active parameter.
+     */
+    pub fn parameter_range(&self) -> Option<Range<usize>> {
+        let signature = self.active()?;
+        let parameters = signature.parameters.as_ref()?;
+        let index = self.active_parameter_index()?;
+        parameters.get(index)?;
+
+        // Labels given as text are looked for in order after the name, as a parameter can be
+        // named like the function or contain the name of an earlier one
+        let label = &signature.label;
+        let mut from = label.find('(').unwrap_or(0);
+        for (i, parameter) in parameters.iter().enumerate().take(index + 1) {
+            let range = match &parameter.label {
+                ParameterLabel::Simple(name) => {
+                    let start = from + label[from..].find(name.as_str())?;
+                    start..start + name.len()
+                }
+                ParameterLabel::LabelOffsets([start, end]) => {
+                    utf16_to_byte(label, *start)..utf16_to_byte(label, *end)
+                }
+            };
+            if i == index {
+                return Some(range);
+            }
+            from = range.end.max(from);
+        }
+        None
+    }
+
+    /**
+     * Shows the next overload, going back to the first after the last.
+     */
+    pub fn next(&mut self) {
+        if !self.is_empty() {
+            self.active = (self.active + 1) % self.len();
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if !self.is_empty() {
+            self.active = (self.active + self.len() - 1) % self.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod signature_help_tests {
+    use async_lsp::lsp_types::{
+        ParameterInformation, ParameterLabel, SignatureHelp, SignatureInformation,
+    };
+    use laurel_common::text::Position;
+    use pretty_assertions::assert_eq;
+
+    use super::{enclosing_call, Signatures};
+
+    fn signature(label: &str, parameters: Vec<ParameterLabel>) -> SignatureInformation {
+        SignatureInformation {
+            label: label.to_owned(),
+            documentation: None,
+            parameters: Some(
+                parameters
+                    .into_iter()
+                    .map(|label| ParameterInformation {
+                        label,
+                        documentation: None,
+                    })
+                    .collect(),
+            ),
+            active_parameter: None,
+        }
+    }
+
+    fn signatures(signatures: Vec<SignatureInformation>, active_parameter: u32) -> Signatures {
+        let help = SignatureHelp {
+            signatures,
+            active_signature: None,
+            active_parameter: Some(active_parameter),
+        };
+        Signatures::new(help, Position::default(), Position::default())
+    }
+
+    #[test]
+    fn test_enclosing_call() {
+        assert_eq!(enclosing_call("foo("), Some(3));
+        assert_eq!(enclosing_call("foo(a, b"), Some(3));
+        assert_eq!(enclosing_call("foo(bar(1), [2, 3], "), Some(3));
+        assert_eq!(enclosing_call("foo([1, 2"), Some(3));
+        assert_eq!(enclosing_call("foo(bar(1"), Some(7));
+        assert_eq!(enclosing_call("foo(\n    a,\n    b"), Some(3));
+        assert_eq!(enclosing_call("é(ü"), Some(2));
+
+        assert_eq!(enclosing_call("foo()"), None);
+        assert_eq!(enclosing_call("foo(a); b"), None);
+        assert_eq!(enclosing_call("foo(|x| { x"), None);
+        assert_eq!(enclosing_call("let a = 1"), None);
+    }
+
+    #[test]
+    fn test_parameter_range() {
+        let simple = |name: &str| ParameterLabel::Simple(name.to_owned());
+        let label = "fn sum(s: i32, sum: i32)";
+        let help = signatures(vec![signature(label, vec![simple("s"), simple("sum")])], 1);
+        // Neither the name of the function nor the first parameter
+        assert_eq!(help.parameter_range(), Some(15..18));
+        let help = signatures(vec![signature(label, vec![simple("s"), simple("sum")])], 0);
+        assert_eq!(help.parameter_range(), Some(7..8));
+
+        // Offsets are in UTF-16 code units
+        let label = "ƒ(é: u8, b: u8)";
+        let help = signatures(
+            vec![signature(
+                label,
+                vec![
+                    ParameterLabel::LabelOffsets([2, 7]),
+                    ParameterLabel::LabelOffsets([9, 14]),
+                ],
+            )],
+            0,
+        );
+        assert_eq!(
+            help.parameter_range().map(|range| &label[range]),
+            Some("é: u8")
+        );
+
+        // Past the last parameter, e.g. after a trailing comma
+        let help = signatures(vec![signature("f(a)", vec![simple("a")])], 1);
+        assert_eq!(help.parameter_range(), None);
+        assert!(help.active_parameter().is_none());
+    }
+
+    #[test]
+    fn test_overloads() {
+        let simple = |name: &str| ParameterLabel::Simple(name.to_owned());
+        let mut with_parameter = signature("f(a, b)", vec![simple("a"), simple("b")]);
+        with_parameter.active_parameter = Some(0);
+        let mut help = signatures(
+            vec![
+                signature("f()", vec![]),
+                signature("f(a)", vec![simple("a")]),
+                with_parameter,
+            ],
+            1,
+        );
+        assert_eq!(help.active_index(), 0);
+        assert_eq!(help.parameter_range(), None);
+
+        help.next();
+        assert_eq!(help.active().unwrap().label, "f(a)");
+        assert_eq!(help.parameter_range(), None);
+        help.next();
+        // The signature's own parameter wins
+        assert_eq!(help.parameter_range(), Some(2..3));
+        help.next();
+        assert_eq!(help.active_index(), 0);
+        help.previous();
+        assert_eq!(help.active_index(), 2);
+        assert_eq!(help.help().active_signature, Some(2));
+
+        let out_of_range = SignatureHelp {
+            signatures: vec![signature("f()", vec![])],
+            active_signature: Some(4),
+            active_parameter: None,
+        };
+        let help = Signatures::new(out_of_range, Position::default(), Position::default());
+        assert_eq!(help.active_index(), 0);
+    }
+}
@@ -0,0 +1,551 @@
+use std::{collections::BTreeMap, ops::Range};
+
+/**
+ * A piece of a snippet in the TextMate syntax, see
+ * <https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#snippet_syntax>.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Text(String),
+    /// `$1`, `${1:placeholder}` or `${1|one,two|}`
+    TabStop {
+        index: u32,
+        placeholder: Vec<Node>,
+        choices: Vec<String>,
+    },
+    /// `$NAME` or `${NAME:default}`, transforms are parsed but not applied
+    Variable {
+        name: String,
+        default: Vec<Node>,
+    },
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let eaten = self.peek() == Some(c);
+        if eaten {
+            self.position += 1;
+        }
+        eaten
+    }
+
+    /**
+     * Parses until the end of the source, or until the `}` that closes a placeholder when
+     * `nested`, which is left for the caller.
+     */
+    fn nodes(&mut self, nested: bool) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            if nested && c == '}' {
+                break;
+            }
+            self.position += 1;
+            match c {
+                '\\' => match self.peek() {
+                    Some(escaped @ ('$' | '}' | '\\')) => {
+                        self.position += 1;
+                        text.push(escaped);
+                    }
+                    _ => text.push('\\'),
+                },
+                '$' => {
+                    let start = self.position;
+                    match self.dollar() {
+                        Some(node) => {
+                            if !text.is_empty() {
+                                nodes.push(Node::Text(std::mem::take(&mut text)));
+                            }
+                            nodes.push(node);
+                        }
+                        // Not a tab stop or a variable after all, so the `$` is plain text
+                        None => {
+                            self.position = start;
+                            text.push('$');
+                        }
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            nodes.push(Node::Text(text));
+        }
+        nodes
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        let digits: String = self.chars[start..self.position].iter().collect();
+        digits.parse().ok()
+    }
+
+    fn name(&mut self) -> Option<String> {
+        if !self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        {
+            return None;
+        }
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            self.position += 1;
+        }
+        Some(self.chars[start..self.position].iter().collect())
+    }
+
+    /**
+     * Parses what follows a `$`.
+     */
+    fn dollar(&mut self) -> Option<Node> {
+        if !self.eat('{') {
+            if let Some(index) = self.number() {
+                return Some(tab_stop(index, Vec::new(), Vec::new()));
+            }
+            let name = self.name()?;
+            return Some(Node::Variable {
+                name,
+                default: Vec::new(),
+            });
+        }
+
+        if let Some(index) = self.number() {
+            if self.eat('}') {
+                return Some(tab_stop(index, Vec::new(), Vec::new()));
+            }
+            if self.eat(':') {
+                let placeholder = self.nodes(true);
+                return self
+                    .eat('}')
+                    .then(|| tab_stop(index, placeholder, Vec::new()));
+            }
+            if self.eat('|') {
+                let choices = self.choices()?;
+                return Some(tab_stop(index, Vec::new(), choices));
+            }
+            return None;
+        }
+
+        let name = self.name()?;
+        let default = if self.eat(':') {
+            self.nodes(true)
+        } else if self.peek() == Some('/') {
+            self.skip_transform()?;
+            Vec::new()
+        } else {
+            Vec::new()
+        };
+        self.eat('}').then_some(Node::Variable { name, default })
+    }
+
+    /**
+     * Parses `one,two|}`, the rest of a choice.
+     */
+    fn choices(&mut self) -> Option<Vec<String>> {
+        let mut choices = vec![String::new()];
+        loop {
+            match self.peek()? {
+                '\\' if matches!(self.chars.get(self.position + 1), Some(',' | '|' | '\\')) => {
+                    choices.last_mut()?.push(self.chars[self.position + 1]);
+                    self.position += 2;
+                }
+                ',' => {
+                    choices.push(String::new());
+                    self.position += 1;
+                }
+                '|' => {
+                    self.position += 1;
+                    return self.eat('}').then_some(choices);
+                }
+                c => {
+                    choices.last_mut()?.push(c);
+                    self.position += 1;
+                }
+            }
+        }
+    }
+
+    /**
+     * Skips `/regex/format/options`, up to the closing `}`.
+     */
+    fn skip_transform(&mut self) -> Option<()> {
+        let mut slashes = 0;
+        while slashes < 3 {
+            match self.peek()? {
+                '\\' => self.position += 1,
+                '/' => slashes += 1,
+                _ => (),
+            }
+            self.position += 1;
+        }
+        while self.peek()? != '}' {
+            self.position += 1;
+        }
+        Some(())
+    }
+}
+
+fn tab_stop(index: u32, placeholder: Vec<Node>, choices: Vec<String>) -> Node {
+    Node::TabStop {
+        index,
+        placeholder,
+        choices,
+    }
+}
+
+/**
+ * A place the cursor visits in a snippet, with all its mirrors.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TabStop {
+    /// The first range is where the placeholder is typed, the others mirror it. They're byte
+    /// offsets into the snippet, or into the document once it's inserted.
+    pub ranges: Vec<Range<usize>>,
+    pub choices: Vec<String>,
+}
+
+/**
+ * An expanded snippet, with its tab stops in the order the cursor visits them, the final one
+ * last.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub text: String,
+    pub tab_stops: Vec<TabStop>,
+}
+
+struct Expander<'a> {
+    text: String,
+    tab_stops: BTreeMap<u32, TabStop>,
+    /// The first placeholder of each tab stop, which its mirrors repeat
+    placeholders: BTreeMap<u32, Vec<Node>>,
+    variables: &'a dyn Fn(&str) -> Option<String>,
+}
+
+impl Expander<'_> {
+    fn collect_placeholders(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::TabStop {
+                    index, placeholder, ..
+                } if !placeholder.is_empty() => {
+                    self.placeholders
+                        .entry(*index)
+                        .or_insert_with(|| placeholder.clone());
+                    self.collect_placeholders(placeholder);
+                }
+                Node::TabStop { index, choices, .. } if !choices.is_empty() => {
+                    self.placeholders
+                        .entry(*index)
+                        .or_insert_with(|| vec![Node::Text(choices[0].clone())]);
+                }
+                Node::Variable { default, .. } => self.collect_placeholders(default),
+                _ => (),
+            }
+        }
+    }
+
+    /**
+     * Writes out the nodes, keeping track of the tab stops unless it's writing a mirror.
+     */
+    fn expand(&mut self, nodes: &[Node], is_mirror: bool) {
+        for node in nodes {
+            match node {
+                Node::Text(text) => self.text.push_str(text),
+                Node::TabStop {
+                    index,
+                    placeholder,
+                    choices,
+                } => {
+                    let start = self.text.len();
+                    if let Some(choice) = choices.first() {
+                        self.text.push_str(choice);
+                    } else if !placeholder.is_empty() {
+                        self.expand(placeholder, is_mirror);
+                    } else if let Some(placeholder) =
+                        self.placeholders.get(index).filter(|_| !is_mirror).cloned()
+                    {
+                        self.expand(&placeholder, true);
+                    }
+                    if is_mirror {
+                        continue;
+                    }
+                    let tab_stop = self.tab_stops.entry(*index).or_default();
+                    tab_stop.ranges.push(start..self.text.len());
+                    if tab_stop.choices.is_empty() {
+                        tab_stop.choices.clone_from(choices);
+                    }
+                }
+                Node::Variable { name, default } => match (self.variables)(name) {
+                    Some(value) => self.text.push_str(&value),
+                    None if !default.is_empty() => self.expand(default, is_mirror),
+                    // Unknown variables are written out, so it's clear something is missing
+                    None => self.text.push_str(name),
+                },
+            }
+        }
+    }
+}
+
+impl Snippet {
+    /**
+     * Expands a snippet, getting the values of its variables from `variables`.
+     */
+    pub fn parse(source: &str, variables: &dyn Fn(&str) -> Option<String>) -> Self {
+        let mut parser = Parser {
+            chars: source.chars().collect(),
+            position: 0,
+        };
+        let nodes = parser.nodes(false);
+
+        let mut expander = Expander {
+            text: String::new(),
+            tab_stops: BTreeMap::new(),
+            placeholders: BTreeMap::new(),
+            variables,
+        };
+        expander.collect_placeholders(&nodes);
+        expander.expand(&nodes, false);
+
+        // `$0` is visited last, at the end of the snippet when it doesn't have one
+        let end = expander.text.len();
+        let last = expander.tab_stops.remove(&0).unwrap_or(TabStop {
+            ranges: vec![end..end],
+            choices: Vec::new(),
+        });
+        let mut tab_stops: Vec<TabStop> = expander.tab_stops.into_values().collect();
+        tab_stops.push(last);
+
+        Self {
+            text: expander.text,
+            tab_stops,
+        }
+    }
+
+    /**
+     * Indents every line after the first like the line the snippet is inserted on, and replaces
+     * tabs with `tab`.
+     */
+    pub fn reindent(&mut self, indent: &str, tab: &str) {
+        let mut text = String::with_capacity(self.text.len());
+        // Where every byte offset of the old text ends up
+        let mut offsets = vec![0; self.text.len() + 1];
+        for (offset, c) in self.text.char_indices() {
+            offsets[offset] = text.len();
+            match c {
+                '\t' => text.push_str(tab),
+                '\n' => {
+                    text.push('\n');
+                    text.push_str(indent);
+                }
+                c => text.push(c),
+            }
+        }
+        offsets[self.text.len()] = text.len();
+
+        for tab_stop in &mut self.tab_stops {
+            for range in &mut tab_stop.ranges {
+                *range = offsets[range.start]..offsets[range.end];
+            }
+        }
+        self.text = text;
+    }
+}
+
+/**
+ * A snippet that was inserted into the document, whose tab stops follow the edits made
+ * afterwards.
+ */
+#[derive(Debug, Clone)]
+pub struct SnippetSession {
+    tab_stops: Vec<TabStop>,
+    current: usize,
+}
+
+impl SnippetSession {
+    /**
+     * Starts at the first tab stop of a snippet that was inserted at byte `offset`.
+     */
+    pub fn new(snippet: Snippet, offset: usize) -> Self {
+        let mut tab_stops = snippet.tab_stops;
+        for range in tab_stops
+            .iter_mut()
+            .flat_map(|tab_stop| &mut tab_stop.ranges)
+        {
+            *range = range.start + offset..range.end + offset;
+        }
+        Self {
+            tab_stops,
+            current: 0,
+        }
+    }
+
+    pub fn current(&self) -> &TabStop {
+        &self.tab_stops[self.current]
+    }
+
+    /**
+     * Whether the cursor reached the final tab stop, where the snippet is done.
+     */
+    pub fn is_finished(&self) -> bool {
+        self.current + 1 == self.tab_stops.len()
+    }
+
+    pub fn next(&mut self) {
+        self.current = (self.current + 1).min(self.tab_stops.len() - 1);
+    }
+
+    pub fn previous(&mut self) {
+        self.current = self.current.saturating_sub(1);
+    }
+
+    /**
+     * Moves the tab stops to follow an edit that replaced the bytes `start..old_end` with
+     * `inserted` bytes. Typing at either end of the current tab stop grows it.
+     *
+     * Returns `false` when the edit cut through a tab stop, which ends the snippet.
+     */
+    pub fn apply_change(&mut self, start: usize, old_end: usize, inserted: usize) -> bool {
+        let removed = old_end - start;
+        let current = self.current;
+        let mut claimed = false;
+        // The current tab stop gets the first say, so typing right between two tab stops
+        // goes into it
+        let order =
+            std::iter::once(current).chain((0..self.tab_stops.len()).filter(|i| *i != current));
+        for index in order {
+            for range in &mut self.tab_stops[index].ranges {
+                let within = range.start <= start && old_end <= range.end;
+                let inside = range.start < start && old_end < range.end;
+                if within && (inside || (index == current && !claimed)) {
+                    claimed |= index == current;
+                    range.end = range.end + inserted - removed;
+                } else if range.end <= start {
+                    continue;
+                } else if old_end <= range.start {
+                    *range = range.start + inserted - removed..range.end + inserted - removed;
+                } else if start <= range.start && range.end <= old_end {
+                    // A nested tab stop whose placeholder was typed over
+                    *range = start..start;
+                } else {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /**
+     * Finds a mirror of the current tab stop that doesn't match what was typed into it yet,
+     * along with the text it should have. `text` gets the text of a range of the document.
+     */
+    pub fn unmirrored(
+        &self,
+        text: impl Fn(Range<usize>) -> String,
+    ) -> Option<(Range<usize>, String)> {
+        let (primary, mirrors) = self.current().ranges.split_first()?;
+        let typed = text(primary.clone());
+        mirrors
+            .iter()
+            .find(|mirror| text((*mirror).clone()) != typed)
+            .map(|mirror| (mirror.clone(), typed))
+    }
+}
+
+#[cfg(test)]
+mod snippet_tests {
+    use super::{Snippet, SnippetSession, TabStop};
+    use pretty_assertions::assert_eq;
+
+    fn expand(source: &str) -> Snippet {
+        Snippet::parse(source, &|name| {
+            (name == "TM_FILENAME").then(|| String::from("main.rs"))
+        })
+    }
+
+    fn stop(ranges: &[std::ops::Range<usize>]) -> TabStop {
+        TabStop {
+            ranges: ranges.to_vec(),
+            choices: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_tab_stops() {
+        let snippet = expand("fn ${1:name}($2) {\n    $0\n}");
+        assert_eq!(snippet.text, "fn name() {\n    \n}");
+        assert_eq!(
+            snippet.tab_stops,
+            vec![stop(&[3..7]), stop(&[8..8]), stop(&[16..16])]
+        );
+    }
+
+    #[test]
+    fn test_mirrors_and_nesting() {
+        let snippet = expand("${1:a ${2:b}} $1 $2");
+        assert_eq!(snippet.text, "a b a b b");
+        assert_eq!(
+            snippet.tab_stops,
+            vec![stop(&[0..3, 4..7]), stop(&[2..3, 8..9]), stop(&[9..9])]
+        );
+    }
+
+    #[test]
+    fn test_choices_variables_and_escapes() {
+        let snippet = expand("${1|one,t\\,wo|} $TM_FILENAME ${TM_LINE:1} $UNKNOWN \\$1 ${x");
+        assert_eq!(snippet.text, "one main.rs 1 UNKNOWN $1 ${x");
+        assert_eq!(snippet.tab_stops[0].choices, vec!["one", "t,wo"]);
+
+        let transformed = expand("${TM_FILENAME/(.*)\\.rs/$1/}!");
+        assert_eq!(transformed.text, "main.rs!");
+    }
+
+    #[test]
+    fn test_reindent() {
+        let mut snippet = expand("{\n\t$1\n}");
+        snippet.reindent("  ", "    ");
+        assert_eq!(snippet.text, "{\n      \n  }");
+        assert_eq!(snippet.tab_stops[0], stop(&[8..8]));
+    }
+
+    #[test]
+    fn test_session_follows_edits() {
+        // `let ${1:x} = $1;` inserted after `// `
+        let mut session = SnippetSession::new(expand("let ${1:x} = $1;"), 3);
+        assert_eq!(session.current().ranges, vec![7..8, 11..12]);
+
+        // `x` was typed over with `value`, then the mirror follows
+        assert!(session.apply_change(7, 8, 5));
+        assert_eq!(session.current().ranges, vec![7..12, 15..16]);
+        let text = "// let value = x;";
+        let unmirrored = session.unmirrored(|range| text[range].to_owned());
+        assert_eq!(unmirrored, Some((15..16, String::from("value"))));
+        assert!(session.apply_change(15, 16, 5));
+        assert_eq!(session.current().ranges, vec![7..12, 15..20]);
+
+        // Typing before the snippet moves everything along
+        assert!(session.apply_change(0, 0, 2));
+        assert_eq!(session.current().ranges, vec![9..14, 17..22]);
+        session.next();
+        assert!(session.is_finished());
+        assert_eq!(session.current().ranges, vec![23..23]);
+
+        // Deleting across a tab stop ends the snippet
+        assert!(!session.apply_change(10, 20, 0));
+    }
+}
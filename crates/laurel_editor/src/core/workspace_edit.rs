@@ -0,0 +1,452 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use async_lsp::lsp_types::{
+    DocumentChangeOperation, DocumentChanges, OneOf, Position, ResourceOp, TextDocumentEdit,
+    TextEdit, Url, WorkspaceEdit,
+};
+
+/**
+ * A change of a workspace edit to a single file.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileChange {
+    /// Edits whose ranges refer to the text before any of them
+    Edit(Url, Vec<TextEdit>),
+    Create {
+        uri: Url,
+        overwrite: bool,
+        ignore_if_exists: bool,
+    },
+    Rename {
+        old_uri: Url,
+        new_uri: Url,
+        overwrite: bool,
+        ignore_if_exists: bool,
+    },
+    Delete {
+        uri: Url,
+        recursive: bool,
+        ignore_if_not_exists: bool,
+    },
+}
+
+/**
+ * The changes of a workspace edit in the order they have to be made. The document changes are
+ * preferred over the plain changes, like the protocol asks.
+ */
+pub fn file_changes(edit: WorkspaceEdit) -> Vec<FileChange> {
+    let edit_change = |edit: TextDocumentEdit| {
+        let edits = edit
+            .edits
+            .into_iter()
+            .map(|edit| match edit {
+                OneOf::Left(edit) => edit,
+                OneOf::Right(annotated) => annotated.text_edit,
+            })
+            .collect();
+        FileChange::Edit(edit.text_document.uri, edits)
+    };
+
+    match edit.document_changes {
+        Some(DocumentChanges::Edits(edits)) => edits.into_iter().map(edit_change).collect(),
+        Some(DocumentChanges::Operations(operations)) => operations
+            .into_iter()
+            .map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => edit_change(edit),
+                DocumentChangeOperation::Op(ResourceOp::Create(create)) => {
+                    let options = create.options.as_ref();
+                    FileChange::Create {
+                        overwrite: options.and_then(|o| o.overwrite).unwrap_or(false),
+                        ignore_if_exists: options.and_then(|o| o.ignore_if_exists).unwrap_or(false),
+                        uri: create.uri,
+                    }
+                }
+                DocumentChangeOperation::Op(ResourceOp::Rename(rename)) => {
+                    let options = rename.options.as_ref();
+                    FileChange::Rename {
+                        overwrite: options.and_then(|o| o.overwrite).unwrap_or(false),
+                        ignore_if_exists: options.and_then(|o| o.ignore_if_exists).unwrap_or(false),
+                        old_uri: rename.old_uri,
+                        new_uri: rename.new_uri,
+                    }
+                }
+                DocumentChangeOperation::Op(ResourceOp::Delete(delete)) => {
+                    let options = delete.options.as_ref();
+                    FileChange::Delete {
+                        recursive: options.and_then(|o| o.recursive).unwrap_or(false),
+                        ignore_if_not_exists: options
+                            .and_then(|o| o.ignore_if_not_exists)
+                            .unwrap_or(false),
+                        uri: delete.uri,
+                    }
+                }
+            })
+            .collect(),
+        None => {
+            let mut changes: Vec<(Url, Vec<TextEdit>)> =
+                edit.changes.unwrap_or_default().into_iter().collect();
+            // The map has no order, the files are changed in the order of their paths
+            changes.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+            changes
+                .into_iter()
+                .map(|(uri, edits)| FileChange::Edit(uri, edits))
+                .collect()
+        }
+    }
+}
+
+/**
+ * The byte offset of a position whose column is in UTF-16 code units. A position past the end of
+ * its line is at the end of the line, and one past the last line at the end of the text.
+ */
+fn byte_offset(text: &str, line_starts: &[usize], position: Position) -> usize {
+    let Some(&start) = line_starts.get(position.line as usize) else {
+        return text.len();
+    };
+    let line = &text[start..];
+    let line = &line[..line.find('\n').unwrap_or(line.len())];
+    let mut utf16 = 0;
+    for (index, c) in line.char_indices() {
+        if utf16 >= position.character as usize {
+            return start + index;
+        }
+        utf16 += c.len_utf16();
+    }
+    start + line.len()
+}
+
+fn line_starts(text: &str) -> Vec<usize> {
+    let newlines = text.match_indices('\n').map(|(index, _)| index + 1);
+    std::iter::once(0).chain(newlines).collect()
+}
+
+/**
+ * The byte ranges the edits replace sorted by where they start, keeping the order of the edits
+ * that insert at the same place. An edit that overlaps the one before it is cut short.
+ */
+fn resolve<'a>(text: &str, edits: &'a [TextEdit]) -> Vec<(Range<usize>, &'a str)> {
+    let line_starts = line_starts(text);
+    let mut resolved: Vec<(Range<usize>, &str)> = edits
+        .iter()
+        .map(|edit| {
+            let start = byte_offset(text, &line_starts, edit.range.start);
+            let end = byte_offset(text, &line_starts, edit.range.end).max(start);
+            (start..end, edit.new_text.as_str())
+        })
+        .collect();
+    resolved.sort_by_key(|(range, _)| range.start);
+
+    let mut end = 0;
+    for (range, _) in &mut resolved {
+        range.start = range.start.max(end);
+        range.end = range.end.max(range.start);
+        end = range.end;
+    }
+    resolved
+}
+
+/**
+ * The text after the edits, whose ranges refer to the text before any of them.
+ */
+pub fn apply_edits(text: &str, edits: &[TextEdit]) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut from = 0;
+    for (range, new_text) in resolve(text, edits) {
+        result.push_str(&text[from..range.start]);
+        result.push_str(new_text);
+        from = range.end;
+    }
+    result.push_str(&text[from..]);
+    result
+}
+
+/**
+ * The lines starting at `line` that the edits change, as they are before and after them.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineChange {
+    pub line: usize,
+    pub before: String,
+    pub after: String,
+}
+
+/**
+ * The lines the edits change, with the edits on the same lines shown together.
+ */
+pub fn line_changes(text: &str, edits: &[TextEdit]) -> Vec<LineChange> {
+    let line_starts = line_starts(text);
+    let line_of = |offset: usize| line_starts.partition_point(|start| *start <= offset) - 1;
+    let line_end = |line: usize| {
+        line_starts
+            .get(line + 1)
+            .map_or(text.len(), |next| next - 1)
+    };
+
+    // The edits grouped by the lines they touch
+    let mut groups: Vec<(usize, usize, Vec<(Range<usize>, &str)>)> = Vec::new();
+    for (range, new_text) in resolve(text, edits) {
+        let (first, last) = (line_of(range.start), line_of(range.end));
+        match groups.last_mut() {
+            Some((_, group_last, group)) if first <= *group_last => {
+                *group_last = last.max(*group_last);
+                group.push((range, new_text));
+            }
+            _ => groups.push((first, last, vec![(range, new_text)])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(first, last, group)| {
+            let (start, end) = (line_starts[first], line_end(last));
+            let mut after = String::new();
+            let mut from = start;
+            for (range, new_text) in group {
+                after.push_str(&text[from..range.start]);
+                after.push_str(new_text);
+                from = range.end;
+            }
+            after.push_str(&text[from..end]);
+            LineChange {
+                line: first,
+                before: text[start..end].to_owned(),
+                after,
+            }
+        })
+        .collect()
+}
+
+fn file_path(uri: &Url) -> io::Result<PathBuf> {
+    uri.to_file_path()
+        .map_err(|_| io::Error::new(ErrorKind::InvalidInput, format!("{uri} isn't a file")))
+}
+
+fn create_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) => fs::create_dir_all(parent),
+        None => Ok(()),
+    }
+}
+
+/**
+ * Makes a change to a file that isn't open, as the options of the change ask.
+ */
+pub fn apply_on_disk(change: &FileChange) -> io::Result<()> {
+    match change {
+        FileChange::Edit(uri, edits) => {
+            let path = file_path(uri)?;
+            let text = fs::read_to_string(&path)?;
+            fs::write(&path, apply_edits(&text, edits))
+        }
+        FileChange::Create {
+            uri,
+            overwrite,
+            ignore_if_exists,
+        } => {
+            let path = file_path(uri)?;
+            if path.exists() && !overwrite {
+                return match ignore_if_exists {
+                    true => Ok(()),
+                    false => Err(ErrorKind::AlreadyExists.into()),
+                };
+            }
+            create_parent(&path)?;
+            fs::write(&path, "")
+        }
+        FileChange::Rename {
+            old_uri,
+            new_uri,
+            overwrite,
+            ignore_if_exists,
+        } => {
+            let (old_path, new_path) = (file_path(old_uri)?, file_path(new_uri)?);
+            if new_path.exists() && !overwrite {
+                return match ignore_if_exists {
+                    true => Ok(()),
+                    false => Err(ErrorKind::AlreadyExists.into()),
+                };
+            }
+            create_parent(&new_path)?;
+            fs::rename(old_path, new_path)
+        }
+        FileChange::Delete {
+            uri,
+            recursive,
+            ignore_if_not_exists,
+        } => {
+            let path = file_path(uri)?;
+            if !path.exists() {
+                return match ignore_if_not_exists {
+                    true => Ok(()),
+                    false => Err(ErrorKind::NotFound.into()),
+                };
+            }
+            match (path.is_dir(), recursive) {
+                (true, true) => fs::remove_dir_all(path),
+                (true, false) => fs::remove_dir(path),
+                (false, _) => fs::remove_file(path),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod workspace_edit_tests {
+    use std::{collections::HashMap, fs};
+
+    use async_lsp::lsp_types::{
+        DocumentChangeOperation, DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier,
+        Position, Range, RenameFile, ResourceOp, TextDocumentEdit, TextEdit, Url, WorkspaceEdit,
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::{apply_edits, apply_on_disk, file_changes, line_changes, FileChange, LineChange};
+
+    fn edit(start: (u32, u32), end: (u32, u32), text: &str) -> TextEdit {
+        TextEdit::new(
+            Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1)),
+            text.to_owned(),
+        )
+    }
+
+    #[test]
+    fn test_apply_edits() {
+        let text = "let é = 1;\nlet b = é + 1;\n";
+        let edits = [
+            edit((1, 8), (1, 9), "renamed"),
+            edit((0, 4), (0, 5), "renamed"),
+            // Inserts at the same place keep their order
+            edit((1, 0), (1, 0), "// a\n"),
+            edit((1, 0), (1, 0), "// b\n"),
+            // Past the end of the line
+            edit((0, 40), (0, 40), " // one"),
+        ];
+        assert_eq!(
+            apply_edits(text, &edits),
+            "let renamed = 1; // one\n// a\n// b\nlet b = renamed + 1;\n"
+        );
+        // UTF-16: `𝕏` is two code units
+        assert_eq!(apply_edits("𝕏a", &[edit((0, 2), (0, 3), "b")]), "𝕏b");
+    }
+
+    #[test]
+    fn test_line_changes() {
+        let text = "fn a() {}\nfn b() {\n    a();\n    a();\n}\n";
+        let edits = [
+            edit((0, 3), (0, 4), "c"),
+            edit((2, 4), (2, 5), "c"),
+            edit((3, 4), (3, 5), "c"),
+            // Joins the lines of the last two calls
+            edit((2, 8), (3, 4), " "),
+        ];
+        assert_eq!(
+            line_changes(text, &edits),
+            vec![
+                LineChange {
+                    line: 0,
+                    before: String::from("fn a() {}"),
+                    after: String::from("fn c() {}"),
+                },
+                LineChange {
+                    line: 2,
+                    before: String::from("    a();\n    a();"),
+                    after: String::from("    c(); c();"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_file_changes() {
+        let uri = |path: &str| Url::parse(&format!("file:///{path}")).unwrap();
+        let rename = WorkspaceEdit {
+            changes: Some(HashMap::from([
+                (uri("b.rs"), vec![]),
+                (uri("a.rs"), vec![]),
+            ])),
+            ..WorkspaceEdit::default()
+        };
+        assert_eq!(
+            file_changes(rename),
+            vec![
+                FileChange::Edit(uri("a.rs"), vec![]),
+                FileChange::Edit(uri("b.rs"), vec![])
+            ]
+        );
+
+        let text_edit = TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                uri: uri("a.rs"),
+                version: None,
+            },
+            edits: vec![OneOf::Left(edit((0, 0), (0, 1), "b"))],
+        };
+        let operations = WorkspaceEdit {
+            changes: Some(HashMap::from([(uri("ignored.rs"), vec![])])),
+            document_changes: Some(DocumentChanges::Operations(vec![
+                DocumentChangeOperation::Edit(text_edit),
+                DocumentChangeOperation::Op(ResourceOp::Rename(RenameFile {
+                    old_uri: uri("a.rs"),
+                    new_uri: uri("b.rs"),
+                    options: None,
+                    annotation_id: None,
+                })),
+            ])),
+            change_annotations: None,
+        };
+        assert_eq!(
+            file_changes(operations),
+            vec![
+                FileChange::Edit(uri("a.rs"), vec![edit((0, 0), (0, 1), "b")]),
+                FileChange::Rename {
+                    old_uri: uri("a.rs"),
+                    new_uri: uri("b.rs"),
+                    overwrite: false,
+                    ignore_if_exists: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_on_disk() {
+        let dir =
+            std::env::temp_dir().join(format!("laurel_workspace_edit_test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let uri = |name: &str| Url::from_file_path(dir.join(name)).unwrap();
+
+        let create = FileChange::Create {
+            uri: uri("module/a.rs"),
+            overwrite: false,
+            ignore_if_exists: false,
+        };
+        apply_on_disk(&create).unwrap();
+        assert!(apply_on_disk(&create).is_err());
+
+        let edit = FileChange::Edit(uri("module/a.rs"), vec![edit((0, 0), (0, 0), "fn a() {}")]);
+        apply_on_disk(&edit).unwrap();
+        let rename = FileChange::Rename {
+            old_uri: uri("module/a.rs"),
+            new_uri: uri("b.rs"),
+            overwrite: false,
+            ignore_if_exists: false,
+        };
+        apply_on_disk(&rename).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("b.rs")).unwrap(), "fn a() {}");
+
+        let delete = |uri, recursive| FileChange::Delete {
+            uri,
+            recursive,
+            ignore_if_not_exists: false,
+        };
+        assert!(apply_on_disk(&delete(uri("module"), false)).is_ok());
+        assert!(apply_on_disk(&delete(uri("module"), false)).is_err());
+        apply_on_disk(&delete(uri(""), true)).unwrap();
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,207 @@
+use std::{cmp::Reverse, iter, ops::Range};
+
+/// A highlight capture clipped to a single line.
+#[derive(Debug, Clone)]
+pub struct LineSpan {
+    /// Index of the capture name within the highlighter's capture names.
+    pub capture: u32,
+    pub kind: &'static str,
+    /// Byte columns of the span, relative to the start of the line.
+    pub bytes: Range<usize>,
+    /// Character columns of the span, relative to the start of the line.
+    pub columns: Range<usize>,
+}
+
+/**
+ * Turns the spans of every layer on a line, paired with the depth of their layer, into spans that
+ * don't overlap.
+ *
+ * Where spans overlap the most deeply injected one wins, and within a layer the one that starts
+ * first, or was captured first.
+ */
+pub fn flatten(spans: Vec<(usize, LineSpan)>) -> Vec<LineSpan> {
+    // The character and byte column of every edge
+    let mut edges: Vec<(usize, usize)> = spans
+        .iter()
+        .flat_map(|(_, span)| {
+            [
+                (span.columns.start, span.bytes.start),
+                (span.columns.end, span.bytes.end),
+            ]
+        })
+        .collect();
+    edges.sort_unstable();
+    edges.dedup();
+
+    let mut flat: Vec<LineSpan> = Vec::new();
+    for edge in edges.windows(2) {
+        let (from, to) = (edge[0], edge[1]);
+        let winner = spans
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, span))| span.columns.start <= from.0 && to.0 <= span.columns.end)
+            .min_by_key(|(index, (depth, span))| (Reverse(*depth), span.columns.start, *index));
+        let Some((_, (_, span))) = winner else {
+            continue;
+        };
+
+        match flat.last_mut() {
+            Some(last)
+                if last.columns.end == from.0
+                    && last.capture == span.capture
+                    && last.kind == span.kind =>
+            {
+                last.bytes.end = to.1;
+                last.columns.end = to.0;
+            }
+            _ => flat.push(LineSpan {
+                capture: span.capture,
+                kind: span.kind,
+                bytes: from.1..to.1,
+                columns: from.0..to.0,
+            }),
+        }
+    }
+    flat
+}
+
+/**
+ * Caches the highlight spans of every line that has been queried.
+ *
+ * A line is `None` until it's been queried, or after an edit touched it.
+ */
+#[derive(Debug, Default)]
+pub struct HighlightCache {
+    lines: Vec<Option<Vec<LineSpan>>>,
+}
+
+impl HighlightCache {
+    pub fn get(&self, line: usize) -> Option<&[LineSpan]> {
+        self.lines.get(line)?.as_deref()
+    }
+
+    pub fn insert(&mut self, line: usize, spans: Vec<LineSpan>) {
+        if line >= self.lines.len() {
+            self.lines.resize(line + 1, None);
+        }
+        self.lines[line] = Some(spans);
+    }
+
+    /**
+     * Returns the smallest range of lines covering every line within `lines` that isn't cached.
+     */
+    pub fn missing(&self, lines: Range<usize>) -> Option<Range<usize>> {
+        let start = lines.clone().find(|line| self.get(*line).is_none())?;
+        let end = lines.rev().find(|line| self.get(*line).is_none())?;
+        Some(start..end + 1)
+    }
+
+    pub fn invalidate(&mut self, lines: Range<usize>) {
+        for line in lines {
+            if let Some(spans) = self.lines.get_mut(line) {
+                *spans = None;
+            }
+        }
+    }
+
+    /**
+     * Replaces the rows `start_row..=old_end_row` with the uncached rows `start_row..=new_end_row`.
+     *
+     * The lines after the edit keep their spans, they only move up or down.
+     */
+    pub fn splice(&mut self, start_row: usize, old_end_row: usize, new_end_row: usize) {
+        if start_row >= self.lines.len() {
+            return;
+        }
+        let end = (old_end_row + 1).min(self.lines.len());
+        let inserted = new_end_row.saturating_sub(start_row) + 1;
+        self.lines
+            .splice(start_row..end, iter::repeat_with(|| None).take(inserted));
+    }
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use super::{flatten, HighlightCache, LineSpan};
+    use pretty_assertions::assert_eq;
+
+    fn span(start: usize, end: usize) -> LineSpan {
+        LineSpan {
+            capture: 0,
+            kind: "identifier",
+            bytes: start..end,
+            columns: start..end,
+        }
+    }
+
+    fn cache_with_lines(lines: usize) -> HighlightCache {
+        let mut cache = HighlightCache::default();
+        for line in 0..lines {
+            cache.insert(line, vec![span(line, line + 1)]);
+        }
+        cache
+    }
+
+    #[test]
+    fn test_missing_lines() {
+        let mut cache = cache_with_lines(10);
+        assert_eq!(cache.missing(0..10), None);
+        assert_eq!(cache.missing(5..15), Some(10..15));
+
+        cache.invalidate(3..5);
+        assert_eq!(cache.missing(0..10), Some(3..5));
+    }
+
+    #[test]
+    fn test_splice_inserted_lines() {
+        let mut cache = cache_with_lines(5);
+        // A newline typed on line 1
+        cache.splice(1, 1, 2);
+
+        assert!(cache.get(1).is_none());
+        assert!(cache.get(2).is_none());
+        // The old line 2 moved to line 3
+        assert_eq!(cache.get(3).unwrap()[0].columns, 2..3);
+        assert_eq!(cache.get(5).unwrap()[0].columns, 4..5);
+    }
+
+    #[test]
+    fn test_splice_removed_lines() {
+        let mut cache = cache_with_lines(5);
+        // Lines 1 to 3 joined into line 1
+        cache.splice(1, 3, 1);
+
+        assert!(cache.get(1).is_none());
+        assert_eq!(cache.get(2).unwrap()[0].columns, 4..5);
+        assert!(cache.get(3).is_none());
+    }
+
+    #[test]
+    fn test_flatten_prefers_injected_spans() {
+        let string = LineSpan {
+            capture: 1,
+            kind: "string_literal",
+            ..span(0, 10)
+        };
+        let flat = flatten(vec![(0, string), (1, span(3, 5)), (1, span(4, 6))]);
+
+        let edges: Vec<_> = flat
+            .iter()
+            .map(|span| (span.capture, span.columns.clone()))
+            .collect();
+        assert_eq!(edges, vec![(1, 0..3), (0, 3..6), (1, 6..10)]);
+    }
+
+    #[test]
+    fn test_flatten_keeps_first_span_of_a_layer() {
+        let outer = LineSpan {
+            capture: 1,
+            ..span(0, 4)
+        };
+        let flat = flatten(vec![(0, span(2, 3)), (0, outer)]);
+
+        assert_eq!(flat.len(), 1);
+        assert_eq!(flat[0].capture, 1);
+        assert_eq!(flat[0].columns, 0..4);
+    }
+}
@@ -2,38 +2,30 @@ use crate::highlighter::HighlightItem;
 use iced::Color;
 use std::vec::IntoIter;
 
-use laurel_common::text::Position;
+use laurel_common::{text::Position, LaurelTheme};
 
-pub struct ColorSelector;
-
-impl ColorSelector {
-    /**
-    * Program uses the color theme from one-dark and is under the following license:
-
-       Copyright (c) 2016 GitHub Inc.
+/**
+ * Picks the colour of each character from the highlights and the theme.
+ */
+pub struct ColorSelector<'a> {
+    theme: &'a LaurelTheme,
+}
 
-       Permission is hereby granted, free of charge, to any person obtaining a copy of this software and associated documentation files (the "Software"), to deal in the Software without restriction, including without limitation the rights to use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is furnished to do so, subject to the following conditions:
+impl<'a> ColorSelector<'a> {
+    pub fn new(theme: &'a LaurelTheme) -> Self {
+        Self { theme }
+    }
 
-       The above copyright notice and this permission notice shall be included in all copies or substantial portions of the Software.
-    */
+    /**
+     * Gets the theme's colour of a capture, e.g. `function.method` falls back to `function` when
+     * the theme doesn't colour methods on their own.
+     */
     pub fn get_highlighter_color(&self, capture_name: &str) -> Color {
-        match capture_name {
-            "type" | "type.builtin" => Color::from_rgb8(229, 192, 123),
-            "constant" | "constant.builtin" => Color::from_rgb8(209, 154, 102),
-            "constructor" | "function" | "function.macro" | "function.method" => {
-                Color::from_rgb8(97, 175, 239)
-            }
-            "comment" => Color::from_rgb8(153, 153, 153),
-            "string" => Color::from_rgb8(110, 255, 89),
-            "punctuation.bracket" | "punctuation.delimiter" => Color::from_rgb8(190, 190, 190),
-            "variable.builtin" | "variable.parameter" => Color::from_rgb8(224, 108, 117),
-            "keyword" | "operator" => Color::from_rgb8(198, 120, 221),
-            _ => Color::from_rgb8(171, 178, 191),
-        }
+        self.theme.syntax_color(capture_name)
     }
 
     pub fn default_text_color(&self) -> Color {
-        Color::from_rgb8(171, 178, 191)
+        self.theme.text
     }
 
     /**
@@ -81,8 +73,7 @@ impl ColorSelector {
 #[cfg(test)]
 mod color_selector_tests {
     use crate::highlighter::HighlightItem;
-    use iced::Color;
-    use laurel_common::text::Position;
+    use laurel_common::{text::Position, LaurelTheme};
     use pretty_assertions::assert_eq;
     use tree_sitter::Range;
 
@@ -109,14 +100,15 @@ mod color_selector_tests {
         let pos = Position::new(0, 0);
 
         // Select a color
-        let color_selector = ColorSelector;
+        let theme = LaurelTheme::default();
+        let color_selector = ColorSelector::new(&theme);
         let color = color_selector.select(&mut highlighter, &mut current_item, &pos);
 
         // Should try to get the next item, which will become `None``
         assert!(current_item.is_none());
 
         // Should use the default color
-        assert_eq!(color, Color::from_rgb8(255, 128, 128));
+        assert_eq!(color, theme.text);
     }
 
     #[test]
@@ -140,14 +132,15 @@ mod color_selector_tests {
         let pos = Position::new(0, 0);
 
         // Select a color
-        let color_selector = ColorSelector;
+        let theme = LaurelTheme::default();
+        let color_selector = ColorSelector::new(&theme);
         let color = color_selector.select(&mut highlighter, &mut current_item, &pos);
 
         // Should try to get the next item, which will become `None``
         assert!(current_item.is_some());
 
-        // Should use the default color
-        assert_eq!(color, Color::from_rgb8(255, 143, 0));
+        // Should use the color of the capture
+        assert_eq!(color, theme.syntax_color("constant"));
     }
 
     #[test]
@@ -171,13 +164,14 @@ mod color_selector_tests {
         let pos = Position::new(0, 0);
 
         // Select a color
-        let color_selector = ColorSelector;
+        let theme = LaurelTheme::default();
+        let color_selector = ColorSelector::new(&theme);
         let color = color_selector.select(&mut highlighter, &mut current_item, &pos);
 
         // We shouldn't change the current item.
         assert!(current_item.is_some());
 
         // Should use the default color
-        assert_eq!(color, Color::from_rgb8(255, 128, 128));
+        assert_eq!(color, theme.text);
     }
 }
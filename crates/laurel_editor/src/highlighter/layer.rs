@@ -0,0 +1,440 @@
+use ropey::RopeSlice;
+use std::{collections::HashMap, ops};
+use tracing::warn;
+use tree_sitter::{
+    InputEdit, Language, Parser, Point, Query, QueryCursor, QueryPredicateArg, Range, Tree,
+};
+
+use crate::language::{
+    self,
+    runtime::{self, QueryKind},
+    LanguageConfig,
+};
+
+use super::{parse_rope, RopeProvider};
+
+/// Injections nested deeper than this aren't parsed, so a language can't keep injecting itself.
+pub const MAX_DEPTH: usize = 4;
+
+/**
+ * The compiled queries of a language used somewhere in the document.
+ */
+pub struct LayerConfig {
+    pub language: &'static LanguageConfig,
+    pub grammar: Language,
+    pub highlights: Query,
+    pub injections: Option<Query>,
+    /// The index of each capture of `highlights` within the highlighter's capture names.
+    pub captures: Vec<u32>,
+}
+
+impl LayerConfig {
+    /**
+     * Compiles the queries of the language, adding its capture names to `capture_names`.
+     *
+     * Returns `None` when the language has no grammar.
+     */
+    pub fn new(language: &'static LanguageConfig, capture_names: &mut Vec<String>) -> Option<Self> {
+        let grammar = runtime::grammar(language)?;
+        let highlights = compile_query(language, grammar, QueryKind::Highlights)?;
+        let injections = compile_query(language, grammar, QueryKind::Injections)
+            .filter(|query| query.pattern_count() > 0);
+
+        let captures = highlights
+            .capture_names()
+            .iter()
+            .map(|name| intern(capture_names, name))
+            .collect();
+
+        Some(Self {
+            language,
+            grammar,
+            highlights,
+            injections,
+            captures,
+        })
+    }
+}
+
+pub(super) fn intern(names: &mut Vec<String>, name: &str) -> u32 {
+    match names.iter().position(|known| known == name) {
+        Some(index) => index as u32,
+        None => {
+            names.push(name.to_owned());
+            (names.len() - 1) as u32
+        }
+    }
+}
+
+/**
+ * Compiles a query of the language, falling back to the default query when the user's query
+ * doesn't compile.
+ */
+fn compile_query(language: &LanguageConfig, grammar: Language, kind: QueryKind) -> Option<Query> {
+    let query = runtime::query(language, kind);
+    match Query::new(grammar, &query) {
+        Ok(query) => return Some(query),
+        Err(error) => warn!(error = ?error, "Invalid {} {kind:?} query", language.name),
+    }
+
+    Query::new(grammar, runtime::default_query(language, kind))
+        .map_err(|error| warn!(error = ?error, "Invalid default {} {kind:?} query", language.name))
+        .ok()
+}
+
+/**
+ * A syntax tree of part of the document. The root layer covers all of it, an injected layer only
+ * covers the ranges its parent injected it into.
+ */
+pub struct Layer {
+    /// Index of the layer's language within the highlighter's configs.
+    pub config: usize,
+    pub tree: Tree,
+    /// The ranges the layer was parsed over, empty for the root layer.
+    pub ranges: Vec<Range>,
+    pub depth: usize,
+    /// The language of the code it injects without naming one, see [`Injection::code_language`].
+    pub code_language: Option<&'static LanguageConfig>,
+}
+
+impl Layer {
+    /**
+     * Parses `ranges` of the document, or all of it when `ranges` is empty.
+     */
+    pub fn parse(
+        parser: &mut Parser,
+        config: &LayerConfig,
+        content: &RopeSlice,
+        ranges: &[Range],
+        old_tree: Option<&Tree>,
+    ) -> Option<Tree> {
+        if let Err(error) = parser.set_language(config.grammar) {
+            warn!(error = ?error, "The {} grammar isn't compatible", config.language.name);
+            return None;
+        }
+        if let Err(error) = parser.set_included_ranges(ranges) {
+            warn!(error = ?error, "Invalid {} injection ranges", config.language.name);
+            return None;
+        }
+        parse_rope(parser, content, old_tree)
+    }
+
+    /**
+     * Moves the tree and ranges of the layer along with an edit of the document.
+     */
+    pub fn edit(&mut self, edit: &InputEdit) {
+        self.tree.edit(edit);
+        for range in &mut self.ranges {
+            (range.start_byte, range.start_point) =
+                edit_position(range.start_byte, range.start_point, edit, false);
+            (range.end_byte, range.end_point) =
+                edit_position(range.end_byte, range.end_point, edit, true);
+        }
+    }
+
+    pub fn covers(&self, bytes: &ops::Range<usize>) -> bool {
+        self.ranges.is_empty()
+            || self
+                .ranges
+                .iter()
+                .any(|range| range.start_byte < bytes.end && bytes.start < range.end_byte)
+    }
+
+    /**
+     * Clips `range` to the parts of the document the layer was parsed over.
+     */
+    pub fn clip(&self, range: Range) -> Vec<Range> {
+        intersect(&[range], &self.ranges)
+    }
+}
+
+/**
+ * Moves a position along with an edit. A position within the replaced text moves to the start of
+ * the edit, or to the end of the new text when it's the `end` of a range.
+ */
+fn edit_position(byte: usize, point: Point, edit: &InputEdit, end: bool) -> (usize, Point) {
+    if byte <= edit.start_byte {
+        return (byte, point);
+    }
+    if byte < edit.old_end_byte {
+        return match end {
+            true => (edit.new_end_byte, edit.new_end_position),
+            false => (edit.start_byte, edit.start_position),
+        };
+    }
+
+    let old_end = edit.old_end_position;
+    let new_end = edit.new_end_position;
+    let column = if point.row == old_end.row {
+        point.column - old_end.column + new_end.column
+    } else {
+        point.column
+    };
+    (
+        byte - edit.old_end_byte + edit.new_end_byte,
+        Point::new(point.row - old_end.row + new_end.row, column),
+    )
+}
+
+/**
+ * The parts of `ranges` that are within `bounds`, where empty `bounds` cover everything.
+ */
+pub fn intersect(ranges: &[Range], bounds: &[Range]) -> Vec<Range> {
+    if bounds.is_empty() {
+        return ranges.to_vec();
+    }
+
+    let mut result = Vec::new();
+    for range in ranges {
+        for bound in bounds {
+            let (start_byte, start_point) = if range.start_byte > bound.start_byte {
+                (range.start_byte, range.start_point)
+            } else {
+                (bound.start_byte, bound.start_point)
+            };
+            let (end_byte, end_point) = if range.end_byte < bound.end_byte {
+                (range.end_byte, range.end_point)
+            } else {
+                (bound.end_byte, bound.end_point)
+            };
+            if start_byte < end_byte {
+                result.push(Range {
+                    start_byte,
+                    end_byte,
+                    start_point,
+                    end_point,
+                });
+            }
+        }
+    }
+    result
+}
+
+/**
+ * Sorts the ranges and merges the ones that overlap, as the parser expects them.
+ */
+fn normalize(ranges: &mut Vec<Range>) {
+    ranges.sort_by_key(|range| range.start_byte);
+    ranges.dedup_by(|next, previous| {
+        if next.start_byte > previous.end_byte {
+            return false;
+        }
+        if next.end_byte > previous.end_byte {
+            previous.end_byte = next.end_byte;
+            previous.end_point = next.end_point;
+        }
+        true
+    });
+}
+
+/**
+ * Where a layer injects another language.
+ */
+pub struct Injection {
+    pub language: &'static LanguageConfig,
+    pub ranges: Vec<Range>,
+    /// The language of the code without one in the injected layer, from the
+    /// `injection.code-language` property. A Rust doc comment is Markdown whose bare code blocks
+    /// are Rust.
+    pub code_language: Option<&'static LanguageConfig>,
+}
+
+/**
+ * Runs the injection query of a layer, returning the injections in the order they were found.
+ *
+ * The language comes from the `@injection.language` capture or the `injection.language`
+ * property, or else it's the code language of the layer, and the content from the
+ * `@injection.content` captures. Patterns with the
+ * `injection.combined` property are parsed as a single layer, and `#offset!` moves the edges of
+ * the content by rows and byte columns, e.g. to leave out the quotes of a string.
+ */
+pub fn injections(layer: &Layer, config: &LayerConfig, content: &RopeSlice) -> Vec<Injection> {
+    let Some(query) = &config.injections else {
+        return Vec::new();
+    };
+    let content_capture = query.capture_index_for_name("injection.content");
+    let language_capture = query.capture_index_for_name("injection.language");
+
+    let mut injections: Vec<Injection> = Vec::new();
+    // The injection each combined pattern adds its ranges to
+    let mut combined: HashMap<(usize, &str), usize> = HashMap::new();
+
+    let mut cursor = QueryCursor::new();
+    for q_match in cursor.matches(query, layer.tree.root_node(), RopeProvider(*content)) {
+        let settings = query.property_settings(q_match.pattern_index);
+        let setting = |key: &str| settings.iter().find(|property| &*property.key == key);
+
+        let name = q_match
+            .captures
+            .iter()
+            .find(|capture| Some(capture.index) == language_capture)
+            .map(|capture| content.byte_slice(capture.node.byte_range()).to_string())
+            .or_else(|| {
+                setting("injection.language")
+                    .and_then(|property| property.value.as_deref())
+                    .map(str::to_owned)
+            });
+        let language = match name {
+            Some(name) => language::by_injection_name(&name),
+            None => layer.code_language,
+        };
+        let Some(language) = language else {
+            continue;
+        };
+        let code_language = setting("injection.code-language")
+            .and_then(|property| property.value.as_deref())
+            .and_then(language::by_injection_name);
+
+        let offset = content_offset(query, q_match.pattern_index);
+        let mut ranges: Vec<Range> = q_match
+            .captures
+            .iter()
+            .filter(|capture| Some(capture.index) == content_capture)
+            .flat_map(|capture| layer.clip(offset_range(capture.node.range(), offset, content)))
+            .collect();
+        if ranges.is_empty() {
+            continue;
+        }
+
+        if setting("injection.combined").is_some() {
+            let key = (q_match.pattern_index, language.name);
+            if let Some(index) = combined.get(&key) {
+                injections[*index].ranges.append(&mut ranges);
+                continue;
+            }
+            combined.insert(key, injections.len());
+        }
+        injections.push(Injection {
+            language,
+            ranges,
+            code_language,
+        });
+    }
+
+    for injection in injections.iter_mut() {
+        normalize(&mut injection.ranges);
+    }
+    // Several patterns can inject the same language into the same node
+    let mut unique: Vec<Injection> = Vec::with_capacity(injections.len());
+    for injection in injections {
+        let duplicate = unique.iter().any(|known| {
+            known.language.name == injection.language.name && known.ranges == injection.ranges
+        });
+        if !duplicate {
+            unique.push(injection);
+        }
+    }
+    unique
+}
+
+/**
+ * Reads `(#offset! @capture start_row start_column end_row end_column)` of a pattern.
+ */
+fn content_offset(query: &Query, pattern_index: usize) -> Option<[isize; 4]> {
+    let predicate = query
+        .general_predicates(pattern_index)
+        .iter()
+        .find(|predicate| &*predicate.operator == "offset!")?;
+    let mut numbers = predicate.args.iter().filter_map(|arg| match arg {
+        QueryPredicateArg::String(number) => number.parse().ok(),
+        QueryPredicateArg::Capture(_) => None,
+    });
+    Some([
+        numbers.next()?,
+        numbers.next()?,
+        numbers.next()?,
+        numbers.next()?,
+    ])
+}
+
+fn offset_range(range: Range, offset: Option<[isize; 4]>, content: &RopeSlice) -> Range {
+    let Some([start_row, start_column, end_row, end_column]) = offset else {
+        return range;
+    };
+    let start = offset_byte(range.start_point, start_row, start_column, content);
+    let end = offset_byte(range.end_point, end_row, end_column, content).max(start);
+
+    Range {
+        start_byte: start,
+        end_byte: end,
+        start_point: byte_to_point(start, content),
+        end_point: byte_to_point(end, content),
+    }
+}
+
+fn offset_byte(point: Point, rows: isize, columns: isize, content: &RopeSlice) -> usize {
+    let row = point
+        .row
+        .saturating_add_signed(rows)
+        .min(content.len_lines() - 1);
+    let byte = content.line_to_byte(row) + point.column.saturating_add_signed(columns);
+    byte.min(content.len_bytes())
+}
+
+fn byte_to_point(byte: usize, content: &RopeSlice) -> Point {
+    let row = content.byte_to_line(byte);
+    Point::new(row, byte - content.line_to_byte(row))
+}
+
+#[cfg(test)]
+mod layer_tests {
+    use super::{edit_position, intersect, normalize};
+    use pretty_assertions::assert_eq;
+    use tree_sitter::{InputEdit, Point, Range};
+
+    fn range(start: usize, end: usize) -> Range {
+        Range {
+            start_byte: start,
+            end_byte: end,
+            start_point: Point::new(0, start),
+            end_point: Point::new(0, end),
+        }
+    }
+
+    #[test]
+    fn test_intersect() {
+        let bounds = [range(2, 5), range(8, 12)];
+        assert_eq!(
+            intersect(&[range(0, 10)], &bounds),
+            vec![range(2, 5), range(8, 10)]
+        );
+        assert_eq!(intersect(&[range(5, 8)], &bounds), vec![]);
+        assert_eq!(intersect(&[range(0, 10)], &[]), vec![range(0, 10)]);
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut ranges = vec![range(8, 12), range(0, 4), range(3, 6), range(6, 7)];
+        normalize(&mut ranges);
+        assert_eq!(ranges, vec![range(0, 7), range(8, 12)]);
+    }
+
+    #[test]
+    fn test_edit_position() {
+        // "abcdef" with "cd" replaced by "\nxyz"
+        let edit = InputEdit {
+            start_byte: 2,
+            old_end_byte: 4,
+            new_end_byte: 6,
+            start_position: Point::new(0, 2),
+            old_end_position: Point::new(0, 4),
+            new_end_position: Point::new(1, 3),
+        };
+        assert_eq!(
+            edit_position(1, Point::new(0, 1), &edit, false),
+            (1, Point::new(0, 1))
+        );
+        assert_eq!(
+            edit_position(3, Point::new(0, 3), &edit, false),
+            (2, Point::new(0, 2))
+        );
+        assert_eq!(
+            edit_position(3, Point::new(0, 3), &edit, true),
+            (6, Point::new(1, 3))
+        );
+        assert_eq!(
+            edit_position(5, Point::new(0, 5), &edit, true),
+            (7, Point::new(1, 4))
+        );
+    }
+}
@@ -1,144 +1,429 @@
-use ropey::RopeSlice;
-use std::ops;
-use tree_sitter::{InputEdit, Parser, Query, QueryCursor, Range, Tree};
+use async_lsp::lsp_types::{SemanticToken, SemanticTokensEdit, SemanticTokensLegend};
+use ropey::{iter::Chunks, RopeSlice};
+use std::{cell::RefCell, iter, ops};
+use tree_sitter::{Node, Parser, Point, QueryCursor, Range, TextProvider, Tree};
 
-use crate::core::document::Document;
-use laurel_common::text::Position;
+use crate::{core::document_change::DocumentChange, language::LanguageConfig};
 
+use cache::{HighlightCache, LineSpan};
+use layer::{Injection, Layer, LayerConfig, MAX_DEPTH};
+use semantic::SemanticTokens;
+
+pub mod cache;
 pub mod color_selector;
+pub mod layer;
+pub mod semantic;
 
 pub struct HighlighterConfig {
-    tree: Tree,
-    query: Query,
     parser: Parser,
+    /// The root layer comes first, followed by the injected layers in the order they were found.
+    layers: Vec<Layer>,
+    configs: Vec<LayerConfig>,
+    /// Injected languages without a grammar, so they aren't looked up after every edit.
+    unsupported: Vec<&'static str>,
+    /// The capture names of every language, so spans from any layer can share the cache.
+    capture_names: Vec<String>,
+    /// The language server's tokens, drawn over the captures of every layer.
+    semantic: Option<SemanticTokens>,
+    cache: RefCell<HighlightCache>,
 }
 
 impl HighlighterConfig {
-    pub fn new(tree: Tree, query: Query, parser: Parser) -> Self {
-        Self {
-            tree,
-            query,
+    /**
+     * Sets up highlighting for `language`, returning `None` when it has no grammar.
+     */
+    pub fn for_language(language: &'static LanguageConfig, content: &RopeSlice) -> Option<Self> {
+        let mut capture_names = Vec::new();
+        let config = LayerConfig::new(language, &mut capture_names)?;
+        let mut parser = Parser::new();
+        let tree = Layer::parse(&mut parser, &config, content, &[], None)?;
+
+        let mut highlighter = Self {
             parser,
-        }
+            layers: vec![Layer {
+                config: 0,
+                tree,
+                ranges: Vec::new(),
+                depth: 0,
+                code_language: None,
+            }],
+            configs: vec![config],
+            unsupported: Vec::new(),
+            capture_names,
+            semantic: None,
+            cache: RefCell::new(HighlightCache::default()),
+        };
+        highlighter.update_injections(content, Vec::new(), None);
+        Some(highlighter)
     }
 
-    pub fn rust_config(source_code: &str) -> Self {
-        let mut parser = Parser::new();
-        parser
-            .set_language(tree_sitter_rust::language())
-            .expect("Error loading Rust grammar");
-        let tree = parser.parse(source_code, None).unwrap();
+    /**
+     * Applies the change to every layer and reparses the ones it touched, invalidating the cached
+     * lines the change touched along with the lines tree-sitter reports as changed.
+     */
+    pub fn edit(&mut self, change: &DocumentChange, content: &RopeSlice) {
+        let input = change.input_edit();
+        for layer in self.layers.iter_mut() {
+            layer.edit(&input);
+        }
+        self.cache.get_mut().splice(
+            input.start_position.row,
+            input.old_end_position.row,
+            input.new_end_position.row,
+        );
+        if let Some(semantic) = &mut self.semantic {
+            semantic.splice(
+                input.start_position.row,
+                input.old_end_position.row,
+                input.new_end_position.row,
+            );
+        }
 
-        let query = Query::new(
-            tree_sitter_rust::language(),
-            include_str!("../../queries/highlights.scm"),
-        )
-        .unwrap();
+        let root = &self.layers[0];
+        let config = &self.configs[root.config];
+        let Some(tree) = Layer::parse(&mut self.parser, config, content, &[], Some(&root.tree))
+        else {
+            return;
+        };
+        invalidate(self.cache.get_mut(), root.tree.changed_ranges(&tree));
+        self.layers[0].tree = tree;
 
-        Self::new(tree, query, parser)
+        let old_layers = self.layers.split_off(1);
+        let edited = input.start_byte..input.new_end_byte;
+        self.update_injections(content, old_layers, Some(edited));
     }
 
-    fn edit(&mut self, input: &InputEdit, content: &RopeSlice) {
-        self.tree.edit(input);
-        let tree = self.parser.parse(content.to_string(), Some(&self.tree));
-        self.tree = tree.unwrap()
+    /**
+     * Overlays the tokens of a full semantic tokens response on the captures.
+     */
+    pub fn set_semantic_tokens(
+        &mut self,
+        legend: &SemanticTokensLegend,
+        result_id: Option<String>,
+        tokens: Vec<SemanticToken>,
+        content: &RopeSlice,
+    ) {
+        let mut semantic = SemanticTokens::new(legend, result_id, tokens);
+        semantic.decode(content, &mut self.capture_names);
+        self.semantic = Some(semantic);
+        *self.cache.get_mut() = HighlightCache::default();
     }
 
-    pub fn delete(
+    /**
+     * Applies a semantic tokens delta, returning `false` when it was computed from other tokens
+     * than the ones kept here. The full tokens have to be requested again in that case.
+     */
+    pub fn apply_semantic_delta(
         &mut self,
-        start_byte: usize,
-        end_byte: usize,
-        start_pos: Position,
-        end_pos: Position,
+        previous_result_id: &str,
+        result_id: Option<String>,
+        edits: Vec<SemanticTokensEdit>,
         content: &RopeSlice,
-    ) {
-        let input = tree_sitter::InputEdit {
-            start_byte: start_byte + 1,
-            old_end_byte: end_byte,
-            new_end_byte: start_byte,
-            start_position: tree_sitter::Point {
-                row: start_pos.line(),
-                column: start_pos.character() + 1,
-            },
-            old_end_position: tree_sitter::Point {
-                row: end_pos.line(),
-                column: end_pos.character(),
-            },
-            new_end_position: tree_sitter::Point {
-                row: start_pos.line(),
-                column: start_pos.character(),
-            },
+    ) -> bool {
+        let Some(semantic) = self
+            .semantic
+            .as_mut()
+            .filter(|semantic| semantic.result_id() == Some(previous_result_id))
+        else {
+            return false;
         };
-        self.edit(&input, content)
+        semantic.apply_delta(result_id, edits);
+        semantic.decode(content, &mut self.capture_names);
+        *self.cache.get_mut() = HighlightCache::default();
+        true
+    }
+
+    pub fn semantic_result_id(&self) -> Option<&str> {
+        self.semantic.as_ref()?.result_id()
     }
 
-    pub fn insert(
+    /**
+     * Parses a layer for every injection, breadth first from the root layer.
+     *
+     * A layer from before the edit is reused when it covers the same injection, so only the
+     * lines that changed within it are highlighted again. Layers the edit didn't touch are kept
+     * as they are.
+     */
+    fn update_injections(
         &mut self,
-        start_byte: usize,
-        end_byte: usize,
-        start_pos: Position,
-        end_pos: Position,
-        length: usize,
         content: &RopeSlice,
+        mut old_layers: Vec<Layer>,
+        edited: Option<ops::Range<usize>>,
     ) {
-        let input = tree_sitter::InputEdit {
-            start_byte,
-            old_end_byte: end_byte,
-            new_end_byte: start_byte + length,
-            start_position: tree_sitter::Point {
-                row: start_pos.line(),
-                column: start_pos.character(),
-            },
-            old_end_position: tree_sitter::Point {
-                row: end_pos.line(),
-                column: end_pos.character(),
-            },
-            new_end_position: tree_sitter::Point {
-                row: start_pos.line(),
-                column: start_pos.character() + 1,
-            },
+        let mut index = 0;
+        while index < self.layers.len() {
+            let layer = &self.layers[index];
+            let depth = layer.depth + 1;
+            if depth <= MAX_DEPTH {
+                for injection in layer::injections(layer, &self.configs[layer.config], content) {
+                    let layer = self.inject(injection, depth, content, &mut old_layers, &edited);
+                    if let Some(layer) = layer {
+                        self.layers.push(layer);
+                    }
+                }
+            }
+            index += 1;
+        }
+
+        // The injections that are gone leave their lines to the parent layer
+        for layer in old_layers {
+            invalidate(self.cache.get_mut(), layer.ranges);
+        }
+    }
+
+    fn inject(
+        &mut self,
+        injection: Injection,
+        depth: usize,
+        content: &RopeSlice,
+        old_layers: &mut Vec<Layer>,
+        edited: &Option<ops::Range<usize>>,
+    ) -> Option<Layer> {
+        let config = self.config_index(injection.language)?;
+        let old_layer = old_layers
+            .iter()
+            .position(|layer| {
+                layer.config == config
+                    && layer.depth == depth
+                    && !layer::intersect(&layer.ranges, &injection.ranges).is_empty()
+            })
+            .map(|index| old_layers.remove(index));
+
+        // Reparsing a layer away from the edit can still change its tree, the Markdown scanner
+        // loses inline nodes that way
+        let same_ranges = |layer: &Layer| layer.ranges == injection.ranges;
+        let untouched = |layer: &Layer| {
+            edited.as_ref().is_some_and(|edited| {
+                !layer
+                    .ranges
+                    .iter()
+                    .any(|range| range.start_byte <= edited.end && edited.start <= range.end_byte)
+            })
         };
-        self.edit(&input, content)
+        if old_layer
+            .as_ref()
+            .is_some_and(|layer| same_ranges(layer) && untouched(layer))
+        {
+            return old_layer.map(|layer| Layer {
+                code_language: injection.code_language,
+                ..layer
+            });
+        }
+
+        // Reusing a tree that was parsed over different ranges can crash the parser, so only a
+        // layer over the same ranges is parsed incrementally
+        let old_tree = old_layer
+            .as_ref()
+            .filter(|layer| same_ranges(layer))
+            .map(|layer| &layer.tree);
+        let tree = Layer::parse(
+            &mut self.parser,
+            &self.configs[config],
+            content,
+            &injection.ranges,
+            old_tree,
+        )?;
+
+        let cache = self.cache.get_mut();
+        match old_layer {
+            Some(old_layer) if old_layer.ranges == injection.ranges => {
+                invalidate(cache, old_layer.tree.changed_ranges(&tree))
+            }
+            Some(old_layer) => {
+                invalidate(cache, old_layer.ranges);
+                invalidate(cache, injection.ranges.iter().copied());
+            }
+            None => invalidate(cache, injection.ranges.iter().copied()),
+        }
+
+        Some(Layer {
+            config,
+            tree,
+            ranges: injection.ranges,
+            depth,
+            code_language: injection.code_language,
+        })
+    }
+
+    /**
+     * Gets the index of the language's config, compiling its queries the first time it's used.
+     */
+    fn config_index(&mut self, language: &'static LanguageConfig) -> Option<usize> {
+        if let Some(index) = self
+            .configs
+            .iter()
+            .position(|config| config.language.name == language.name)
+        {
+            return Some(index);
+        }
+        if self.unsupported.contains(&language.name) {
+            return None;
+        }
+
+        match LayerConfig::new(language, &mut self.capture_names) {
+            Some(config) => {
+                self.configs.push(config);
+                Some(self.configs.len() - 1)
+            }
+            None => {
+                self.unsupported.push(language.name);
+                None
+            }
+        }
+    }
+
+    /**
+     * Runs the highlight queries of every layer over `lines`, returning the spans of each line
+     * in order. Where layers overlap the most deeply injected one wins, and semantic tokens win
+     * over every layer.
+     */
+    fn query_lines(&self, lines: ops::Range<usize>, content: &RopeSlice) -> Vec<Vec<LineSpan>> {
+        if lines.is_empty() {
+            return Vec::new();
+        }
+        let mut spans = vec![Vec::new(); lines.len()];
+        let bytes = content.line_to_byte(lines.start)..content.line_to_byte(lines.end);
+
+        let mut cursor = QueryCursor::new();
+        for layer in self.layers.iter().filter(|layer| layer.covers(&bytes)) {
+            let config = &self.configs[layer.config];
+            cursor.set_byte_range(bytes.clone());
+
+            let root = layer.tree.root_node();
+            let captures = cursor.captures(&config.highlights, root, RopeProvider(*content));
+            for (q_match, index) in captures {
+                let cap = q_match.captures[index];
+                let capture = config.captures[cap.index as usize];
+
+                for range in layer.clip(cap.node.range()) {
+                    let start = range.start_point;
+                    let end = range.end_point;
+
+                    // Captures can span several lines, e.g. block comments
+                    for row in start.row.max(lines.start)..=end.row.min(lines.end - 1) {
+                        let line = content.line(row);
+                        let from = if row == start.row { start.column } else { 0 };
+                        let to = if row == end.row {
+                            end.column
+                        } else {
+                            line.len_bytes()
+                        };
+                        let to = to.min(line.len_bytes());
+                        if from >= to {
+                            continue;
+                        }
+
+                        let span = LineSpan {
+                            capture,
+                            kind: cap.node.kind(),
+                            bytes: from..to,
+                            columns: line.byte_to_char(from)..line.byte_to_char(to),
+                        };
+                        spans[row - lines.start].push((layer.depth, span));
+                    }
+                }
+            }
+        }
+
+        if let Some(semantic) = &self.semantic {
+            for (row, spans) in lines.zip(spans.iter_mut()) {
+                let tokens = semantic.line(row).iter().cloned();
+                spans.extend(tokens.map(|span| (semantic::DEPTH, span)));
+            }
+        }
+
+        spans.into_iter().map(cache::flatten).collect()
+    }
+}
+
+fn invalidate(cache: &mut HighlightCache, ranges: impl IntoIterator<Item = Range>) {
+    for range in ranges {
+        cache.invalidate(range.start_point.row..range.end_point.row + 1);
+    }
+}
+
+/**
+ * Parses the rope chunk by chunk, so the document is never copied into a single string.
+ */
+fn parse_rope(parser: &mut Parser, content: &RopeSlice, old_tree: Option<&Tree>) -> Option<Tree> {
+    parser.parse_with(
+        &mut |byte, _| {
+            if byte >= content.len_bytes() {
+                return &[] as &[u8];
+            }
+            let (chunk, chunk_start, _, _) = content.chunk_at_byte(byte);
+            &chunk.as_bytes()[byte - chunk_start..]
+        },
+        old_tree,
+    )
+}
+
+/**
+ * Gives the query cursor the text of a node straight from the rope's chunks.
+ */
+struct RopeProvider<'a>(RopeSlice<'a>);
+
+impl<'a> TextProvider<'a> for RopeProvider<'a> {
+    type I = iter::Map<Chunks<'a>, fn(&'a str) -> &'a [u8]>;
+
+    fn text(&mut self, node: Node) -> Self::I {
+        let slice = self
+            .0
+            .get_byte_slice(node.byte_range())
+            .unwrap_or_else(|| self.0.byte_slice(0..0));
+        slice.chunks().map(str::as_bytes)
     }
 }
 
+/**
+ * A highlighted span of a single line. The columns of `range` are in characters, like [`Position`].
+ */
 #[derive(Debug, Clone)]
 pub struct HighlightItem {
     pub capture_name: String,
     pub kind: String,
     pub range: Range,
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Highlighter {
     pub captures: Vec<HighlightItem>,
 }
 
 impl Highlighter {
+    /**
+     * Collects the highlights of `lines`, only querying the lines that aren't cached.
+     */
     pub fn new(
         config: &HighlighterConfig,
-        range: ops::Range<usize>,
-        buffer: &Document,
+        lines: ops::Range<usize>,
+        content: &RopeSlice,
     ) -> Option<Self> {
-        let capture_names = config.query.capture_names();
-
-        let root_node = config.tree.root_node();
-
-        // println!("Range is {:?}", range);
-        let mut cursor = QueryCursor::new();
-        cursor.set_byte_range(range);
+        let line_count = content.len_lines();
+        let lines = lines.start.min(line_count)..lines.end.min(line_count);
+        let capture_names = &config.capture_names;
 
-        let content = buffer.to_string();
-        let captures = cursor.captures(&config.query, root_node, content.as_bytes());
+        let mut cache = config.cache.borrow_mut();
+        if let Some(missing) = cache.missing(lines.clone()) {
+            let spans = config.query_lines(missing.clone(), content);
+            for (line, spans) in missing.zip(spans) {
+                cache.insert(line, spans);
+            }
+        }
 
         let mut highlight_items: Vec<HighlightItem> = Vec::new();
-        for (q_match, _i) in captures {
-            for cap in q_match.captures {
-                let item: HighlightItem = HighlightItem {
-                    capture_name: capture_names.get(cap.index as usize)?.to_string(),
-                    kind: cap.node.kind().to_string(),
-                    range: cap.node.range(),
-                };
-                highlight_items.push(item)
+        for line in lines {
+            let line_byte = content.line_to_byte(line);
+            for span in cache.get(line)? {
+                highlight_items.push(HighlightItem {
+                    capture_name: capture_names.get(span.capture as usize)?.to_string(),
+                    kind: span.kind.to_string(),
+                    range: Range {
+                        start_byte: line_byte + span.bytes.start,
+                        end_byte: line_byte + span.bytes.end,
+                        start_point: Point::new(line, span.columns.start),
+                        end_point: Point::new(line, span.columns.end),
+                    },
+                });
             }
         }
 
@@ -147,3 +432,221 @@ impl Highlighter {
         })
     }
 }
+
+#[cfg(test)]
+mod highlighter_tests {
+    use super::{Highlighter, HighlighterConfig};
+    use crate::{core::document::Document, language::definitions::RUST};
+    use async_lsp::lsp_types::{
+        SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokensLegend,
+    };
+    use laurel_common::text::Position;
+    use pretty_assertions::assert_eq;
+    use ropey::Rope;
+
+    const SOURCE: &str = "/// ```rust\n/// let x = 1;\n/// ```\nfn main() {\n    let greeting = \"héllo\";\n    println!(\"{greeting}\");\n}\n";
+
+    const FRAGMENTS: [&str; 14] = [
+        "fn ",
+        "{",
+        "}\n",
+        "\n",
+        "let x = 1;",
+        "é",
+        "\"",
+        "/*",
+        "*/",
+        "// ü\n",
+        "(",
+        "\n\n  ",
+        "/// ```rust\n",
+        "```",
+    ];
+
+    /// A small xorshift generator, so the test is reproducible without extra dependencies.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 as usize
+        }
+
+        fn below(&mut self, max: usize) -> usize {
+            self.next() % max
+        }
+    }
+
+    fn highlights(config: &HighlighterConfig, document: &Document) -> Vec<String> {
+        Highlighter::new(config, 0..document.len(), &document.slice_all())
+            .unwrap()
+            .captures
+            .into_iter()
+            .map(|item| format!("{} {:?}", item.capture_name, item.range))
+            .collect()
+    }
+
+    /// The capture name and text of every highlight
+    fn captures(config: &HighlighterConfig, document: &Document) -> Vec<(String, String)> {
+        let content = document.slice_all();
+        Highlighter::new(config, 0..document.len(), &content)
+            .unwrap()
+            .captures
+            .into_iter()
+            .map(|item| {
+                let text = content.byte_slice(item.range.start_byte..item.range.end_byte);
+                (item.capture_name, text.to_string())
+            })
+            .collect()
+    }
+
+    fn capture(name: &str, text: &str) -> (String, String) {
+        (name.to_owned(), text.to_owned())
+    }
+
+    #[test]
+    fn test_markdown_code_block_is_injected() {
+        let source = "# Title\n\n```rust\nfn main() {}\n```\n";
+        let document = Document::from_rope(Rope::from_str(source), "/tmp/README.md");
+        let language = document.language().unwrap();
+        let config = HighlighterConfig::for_language(language, &document.slice_all()).unwrap();
+
+        let captures = captures(&config, &document);
+        assert!(captures.contains(&capture("keyword", "fn")));
+        assert!(captures.contains(&capture("function", "main")));
+    }
+
+    #[test]
+    fn test_doc_comment_example_is_injected() {
+        let source = "/// ```rust\n/// let x = 1;\n/// ```\nfn f() {}\n";
+        let document = Document::from_rope(Rope::from_str(source), "/tmp/lib.rs");
+        let config = HighlighterConfig::for_language(&RUST, &document.slice_all()).unwrap();
+
+        let captures = captures(&config, &document);
+        assert!(captures.contains(&capture("keyword", "let")));
+        // The comment marker stays a comment
+        assert!(captures.contains(&capture("comment", "///")));
+    }
+
+    #[test]
+    fn test_bare_doc_comment_example_is_rust() {
+        let source = "/// ```\n/// let x = 1;\n/// ```\nfn f() {}\n";
+        let document = Document::from_rope(Rope::from_str(source), "/tmp/lib.rs");
+        let config = HighlighterConfig::for_language(&RUST, &document.slice_all()).unwrap();
+        assert!(captures(&config, &document).contains(&capture("keyword", "let")));
+
+        // A bare code block of a Markdown file has no language
+        let source = "```\nlet x = 1;\n```\n";
+        let document = Document::from_rope(Rope::from_str(source), "/tmp/README.md");
+        let language = document.language().unwrap();
+        let config = HighlighterConfig::for_language(language, &document.slice_all()).unwrap();
+        assert!(!captures(&config, &document).contains(&capture("keyword", "let")));
+    }
+
+    #[test]
+    fn test_regex_pattern_is_injected() {
+        let source = "let re = Regex::new(r\"^\\d+$\").unwrap();\n";
+        let document = Document::from_rope(Rope::from_str(source), "/tmp/lib.rs");
+        let config = HighlighterConfig::for_language(&RUST, &document.slice_all()).unwrap();
+
+        let captures = captures(&config, &document);
+        assert!(captures.contains(&capture("escape", "\\d")));
+        assert!(captures.contains(&capture("operator", "+")));
+    }
+
+    #[test]
+    fn test_sql_query_is_injected() {
+        let source = "let user = sqlx::query!(\"SELECT name FROM users\");\n";
+        let document = Document::from_rope(Rope::from_str(source), "/tmp/lib.rs");
+        let config = HighlighterConfig::for_language(&RUST, &document.slice_all()).unwrap();
+
+        let captures = captures(&config, &document);
+        assert!(captures.contains(&capture("keyword", "SELECT")));
+        assert!(captures.contains(&capture("keyword", "FROM")));
+    }
+
+    #[test]
+    fn test_semantic_tokens_win_over_captures() {
+        let source = "fn main() {\n    let mut x = 1;\n}\n";
+        let mut document = Document::from_rope(Rope::from_str(source), "/tmp/main.rs");
+        let mut config = HighlighterConfig::for_language(&RUST, &document.slice_all()).unwrap();
+        let legend = SemanticTokensLegend {
+            token_types: vec![SemanticTokenType::VARIABLE],
+            token_modifiers: vec![SemanticTokenModifier::new("mutable")],
+        };
+        let token = SemanticToken {
+            delta_line: 1,
+            delta_start: 12,
+            length: 1,
+            token_type: 0,
+            token_modifiers_bitset: 1,
+        };
+        // Fill the cache, so stale lines would show up below
+        captures(&config, &document);
+        config.set_semantic_tokens(&legend, None, vec![token], &document.slice_all());
+        assert!(captures(&config, &document).contains(&capture("variable.mutable", "x")));
+
+        // The token moves down with its line
+        let change = document
+            .insert(&Position::new(0, 0), "\n".to_owned())
+            .unwrap();
+        config.edit(&change, &document.slice_all());
+        assert!(captures(&config, &document).contains(&capture("variable.mutable", "x")));
+
+        // An edit on its line drops it until the server sends new tokens
+        let change = document
+            .insert(&Position::new(2, 0), " ".to_owned())
+            .unwrap();
+        config.edit(&change, &document.slice_all());
+        let captures = captures(&config, &document);
+        assert!(!captures.iter().any(|(name, _)| name == "variable.mutable"));
+        assert!(captures.contains(&capture("keyword", "let")));
+    }
+
+    #[test]
+    fn test_incremental_tree_matches_fresh_parse() {
+        for seed in 1..=8 {
+            let mut rng = Rng(seed);
+            let mut document = Document::from_rope(Rope::from_str(SOURCE), "/tmp/main.rs");
+            let mut config = HighlighterConfig::for_language(&RUST, &document.slice_all()).unwrap();
+
+            for _ in 0..30 {
+                let len = document.slice_all().len_chars();
+                let start = rng.below(len + 1);
+                let end = (start + rng.below(8)).min(len);
+                let text = if rng.below(3) == 0 {
+                    String::new()
+                } else {
+                    FRAGMENTS[rng.below(FRAGMENTS.len())].to_owned()
+                };
+
+                let start = document.char_to_position(start);
+                let end = document.char_to_position(end);
+                // Fill the cache, so stale lines would show up below
+                highlights(&config, &document);
+
+                if let Some(change) = document.replace(&start, &end, text) {
+                    config.edit(&change, &document.slice_all());
+                }
+
+                let fresh = HighlighterConfig::for_language(&RUST, &document.slice_all()).unwrap();
+                assert_eq!(
+                    config.layers[0].tree.root_node().to_sexp(),
+                    fresh.layers[0].tree.root_node().to_sexp(),
+                    "seed {seed}: {:?}",
+                    document.to_string()
+                );
+                assert_eq!(
+                    config.layers[0].tree.root_node().end_position(),
+                    fresh.layers[0].tree.root_node().end_position()
+                );
+                assert_eq!(
+                    highlights(&config, &document),
+                    highlights(&fresh, &document)
+                );
+            }
+        }
+    }
+}
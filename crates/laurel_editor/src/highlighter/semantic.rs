@@ -0,0 +1,381 @@
+use async_lsp::lsp_types::{SemanticToken, SemanticTokensEdit, SemanticTokensLegend};
+use ropey::RopeSlice;
+use std::{collections::HashMap, iter};
+
+use super::{
+    cache::LineSpan,
+    layer::{self, MAX_DEPTH},
+};
+
+/// Semantic tokens win over the captures of every layer.
+pub const DEPTH: usize = MAX_DEPTH + 1;
+
+/// The kind of every span that came from a semantic token, as they don't have a node.
+const KIND: &str = "semantic";
+
+/// The modifiers that are added to a token's scope, in the order they're added.
+const MODIFIERS: [(&str, &str); 6] = [
+    ("unsafe", "unsafe"),
+    ("mutable", "mutable"),
+    ("trait", "trait"),
+    ("defaultLibrary", "builtin"),
+    ("static", "static"),
+    ("deprecated", "deprecated"),
+];
+
+/**
+ * The semantic tokens of a document, as sent by the language server.
+ *
+ * The tokens are kept in the server's relative encoding, so a delta can be applied to them, and
+ * decoded into spans for every line.
+ */
+#[derive(Debug, Default)]
+pub struct SemanticTokens {
+    result_id: Option<String>,
+    token_types: Vec<String>,
+    token_modifiers: Vec<String>,
+    /// Five integers for every token: line delta, start delta, length, type and modifiers.
+    data: Vec<u32>,
+    /// The capture of each combination of type and modifiers, `None` when it's left to
+    /// tree-sitter.
+    captures: HashMap<(u32, u32), Option<u32>>,
+    lines: Vec<Vec<LineSpan>>,
+}
+
+impl SemanticTokens {
+    pub fn new(
+        legend: &SemanticTokensLegend,
+        result_id: Option<String>,
+        tokens: Vec<SemanticToken>,
+    ) -> Self {
+        Self {
+            result_id,
+            token_types: legend
+                .token_types
+                .iter()
+                .map(|token_type| token_type.as_str().to_owned())
+                .collect(),
+            token_modifiers: legend
+                .token_modifiers
+                .iter()
+                .map(|modifier| modifier.as_str().to_owned())
+                .collect(),
+            data: flatten(tokens),
+            captures: HashMap::new(),
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn result_id(&self) -> Option<&str> {
+        self.result_id.as_deref()
+    }
+
+    /**
+     * Applies the edits of a delta response. The edits index the integers of the tokens before
+     * any of them was applied, so they're applied from the back.
+     */
+    pub fn apply_delta(&mut self, result_id: Option<String>, mut edits: Vec<SemanticTokensEdit>) {
+        edits.sort_by_key(|edit| edit.start);
+        for edit in edits.into_iter().rev() {
+            let start = (edit.start as usize).min(self.data.len());
+            let end = (start + edit.delete_count as usize).min(self.data.len());
+            self.data
+                .splice(start..end, flatten(edit.data.unwrap_or_default()));
+        }
+        self.result_id = result_id;
+    }
+
+    /**
+     * Decodes the tokens into spans for every line, adding the scope of every token to
+     * `capture_names`.
+     *
+     * The token columns are counted in UTF-16 code units, which is the encoding every server
+     * supports.
+     */
+    pub fn decode(&mut self, content: &RopeSlice, capture_names: &mut Vec<String>) {
+        let mut lines = vec![Vec::new(); content.len_lines()];
+        let (mut line, mut start) = (0, 0);
+        for token in self.data.chunks_exact(5) {
+            let [delta_line, delta_start, length, token_type, bitset] = token else {
+                continue;
+            };
+            if *delta_line > 0 {
+                line += *delta_line as usize;
+                start = *delta_start as usize;
+            } else {
+                start += *delta_start as usize;
+            }
+
+            let capture = *self
+                .captures
+                .entry((*token_type, *bitset))
+                .or_insert_with(|| {
+                    let token_type = self.token_types.get(*token_type as usize)?;
+                    let modifiers = self
+                        .token_modifiers
+                        .iter()
+                        .enumerate()
+                        // The bitset only has room for the first 32 modifiers of the legend
+                        .filter(|(index, _)| {
+                            1u32.checked_shl(*index as u32)
+                                .is_some_and(|bit| bitset & bit != 0)
+                        })
+                        .map(|(_, modifier)| modifier.as_str());
+                    let scope = scope(token_type, modifiers)?;
+                    Some(layer::intern(capture_names, &scope))
+                });
+            let (Some(capture), Some(spans)) = (capture, lines.get_mut(line)) else {
+                continue;
+            };
+
+            let text = content.line(line);
+            let units = text.len_utf16_cu();
+            if start >= units {
+                continue;
+            }
+            let from = text.utf16_cu_to_char(start);
+            let to = text.utf16_cu_to_char((start + *length as usize).min(units));
+            if from < to {
+                spans.push(LineSpan {
+                    capture,
+                    kind: KIND,
+                    bytes: text.char_to_byte(from)..text.char_to_byte(to),
+                    columns: from..to,
+                });
+            }
+        }
+        self.lines = lines;
+    }
+
+    pub fn line(&self, line: usize) -> &[LineSpan] {
+        self.lines.get(line).map_or(&[], Vec::as_slice)
+    }
+
+    /**
+     * Drops the spans of the rows `start_row..=old_end_row`, moving the lines after them to
+     * follow `new_end_row`. The edited rows stay with tree-sitter until the server sends new
+     * tokens.
+     */
+    pub fn splice(&mut self, start_row: usize, old_end_row: usize, new_end_row: usize) {
+        if start_row >= self.lines.len() {
+            return;
+        }
+        let end = (old_end_row + 1).min(self.lines.len());
+        let inserted = new_end_row.saturating_sub(start_row) + 1;
+        self.lines
+            .splice(start_row..end, iter::repeat_with(Vec::new).take(inserted));
+    }
+}
+
+fn flatten(tokens: Vec<SemanticToken>) -> Vec<u32> {
+    tokens
+        .into_iter()
+        .flat_map(|token| {
+            [
+                token.delta_line,
+                token.delta_start,
+                token.length,
+                token.token_type,
+                token.token_modifiers_bitset,
+            ]
+        })
+        .collect()
+}
+
+/**
+ * Maps a token type and its modifiers to a theme scope, e.g. a mutable variable to
+ * `variable.mutable`. Comments and strings are left to tree-sitter, which also injects into them.
+ */
+pub fn scope<'a>(token_type: &str, modifiers: impl Iterator<Item = &'a str>) -> Option<String> {
+    let base = match token_type {
+        "namespace" => "namespace",
+        "type" | "class" | "struct" | "enum" | "interface" | "union" | "typeAlias" => "type",
+        "builtinType" => "type.builtin",
+        "typeParameter" => "type.parameter",
+        "parameter" => "variable.parameter",
+        "variable" => "variable",
+        "selfKeyword" | "selfTypeKeyword" => "variable.builtin",
+        "property" => "property",
+        "enumMember" => "constant",
+        "boolean" => "constant.builtin",
+        "function" => "function",
+        "method" => "function.method",
+        "macro" => "function.macro",
+        "keyword" => "keyword",
+        "number" => "number",
+        "regexp" => "string.special",
+        "escapeSequence" => "string.escape",
+        "formatSpecifier" => "punctuation.special",
+        "operator" => "operator",
+        "decorator" | "attribute" => "attribute",
+        "lifetime" => "label",
+        _ => return None,
+    };
+
+    let modifiers: Vec<&str> = modifiers.collect();
+    let mut scope = base.to_owned();
+    for (modifier, segment) in MODIFIERS {
+        if modifiers.contains(&modifier) {
+            scope.push('.');
+            scope.push_str(segment);
+        }
+    }
+    Some(scope)
+}
+
+#[cfg(test)]
+mod semantic_tests {
+    use super::{scope, SemanticTokens};
+    use async_lsp::lsp_types::{
+        SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokensEdit,
+        SemanticTokensLegend,
+    };
+    use pretty_assertions::assert_eq;
+    use ropey::Rope;
+
+    fn legend() -> SemanticTokensLegend {
+        SemanticTokensLegend {
+            token_types: vec![
+                SemanticTokenType::VARIABLE,
+                SemanticTokenType::FUNCTION,
+                SemanticTokenType::COMMENT,
+            ],
+            token_modifiers: vec![
+                SemanticTokenModifier::new("mutable"),
+                SemanticTokenModifier::new("unsafe"),
+            ],
+        }
+    }
+
+    fn token(delta_line: u32, delta_start: u32, length: u32, token_type: u32) -> SemanticToken {
+        SemanticToken {
+            delta_line,
+            delta_start,
+            length,
+            token_type,
+            token_modifiers_bitset: 0,
+        }
+    }
+
+    /// The capture name and text of every span
+    fn spans(tokens: &SemanticTokens, names: &[String], content: &Rope) -> Vec<(String, String)> {
+        (0..content.len_lines())
+            .flat_map(|line| {
+                let text = content.line(line);
+                tokens.line(line).iter().map(move |span| {
+                    let name = names[span.capture as usize].clone();
+                    (name, text.slice(span.columns.clone()).to_string())
+                })
+            })
+            .collect()
+    }
+
+    fn span(name: &str, text: &str) -> (String, String) {
+        (name.to_owned(), text.to_owned())
+    }
+
+    #[test]
+    fn test_scope() {
+        assert_eq!(
+            scope("variable", ["mutable"].into_iter()).unwrap(),
+            "variable.mutable"
+        );
+        assert_eq!(
+            scope("method", ["trait", "unsafe"].into_iter()).unwrap(),
+            "function.method.unsafe.trait"
+        );
+        assert_eq!(scope("comment", [].into_iter()), None);
+    }
+
+    #[test]
+    fn test_decode() {
+        let content = Rope::from_str("let mut 𝕏 = 1;\n// f\nunsafe { f(𝕏) }\n");
+        let mut mutable = token(0, 8, 2, 0);
+        mutable.token_modifiers_bitset = 0b01;
+        let data = vec![
+            mutable,
+            token(1, 0, 4, 2),
+            token(1, 9, 1, 1),
+            // The columns are in UTF-16, so the astral character takes two
+            token(0, 2, 2, 0),
+        ];
+        let mut tokens = SemanticTokens::new(&legend(), Some("1".to_owned()), data);
+        let mut names = vec!["keyword".to_owned()];
+        tokens.decode(&content.slice(..), &mut names);
+
+        assert_eq!(
+            spans(&tokens, &names, &content),
+            vec![
+                span("variable.mutable", "𝕏"),
+                span("function", "f"),
+                span("variable", "𝕏"),
+            ]
+        );
+        assert_eq!(tokens.line(0)[0].bytes, 8..12);
+    }
+
+    #[test]
+    fn test_decode_ignores_modifiers_past_the_bitset() {
+        let content = Rope::from_str("let x = 1;\n");
+        let mut legend = legend();
+        legend.token_modifiers = (0..40)
+            .map(|index| SemanticTokenModifier::from(format!("modifier{index}")))
+            .collect();
+        legend.token_modifiers[0] = SemanticTokenModifier::new("mutable");
+        let mut mutable = token(0, 4, 1, 0);
+        mutable.token_modifiers_bitset = 0b01;
+        let mut tokens = SemanticTokens::new(&legend, None, vec![mutable]);
+        let mut names = Vec::new();
+        tokens.decode(&content.slice(..), &mut names);
+
+        assert_eq!(
+            spans(&tokens, &names, &content),
+            vec![span("variable.mutable", "x")]
+        );
+    }
+
+    #[test]
+    fn test_apply_delta() {
+        let content = Rope::from_str("a b c\n");
+        let data = vec![token(0, 0, 1, 0), token(0, 2, 1, 0), token(0, 2, 1, 0)];
+        let mut tokens = SemanticTokens::new(&legend(), Some("1".to_owned()), data);
+
+        // Turns `b` into a function and drops `c`, both edits index the original data
+        let edits = vec![
+            SemanticTokensEdit {
+                start: 5,
+                delete_count: 5,
+                data: Some(vec![token(0, 2, 1, 1)]),
+            },
+            SemanticTokensEdit {
+                start: 10,
+                delete_count: 5,
+                data: None,
+            },
+        ];
+        tokens.apply_delta(Some("2".to_owned()), edits);
+        let mut names = Vec::new();
+        tokens.decode(&content.slice(..), &mut names);
+
+        assert_eq!(tokens.result_id(), Some("2"));
+        assert_eq!(
+            spans(&tokens, &names, &content),
+            vec![span("variable", "a"), span("function", "b")]
+        );
+    }
+
+    #[test]
+    fn test_splice_moves_lines() {
+        let content = Rope::from_str("a\nb\nc\n");
+        let data = vec![token(0, 0, 1, 0), token(1, 0, 1, 0), token(1, 0, 1, 1)];
+        let mut tokens = SemanticTokens::new(&legend(), None, data);
+        let mut names = Vec::new();
+        tokens.decode(&content.slice(..), &mut names);
+
+        // A line was added after `b`
+        tokens.splice(1, 1, 2);
+        assert!(tokens.line(1).is_empty());
+        assert!(tokens.line(2).is_empty());
+        assert_eq!(names[tokens.line(3)[0].capture as usize], "function");
+    }
+}
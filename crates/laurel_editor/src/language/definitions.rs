@@ -0,0 +1,193 @@
+use super::{CommentTokens, LanguageConfig, LanguageQueries, LanguageServerCommand};
+
+pub static LANGUAGES: &[LanguageConfig] = &[RUST, TOML, MARKDOWN, JSON, TYPST, PYTHON, SQL, REGEX];
+
+pub const RUST: LanguageConfig = LanguageConfig {
+    name: "rust",
+    grammar: Some(tree_sitter_rust::language),
+    queries: LanguageQueries {
+        highlights: include_str!("../../queries/rust/highlights.scm"),
+        indents: include_str!("../../queries/rust/indents.scm"),
+        folds: include_str!("../../queries/rust/folds.scm"),
+        injections: include_str!("../../queries/rust/injections.scm"),
+    },
+    comment_tokens: CommentTokens {
+        line: Some("//"),
+        block: Some(("/*", "*/")),
+    },
+    file_globs: &["*.rs"],
+    shebangs: &["rust-script"],
+    language_server: Some(LanguageServerCommand {
+        command: "rust-analyzer",
+        args: &[],
+        root_markers: &["Cargo.lock", "Cargo.toml"],
+        settings_section: Some("rust-analyzer"),
+    }),
+    format_on_save: true,
+};
+
+pub const TOML: LanguageConfig = LanguageConfig {
+    name: "toml",
+    grammar: Some(tree_sitter_toml::language),
+    queries: LanguageQueries {
+        highlights: include_str!("../../queries/toml/highlights.scm"),
+        indents: include_str!("../../queries/toml/indents.scm"),
+        folds: include_str!("../../queries/toml/folds.scm"),
+        injections: "",
+    },
+    comment_tokens: CommentTokens {
+        line: Some("#"),
+        block: None,
+    },
+    file_globs: &["*.toml", "Cargo.lock", "Pipfile", "poetry.lock"],
+    shebangs: &[],
+    language_server: Some(LanguageServerCommand {
+        command: "taplo",
+        args: &["lsp", "stdio"],
+        root_markers: &[".taplo.toml", "taplo.toml"],
+        settings_section: Some("evenBetterToml"),
+    }),
+    format_on_save: false,
+};
+
+pub const MARKDOWN: LanguageConfig = LanguageConfig {
+    name: "markdown",
+    grammar: Some(tree_sitter_md::language),
+    queries: LanguageQueries {
+        highlights: include_str!("../../queries/markdown/highlights.scm"),
+        indents: include_str!("../../queries/markdown/indents.scm"),
+        folds: include_str!("../../queries/markdown/folds.scm"),
+        injections: include_str!("../../queries/markdown/injections.scm"),
+    },
+    comment_tokens: CommentTokens {
+        line: None,
+        block: Some(("<!--", "-->")),
+    },
+    file_globs: &["*.md", "*.markdown"],
+    shebangs: &[],
+    language_server: Some(LanguageServerCommand {
+        command: "marksman",
+        args: &["server"],
+        root_markers: &[".marksman.toml"],
+        settings_section: None,
+    }),
+    format_on_save: false,
+};
+
+pub const JSON: LanguageConfig = LanguageConfig {
+    name: "json",
+    grammar: Some(tree_sitter_json::language),
+    queries: LanguageQueries {
+        highlights: include_str!("../../queries/json/highlights.scm"),
+        indents: include_str!("../../queries/json/indents.scm"),
+        folds: include_str!("../../queries/json/folds.scm"),
+        injections: "",
+    },
+    comment_tokens: CommentTokens {
+        line: None,
+        block: None,
+    },
+    file_globs: &["*.json", ".prettierrc", ".eslintrc"],
+    shebangs: &[],
+    language_server: Some(LanguageServerCommand {
+        command: "vscode-json-language-server",
+        args: &["--stdio"],
+        root_markers: &[],
+        settings_section: Some("json"),
+    }),
+    format_on_save: false,
+};
+
+/// There's no Typst grammar compiled in, it can be added to the runtime directory along with its
+/// queries.
+pub const TYPST: LanguageConfig = LanguageConfig {
+    name: "typst",
+    grammar: None,
+    queries: LanguageQueries {
+        highlights: "",
+        indents: "",
+        folds: "",
+        injections: "",
+    },
+    comment_tokens: CommentTokens {
+        line: Some("//"),
+        block: Some(("/*", "*/")),
+    },
+    file_globs: &["*.typ"],
+    shebangs: &[],
+    language_server: Some(LanguageServerCommand {
+        command: "tinymist",
+        args: &[],
+        root_markers: &["typst.toml"],
+        settings_section: Some("tinymist"),
+    }),
+    format_on_save: false,
+};
+
+pub const PYTHON: LanguageConfig = LanguageConfig {
+    name: "python",
+    grammar: Some(tree_sitter_python::language),
+    queries: LanguageQueries {
+        highlights: include_str!("../../queries/python/highlights.scm"),
+        indents: include_str!("../../queries/python/indents.scm"),
+        folds: include_str!("../../queries/python/folds.scm"),
+        injections: include_str!("../../queries/python/injections.scm"),
+    },
+    comment_tokens: CommentTokens {
+        line: Some("#"),
+        block: None,
+    },
+    file_globs: &["*.py", "*.pyi", "*.pyw"],
+    shebangs: &["python"],
+    language_server: Some(LanguageServerCommand {
+        command: "pylsp",
+        args: &[],
+        root_markers: &["pyproject.toml", "setup.py", "setup.cfg"],
+        settings_section: Some("pylsp"),
+    }),
+    format_on_save: false,
+};
+
+pub const SQL: LanguageConfig = LanguageConfig {
+    name: "sql",
+    grammar: Some(tree_sitter_sequel::language),
+    queries: LanguageQueries {
+        highlights: include_str!("../../queries/sql/highlights.scm"),
+        indents: "",
+        folds: "",
+        injections: "",
+    },
+    comment_tokens: CommentTokens {
+        line: Some("--"),
+        block: Some(("/*", "*/")),
+    },
+    file_globs: &["*.sql"],
+    shebangs: &[],
+    language_server: Some(LanguageServerCommand {
+        command: "sqls",
+        args: &[],
+        root_markers: &[],
+        settings_section: None,
+    }),
+    format_on_save: false,
+};
+
+/// Only injected, into the patterns given to the `regex` crate.
+pub const REGEX: LanguageConfig = LanguageConfig {
+    name: "regex",
+    grammar: Some(tree_sitter_regex::language),
+    queries: LanguageQueries {
+        highlights: include_str!("../../queries/regex/highlights.scm"),
+        indents: "",
+        folds: "",
+        injections: "",
+    },
+    comment_tokens: CommentTokens {
+        line: None,
+        block: None,
+    },
+    file_globs: &[],
+    shebangs: &[],
+    language_server: None,
+    format_on_save: false,
+};
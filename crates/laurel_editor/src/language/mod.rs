@@ -0,0 +1,280 @@
+use std::path::Path;
+
+use tree_sitter::Language;
+
+pub mod definitions;
+pub mod runtime;
+pub mod servers;
+
+/**
+ * The tree-sitter queries compiled into the editor, used when the query files can't be read.
+ */
+pub struct LanguageQueries {
+    pub highlights: &'static str,
+    pub indents: &'static str,
+    pub folds: &'static str,
+    pub injections: &'static str,
+}
+
+pub struct CommentTokens {
+    pub line: Option<&'static str>,
+    pub block: Option<(&'static str, &'static str)>,
+}
+
+/**
+ * The command that starts the language server of a language.
+ */
+pub struct LanguageServerCommand {
+    pub command: &'static str,
+    pub args: &'static [&'static str],
+    /// Files or directories that mark the root of a workspace, see [`servers::find_root`].
+    pub root_markers: &'static [&'static str],
+    /// The section the server asks for its settings in, e.g. `rust-analyzer`.
+    pub settings_section: Option<&'static str>,
+}
+
+/**
+ * Everything the editor needs to know to support a language.
+ */
+pub struct LanguageConfig {
+    /// The language identifier sent to the language server, e.g. `rust`.
+    pub name: &'static str,
+    /// The grammar compiled into the editor, a grammar in the runtime directory takes precedence.
+    /// Without either the language isn't highlighted.
+    pub grammar: Option<fn() -> Language>,
+    pub queries: LanguageQueries,
+    pub comment_tokens: CommentTokens,
+    /// Globs matched against the file name, or against the whole path when they contain a `/`.
+    pub file_globs: &'static [&'static str],
+    /// Interpreters that identify the language in a `#!` line, e.g. `python`.
+    pub shebangs: &'static [&'static str],
+    pub language_server: Option<LanguageServerCommand>,
+    /// Whether saving a file formats it with the language server first.
+    pub format_on_save: bool,
+}
+
+impl LanguageConfig {
+    pub fn matches_path(&self, path: &Path) -> bool {
+        let file_name = path.file_name().and_then(|name| name.to_str());
+        let full_path = path.to_str();
+
+        self.file_globs.iter().any(|glob| {
+            let text = if glob.contains('/') {
+                full_path
+            } else {
+                file_name
+            };
+            text.is_some_and(|text| glob_matches(glob, text))
+        })
+    }
+
+    pub fn matches_shebang(&self, first_line: &str) -> bool {
+        let Some(interpreter) = shebang_interpreter(first_line) else {
+            return false;
+        };
+        // `python3.12` is still `python`
+        let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+        self.shebangs.contains(&interpreter)
+    }
+}
+
+pub fn by_name(name: &str) -> Option<&'static LanguageConfig> {
+    definitions::LANGUAGES
+        .iter()
+        .find(|language| language.name == name)
+}
+
+/**
+ * Finds the language an injection refers to, by its name or by a file extension, like the `rs`
+ * of a Markdown code block.
+ */
+pub fn by_injection_name(name: &str) -> Option<&'static LanguageConfig> {
+    let name = name.trim().to_ascii_lowercase();
+    by_name(&name).or_else(|| {
+        let path = format!("injection.{name}");
+        definitions::LANGUAGES
+            .iter()
+            .find(|language| language.matches_path(Path::new(&path)))
+    })
+}
+
+/**
+ * Picks the language of a file from its path, falling back to the `#!` line for scripts
+ * without an extension.
+ */
+pub fn detect(path: &Path, first_line: Option<&str>) -> Option<&'static LanguageConfig> {
+    definitions::LANGUAGES
+        .iter()
+        .find(|language| language.matches_path(path))
+        .or_else(|| {
+            let first_line = first_line?;
+            definitions::LANGUAGES
+                .iter()
+                .find(|language| language.matches_shebang(first_line))
+        })
+}
+
+/**
+ * Matches `text` against a glob, where `*` matches any run of characters and `?` matches a
+ * single character.
+ */
+fn glob_matches(glob: &str, text: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // Position of the last `*` in the glob and of the text it's matched up to
+    let mut star: Option<(usize, usize)> = None;
+    let (mut g, mut t) = (0, 0);
+    while t < text.len() {
+        match glob.get(g) {
+            Some('*') => {
+                star = Some((g, t));
+                g += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                g += 1;
+                t += 1;
+            }
+            _ => match star {
+                // Let the last `*` swallow one more character
+                Some((star_g, star_t)) => {
+                    star = Some((star_g, star_t + 1));
+                    g = star_g + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    glob[g..].iter().all(|c| *c == '*')
+}
+
+/**
+ * Gets the name of the interpreter from a `#!` line, looking through `env` and its flags.
+ */
+fn shebang_interpreter(line: &str) -> Option<&str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let program = words.next()?.rsplit('/').next()?;
+    if program != "env" {
+        return Some(program);
+    }
+
+    words
+        .find(|word| !word.starts_with('-') && !word.contains('='))
+        .map(|word| word.rsplit('/').next().unwrap_or(word))
+}
+
+#[cfg(test)]
+mod language_tests {
+    use super::{
+        by_injection_name, by_name, definitions::LANGUAGES, detect, glob_matches,
+        shebang_interpreter,
+    };
+    use pretty_assertions::assert_eq;
+    use std::path::Path;
+    use tree_sitter::Query;
+
+    fn detected(path: &str, first_line: Option<&str>) -> Option<&'static str> {
+        detect(Path::new(path), first_line).map(|language| language.name)
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("*.rs", "main.rs"));
+        assert!(glob_matches("*.rs", ".rs"));
+        assert!(glob_matches("Cargo.lock", "Cargo.lock"));
+        assert!(glob_matches("*.py?", "stubs.pyi"));
+        assert!(glob_matches(
+            "*/.cargo/*.toml",
+            "/home/user/.cargo/config.toml"
+        ));
+        assert!(!glob_matches("*.rs", "main.rs.orig"));
+        assert!(!glob_matches("*.md", "md"));
+    }
+
+    #[test]
+    fn test_shebang_interpreter() {
+        assert_eq!(shebang_interpreter("#!/usr/bin/python3"), Some("python3"));
+        assert_eq!(shebang_interpreter("#!/usr/bin/env python"), Some("python"));
+        assert_eq!(
+            shebang_interpreter("#!/usr/bin/env -S PYTHONPATH=. python3 -u"),
+            Some("python3")
+        );
+        assert_eq!(shebang_interpreter("import os"), None);
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detected("/src/main.rs", None), Some("rust"));
+        assert_eq!(detected("/Cargo.lock", None), Some("toml"));
+        assert_eq!(detected("/README.md", None), Some("markdown"));
+        assert_eq!(detected("/package.json", None), Some("json"));
+        assert_eq!(detected("/paper.typ", None), Some("typst"));
+        assert_eq!(
+            detected("/bin/tool", Some("#!/usr/bin/env python3.12")),
+            Some("python")
+        );
+        // The path wins over the shebang
+        assert_eq!(
+            detected("/build.rs", Some("#!/usr/bin/python")),
+            Some("rust")
+        );
+        assert_eq!(detected("/notes.txt", None), None);
+    }
+
+    #[test]
+    fn test_injection_names() {
+        let name = |name| by_injection_name(name).map(|language| language.name);
+        assert_eq!(name("rust"), Some("rust"));
+        assert_eq!(name("rs"), Some("rust"));
+        assert_eq!(name("Python"), Some("python"));
+        assert_eq!(name("regex"), Some("regex"));
+        assert_eq!(name(""), None);
+    }
+
+    #[test]
+    fn test_queries_compile() {
+        for language in LANGUAGES {
+            let Some(grammar) = language.grammar else {
+                continue;
+            };
+            let queries = &language.queries;
+            for (kind, source) in [
+                ("highlights", queries.highlights),
+                ("indents", queries.indents),
+                ("folds", queries.folds),
+                ("injections", queries.injections),
+            ] {
+                if let Err(error) = Query::new(grammar(), source) {
+                    panic!("{} {kind} query: {error:?}", language.name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_grammars_are_compatible() {
+        // A grammar generated for another ABI than the one the parser reads can't be loaded
+        let supported =
+            tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION..=tree_sitter::LANGUAGE_VERSION;
+        for language in LANGUAGES {
+            let Some(grammar) = language.grammar else {
+                continue;
+            };
+            let version = grammar().version();
+            assert!(
+                supported.contains(&version),
+                "{} grammar has ABI version {version}, supported are {supported:?}",
+                language.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_names_are_unique() {
+        for language in LANGUAGES {
+            assert!(std::ptr::eq(by_name(language.name).unwrap(), language));
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::{
+    env::{self, consts::DLL_EXTENSION},
+    fs, mem,
+    path::{Path, PathBuf},
+};
+
+use libloading::{Library, Symbol};
+use tracing::{info, warn};
+use tree_sitter::Language;
+
+use super::LanguageConfig;
+
+/// Overrides the user runtime directory.
+const RUNTIME_ENV: &str = "LAUREL_RUNTIME";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryKind {
+    Highlights,
+    Indents,
+    Folds,
+    Injections,
+}
+
+impl QueryKind {
+    fn file_name(self) -> &'static str {
+        match self {
+            QueryKind::Highlights => "highlights.scm",
+            QueryKind::Indents => "indents.scm",
+            QueryKind::Folds => "folds.scm",
+            QueryKind::Injections => "injections.scm",
+        }
+    }
+
+    /// The copy of the query compiled into the binary.
+    fn bundled(self, language: &LanguageConfig) -> &'static str {
+        match self {
+            QueryKind::Highlights => language.queries.highlights,
+            QueryKind::Indents => language.queries.indents,
+            QueryKind::Folds => language.queries.folds,
+            QueryKind::Injections => language.queries.injections,
+        }
+    }
+}
+
+/**
+ * The user runtime directory, `$LAUREL_RUNTIME` or `<config dir>/laurel/runtime`.
+ *
+ * Grammars are shared libraries in `grammars/`, named after the language, e.g.
+ * `grammars/typst.so`. Queries go in `queries/<language>/`, and are layered on top of the ones
+ * compiled into the editor.
+ */
+pub fn runtime_dir() -> Option<PathBuf> {
+    env::var_os(RUNTIME_ENV)
+        .map(PathBuf::from)
+        .or_else(|| dirs::config_dir().map(|dir| dir.join("laurel").join("runtime")))
+}
+
+/**
+ * Gets the grammar of the language, preferring a shared library in the runtime directory over
+ * the grammar compiled into the editor.
+ */
+pub fn grammar(language: &LanguageConfig) -> Option<Language> {
+    let library = runtime_dir().map(|dir| {
+        dir.join("grammars")
+            .join(language.name)
+            .with_extension(DLL_EXTENSION)
+    });
+
+    match library {
+        Some(path) if path.is_file() => match load_grammar(&path, language.name) {
+            Ok(grammar) => {
+                info!(
+                    "Loaded the {} grammar from {}",
+                    language.name,
+                    path.display()
+                );
+                return Some(grammar);
+            }
+            Err(error) => warn!(error = ?error, "Couldn't load {}", path.display()),
+        },
+        _ => {}
+    }
+
+    language.grammar.map(|grammar| grammar())
+}
+
+/**
+ * Loads the `tree_sitter_<name>` function of a compiled grammar.
+ *
+ * The library is never unloaded, since the language points into it.
+ */
+fn load_grammar(path: &Path, name: &str) -> Result<Language, libloading::Error> {
+    let symbol = format!("tree_sitter_{}", name.replace('-', "_"));
+
+    // SAFETY: tree-sitter grammars export a function returning a pointer to their static
+    // language, which stays valid as long as the library is loaded.
+    unsafe {
+        let library = Library::new(path)?;
+        let grammar = {
+            let language: Symbol<unsafe extern "C" fn() -> Language> =
+                library.get(symbol.as_bytes())?;
+            language()
+        };
+        mem::forget(library);
+        Ok(grammar)
+    }
+}
+
+/**
+ * Gets the default query of the language, the one compiled into the editor, without the user's
+ * changes.
+ */
+pub fn default_query(language: &LanguageConfig, kind: QueryKind) -> &'static str {
+    kind.bundled(language)
+}
+
+/**
+ * Gets the query of the language with the user's query layered on top of the default one.
+ *
+ * The user's patterns come first, so they take precedence over the defaults for the same node.
+ */
+pub fn query(language: &LanguageConfig, kind: QueryKind) -> String {
+    layered_query(
+        runtime_dir().as_deref(),
+        default_query(language, kind),
+        language,
+        kind,
+    )
+}
+
+fn layered_query(
+    runtime_dir: Option<&Path>,
+    default: &str,
+    language: &LanguageConfig,
+    kind: QueryKind,
+) -> String {
+    let user = runtime_dir.and_then(|dir| read_query(&dir.join("queries"), language, kind));
+    match user {
+        Some(user) => format!("{user}\n{default}"),
+        None => default.to_owned(),
+    }
+}
+
+fn read_query(query_dir: &Path, language: &LanguageConfig, kind: QueryKind) -> Option<String> {
+    let path = query_dir.join(language.name).join(kind.file_name());
+    fs::read_to_string(path).ok()
+}
+
+#[cfg(test)]
+mod runtime_tests {
+    use super::{default_query, layered_query, QueryKind};
+    use crate::language::definitions::{RUST, TYPST};
+    use pretty_assertions::assert_eq;
+    use std::{env, fs};
+
+    #[test]
+    fn test_default_queries_are_bundled() {
+        assert_eq!(
+            default_query(&RUST, QueryKind::Highlights),
+            RUST.queries.highlights
+        );
+        assert_eq!(default_query(&TYPST, QueryKind::Folds), "");
+        // Without a runtime directory there's nothing to layer on top
+        assert_eq!(
+            layered_query(None, RUST.queries.folds, &RUST, QueryKind::Folds),
+            RUST.queries.folds
+        );
+    }
+
+    #[test]
+    fn test_user_query_comes_first() {
+        let runtime = env::temp_dir().join(format!("laurel-runtime-{}", std::process::id()));
+        let query_dir = runtime.join("queries").join("rust");
+        fs::create_dir_all(&query_dir).unwrap();
+        fs::write(query_dir.join("folds.scm"), "(line_comment) @fold").unwrap();
+
+        let folds = layered_query(Some(&runtime), "(block) @fold", &RUST, QueryKind::Folds);
+        let indents = layered_query(Some(&runtime), "(block) @indent", &RUST, QueryKind::Indents);
+        fs::remove_dir_all(&runtime).unwrap();
+
+        assert_eq!(folds, "(line_comment) @fold\n(block) @fold");
+        assert_eq!(indents, "(block) @indent");
+    }
+}
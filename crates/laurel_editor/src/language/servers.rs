@@ -0,0 +1,444 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use async_lsp::lsp_types::LSPAny;
+use laurel_lsp::{error::LspClientError, LspConnection, LspServers, ServerConfig, ServerId};
+use toml::{Table, Value};
+use tracing::{info, warn};
+
+use super::{definitions::LANGUAGES, runtime::runtime_dir};
+
+/**
+ * How the language servers of the languages are started: the servers the languages come with,
+ * changed by the user's `servers.toml` in the runtime directory, one table per language:
+ *
+ * ```toml
+ * [rust]
+ * args = ["--log-file", "/tmp/rust-analyzer.log"]
+ * root_markers = ["Cargo.lock"]
+ *
+ * [rust.initialization_options]
+ * cargo = { features = "all" }
+ *
+ * [python]
+ * enabled = false
+ * ```
+ *
+ * What's left out keeps its default, and a language without a server gets one by giving it a
+ * `command`. The initialization options also answer the server when it asks for its settings,
+ * as the `settings_section` the server asks for: `rust-analyzer.cargo.features` above is
+ * `"all"`. A server of the user's own can set `settings_section` too.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ServerRegistry {
+    configs: HashMap<String, ServerConfig>,
+}
+
+impl ServerRegistry {
+    /**
+     * The servers the languages come with.
+     */
+    pub fn bundled() -> Self {
+        let configs = LANGUAGES
+            .iter()
+            .filter_map(|language| {
+                let server = language.language_server.as_ref()?;
+                let config = ServerConfig {
+                    command: server.command.to_owned(),
+                    args: server.args.iter().map(|arg| (*arg).to_owned()).collect(),
+                    initialization_options: None,
+                    root_markers: server
+                        .root_markers
+                        .iter()
+                        .map(|marker| (*marker).to_owned())
+                        .collect(),
+                    settings_section: server.settings_section.map(ToOwned::to_owned),
+                };
+                Some((language.name.to_owned(), config))
+            })
+            .collect();
+        Self { configs }
+    }
+
+    /**
+     * The bundled servers with the user's changes, only the bundled ones when there's no
+     * `servers.toml`.
+     */
+    pub fn load() -> Self {
+        let mut registry = Self::bundled();
+        let Some(path) = runtime_dir().map(|dir| dir.join("servers.toml")) else {
+            return registry;
+        };
+        let Ok(source) = fs::read_to_string(&path) else {
+            return registry;
+        };
+        if let Err(error) = registry.apply(&source) {
+            warn!(error = %error, "Couldn't read the language servers in {}", path.display());
+        }
+        registry
+    }
+
+    /**
+     * Changes the servers with the tables of a `servers.toml`, skipping the ones that are wrong.
+     */
+    pub fn apply(&mut self, source: &str) -> Result<(), toml::de::Error> {
+        let table: Table = source.parse()?;
+        for (language, value) in table {
+            let Value::Table(table) = value else {
+                warn!("The server of `{language}` should be a table");
+                continue;
+            };
+            self.apply_table(language, table);
+        }
+        Ok(())
+    }
+
+    fn apply_table(&mut self, language: String, mut table: Table) {
+        if table
+            .remove("enabled")
+            .and_then(|enabled| enabled.as_bool())
+            == Some(false)
+        {
+            self.configs.remove(&language);
+            return;
+        }
+        let command = match table.remove("command") {
+            Some(Value::String(command)) => Some(command),
+            Some(_) => {
+                warn!("The command of the `{language}` server should be a string");
+                return;
+            }
+            None => None,
+        };
+
+        let config = self.configs.remove(&language).or_else(|| {
+            Some(ServerConfig {
+                command: command.clone()?,
+                args: Vec::new(),
+                initialization_options: None,
+                root_markers: Vec::new(),
+                settings_section: None,
+            })
+        });
+        let Some(mut config) = config else {
+            warn!("The `{language}` server has no command");
+            return;
+        };
+        if let Some(command) = command {
+            config.command = command;
+        }
+        if let Some(args) = strings(&language, "args", table.remove("args")) {
+            config.args = args;
+        }
+        if let Some(markers) = strings(&language, "root_markers", table.remove("root_markers")) {
+            config.root_markers = markers;
+        }
+        match table.remove("settings_section") {
+            Some(Value::String(section)) => config.settings_section = Some(section),
+            Some(_) => warn!("The settings section of the `{language}` server should be a string"),
+            None => {}
+        }
+        if let Some(options) = table.remove("initialization_options") {
+            config.initialization_options = Some(to_lsp_any(options));
+        }
+        self.configs.insert(language, config);
+    }
+
+    pub fn config(&self, language: &str) -> Option<&ServerConfig> {
+        self.configs.get(language)
+    }
+
+    /**
+     * The server of a file in the language, `None` when the language has no server.
+     */
+    pub fn server_for(&self, language: &str, path: &Path) -> Option<ServerId> {
+        let config = self.configs.get(language)?;
+        Some(ServerId {
+            language: language.to_owned(),
+            root: find_root(path, &config.root_markers),
+        })
+    }
+}
+
+fn strings(language: &str, key: &str, value: Option<Value>) -> Option<Vec<String>> {
+    let strings = value?.as_array().and_then(|values| {
+        values
+            .iter()
+            .map(|value| value.as_str().map(ToOwned::to_owned))
+            .collect()
+    });
+    if strings.is_none() {
+        warn!("The {key} of the `{language}` server should be a list of strings");
+    }
+    strings
+}
+
+/**
+ * Converts the TOML of the initialization options to the JSON that's sent to the server, dates
+ * become strings.
+ */
+fn to_lsp_any(value: Value) -> LSPAny {
+    match value {
+        Value::String(value) => LSPAny::String(value),
+        Value::Integer(value) => LSPAny::from(value),
+        Value::Float(value) => LSPAny::from(value),
+        Value::Boolean(value) => LSPAny::Bool(value),
+        Value::Datetime(value) => LSPAny::String(value.to_string()),
+        Value::Array(values) => LSPAny::Array(values.into_iter().map(to_lsp_any).collect()),
+        Value::Table(table) => LSPAny::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, to_lsp_any(value)))
+                .collect(),
+        ),
+    }
+}
+
+/**
+ * The root of the workspace a file is in: the outermost directory with one of the markers,
+ * without leaving the repository of the file. Without a marker it's the root of the repository,
+ * or the directory of the file outside of one.
+ */
+pub fn find_root(file: &Path, markers: &[String]) -> PathBuf {
+    let dir = file.parent().unwrap_or(file);
+    let mut root = None;
+    for ancestor in dir.ancestors() {
+        if markers.iter().any(|marker| ancestor.join(marker).exists()) {
+            root = Some(ancestor);
+        }
+        // The markers above the repository belong to something else
+        if ancestor.join(".git").exists() {
+            return root.unwrap_or(ancestor).to_path_buf();
+        }
+    }
+    root.unwrap_or(dir).to_path_buf()
+}
+
+/**
+ * A language server as far as the editor knows.
+ */
+#[derive(Debug, Clone)]
+pub enum ServerState {
+    Starting,
+    Running(LspConnection),
+    /// It stopped, and it's started again after a while
+    Restarting(LspClientError),
+    /// It stopped for good, until it's restarted by hand
+    Stopped(LspClientError),
+}
+
+impl ServerState {
+    /**
+     * What the state is called in the status of the server.
+     */
+    pub fn label(&self) -> &'static str {
+        match self {
+            ServerState::Starting => "Starting",
+            ServerState::Running(_) => "Running",
+            ServerState::Restarting(_) => "Restarting",
+            ServerState::Stopped(_) => "Stopped",
+        }
+    }
+}
+
+/**
+ * The language servers that were started, one for each language and workspace root.
+ */
+#[derive(Debug, Default)]
+pub struct LanguageServers {
+    registry: ServerRegistry,
+    /// Starts the servers, `None` until the language server stream is ready
+    handle: Option<LspServers>,
+    servers: HashMap<ServerId, ServerState>,
+    /// Whether the messages to and from the servers are traced
+    trace: bool,
+}
+
+impl LanguageServers {
+    pub fn new(registry: ServerRegistry) -> Self {
+        Self {
+            registry,
+            handle: None,
+            servers: HashMap::new(),
+            trace: false,
+        }
+    }
+
+    pub fn set_handle(&mut self, handle: LspServers) {
+        handle.set_trace(self.trace);
+        self.handle = Some(handle);
+    }
+
+    pub fn trace(&self) -> bool {
+        self.trace
+    }
+
+    /**
+     * Starts or stops the trace of the messages to and from the servers, once they can be started
+     * when they can't yet.
+     */
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+        if let Some(handle) = self.handle.as_ref() {
+            handle.set_trace(trace);
+        }
+    }
+
+    /**
+     * The server of a file in the language, see [`ServerRegistry::server_for`].
+     */
+    pub fn server_for(&self, language: &str, path: &Path) -> Option<ServerId> {
+        self.registry.server_for(language, path)
+    }
+
+    /**
+     * The connection to a server, which is started when it wasn't yet. `None` until the server
+     * is initialized, or when it couldn't be started.
+     */
+    pub fn connect(&mut self, id: &ServerId) -> Option<LspConnection> {
+        match self.servers.get(id) {
+            Some(ServerState::Running(connection)) => return Some(connection.clone()),
+            Some(_) => return None,
+            None => {}
+        }
+        let handle = self.handle.as_ref()?;
+        let config = self.registry.config(&id.language)?.clone();
+        info!(server = %id, command = config.command, "Starting a language server");
+        handle.start(id.clone(), config);
+        self.servers.insert(id.clone(), ServerState::Starting);
+        None
+    }
+
+    /**
+     * Starts a server again straight away, whether it's running, waiting to be restarted or
+     * stopped for good.
+     */
+    pub fn restart(&mut self, id: &ServerId) {
+        let (Some(handle), Some(config)) =
+            (self.handle.as_ref(), self.registry.config(&id.language))
+        else {
+            return;
+        };
+        info!(server = %id, "Restarting a language server");
+        handle.start(id.clone(), config.clone());
+        self.servers.insert(id.clone(), ServerState::Starting);
+    }
+
+    pub fn state(&self, id: &ServerId) -> Option<&ServerState> {
+        self.servers.get(id)
+    }
+
+    pub fn starting(&mut self, id: ServerId) {
+        self.servers.insert(id, ServerState::Starting);
+    }
+
+    pub fn initialized(&mut self, connection: LspConnection) {
+        self.servers.insert(
+            connection.server().clone(),
+            ServerState::Running(connection),
+        );
+    }
+
+    /**
+     * The server stopped, and it's restarted when `restarting`.
+     */
+    pub fn stopped(&mut self, id: ServerId, error: LspClientError, restarting: bool) {
+        let state = if restarting {
+            ServerState::Restarting(error)
+        } else {
+            ServerState::Stopped(error)
+        };
+        self.servers.insert(id, state);
+    }
+}
+
+#[cfg(test)]
+mod servers_tests {
+    use super::{find_root, ServerRegistry};
+    use async_lsp::lsp_types::{LSPAny, LSPObject};
+    use pretty_assertions::assert_eq;
+    use std::{env, fs, path::Path};
+
+    #[test]
+    fn test_user_servers() {
+        let mut registry = ServerRegistry::bundled();
+        registry
+            .apply(
+                r#"
+                [rust]
+                args = ["--verbose"]
+
+                [rust.initialization_options]
+                checkOnSave = false
+                cargo = { features = ["a", "b"] }
+
+                [python]
+                enabled = false
+
+                [regex]
+                command = "regex-lsp"
+                settings_section = "regex"
+
+                [sql]
+                command = 1
+                "#,
+            )
+            .unwrap();
+
+        let rust = registry.config("rust").unwrap();
+        assert_eq!(rust.command, "rust-analyzer");
+        assert_eq!(rust.args, vec![String::from("--verbose")]);
+        assert_eq!(rust.root_markers, vec!["Cargo.lock", "Cargo.toml"]);
+        let mut cargo = LSPObject::new();
+        cargo.insert(
+            String::from("features"),
+            LSPAny::Array(vec![LSPAny::from("a"), LSPAny::from("b")]),
+        );
+        let mut options = LSPObject::new();
+        options.insert(String::from("checkOnSave"), LSPAny::Bool(false));
+        options.insert(String::from("cargo"), LSPAny::Object(cargo));
+        assert_eq!(rust.initialization_options, Some(LSPAny::Object(options)));
+        assert_eq!(rust.settings_section.as_deref(), Some("rust-analyzer"));
+
+        assert!(registry.config("python").is_none());
+        let regex = registry.config("regex").unwrap();
+        assert_eq!(regex.command, "regex-lsp");
+        assert_eq!(regex.settings_section.as_deref(), Some("regex"));
+        // A wrong table keeps the bundled server
+        assert_eq!(registry.config("sql").unwrap().command, "sqls");
+        assert!(registry
+            .server_for("python", Path::new("/main.py"))
+            .is_none());
+    }
+
+    #[test]
+    fn test_find_root() {
+        let base = env::temp_dir().join(format!("laurel-servers-{}", std::process::id()));
+        let repo = base.join("repo");
+        let src = repo.join("crates").join("editor").join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::create_dir_all(base.join("scripts")).unwrap();
+        fs::write(base.join("Cargo.toml"), "").unwrap();
+        fs::write(repo.join("Cargo.toml"), "").unwrap();
+        fs::write(repo.join("crates").join("editor").join("Cargo.toml"), "").unwrap();
+        fs::write(base.join("scripts").join("pyproject.toml"), "").unwrap();
+
+        let markers = vec![String::from("Cargo.toml")];
+        let rust_root = find_root(&src.join("lib.rs"), &markers);
+        let plain_root = find_root(&src.join("notes.txt"), &[]);
+        let python_root = find_root(
+            &base.join("scripts").join("tool.py"),
+            &[String::from("pyproject.toml")],
+        );
+        fs::remove_dir_all(&base).unwrap();
+
+        // The workspace rather than the crate, but not past the repository
+        assert_eq!(rust_root, repo);
+        assert_eq!(plain_root, repo);
+        assert_eq!(python_root, base.join("scripts"));
+    }
+}
@@ -1,31 +1,66 @@
 use core::buffer::Buffer;
+use core::code_action::CodeActions;
+use core::completion::{self, Completion};
+use core::diagnostics::{DiagnosticSort, WorkspaceDiagnostics};
+use core::formatting;
+use core::inlay_hints::{self, InlayHints};
+use core::jump_list::{Jump, JumpList};
+use core::navigation::Navigation;
+use core::output::{OutputKind, OutputLog};
+use core::progress::Progress;
+use core::references::References;
+use core::requests::{PendingRequest, PendingRequests};
+use core::signature_help::{self, Signatures};
 use core::window::VirtualWindow;
-use std::path::PathBuf;
+use core::workspace_edit::{self, FileChange};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use std::vec;
 
-use async_lsp::lsp_types::Url;
+use async_lsp::lsp_types::{
+    self, ApplyWorkspaceEditResponse, CodeActionOrCommand, CompletionItem, CompletionItemKind,
+    CompletionTextEdit, InsertTextFormat, Location, MarkupKind, MessageType, PrepareRenameResponse,
+    SemanticTokensDelta, SemanticTokensFullDeltaResult, TextDocumentSyncKind, TextEdit, Url,
+};
 use core::document::Document;
 use highlighter::HighlighterConfig;
+use language::servers::{LanguageServers, ServerRegistry};
 use tokio::sync::mpsc::error::SendError;
-use tracing::{debug, info, warn};
+use tracing::{debug, error, info, warn};
 
-use iced::keyboard::Key;
+use iced::keyboard::{self, key::Named, Key};
 use iced::widget::scrollable::Scrollbar;
-use iced::widget::{column, container, row, scrollable, text};
-use iced::{Font, Length, Padding, Renderer, Subscription, Task};
+use iced::widget::{column, container, row, scrollable, text, text_input};
+use iced::{clipboard, event, Event, Font, Length, Padding, Renderer, Subscription, Task};
 
 use laurel_common::{
-    text::{CursorMessage, Position},
+    text::{CursorMessage, Position, Range},
     Element, LaurelTheme,
 };
 use laurel_lsp::{
-    LspClientNotification, LspCommand, LspConnection, LspMessage, LspServerNotification,
-    Synchronise,
+    diagnostics::Severity, GotoKind, LspClientNotification, LspCommand, LspConnection, LspMessage,
+    LspRequest, LspResponse, LspServerNotification, LspServerRequest, RequestId, ServerId,
+    Synchronise, TextChange,
 };
 
 use rfd::FileDialog;
 
+use snippets::UserSnippet;
+use widgets::floating_text::code_actions::CodeActionMenu;
+use widgets::floating_text::completion::CompletionPopup;
+use widgets::floating_text::markdown;
+use widgets::floating_text::rename::{self, RenameInput};
+use widgets::floating_text::signature_help::SignaturePopup;
+use widgets::modal::edit_preview::EditPreview;
 use widgets::modal::file_selector::Modal;
+use widgets::modal::location_picker::{self, LocationItem};
+use widgets::modal::message_request::MessageRequest;
+use widgets::modal::unsaved_changes::UnsavedChanges;
+use widgets::output_panel::output_panel;
+use widgets::problems_panel::problems_panel;
+use widgets::references_panel::references_panel;
 use widgets::textbox::Textbox;
 use widgets::textbox_container::TextboxContainer;
 use widgets::view_port::{ViewPort, ViewPortMessage};
@@ -33,7 +68,10 @@ use widgets::{layout, line_number};
 
 pub mod core;
 pub mod highlighter;
+pub mod language;
+pub mod snippets;
 pub mod styles;
+pub mod theme;
 pub mod widgets;
 
 #[derive(Debug, Clone)]
@@ -51,6 +89,8 @@ impl<T> From<Result<(), SendError<T>>> for Message {
 #[derive(Debug, Clone)]
 pub enum Message {
     KeyEvent(KeyEvent),
+    /// The held modifiers changed, they turn a click into ctrl+click
+    ModifiersChanged(Modifiers),
     CursorEvent(Position),
     Offset(f32, f32),
     View(f32, f32),
@@ -61,6 +101,57 @@ pub enum Message {
     FileFilter(String),
     SetTextBoxFocus(bool),
     CloseClient,
+    /// The text under the mouse changed
+    MouseHover(Option<Position>),
+    /// The mouse stayed over the position long enough to ask for its hover
+    HoverDwell(Position),
+    /// The inlay hint under the mouse changed
+    InlayHintHover(Option<usize>),
+    /// The mouse stayed over the inlay hint long enough to show its tooltip
+    InlayHintDwell(usize),
+    /// Inserts the completion at this index of the popup
+    AcceptCompletion(usize),
+    /// Moves to the next tab stop of the snippet that's being filled in
+    NextTabStop,
+    /// Shows the next overload of the call's signatures
+    NextSignature,
+    PreviousSignature,
+    /// Goes to a location that was picked, remembering where the cursor was
+    OpenLocation(Location),
+    /// Goes to the reference at this index of the references panel
+    OpenReference(usize),
+    NextReference,
+    PreviousReference,
+    CloseReferences,
+    /// The new name typed in the rename input
+    RenameInput(String),
+    SubmitRename,
+    /// Makes the changes of the workspace edit that's previewed
+    ApplyWorkspaceEdit,
+    /// Applies the code action at this index of the menu
+    PickCodeAction(usize),
+    /// Answers the message of the language server with the action at this index
+    AnswerMessage(usize),
+    /// Saves the changes of the open document, or discards them, and goes where it was asked to
+    LeaveUnsaved(bool),
+    /// The formatting request with the id took too long, so the document is saved as it is
+    FormatTimeout(RequestId),
+    /// The text the entries of the output panel are filtered by
+    OutputFilter(String),
+    /// Shows or hides the entries of the kind in the output panel
+    ToggleOutputKind(OutputKind),
+    /// Starts or stops the trace of the messages to and from the language servers
+    ToggleTrace,
+    /// Copies the entries shown in the output panel
+    CopyOutput,
+    /// Copies the entry of the output at this index
+    CopyOutputEntry(usize),
+    ClearOutput,
+    /// The text the problems in the panel are filtered by
+    DiagnosticFilter(String),
+    /// Shows or hides the problems of the severity in the panel
+    ToggleSeverity(Severity),
+    SortDiagnostics(DiagnosticSort),
 
     // Client messages
     ClientStart(()),
@@ -80,6 +171,29 @@ pub enum Message {
     SelectFile,
     SelectFolder,
     Save,
+    ReloadQueries,
+    NextTheme,
+    Hover,
+    Complete,
+    SignatureHelp,
+    Goto(GotoKind),
+    GoBack,
+    GoForward,
+    FindReferences,
+    Undo,
+    Redo,
+    Rename,
+    CodeActions,
+    Format,
+    /// Restarts the language server of the open document
+    RestartServer,
+    /// Opens or closes the output of the language servers
+    ToggleOutput,
+    /// Opens or closes the problems of all the files
+    ToggleProblems,
+    /// Goes to the next problem after the cursor, in this file or the next one
+    NextDiagnostic,
+    PreviousDiagnostic,
 
     SendError,
 }
@@ -148,6 +262,12 @@ impl CursorMessage for Message {
     fn from_selection_move(pos: Position) -> Self {
         Self::SelectionMove(pos)
     }
+    fn from_mouse_hover(pos: Option<Position>) -> Self {
+        Self::MouseHover(pos)
+    }
+    fn from_inlay_hint_hover(hint: Option<usize>) -> Self {
+        Self::InlayHintHover(hint)
+    }
 }
 
 impl ViewPortMessage for Message {
@@ -179,16 +299,58 @@ impl From<iced::keyboard::Modifiers> for Modifiers {
     }
 }
 
+/// How long the mouse has to stay over some text before its hover is requested
+const HOVER_DWELL: Duration = Duration::from_millis(500);
+/// How many lines before the cursor are looked at for the start of the call it's in
+const CALL_LINES: usize = 20;
+/// How long saving waits for the document to be formatted before it's saved as it is
+const FORMAT_ON_SAVE_TIMEOUT: Duration = Duration::from_secs(2);
+/// How many lines before and after the visible ones the inlay hints are asked for, at least
+const INLAY_HINT_LINES: usize = 50;
+
 pub struct Editor {
     // last_event: Option<Key>,
     text_box: Option<Textbox>,
     modifiers: Modifiers,
     modal: Option<Modal>,
     // workspace: Option<PathBuf>,
+    /// The language servers, one for each language and workspace root
+    servers: LanguageServers,
+    /// The language server of the open document
+    server: Option<ServerId>,
+    /// The connection to the server of the open document, `None` until it's initialized
     lsp: Option<LspConnection>,
     file_filter: String,
     // client_id: usize,
     theme: LaurelTheme,
+    /// The text under the mouse
+    mouse_position: Option<Position>,
+    /// The requests to the language server that are waiting for a response
+    requests: PendingRequests,
+    /// The user's snippets for the language of the open document
+    snippets: Vec<UserSnippet>,
+    /// Where the cursor jumped from, to go back and forth
+    jumps: JumpList,
+    /// The references listed in the panel, which follow the edits of the open document
+    references: Option<References>,
+    /// The line of the cursor and the version of the document the code actions were asked for
+    code_actions_asked: Option<(usize, i32)>,
+    /// The line of the cursor when it has code actions, where the lightbulb is shown
+    lightbulb: Option<usize>,
+    /// The version of the document and the lines the inlay hints were asked for
+    inlay_hints_asked: Option<(i32, usize, usize)>,
+    /// The inlay hint under the mouse
+    hovered_hint: Option<usize>,
+    /// The work the language servers are doing, shown in the status bar
+    progress: Progress,
+    /// What the language servers said besides their answers
+    output: OutputLog,
+    /// Whether the output panel is open
+    output_open: bool,
+    /// The diagnostics of all the files, listed in the problems panel
+    diagnostics: WorkspaceDiagnostics,
+    /// Whether the problems panel is open
+    problems_open: bool,
 }
 
 impl Editor {
@@ -204,121 +366,1567 @@ impl Editor {
                 text_box: None,
                 modifiers: Modifiers::default(),
                 modal: None,
+                servers: LanguageServers::new(ServerRegistry::load()),
+                server: None,
                 lsp: None,
                 // workspace: None,
                 file_filter: String::default(),
                 // client_id: 1,
-                theme: LaurelTheme::default(),
+                theme: theme::default_theme(),
+                mouse_position: None,
+                requests: PendingRequests::default(),
+                snippets: Vec::new(),
+                jumps: JumpList::default(),
+                references: None,
+                code_actions_asked: None,
+                lightbulb: None,
+                inlay_hints_asked: None,
+                hovered_hint: None,
+                progress: Progress::default(),
+                output: OutputLog::default(),
+                output_open: false,
+                diagnostics: WorkspaceDiagnostics::default(),
+                problems_open: false,
             },
             Task::none(),
         )
     }
 
-    pub fn title(&self) -> String {
-        String::from("A code editor")
+    /**
+     * The title of the window, with how many errors and warnings there are in the workspace.
+     */
+    pub fn title(&self) -> String {
+        let title = String::from("Laurel LSP test");
+        let (errors, warnings) = self.diagnostics.counts();
+        if errors == 0 && warnings == 0 {
+            return title;
+        }
+        let plural = |count: usize, name: &str| match count {
+            1 => format!("1 {name}"),
+            count => format!("{count} {name}s"),
+        };
+        format!(
+            "{title} ({}, {})",
+            plural(errors, "error"),
+            plural(warnings, "warning")
+        )
+    }
+
+    pub fn update(&mut self, message: Message) -> Task<Message> {
+        match self.process_event(message) {
+            Some(tasks) => Task::batch(tasks),
+            None => Task::none(),
+        }
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        if let Some(text_box) = &self.text_box {
+            return self.text_box_view(text_box);
+        }
+        self.no_workspace_view()
+    }
+
+    pub fn subscription(&self) -> Subscription<Message> {
+        // subscription::events()
+        // let app_events = subscription::events_with(|event, _status| match event {
+        // Event::Keyboard(keyboard::Event::KeyPressed { modifiers, .. }) => {
+        //     Message::from_keycode(key_code, Modifiers::from(modifiers))
+        // }
+        // Event::Keyboard(keyboard::Event::KeyReleased { modifiers, .. }) => {
+        //     Message::from_keyrelease(Modifiers::from(modifiers))
+        // }
+        // Event::Keyboard(keyboard::Event::CharacterReceived(character)) => {
+        //     if !character.is_control() {
+        //         Some(Message::KeyEvent(KeyEvent::CharacterReceived(character)))
+        //     } else {
+        //         None
+        //     }
+        // }
+        // Event::Window(window::Event::Resized(_)) => None,
+        //     _ => None,
+        // });
+        // let mut events: Vec<Subscription<Message>> = vec![]; // app_events
+        // if let Some(lsp) = self.lsp.as_ref() {
+        //     let lsp_events = Subscription::run(connect::connect).map(|event| match event {
+        //         connect::Event::Response(value) => {
+        //             if let LspResponse::Initialized = value {
+        //                 return Message::ClientStart(());
+        //             }
+        //             Message::LspMessage(value)
+        //         }
+        //         connect::Event::Ready(mut sender) => {
+        //             sender.send(connect::Event::Connect(lsp.new_receiver().clone()));
+        //             Message::None
+        //         }
+        //         connect::Event::Disconnected => Message::CloseClient,
+        //         _ => Message::None,
+        //     });
+        //     events.push(lsp_events)
+        // }
+        let modifiers = event::listen_with(|event, _status, _window| match event {
+            Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                Some(Message::ModifiersChanged(Modifiers::from(modifiers)))
+            }
+            _ => None,
+        });
+        Subscription::batch(vec![
+            Subscription::run(laurel_lsp::connect).map(Message::LspMessage),
+            modifiers,
+        ])
+    }
+
+    pub fn theme(&self) -> LaurelTheme {
+        self.theme.clone()
+    }
+}
+
+impl Editor {
+    fn open(
+        &mut self,
+        file: &str,
+        old_file: Option<Url>,
+        tasks: &mut Vec<Task<Message>>,
+    ) -> io::Result<()> {
+        info!("Opening file {}", file);
+
+        let document = Document::open(file)?;
+        self.set_file(document, old_file);
+        Ok(())
+    }
+
+    fn set_file(&mut self, document: Document, old_file: Option<Url>) {
+        let language_id = document
+            .language()
+            .map_or("plaintext", |language| language.name);
+        let highlighter_config = document
+            .language()
+            .and_then(|language| HighlighterConfig::for_language(language, &document.slice_all()));
+
+        // The old document is closed on its own server
+        if let (Some(old_file_uri), Some(lsp)) = (old_file, self.lsp.as_mut()) {
+            lsp.send(LspCommand::Notification(
+                LspServerNotification::Synchronise(Synchronise::DidClose, old_file_uri),
+            ));
+        }
+
+        self.snippets = snippets::for_language(language_id);
+        self.code_actions_asked = None;
+        self.lightbulb = None;
+        // The responses would be for the document that was open
+        self.requests.forget(|request| {
+            matches!(
+                request,
+                PendingRequest::Formatting { .. }
+                    | PendingRequest::InlayHints
+                    | PendingRequest::ResolveInlayHint(_)
+            )
+        });
+        self.inlay_hints_asked = None;
+        self.hovered_hint = None;
+        let mut buffer = Buffer::new(document, highlighter_config);
+        buffer.set_diagnostics(self.diagnostics.get(buffer.document().uri()));
+        self.text_box = Some(Textbox::new(buffer).font(Font::MONOSPACE).font_size(14.0));
+        self.connect_document();
+        self.refresh_reference_highlights();
+        self.request_semantic_tokens();
+    }
+
+    /**
+     * Picks the language server of the open document, which is started when it isn't running
+     * yet, and opens the document on it once it's initialized.
+     */
+    fn connect_document(&mut self) -> Option<()> {
+        self.server = None;
+        self.lsp = None;
+        let document = self.text_box.as_ref()?.buffer().document();
+        let language = document.language()?;
+        let server = self
+            .servers
+            .server_for(language.name, Path::new(document.filename()))?;
+        self.lsp = self.servers.connect(&server);
+        self.server = Some(server);
+        self.open_document()
+    }
+
+    /**
+     * Tells the language server about the open document, with its whole text.
+     */
+    fn open_document(&mut self) -> Option<()> {
+        let document = self.text_box.as_ref()?.buffer().document();
+        let language_id = document
+            .language()
+            .map_or("plaintext", |language| language.name);
+        self.lsp.as_mut()?.send(LspCommand::Notification(
+            LspServerNotification::Synchronise(
+                Synchronise::DidOpen(document.to_string(), language_id.to_owned()),
+                document.uri().clone(),
+            ),
+        ));
+        Some(())
+    }
+
+    /**
+     * Tells the language server about the changes made to the open document, as the server
+     * asked to be told, and asks for the semantic tokens of the new text.
+     */
+    fn sync_document(&mut self) -> Option<()> {
+        let textbox = self.text_box.as_mut()?;
+        let changes = textbox.take_changes();
+        if changes.is_empty() {
+            return None;
+        }
+
+        if !textbox.inlay_hints().is_empty() {
+            let hints = textbox.inlay_hints_mut();
+            for change in &changes {
+                let range = change.range.map(lsp_types::Range::from);
+                hints.apply_change(range.as_ref(), &change.text);
+            }
+        }
+
+        let document = textbox.buffer().document();
+        if let Some(references) = self.references.as_mut() {
+            for change in &changes {
+                let range = change.range.map(lsp_types::Range::from);
+                references.apply_change(document.uri(), range.as_ref(), &change.text);
+            }
+            references.refresh_previews(document.uri(), |location| {
+                let line = document.get_line(location.range.start.line as usize)?;
+                Some(line.to_string())
+            });
+            self.refresh_reference_highlights();
+        }
+
+        let lsp = self.lsp.as_mut()?;
+        let document = self.text_box.as_ref()?.buffer().document();
+        let changes = match lsp.text_document_sync_kind() {
+            TextDocumentSyncKind::INCREMENTAL => changes,
+            TextDocumentSyncKind::FULL => vec![TextChange::full(document.to_string())],
+            _ => return None,
+        };
+        lsp.send(LspCommand::Notification(
+            LspServerNotification::Synchronise(
+                Synchronise::DidChange(changes, document.version()),
+                document.uri().clone(),
+            ),
+        ));
+
+        self.request_semantic_tokens()
+    }
+
+    /**
+     * Asks the language server for the semantic tokens of the open document, only for the changes
+     * since the last response when the server supports it.
+     */
+    fn request_semantic_tokens(&mut self) -> Option<()> {
+        let lsp = self.lsp.as_ref()?;
+        lsp.semantic_tokens_legend()?;
+        let buffer = self.text_box.as_ref()?.buffer();
+        let uri = buffer.document().uri().clone();
+
+        let request = match buffer.semantic_result_id() {
+            Some(previous) if lsp.supports_semantic_tokens_delta() => {
+                LspRequest::SemanticTokensDelta(uri, previous.to_owned())
+            }
+            _ => LspRequest::SemanticTokens(uri),
+        };
+        // The tokens of the last request would be out of date by the time they arrive
+        self.send_request(request, PendingRequest::SemanticTokens)?;
+        Some(())
+    }
+
+    /**
+     * Asks the language server about the symbol at `position`, which is in characters.
+     */
+    fn request_hover(&mut self, position: Position) -> Option<()> {
+        if !self.lsp.as_ref()?.supports_hover() {
+            return None;
+        }
+        let document = self.text_box.as_ref()?.buffer().document();
+        let request = LspRequest::Hover(
+            document.uri().clone(),
+            document.position_to_utf16(&position).into(),
+        );
+
+        self.send_request(request, PendingRequest::Hover(position))?;
+        Some(())
+    }
+
+    /**
+     * Sends a request to the language server of the open document, in place of the request of
+     * the same kind that's waiting for a response, which is cancelled.
+     */
+    fn send_request(&mut self, request: LspRequest, pending: PendingRequest) -> Option<RequestId> {
+        let lsp = self.lsp.as_mut()?;
+        let id = lsp.request(request);
+        if let Some(previous) = self.requests.insert(id, pending) {
+            lsp.cancel(previous);
+        }
+        Some(id)
+    }
+
+    /**
+     * Cancels the request of a kind that's waiting for a response, since it was superseded.
+     */
+    fn cancel_request(&mut self, kind: impl Fn(&PendingRequest) -> bool) {
+        if let (Some(id), Some(lsp)) = (self.requests.remove(kind), self.lsp.as_mut()) {
+            lsp.cancel(id);
+        }
+    }
+
+    /**
+     * Handles a request that failed, which most requests only have to stop waiting for.
+     */
+    fn process_failed_request(&mut self, id: RequestId) -> Option<()> {
+        match self.requests.take(id)? {
+            // The document is saved as it is
+            PendingRequest::Formatting { save: true, .. } => {
+                self.save_document();
+            }
+            // The item is inserted with the details it has
+            PendingRequest::ResolveCompletion { accept: true } => {
+                let popup = self.text_box.as_mut()?.completion_mut()?;
+                popup.update(|completion| {
+                    if let Some(item) = completion.selected().cloned() {
+                        completion.resolve(item);
+                    }
+                });
+                self.accept_completion();
+            }
+            _ => {}
+        }
+        Some(())
+    }
+
+    fn process_lsp_response(
+        &mut self,
+        id: RequestId,
+        response: LspResponse,
+        tasks: &mut Vec<Task<Message>>,
+    ) -> Option<()> {
+        // A response that isn't waited for is out of date
+        let request = self.requests.take(id)?;
+        match (request, response) {
+            (PendingRequest::SemanticTokens, LspResponse::SemanticTokens(uri, tokens)) => {
+                let legend = self.lsp.as_ref()?.semantic_tokens_legend()?;
+                let textbox = self.text_box.as_mut()?;
+                if textbox.buffer().document().uri() == &uri {
+                    textbox.set_semantic_tokens(legend, tokens);
+                }
+            }
+            (
+                PendingRequest::SemanticTokens,
+                LspResponse::SemanticTokensDelta(uri, previous_result_id, result),
+            ) => {
+                let legend = self.lsp.as_ref()?.semantic_tokens_legend()?;
+                let textbox = self.text_box.as_mut()?;
+                if textbox.buffer().document().uri() != &uri {
+                    return None;
+                }
+
+                let applied = match result {
+                    SemanticTokensFullDeltaResult::Tokens(tokens) => {
+                        textbox.set_semantic_tokens(legend, tokens);
+                        true
+                    }
+                    SemanticTokensFullDeltaResult::TokensDelta(delta) => {
+                        textbox.apply_semantic_delta(&previous_result_id, delta)
+                    }
+                    SemanticTokensFullDeltaResult::PartialTokensDelta { edits } => {
+                        let delta = SemanticTokensDelta {
+                            result_id: None,
+                            edits,
+                        };
+                        textbox.apply_semantic_delta(&previous_result_id, delta)
+                    }
+                };
+                // The tokens changed since the delta was requested, so it can't be applied
+                if !applied {
+                    let request = LspRequest::SemanticTokens(uri);
+                    self.send_request(request, PendingRequest::SemanticTokens);
+                }
+            }
+            (PendingRequest::Hover(position), LspResponse::Hover(uri, _, contents)) => {
+                let textbox = self.text_box.as_mut()?;
+                if textbox.buffer().document().uri() != &uri {
+                    return None;
+                }
+
+                let blocks = contents.map(markdown::from_hover).unwrap_or_default();
+                if !blocks.is_empty() {
+                    textbox.set_hover(position, blocks);
+                } else if textbox.is_showing_hover() {
+                    textbox.clear_floating_elements();
+                }
+            }
+            (PendingRequest::Completion, LspResponse::Completion(uri, items, is_incomplete)) => {
+                if self.text_box.as_ref()?.buffer().document().uri() != &uri {
+                    return None;
+                }
+                self.show_completion(items, is_incomplete);
+            }
+            (
+                PendingRequest::ResolveCompletion { accept },
+                LspResponse::ResolvedCompletion(item),
+            ) => {
+                let popup = self.text_box.as_mut()?.completion_mut()?;
+                popup.update(|completion| completion.resolve(item));
+                if accept {
+                    self.accept_completion();
+                }
+            }
+            (PendingRequest::SignatureHelp(cursor), LspResponse::SignatureHelp(uri, help)) => {
+                let call = self.enclosing_call();
+                let textbox = self.text_box.as_mut()?;
+                if textbox.buffer().document().uri() != &uri {
+                    return None;
+                }
+                // Nothing to show, or the cursor left the call while the server was busy
+                let signatures = help
+                    .zip(call)
+                    .map(|(help, call)| Signatures::new(help, call, cursor))
+                    .filter(|signatures| !signatures.is_empty());
+                textbox.set_signature_help(signatures.map(SignaturePopup::new));
+            }
+            (PendingRequest::Goto, LspResponse::Locations(kind, locations)) => {
+                match locations.as_slice() {
+                    [] => info!(?kind, "Nothing to go to"),
+                    [location] => {
+                        self.navigate(Navigation::Location(location.clone()), tasks);
+                    }
+                    _ => self.pick_location(locations),
+                }
+            }
+            (PendingRequest::References, LspResponse::References(locations)) => {
+                if locations.is_empty() {
+                    info!("No references found");
+                    return None;
+                }
+                let references =
+                    References::new(locations, |location| self.location_line(location));
+                self.references = Some(references);
+                self.refresh_reference_highlights();
+            }
+            (PendingRequest::Rename, LspResponse::PrepareRename(uri, position, response)) => {
+                let document = self.text_box.as_ref()?.buffer().document();
+                if document.uri() != &uri {
+                    return None;
+                }
+                let (start, name) = match response {
+                    None => {
+                        info!("Nothing to rename");
+                        return None;
+                    }
+                    Some(PrepareRenameResponse::Range(range)) => {
+                        let start = document.utf16_to_position(&range.start.into());
+                        let end = document.utf16_to_position(&range.end.into());
+                        // A name is on a single line
+                        let line = document.get_line(start.line())?;
+                        let name = line
+                            .chars()
+                            .skip(start.character())
+                            .take(end.character().saturating_sub(start.character()))
+                            .collect();
+                        (start, name)
+                    }
+                    Some(PrepareRenameResponse::RangeWithPlaceholder { range, placeholder }) => {
+                        (document.utf16_to_position(&range.start.into()), placeholder)
+                    }
+                    Some(PrepareRenameResponse::DefaultBehavior { .. }) => {
+                        let position = document.utf16_to_position(&position.into());
+                        self.word_at(position)?
+                    }
+                };
+                self.show_rename(start, name, tasks);
+            }
+            (PendingRequest::Rename, LspResponse::Rename(edit)) => {
+                let changes = edit.map(workspace_edit::file_changes).unwrap_or_default();
+                if changes.is_empty() {
+                    info!("Nothing to rename");
+                    return None;
+                }
+                let preview = EditPreview::new(changes, |uri| self.file_text(uri));
+                self.modal = Some(Modal::EditPreview(preview));
+                self.text_box.as_mut()?.set_focus(false);
+            }
+            (PendingRequest::CodeActions { invoked }, LspResponse::CodeActions(actions)) => {
+                let cursor = self.text_box.as_ref()?.buffer().cursor.0;
+                self.lightbulb = (!actions.is_empty()).then_some(cursor.line());
+                if invoked && actions.is_empty() {
+                    info!("No code actions");
+                } else if invoked {
+                    self.close_completion();
+                    self.close_signature_help();
+                    let menu = CodeActionMenu::new(CodeActions::new(actions), cursor);
+                    self.text_box.as_mut()?.set_code_actions(Some(menu));
+                }
+            }
+            (PendingRequest::Formatting { version, save }, LspResponse::Formatting(edits)) => {
+                let textbox = self.text_box.as_mut()?;
+                // The edits are for text that's gone
+                if textbox.buffer().document().version() != version {
+                    info!("The document changed while it was formatted");
+                } else if !edits.is_empty() {
+                    textbox.apply_formatting(edits);
+                }
+                if save {
+                    self.save_document();
+                }
+            }
+            (PendingRequest::InlayHints, LspResponse::InlayHints(uri, hints)) => {
+                if self.text_box.as_ref()?.buffer().document().uri() != &uri {
+                    return None;
+                }
+                // The indices of the old hints are of no use anymore
+                self.hovered_hint = None;
+                self.cancel_request(|request| {
+                    matches!(request, PendingRequest::ResolveInlayHint(_))
+                });
+                self.text_box
+                    .as_mut()?
+                    .set_inlay_hints(InlayHints::new(hints));
+            }
+            (PendingRequest::ResolveInlayHint(index), LspResponse::ResolvedInlayHint(hint)) => {
+                let resolved = self
+                    .text_box
+                    .as_mut()?
+                    .inlay_hints_mut()
+                    .resolve(index, hint);
+                if resolved && self.hovered_hint == Some(index) {
+                    self.show_inlay_hint_tooltip(index);
+                }
+            }
+            (request, response) => {
+                warn!(
+                    ?request,
+                    ?response,
+                    "The response doesn't answer the request"
+                );
+            }
+        }
+        Some(())
+    }
+
+    /**
+     * Asks for the inlay hints of the lines around the visible ones, once the text changed or
+     * the view moved past the lines they were asked for.
+     */
+    fn refresh_inlay_hints(&mut self) -> Option<()> {
+        if !self.lsp.as_ref()?.supports_inlay_hints() {
+            return None;
+        }
+        let buffer = self.text_box.as_ref()?.buffer();
+        let window = buffer.window;
+        let document = buffer.document();
+        let version = document.version();
+        if let Some((asked, start, end)) = self.inlay_hints_asked {
+            if asked == version && start <= window.start_line() && window.end_line() <= end {
+                return None;
+            }
+        }
+
+        let lines = window.lines_height().max(INLAY_HINT_LINES);
+        let start = window.start_line().saturating_sub(lines);
+        let end = window.end_line() + lines;
+        let end_position = if end < document.len() {
+            lsp_types::Position::new(end as u32, 0)
+        } else {
+            document
+                .char_to_utf16_position(document.slice_all().len_chars())
+                .into()
+        };
+        let range = lsp_types::Range::new(lsp_types::Position::new(start as u32, 0), end_position);
+        let request = LspRequest::InlayHints(document.uri().clone(), range);
+        self.inlay_hints_asked = Some((version, start, end));
+
+        self.send_request(request, PendingRequest::InlayHints)?;
+        Some(())
+    }
+
+    /**
+     * Shows the tooltip of the inlay hint at `index`, resolving the hint first when the server
+     * left its tooltip out.
+     */
+    fn request_inlay_hint_tooltip(&mut self, index: usize) -> Option<()> {
+        let hint = self.text_box.as_ref()?.inlay_hints().get(index)?;
+        let lsp = self.lsp.as_ref()?;
+        if !inlay_hints::tooltips(hint).is_empty() || !lsp.supports_inlay_hint_resolve() {
+            return self.show_inlay_hint_tooltip(index);
+        }
+
+        let request = LspRequest::ResolveInlayHint(hint.clone());
+        self.send_request(request, PendingRequest::ResolveInlayHint(index))?;
+        Some(())
+    }
+
+    fn show_inlay_hint_tooltip(&mut self, index: usize) -> Option<()> {
+        let textbox = self.text_box.as_mut()?;
+        let hint = textbox.inlay_hints().get(index)?;
+        let blocks: Vec<_> = inlay_hints::tooltips(hint)
+            .into_iter()
+            .flat_map(|tooltip| match tooltip.kind {
+                MarkupKind::Markdown => markdown::parse(&tooltip.value),
+                MarkupKind::PlainText => markdown::plain(&tooltip.value),
+            })
+            .collect();
+        if blocks.is_empty() {
+            return None;
+        }
+        let position = textbox
+            .buffer()
+            .document()
+            .utf16_to_position(&hint.position.into());
+        textbox.set_hover(position, blocks);
+        Some(())
+    }
+
+    /**
+     * Asks the language server where the symbol at a position is defined, declared, etc.
+     */
+    fn request_goto(&mut self, kind: GotoKind, position: Position) -> Option<()> {
+        if !self.lsp.as_ref()?.supports_goto(kind) {
+            return None;
+        }
+        // The server has to know about the text before it's asked about it
+        self.sync_document();
+        let document = self.text_box.as_ref()?.buffer().document();
+        let request = LspRequest::Goto(
+            kind,
+            document.uri().clone(),
+            document.position_to_utf16(&position).into(),
+        );
+
+        self.send_request(request, PendingRequest::Goto)?;
+        Some(())
+    }
+
+    /**
+     * Lets the user pick one of several locations, the lines they point at are read from the
+     * open document when they're in it.
+     */
+    fn pick_location(&mut self, locations: Vec<Location>) {
+        let items = locations
+            .into_iter()
+            .map(|location| {
+                let line = self.location_line(&location);
+                LocationItem::new(location, line)
+            })
+            .collect();
+        self.file_filter.clear();
+        self.modal = Some(Modal::Locations(items));
+        if let Some(textbox) = self.text_box.as_mut() {
+            textbox.set_focus(false);
+        }
+    }
+
+    /**
+     * The line a location starts on, from the open document when it's in it and from its file
+     * otherwise.
+     */
+    fn location_line(&self, location: &Location) -> Option<String> {
+        let document = self.text_box.as_ref()?.buffer().document();
+        if document.uri() != &location.uri {
+            return location_picker::line_on_disk(location);
+        }
+        let line = document.get_line(location.range.start.line as usize)?;
+        Some(line.to_string())
+    }
+
+    /**
+     * Asks the language server for every reference to the symbol at a position.
+     */
+    fn request_references(&mut self, position: Position) -> Option<()> {
+        if !self.lsp.as_ref()?.supports_references() {
+            return None;
+        }
+        self.sync_document();
+        let document = self.text_box.as_ref()?.buffer().document();
+        let request = LspRequest::References(
+            document.uri().clone(),
+            document.position_to_utf16(&position).into(),
+        );
+
+        self.send_request(request, PendingRequest::References)?;
+        Some(())
+    }
+
+    /**
+     * Asks the language server whether the symbol at a position can be renamed, or shows the
+     * input of its new name right away when the server can't tell.
+     */
+    fn request_rename(&mut self, position: Position, tasks: &mut Vec<Task<Message>>) -> Option<()> {
+        let lsp = self.lsp.as_ref()?;
+        if !lsp.supports_rename() {
+            return None;
+        }
+        if !lsp.supports_prepare_rename() {
+            let (start, name) = self.word_at(position)?;
+            return self.show_rename(start, name, tasks);
+        }
+        self.sync_document();
+        let document = self.text_box.as_ref()?.buffer().document();
+        let request = LspRequest::PrepareRename(
+            document.uri().clone(),
+            document.position_to_utf16(&position).into(),
+        );
+
+        self.send_request(request, PendingRequest::Rename)?;
+        Some(())
+    }
+
+    /**
+     * The start and the text of the identifier around a position of the open document.
+     */
+    fn word_at(&self, position: Position) -> Option<(Position, String)> {
+        let document = self.text_box.as_ref()?.buffer().document();
+        let line = document.get_line(position.line())?.to_string();
+        let start = completion::word_start(&line, position.character());
+        let word: String = line
+            .chars()
+            .skip(start)
+            .take_while(|c| completion::is_identifier(*c))
+            .collect();
+        (!word.is_empty()).then(|| (Position::new(position.line(), start), word))
+    }
+
+    /**
+     * Shows the input of the new name below the symbol, which takes the focus from the text.
+     */
+    fn show_rename(
+        &mut self,
+        start: Position,
+        name: String,
+        tasks: &mut Vec<Task<Message>>,
+    ) -> Option<()> {
+        self.close_floating_elements();
+        let textbox = self.text_box.as_mut()?;
+        textbox.set_rename(Some(RenameInput::new(start, name)));
+        textbox.set_focus(false);
+        tasks.push(text_input::focus(rename::input_id()));
+        Some(())
+    }
+
+    /**
+     * Closes the input of the new name and gives the focus back to the text.
+     */
+    fn close_rename(&mut self) -> Option<()> {
+        let textbox = self.text_box.as_mut()?;
+        textbox.rename()?;
+        textbox.set_rename(None);
+        textbox.set_focus(true);
+        Some(())
+    }
+
+    /**
+     * Asks the language server for the edit that renames the symbol to the name that was typed.
+     */
+    fn submit_rename(&mut self) -> Option<()> {
+        let rename = self.text_box.as_ref()?.rename()?.clone();
+        self.close_rename();
+        if rename.name().is_empty() {
+            return None;
+        }
+        self.sync_document();
+        let document = self.text_box.as_ref()?.buffer().document();
+        let request = LspRequest::Rename(
+            document.uri().clone(),
+            document.position_to_utf16(&rename.position()).into(),
+            rename.name().to_owned(),
+        );
+
+        self.send_request(request, PendingRequest::Rename)?;
+        Some(())
+    }
+
+    /**
+     * The text of a file, from the open document when it's the one and from disk otherwise.
+     */
+    fn file_text(&self, uri: &Url) -> Option<String> {
+        let document = self
+            .text_box
+            .as_ref()
+            .map(|textbox| textbox.buffer().document());
+        match document {
+            Some(document) if document.uri() == uri => Some(document.to_string()),
+            _ => fs::read_to_string(uri.to_file_path().ok()?).ok(),
+        }
+    }
+
+    /**
+     * Makes the changes of a workspace edit in order and stops at the first one that fails, as
+     * the language server was told. The edits of the open document go through its buffer, so
+     * they're undone together, while other files are changed on disk. The response tells which
+     * change failed, for a server that asked for the edit.
+     */
+    fn apply_workspace_edit(
+        &mut self,
+        changes: Vec<FileChange>,
+        tasks: &mut Vec<Task<Message>>,
+    ) -> ApplyWorkspaceEditResponse {
+        let mut response = ApplyWorkspaceEditResponse {
+            applied: true,
+            failure_reason: None,
+            failed_change: None,
+        };
+        for (index, change) in changes.into_iter().enumerate() {
+            let open = self
+                .text_box
+                .as_ref()
+                .map(|textbox| textbox.buffer().document().uri().clone());
+            let result = match &change {
+                FileChange::Edit(uri, edits) if open.as_ref() == Some(uri) => {
+                    if let Some(textbox) = self.text_box.as_mut() {
+                        textbox.apply_text_edits(edits.clone(), None);
+                    }
+                    Ok(())
+                }
+                FileChange::Rename {
+                    old_uri, new_uri, ..
+                } if open.as_ref() == Some(old_uri) => {
+                    // The unsaved text moves along with the file, which is opened again
+                    if !self.is_saved() {
+                        self.save_document();
+                    }
+                    workspace_edit::apply_on_disk(&change).and_then(|()| {
+                        // The file was renamed, but the editor is left without it
+                        self.open_uri(new_uri, tasks).ok_or_else(|| {
+                            io::Error::other(format!("Couldn't open {new_uri} once renamed"))
+                        })
+                    })
+                }
+                FileChange::Delete { uri, .. } if open.as_ref() == Some(uri) => {
+                    let result = workspace_edit::apply_on_disk(&change);
+                    if result.is_ok() {
+                        self.close_document();
+                    }
+                    result
+                }
+                _ => workspace_edit::apply_on_disk(&change),
+            };
+            if let Err(error) = result {
+                warn!(?change, %error, "Couldn't apply the workspace edit");
+                response = ApplyWorkspaceEditResponse {
+                    applied: false,
+                    failure_reason: Some(error.to_string()),
+                    failed_change: u32::try_from(index).ok(),
+                };
+                break;
+            }
+        }
+        if let Some(textbox) = self.text_box.as_mut() {
+            textbox.set_focus(true);
+        }
+        response
+    }
+
+    /**
+     * Saves the document, formatting it first when its language formats on save. Saving waits
+     * for the formatting until [`FORMAT_ON_SAVE_TIMEOUT`].
+     */
+    fn save(&mut self, tasks: &mut Vec<Task<Message>>) -> Option<()> {
+        let language = self.text_box.as_ref()?.buffer().document().language();
+        if language.is_some_and(|language| language.format_on_save)
+            && self.request_formatting(true, tasks).is_some()
+        {
+            return Some(());
+        }
+        self.save_document()
+    }
+
+    /**
+     * Writes the document to its file and tells the language server.
+     */
+    fn save_document(&mut self) -> Option<()> {
+        // The server gets the changes before it's told they're saved
+        self.sync_document();
+        let textbox = self.text_box.as_mut()?;
+        textbox.save();
+        let file_path = textbox.buffer().document().uri().clone();
+        self.lsp.as_mut()?.send(LspCommand::Notification(
+            LspServerNotification::Synchronise(Synchronise::DidSave(None), file_path),
+        ));
+        Some(())
+    }
+
+    /**
+     * Asks the language server to format the selection, or the whole document. `save` saves the
+     * document once it's formatted, or once the request fails or takes too long.
+     */
+    fn request_formatting(&mut self, save: bool, tasks: &mut Vec<Task<Message>>) -> Option<()> {
+        let lsp = self.lsp.as_ref()?;
+        let buffer = self.text_box.as_ref()?.buffer();
+        let document = buffer.document();
+        let request = if !save && !buffer.selection.is_empty() && lsp.supports_range_formatting() {
+            let selection = buffer.selection.correct_position();
+            let range = lsp_types::Range::new(
+                document.position_to_utf16(selection.start()).into(),
+                document.position_to_utf16(selection.end()).into(),
+            );
+            LspRequest::RangeFormatting(document.uri().clone(), range, formatting::options())
+        } else if lsp.supports_formatting() {
+            LspRequest::Formatting(document.uri().clone(), formatting::options())
+        } else {
+            return None;
+        };
+        self.sync_document();
+        let id = self.send_formatting(request, save)?;
+        if save {
+            tasks.push(Task::perform(
+                tokio::time::sleep(FORMAT_ON_SAVE_TIMEOUT),
+                move |_| Message::FormatTimeout(id),
+            ));
+        }
+        Some(())
+    }
+
+    /**
+     * Asks the language server to format the code around the cursor when the character it just
+     * typed is one of its triggers.
+     */
+    fn format_after_typing(&mut self, character: char) -> Option<()> {
+        let typed = character.to_string();
+        if !self.lsp.as_ref()?.formats_on_type(&typed) {
+            return None;
+        }
+        // Formatting for a save goes on, as the document is formatted anyway
+        if self
+            .requests
+            .contains(|request| matches!(request, PendingRequest::Formatting { save: true, .. }))
+        {
+            return None;
+        }
+        self.sync_document();
+        let buffer = self.text_box.as_ref()?.buffer();
+        let document = buffer.document();
+        let request = LspRequest::OnTypeFormatting(
+            document.uri().clone(),
+            document.position_to_utf16(&buffer.cursor.0).into(),
+            typed,
+            formatting::options(),
+        );
+        self.send_formatting(request, false)?;
+        Some(())
+    }
+
+    /**
+     * Sends a formatting request in place of the one that's waiting for a response.
+     */
+    fn send_formatting(&mut self, request: LspRequest, save: bool) -> Option<RequestId> {
+        let version = self.text_box.as_ref()?.buffer().document().version();
+        self.send_request(request, PendingRequest::Formatting { version, save })
+    }
+
+    /**
+     * Answers a request of the language server that needs the editor, like an edit to make or a
+     * message whose action the user picks.
+     */
+    fn process_server_request(
+        &mut self,
+        request: LspServerRequest,
+        tasks: &mut Vec<Task<Message>>,
+    ) -> Option<()> {
+        match request {
+            LspServerRequest::ApplyEdit {
+                label,
+                edit,
+                responder,
+            } => {
+                info!(?label, "Applying the edit of the language server");
+                let response = self.apply_workspace_edit(workspace_edit::file_changes(edit), tasks);
+                // The server hears of the changes to the open document before the answer
+                self.sync_document();
+                responder.respond(response);
+            }
+            LspServerRequest::ShowMessage {
+                typ,
+                message,
+                actions,
+                responder,
+            } => {
+                self.show_message(MessageRequest::new(typ, message, actions, responder));
+            }
+        }
+        Some(())
+    }
+
+    /**
+     * Shows a message in place of the modal that's open.
+     */
+    fn show_message(&mut self, request: MessageRequest) {
+        // Another message that's shown is dismissed, which answers it with no action
+        self.close_floating_elements();
+        self.modal = Some(Modal::MessageRequest(request));
+        if let Some(textbox) = self.text_box.as_mut() {
+            textbox.set_focus(false);
+        }
+    }
+
+    /**
+     * Closes the open document, e.g. once its file was deleted.
+     */
+    fn close_document(&mut self) -> Option<()> {
+        let textbox = self.text_box.take()?;
+        let uri = textbox.buffer().document().uri().clone();
+        self.lsp.as_mut()?.send(LspCommand::Notification(
+            LspServerNotification::Synchronise(Synchronise::DidClose, uri),
+        ));
+        Some(())
+    }
+
+    /**
+     * Asks the language server for the code actions of the selection, or of the cursor, along
+     * with the diagnostics on its lines. `invoked` opens the menu with them, otherwise they only
+     * show the lightbulb.
+     */
+    fn request_code_actions(&mut self, invoked: bool) -> Option<()> {
+        if !self.lsp.as_ref()?.supports_code_actions() {
+            return None;
+        }
+        self.sync_document();
+        let buffer = self.text_box.as_ref()?.buffer();
+        let cursor = buffer.cursor.0;
+        let (start, end) = if buffer.selection.is_empty() {
+            (cursor, cursor)
+        } else {
+            let selection = buffer.selection.correct_position();
+            (*selection.start(), *selection.end())
+        };
+        let document = buffer.document();
+        let range = lsp_types::Range::new(
+            document.position_to_utf16(&start).into(),
+            document.position_to_utf16(&end).into(),
+        );
+        let diagnostics = buffer.diagnostics_on_lines(start.line(), end.line());
+        let request = LspRequest::CodeActions(document.uri().clone(), range, diagnostics, invoked);
+        self.code_actions_asked = Some((cursor.line(), document.version()));
+
+        self.send_request(request, PendingRequest::CodeActions { invoked })?;
+        Some(())
+    }
+
+    /**
+     * Asks for the code actions at the cursor once it moved to another line or the text
+     * changed, to show the lightbulb when there are some.
+     */
+    fn refresh_lightbulb(&mut self) -> Option<()> {
+        let buffer = self.text_box.as_ref()?.buffer();
+        let asked = (buffer.cursor.0.line(), buffer.document().version());
+        if self.code_actions_asked == Some(asked) {
+            return None;
+        }
+        self.lightbulb = None;
+        self.request_code_actions(false)
+    }
+
+    /**
+     * Makes the changes of the action that's selected in the menu, then runs its command when
+     * the server can.
+     */
+    fn apply_code_action(&mut self, tasks: &mut Vec<Task<Message>>) -> Option<()> {
+        let action = self
+            .text_box
+            .as_mut()?
+            .take_code_actions()?
+            .take_selected()?;
+        let (edit, command) = match action {
+            CodeActionOrCommand::Command(command) => (None, Some(command)),
+            CodeActionOrCommand::CodeAction(action) => (action.edit, action.command),
+        };
+        if let Some(edit) = edit {
+            self.apply_workspace_edit(workspace_edit::file_changes(edit), tasks);
+        }
+
+        let command = command?;
+        // The command works on the text after the edit
+        self.sync_document();
+        let lsp = self.lsp.as_mut()?;
+        if !lsp.executes_command(&command.command) {
+            warn!(command = %command.command, "The server doesn't run the command");
+            return None;
+        }
+        lsp.request(LspRequest::ExecuteCommand(command));
+        Some(())
+    }
+
+    /**
+     * Highlights the references of the panel that are in the open document.
+     */
+    fn refresh_reference_highlights(&mut self) -> Option<()> {
+        let textbox = self.text_box.as_mut()?;
+        let document = textbox.buffer().document();
+        let ranges = self
+            .references
+            .as_ref()
+            .map_or_else(Vec::new, |references| {
+                references
+                    .in_file(document.uri())
+                    .map(|range| {
+                        Range::new(
+                            document.utf16_to_position(&range.start.into()),
+                            document.utf16_to_position(&range.end.into()),
+                        )
+                    })
+                    .collect()
+            });
+        textbox.set_references(ranges);
+        Some(())
+    }
+
+    /**
+     * Goes to the reference at `index` of the panel, or to the next or previous one.
+     */
+    fn open_reference(
+        &mut self,
+        step: impl FnOnce(&mut References) -> Option<&Location>,
+        tasks: &mut Vec<Task<Message>>,
+    ) -> Option<()> {
+        let location = step(self.references.as_mut()?)?.clone();
+        self.navigate(Navigation::Location(location), tasks)
+    }
+
+    /**
+     * Where the cursor is, to come back to after a jump.
+     */
+    fn current_jump(&self) -> Option<Jump> {
+        let buffer = self.text_box.as_ref()?.buffer();
+        Some(Jump {
+            uri: buffer.document().uri().clone(),
+            position: buffer.cursor.0,
+        })
+    }
+
+    /**
+     * Goes to a location of the language server, remembering where the cursor was.
+     */
+    fn open_location(&mut self, location: &Location, tasks: &mut Vec<Task<Message>>) -> Option<()> {
+        let from = self.current_jump();
+        self.open_uri(&location.uri, tasks)?;
+        if let Some(from) = from {
+            self.jumps.push(from);
+        }
+        let textbox = self.text_box.as_mut()?;
+        let position = textbox
+            .buffer()
+            .document()
+            .utf16_to_position(&location.range.start.into());
+        textbox.jump_to(position);
+        Some(())
+    }
+
+    /**
+     * Shows the diagnostics of the open document that the server published last.
+     */
+    fn show_diagnostics(&mut self) -> Option<()> {
+        let textbox = self.text_box.as_mut()?;
+        let diagnostics = self.diagnostics.get(textbox.buffer().document().uri());
+        textbox.set_diagnostics(diagnostics);
+        Some(())
+    }
+
+    /**
+     * Goes to the next problem after the cursor, or the previous one before it, in whichever
+     * file it is.
+     */
+    fn open_diagnostic(&mut self, forward: bool, tasks: &mut Vec<Task<Message>>) -> Option<()> {
+        let buffer = self.text_box.as_ref()?.buffer();
+        let document = buffer.document();
+        let position = document.position_to_utf16(&buffer.cursor.0).into();
+        let location = self.diagnostics.next(document.uri(), position, forward)?;
+        self.navigate(Navigation::Location(location), tasks)
+    }
+
+    /**
+     * Goes where the editor was asked to, once the user chose to save or discard the changes of
+     * the open document when it's left for another file.
+     */
+    fn navigate(&mut self, navigation: Navigation, tasks: &mut Vec<Task<Message>>) -> Option<()> {
+        let target = match &navigation {
+            Navigation::File(file) => Url::from_file_path(file).ok(),
+            Navigation::Location(location) => Some(location.uri.clone()),
+            Navigation::Jump { forward } => Some(self.jumps.peek(*forward)?.uri.clone()),
+        };
+        let textbox = self.text_box.as_ref().filter(|textbox| {
+            let uri = textbox.buffer().document().uri();
+            !textbox.is_saved() && target.as_ref() != Some(uri)
+        });
+        if let Some(textbox) = textbox {
+            let filename = textbox.buffer().document().filename().to_owned();
+            self.close_floating_elements();
+            self.modal = Some(Modal::UnsavedChanges(UnsavedChanges {
+                filename,
+                navigation,
+            }));
+            self.text_box.as_mut()?.set_focus(false);
+            return None;
+        }
+        self.go_to(navigation, tasks)
+    }
+
+    fn go_to(&mut self, navigation: Navigation, tasks: &mut Vec<Task<Message>>) -> Option<()> {
+        match navigation {
+            Navigation::File(file) => self.change_file(file, tasks).then_some(()),
+            Navigation::Location(location) => self.open_location(&location, tasks),
+            Navigation::Jump { forward } => self.go_back(forward, tasks),
+        }
+    }
+
+    /**
+     * Goes back to where the cursor jumped from, or forward again to where it jumped to.
+     */
+    fn go_back(&mut self, forward: bool, tasks: &mut Vec<Task<Message>>) -> Option<()> {
+        let current = self.current_jump()?;
+        let jump = self.jumps.peek(forward)?.clone();
+        // The history only moves once the file is open
+        self.open_uri(&jump.uri, tasks)?;
+        if forward {
+            self.jumps.forward(current);
+        } else {
+            self.jumps.back(current);
+        }
+        // The document may have changed since the jump
+        let textbox = self.text_box.as_mut()?;
+        let position = textbox.buffer().document().clamp_position(&jump.position);
+        textbox.jump_to(position);
+        Some(())
+    }
+
+    /**
+     * Opens the document at `uri` unless it's already the open one, which keeps its state.
+     */
+    fn open_uri(&mut self, uri: &Url, tasks: &mut Vec<Task<Message>>) -> Option<()> {
+        let textbox = self.text_box.as_ref();
+        if textbox.is_some_and(|textbox| textbox.buffer().document().uri() == uri) {
+            return Some(());
+        }
+        let path = uri.to_file_path().ok()?;
+        self.change_file(path.to_str()?.to_owned(), tasks)
+            .then_some(())
+    }
+
+    /**
+     * Asks for the signatures of the call at the cursor after a trigger character of the server
+     * was typed, or a retrigger character while they're shown.
+     */
+    fn signature_help_after_typing(&mut self, character: char) -> Option<()> {
+        let typed = character.to_string();
+        let lsp = self.lsp.as_ref()?;
+        let is_showing = self.text_box.as_ref()?.signature_help().is_some();
+        let triggers = lsp.signature_help_trigger_characters()?;
+        if triggers.contains(&typed)
+            || (is_showing && lsp.signature_help_retrigger_characters().contains(&typed))
+        {
+            return self.request_signature_help(Some(typed));
+        }
+        Some(())
+    }
+
+    /**
+     * Asks the language server for the signatures of the call at the cursor, telling it about
+     * the ones that are shown so it keeps the overload that was picked.
+     */
+    fn request_signature_help(&mut self, trigger: Option<String>) -> Option<()> {
+        self.lsp.as_ref()?.signature_help_trigger_characters()?;
+        // Typed outside of a call, e.g. a `,` between the elements of a list
+        if self.enclosing_call().is_none() {
+            self.close_signature_help();
+            return None;
+        }
+        // The server has to know about the text before it's asked about it
+        self.sync_document();
+        let textbox = self.text_box.as_ref()?;
+        let buffer = textbox.buffer();
+        let cursor = buffer.cursor.0;
+        let request = LspRequest::SignatureHelp(
+            buffer.document().uri().clone(),
+            buffer.document().position_to_utf16(&cursor).into(),
+            trigger,
+            textbox
+                .signature_help()
+                .map(|popup| popup.signatures().help()),
+        );
+
+        self.send_request(request, PendingRequest::SignatureHelp(cursor))?;
+        Some(())
+    }
+
+    /**
+     * Where the `(` of the call the cursor is in is, looking back at most [`CALL_LINES`] lines.
+     */
+    fn enclosing_call(&self) -> Option<Position> {
+        let buffer = self.text_box.as_ref()?.buffer();
+        let document = buffer.document();
+        let cursor = document.get_character_pos(&buffer.cursor.0);
+        let first_line = buffer.cursor.0.line().saturating_sub(CALL_LINES);
+        let start = document.get_character_pos(&Position::new(first_line, 0));
+        let text = document.str_from_range(start, cursor).to_string();
+        let call = signature_help::enclosing_call(&text)?;
+        Some(document.char_to_position(start + text[..call].chars().count()))
+    }
+
+    /**
+     * Closes the signatures once the cursor left their call, and asks for them again when it
+     * moved within it, as another parameter may be active.
+     */
+    fn refresh_signature_help(&mut self) -> Option<()> {
+        // The response decides what's shown
+        if self
+            .requests
+            .contains(|request| matches!(request, PendingRequest::SignatureHelp(_)))
+        {
+            return Some(());
+        }
+        let textbox = self.text_box.as_ref()?;
+        let signatures = textbox.signature_help()?.signatures();
+        let (call, requested) = (signatures.call(), signatures.cursor());
+        let cursor = textbox.buffer().cursor.0;
+        if self.enclosing_call() != Some(call) {
+            self.close_signature_help();
+            return None;
+        }
+        if cursor != requested {
+            return self.request_signature_help(None);
+        }
+        Some(())
+    }
+
+    fn close_signature_help(&mut self) {
+        if let Some(textbox) = self.text_box.as_mut() {
+            textbox.set_signature_help(None);
+        }
+        self.cancel_request(|request| matches!(request, PendingRequest::SignatureHelp(_)));
     }
 
-    pub fn update(&mut self, message: Message) -> Task<Message> {
-        match self.process_event(message) {
-            Some(tasks) => Task::batch(tasks),
-            None => Task::none(),
+    /**
+     * Shows the completions of the language server along with the user's snippets.
+     */
+    fn show_completion(&mut self, items: Vec<CompletionItem>, is_incomplete: bool) -> Option<()> {
+        let textbox = self.text_box.as_mut()?;
+        // Whatever was typed while the server was busy filters the items right away
+        let cursor = textbox.buffer().cursor.0;
+        let line = textbox
+            .buffer()
+            .document()
+            .get_line(cursor.line())?
+            .to_string();
+        let start = completion::word_start(&line, cursor.character());
+        let query = completion::typed_word(&line, start, cursor.character())?;
+        let start = Position::new(cursor.line(), start);
+        let snippets = self.snippets.iter().map(UserSnippet::completion_item);
+        let mut completion =
+            Completion::new(items, is_incomplete, start).with_local(snippets.collect());
+        completion.filter(&query);
+
+        if completion.is_empty() {
+            textbox.set_completion(None);
+            return None;
         }
+        textbox.set_completion(Some(CompletionPopup::new(completion)));
+        self.resolve_completion(false)
     }
 
-    pub fn view(&self) -> Element<Message> {
-        if let Some(text_box) = &self.text_box {
-            return self.text_box_view(text_box);
+    /**
+     * Asks for completions after a character was typed. A trigger character of the server always
+     * asks again, while an identifier only asks when there's nothing to filter yet.
+     */
+    fn complete_after_typing(&mut self, character: char) -> Option<()> {
+        let typed = character.to_string();
+        let triggers = self.lsp.as_ref()?.completion_trigger_characters()?;
+        if triggers.contains(&typed) {
+            self.close_completion();
+            return self.request_completion(Some(typed));
         }
-        self.no_workspace_view()
+
+        let is_completing = self.text_box.as_ref()?.completion().is_some()
+            || self
+                .requests
+                .contains(|request| *request == PendingRequest::Completion);
+        if completion::is_identifier(character) && !is_completing {
+            return self.request_completion(None);
+        }
+        Some(())
     }
 
-    pub fn subscription(&self) -> Subscription<Message> {
-        // subscription::events()
-        // let app_events = subscription::events_with(|event, _status| match event {
-        // Event::Keyboard(keyboard::Event::KeyPressed { modifiers, .. }) => {
-        //     Message::from_keycode(key_code, Modifiers::from(modifiers))
-        // }
-        // Event::Keyboard(keyboard::Event::KeyReleased { modifiers, .. }) => {
-        //     Message::from_keyrelease(Modifiers::from(modifiers))
-        // }
-        // Event::Keyboard(keyboard::Event::CharacterReceived(character)) => {
-        //     if !character.is_control() {
-        //         Some(Message::KeyEvent(KeyEvent::CharacterReceived(character)))
-        //     } else {
-        //         None
-        //     }
-        // }
-        // Event::Window(window::Event::Resized(_)) => None,
-        //     _ => None,
-        // });
-        // let mut events: Vec<Subscription<Message>> = vec![]; // app_events
-        // if let Some(lsp) = self.lsp.as_ref() {
-        //     let lsp_events = Subscription::run(connect::connect).map(|event| match event {
-        //         connect::Event::Response(value) => {
-        //             if let LspResponse::Initialized = value {
-        //                 return Message::ClientStart(());
-        //             }
-        //             Message::LspMessage(value)
-        //         }
-        //         connect::Event::Ready(mut sender) => {
-        //             sender.send(connect::Event::Connect(lsp.new_receiver().clone()));
-        //             Message::None
-        //         }
-        //         connect::Event::Disconnected => Message::CloseClient,
-        //         _ => Message::None,
-        //     });
-        //     events.push(lsp_events)
-        // }
-        Subscription::batch(vec![
-            Subscription::run(laurel_lsp::connect).map(Message::LspMessage)
-        ])
+    /**
+     * Asks the language server for the completions at the cursor, along with the trigger
+     * character that was just typed.
+     */
+    fn request_completion(&mut self, trigger: Option<String>) -> Option<()> {
+        self.lsp.as_ref()?.completion_trigger_characters()?;
+        // The server has to know about the text before it's asked about it
+        self.sync_document();
+        let buffer = self.text_box.as_ref()?.buffer();
+        let document = buffer.document();
+        let request = LspRequest::Completion(
+            document.uri().clone(),
+            document.position_to_utf16(&buffer.cursor.0).into(),
+            trigger,
+        );
+
+        self.send_request(request, PendingRequest::Completion)?;
+        Some(())
     }
 
-    pub fn theme(&self) -> LaurelTheme {
-        self.theme.clone()
+    /**
+     * Filters the completions by what was typed since they arrived, closing them once the cursor
+     * leaves the word they complete. The server is asked again when it said the completions
+     * would change.
+     */
+    fn refresh_completion(&mut self) -> Option<()> {
+        let textbox = self.text_box.as_mut()?;
+        let start = textbox.completion()?.completion().start();
+        let cursor = textbox.buffer().cursor.0;
+        let query = textbox
+            .buffer()
+            .document()
+            .get_line(cursor.line())
+            .filter(|_| cursor.line() == start.line())
+            .and_then(|line| {
+                completion::typed_word(&line.to_string(), start.character(), cursor.character())
+            });
+        let Some(query) = query else {
+            self.close_completion();
+            return None;
+        };
+
+        let popup = textbox.completion_mut()?;
+        if popup.completion().query() == query {
+            return Some(());
+        }
+        popup.update(|completion| completion.filter(&query));
+        if popup.completion().is_incomplete() {
+            return self.request_completion(None);
+        }
+        if popup.completion().is_empty() {
+            self.close_completion();
+            return None;
+        }
+        self.resolve_completion(false)
     }
-}
 
-impl Editor {
-    fn open(&mut self, file: &str, old_file: Option<Url>, tasks: &mut Vec<Task<Message>>) {
-        info!("Opening file {}", file);
+    /**
+     * Asks the language server for the details of the selected completion, like its
+     * documentation, and inserts it once they arrive when `accept` is set.
+     */
+    fn resolve_completion(&mut self, accept: bool) -> Option<()> {
+        let lsp = self.lsp.as_ref()?;
+        if !lsp.supports_completion_resolve() {
+            return None;
+        }
+        let item = self
+            .text_box
+            .as_ref()?
+            .completion()?
+            .completion()
+            .unresolved()?;
+        let request = LspRequest::ResolveCompletion(item.clone());
 
-        let document = Document::open(file).expect("Couldn't open file");
-        self.set_file(document, old_file);
+        self.send_request(request, PendingRequest::ResolveCompletion { accept })?;
+        Some(())
     }
 
-    fn set_file(&mut self, document: Document, old_file: Option<Url>) {
-        let document_string = document.to_string();
-        let highlighter_config = HighlighterConfig::rust_config(&document_string);
+    fn select_completion(&mut self, select: impl FnOnce(&mut Completion)) -> Option<()> {
+        self.text_box.as_mut()?.completion_mut()?.update(select);
+        self.resolve_completion(false)
+    }
+
+    /**
+     * Inserts the selected completion with its additional edits, like imports. Its details are
+     * resolved first when the server left them out.
+     */
+    fn accept_completion(&mut self) -> Option<()> {
+        let supports_resolve = self
+            .lsp
+            .as_ref()
+            .is_some_and(LspConnection::supports_completion_resolve);
+        let completion = self.text_box.as_ref()?.completion()?.completion();
+        if supports_resolve && completion.unresolved().is_some() {
+            return self.resolve_completion(true);
+        }
+        let item = completion.selected()?.clone();
+        let start = completion.start();
+        self.close_completion();
 
-        if let Some(ref mut lsp_connection) = self.lsp {
-            let mut lsp_connection = lsp_connection.clone();
+        let textbox = self.text_box.as_mut()?;
+        let buffer = textbox.buffer();
+        let mut edits = completion::text_edits(
+            &item,
+            buffer.document().position_to_utf16(&start).into(),
+            buffer.document().position_to_utf16(&buffer.cursor.0).into(),
+        );
+        // Snippets are expanded before they're inserted, then their tab stops are filled in
+        let snippet = (item.insert_text_format == Some(InsertTextFormat::SNIPPET)).then(|| {
+            let snippet = buffer.expand_snippet(&edits[0].new_text);
+            edits[0].new_text.clone_from(&snippet.text);
+            snippet
+        });
+        // The cursor ends up after the completion itself rather than after the imports
+        textbox.apply_text_edits(edits, Some(0));
+        if let Some(snippet) = snippet {
+            textbox.start_snippet(snippet);
+            self.show_snippet_choices();
+        }
+        Some(())
+    }
 
-            let file_uri = document.uri().clone();
+    /**
+     * Moves to the next or previous tab stop of the snippet that's being filled in.
+     */
+    fn move_tab_stop(&mut self, forward: bool) -> Option<()> {
+        self.close_completion();
+        if self.text_box.as_mut()?.move_tab_stop(forward) {
+            self.show_snippet_choices();
+        }
+        Some(())
+    }
 
-            if let Some(old_file_uri) = old_file {
-                lsp_connection.send(LspCommand::Notification(
-                    LspServerNotification::Synchronise(Synchronise::DidClose, old_file_uri),
-                ));
+    /**
+     * Offers the choices of the current tab stop of the snippet as completions, which replace
+     * its placeholder.
+     */
+    fn show_snippet_choices(&mut self) -> Option<()> {
+        let textbox = self.text_box.as_mut()?;
+        let buffer = textbox.buffer();
+        let tab_stop = buffer.snippet()?.current();
+        let range = tab_stop.ranges.first()?;
+        let document = buffer.document();
+        let start = document.byte_to_position(range.start);
+        let end = document.byte_to_position(range.end);
+        let placeholder = lsp_types::Range::new(
+            document.position_to_utf16(&start).into(),
+            document.position_to_utf16(&end).into(),
+        );
 
-                lsp_connection.send(LspCommand::Notification(
-                    LspServerNotification::Synchronise(
-                        Synchronise::DidOpen(document_string),
-                        file_uri,
-                    ),
-                ));
-            } else {
-                lsp_connection.send(LspCommand::Notification(
-                    LspServerNotification::Synchronise(
-                        Synchronise::DidOpen(document_string),
-                        file_uri,
-                    ),
-                ));
-            }
+        let choices: Vec<CompletionItem> = tab_stop
+            .choices
+            .iter()
+            .map(|choice| CompletionItem {
+                label: choice.clone(),
+                kind: Some(CompletionItemKind::VALUE),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(
+                    placeholder,
+                    choice.clone(),
+                ))),
+                ..CompletionItem::default()
+            })
+            .collect();
+        if choices.is_empty() {
+            return None;
         }
+        // Anchored at the end of the placeholder, so every choice shows until something is typed
+        let completion = Completion::local(choices, end);
+        textbox.set_completion(Some(CompletionPopup::new(completion)));
+        Some(())
+    }
 
-        let buffer = Buffer::new(document, highlighter_config);
-        self.text_box = Some(Textbox::new(buffer).font(Font::MONOSPACE).font_size(14.0));
+    /**
+     * Closes the completions, along with the requests for them.
+     */
+    fn close_completion(&mut self) {
+        if let Some(textbox) = self.text_box.as_mut() {
+            textbox.set_completion(None);
+        }
+        self.cancel_request(|request| *request == PendingRequest::Completion);
+        self.cancel_request(|request| matches!(request, PendingRequest::ResolveCompletion { .. }));
     }
 
     fn process_event(&mut self, message: Message) -> Option<Vec<Task<Message>>> {
@@ -327,7 +1935,16 @@ impl Editor {
             Message::KeyEvent(event) => {
                 self.process_keyboard_event(event, &mut tasks);
             }
+            Message::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers;
+            }
+            // Ctrl+click goes to the definition of what was clicked
+            Message::CursorEvent(pos) if self.modifiers.ctrl => {
+                self.request_goto(GotoKind::Definition, pos);
+            }
             Message::CursorEvent(pos) => {
+                // Clicking the text cancels a rename
+                self.close_rename();
                 let textbox = self.can_edit_textbox()?.set_selection(pos).set_curor(pos);
                 textbox.set_floating_message();
                 textbox.clear();
@@ -360,7 +1977,7 @@ impl Editor {
                 self.can_edit_textbox()?.insert(value);
             }
             Message::Open(file) => {
-                self.change_file(file, &mut tasks);
+                self.navigate(Navigation::File(file), &mut tasks);
             }
             Message::LspNotification(notification) => {
                 self.can_edit_textbox()?
@@ -374,22 +1991,213 @@ impl Editor {
                 let file = self.open_file(None);
                 if let Some(file) = file {
                     if let Some(file) = file.as_path().to_str() {
-                        self.change_file(file.to_owned(), &mut tasks)
+                        self.navigate(Navigation::File(file.to_owned()), &mut tasks);
                     }
                 }
             }
             Message::FileFilter(filter) => self.file_filter = filter,
             Message::Save => {
-                // let workspace = self.workspace().to_owned().clone();
+                self.save(&mut tasks);
+            }
+            Message::Format => {
+                self.request_formatting(false, &mut tasks);
+            }
+            Message::FormatTimeout(id) => {
+                let Some(PendingRequest::Formatting { save: true, .. }) = self.requests.get(id)
+                else {
+                    return None;
+                };
+                warn!("Formatting took too long, saving without it");
+                self.requests.take(id);
+                self.lsp.as_mut()?.cancel(id);
+                self.save_document();
+            }
+            Message::RestartServer => {
+                let server = self.server.clone()?;
+                self.lsp = None;
+                self.servers.restart(&server);
+            }
+            Message::ReloadQueries => {
                 if let Some(textbox) = self.text_box.as_mut() {
-                    textbox.save();
-                    let file_path = textbox.buffer().document().uri().clone();
-                    let mut lsp = self.lsp.clone()?;
-
-                    lsp.send(LspCommand::Notification(
-                        LspServerNotification::Synchronise(Synchronise::DidSave(None), file_path),
-                    ));
+                    textbox.reload_queries();
+                    let language = textbox.buffer().document().language();
+                    self.snippets =
+                        snippets::for_language(language.map_or("plaintext", |l| l.name));
+                    // The highlighter was rebuilt without the semantic tokens
+                    self.request_semantic_tokens();
+                }
+            }
+            Message::NextTheme => {
+                if let Some(theme) = theme::next(&self.theme.name) {
+                    self.theme = theme;
+                    // The text is drawn with the theme's colours, so it has to be drawn again
+                    if let Some(textbox) = self.text_box.as_ref() {
+                        textbox.clear();
+                    }
+                }
+            }
+            Message::Hover => {
+                let cursor = self.text_box.as_ref()?.buffer().cursor.0;
+                self.request_hover(cursor);
+            }
+            Message::MouseHover(position) => {
+                self.mouse_position = position;
+                // The mouse left the text it was asked about
+                self.cancel_request(|request| matches!(request, PendingRequest::Hover(_)));
+                if let Some(position) = position {
+                    tasks.push(Task::perform(tokio::time::sleep(HOVER_DWELL), move |_| {
+                        Message::HoverDwell(position)
+                    }));
+                }
+            }
+            Message::HoverDwell(position) => {
+                // The mouse moved on before the dwell was over
+                if self.mouse_position == Some(position) {
+                    self.request_hover(position);
+                }
+            }
+            Message::InlayHintHover(hint) => {
+                self.hovered_hint = hint;
+                if let Some(index) = hint {
+                    tasks.push(Task::perform(tokio::time::sleep(HOVER_DWELL), move |_| {
+                        Message::InlayHintDwell(index)
+                    }));
+                }
+            }
+            Message::InlayHintDwell(index) => {
+                if self.hovered_hint == Some(index) {
+                    self.request_inlay_hint_tooltip(index);
+                }
+            }
+            Message::Complete => {
+                self.close_completion();
+                // Without a language server there are still the user's snippets
+                if self.request_completion(None).is_none() {
+                    self.show_completion(Vec::new(), false);
+                }
+            }
+            Message::NextTabStop => {
+                self.move_tab_stop(true);
+            }
+            Message::SignatureHelp => {
+                self.request_signature_help(None);
+            }
+            Message::Goto(kind) => {
+                let cursor = self.text_box.as_ref()?.buffer().cursor.0;
+                self.request_goto(kind, cursor);
+            }
+            Message::OpenLocation(location) => {
+                self.modal = None;
+                self.text_box.as_mut()?.set_focus(true);
+                self.navigate(Navigation::Location(location), &mut tasks);
+            }
+            Message::FindReferences => {
+                let cursor = self.text_box.as_ref()?.buffer().cursor.0;
+                self.request_references(cursor);
+            }
+            Message::OpenReference(index) => {
+                self.open_reference(|references| references.select(index), &mut tasks);
+            }
+            Message::NextReference => {
+                self.open_reference(References::next, &mut tasks);
+            }
+            Message::PreviousReference => {
+                self.open_reference(References::previous, &mut tasks);
+            }
+            Message::CloseReferences => {
+                self.references = None;
+                self.refresh_reference_highlights();
+            }
+            Message::ToggleOutput => self.output_open = !self.output_open,
+            Message::OutputFilter(filter) => self.output.set_filter(filter),
+            Message::ToggleOutputKind(kind) => self.output.toggle(kind),
+            Message::ToggleTrace => {
+                let trace = !self.servers.trace();
+                self.servers.set_trace(trace);
+            }
+            Message::CopyOutput => tasks.push(clipboard::write(self.output.copy_text())),
+            Message::CopyOutputEntry(index) => {
+                let entry = self.output.entry(index)?;
+                tasks.push(clipboard::write(entry.text.clone()));
+            }
+            Message::ClearOutput => self.output.clear(),
+            Message::ToggleProblems => self.problems_open = !self.problems_open,
+            Message::DiagnosticFilter(filter) => self.diagnostics.set_filter(filter),
+            Message::ToggleSeverity(severity) => self.diagnostics.toggle(severity),
+            Message::SortDiagnostics(sort) => self.diagnostics.set_sort(sort),
+            Message::NextDiagnostic => {
+                self.open_diagnostic(true, &mut tasks);
+            }
+            Message::PreviousDiagnostic => {
+                self.open_diagnostic(false, &mut tasks);
+            }
+            Message::Rename => {
+                let cursor = self.text_box.as_ref()?.buffer().cursor.0;
+                self.request_rename(cursor, &mut tasks);
+            }
+            Message::RenameInput(name) => {
+                self.text_box.as_mut()?.rename_mut()?.set_name(name);
+            }
+            Message::SubmitRename => {
+                self.submit_rename();
+            }
+            Message::ApplyWorkspaceEdit => {
+                let Some(Modal::EditPreview(preview)) = self.modal.take() else {
+                    return None;
+                };
+                self.apply_workspace_edit(preview.changes, &mut tasks);
+            }
+            Message::CodeActions => {
+                self.request_code_actions(true);
+            }
+            Message::AnswerMessage(index) => {
+                let Some(Modal::MessageRequest(request)) = self.modal.take() else {
+                    return None;
+                };
+                request.answer(index);
+                self.text_box.as_mut()?.set_focus(true);
+            }
+            Message::LeaveUnsaved(save) => {
+                let Some(Modal::UnsavedChanges(prompt)) = self.modal.take() else {
+                    return None;
+                };
+                if save {
+                    self.save_document();
                 }
+                self.text_box.as_mut()?.set_focus(true);
+                self.go_to(prompt.navigation, &mut tasks);
+            }
+            Message::PickCodeAction(index) => {
+                let menu = self.text_box.as_mut()?.code_actions_mut()?;
+                menu.actions_mut().select(index);
+                self.apply_code_action(&mut tasks);
+            }
+            Message::Undo => {
+                self.text_box.as_mut()?.undo(false);
+            }
+            Message::Redo => {
+                self.text_box.as_mut()?.undo(true);
+            }
+            Message::GoBack => {
+                self.navigate(Navigation::Jump { forward: false }, &mut tasks);
+            }
+            Message::GoForward => {
+                self.navigate(Navigation::Jump { forward: true }, &mut tasks);
+            }
+            Message::NextSignature => {
+                let popup = self.text_box.as_mut()?.signature_help_mut()?;
+                popup.update(Signatures::next);
+            }
+            Message::PreviousSignature => {
+                let popup = self.text_box.as_mut()?.signature_help_mut()?;
+                popup.update(Signatures::previous);
+            }
+            Message::AcceptCompletion(index) => {
+                self.text_box
+                    .as_mut()?
+                    .completion_mut()?
+                    .update(|completion| completion.select(index));
+                self.accept_completion();
             }
             Message::SetTextBoxFocus(focus) => {
                 if let Some(textbox) = self.text_box.as_mut() {
@@ -406,24 +2214,89 @@ impl Editor {
             Message::Done(_) => (),
             Message::None => {}
             Message::LspMessage(m) => match m {
+                LspMessage::Ready(handle) => {
+                    self.servers.set_handle(handle);
+                    // A document may have been opened before the servers could be started
+                    self.connect_document();
+                }
+                LspMessage::Starting(server) => {
+                    self.progress.clear_server(&server);
+                    self.output.clear_server(&server);
+                    self.diagnostics.clear_server(&server);
+                    self.show_diagnostics();
+                    self.servers.starting(server);
+                }
                 LspMessage::Initialized(conn) => {
                     debug!(connection = ?conn, "Lsp stream connection initialized");
-                    self.lsp = Some(conn);
+                    let server = conn.server().clone();
+                    self.servers.initialized(conn.clone());
+                    // A restarted server knows nothing about the document, so it's opened again
+                    if self.server.as_ref() == Some(&server) {
+                        self.lsp = Some(conn);
+                        self.inlay_hints_asked = None;
+                        self.open_document();
+                        self.request_semantic_tokens();
+                    }
+                }
+                LspMessage::Stopped(server, e, restart) => {
+                    match restart {
+                        Some(delay) => {
+                            warn!(%server, error = %e, ?delay, "The language server stopped");
+                        }
+                        None => error!(%server, error = %e, "The language server stopped"),
+                    }
+                    if self.server.as_ref() == Some(&server) {
+                        self.lsp = None;
+                    }
+                    self.progress.clear_server(&server);
+                    self.output.clear_server(&server);
+                    self.diagnostics.clear_server(&server);
+                    self.show_diagnostics();
+                    self.servers.stopped(server, e, restart.is_some());
                 }
-                LspMessage::Notification(n) => {
+                LspMessage::Notification(server, LspClientNotification::Progress(token, work)) => {
+                    self.progress.update(server, token, work);
+                }
+                LspMessage::Notification(server, LspClientNotification::Diagnostics(d)) => {
+                    self.diagnostics.publish(server, d);
+                    self.show_diagnostics();
+                }
+                LspMessage::Notification(server, LspClientNotification::Output(output)) => {
+                    self.output.push(&server, output, Instant::now());
+                }
+                LspMessage::Notification(server, LspClientNotification::InlayHintRefresh) => {
+                    // They're asked for again once the message was processed
+                    if self.server.as_ref() == Some(&server) {
+                        self.inlay_hints_asked = None;
+                    }
+                }
+                LspMessage::Notification(_, n) => {
                     info!(notification = ?n, "Notification from LSP");
                 }
-                LspMessage::Response(r) => {
-                    info!(response = ?r, "Response from LSP")
+                LspMessage::Response(id, r) => {
+                    debug!(?id, response = ?r, "Response from LSP");
+                    self.process_lsp_response(id, r, &mut tasks);
+                }
+                LspMessage::Request(request) => {
+                    debug!(?request, "Request from LSP");
+                    self.process_server_request(request, &mut tasks);
                 }
-                LspMessage::Shutdown => {
-                    warn!("Lsp shutdown");
+                LspMessage::RequestFailed(id, e) => {
+                    warn!(?id, error = %e, "LSP request failed");
+                    self.process_failed_request(id);
                 }
             },
 
             _ => {}
         }
+        self.refresh_completion();
+        self.refresh_signature_help();
+        self.sync_document();
+        self.refresh_lightbulb();
+        self.refresh_inlay_hints();
         if let Some(textbox) = self.text_box.as_mut() {
+            // Whatever a message changed is undone together
+            textbox.close_undo_group();
             textbox.correct_position();
             let window = textbox.buffer().window;
             self.correct_scroll(&mut tasks, window);
@@ -452,13 +2325,14 @@ impl Editor {
             .into(),
             self.modal_view(),
             self.is_saved(),
+            Vec::new(),
         )
     }
 
     fn process_keyboard_event(
         &mut self,
         event: KeyEvent,
-        _tasks: &mut Vec<Task<Message>>,
+        tasks: &mut Vec<Task<Message>>,
     ) -> Option<()> {
         match event {
             // KeyEvent::Special(key, modifiers) => {
@@ -521,11 +2395,138 @@ impl Editor {
             //         _ => (),
             //     }
             // }
+            // The code action menu takes the keys that pick and apply an action
+            KeyEvent::Special(
+                Key::Named(
+                    key @ (Named::ArrowUp | Named::ArrowDown | Named::Enter | Named::Escape),
+                ),
+                _,
+            ) if self.text_box.as_ref()?.code_actions().is_some() => {
+                let textbox = self.text_box.as_mut()?;
+                match key {
+                    Named::ArrowUp => textbox.code_actions_mut()?.actions_mut().select_previous(),
+                    Named::ArrowDown => textbox.code_actions_mut()?.actions_mut().select_next(),
+                    Named::Escape => textbox.set_code_actions(None),
+                    _ => {
+                        self.apply_code_action(tasks);
+                    }
+                }
+            }
+            // Ctrl+. lists the code actions at the cursor
+            KeyEvent::Special(Key::Character(key), modifiers)
+                if modifiers.ctrl && key.as_str() == "." =>
+            {
+                self.request_code_actions(true);
+            }
+            // The completion popup takes the keys that pick and insert a completion
+            KeyEvent::Special(
+                Key::Named(
+                    key @ (Named::ArrowUp
+                    | Named::ArrowDown
+                    | Named::Enter
+                    | Named::Tab
+                    | Named::Escape),
+                ),
+                _,
+            ) if self.text_box.as_ref()?.completion().is_some() => match key {
+                Named::ArrowUp => {
+                    self.select_completion(Completion::select_previous);
+                }
+                Named::ArrowDown => {
+                    self.select_completion(Completion::select_next);
+                }
+                Named::Escape => self.close_completion(),
+                _ => {
+                    self.accept_completion();
+                }
+            },
+            // With several overloads, the arrows go through them rather than move the cursor
+            KeyEvent::Special(Key::Named(key @ (Named::ArrowUp | Named::ArrowDown)), _)
+                if self
+                    .text_box
+                    .as_ref()?
+                    .signature_help()
+                    .is_some_and(|popup| popup.signatures().len() > 1) =>
+            {
+                let popup = self.text_box.as_mut()?.signature_help_mut()?;
+                if key == Named::ArrowUp {
+                    popup.update(Signatures::previous);
+                } else {
+                    popup.update(Signatures::next);
+                }
+            }
+            KeyEvent::Special(Key::Named(Named::Escape), _)
+                if self.text_box.as_ref()?.signature_help().is_some() =>
+            {
+                self.close_signature_help();
+            }
+            KeyEvent::Special(Key::Named(Named::Escape), _)
+                if self.text_box.as_ref()?.rename().is_some() =>
+            {
+                self.close_rename();
+            }
+            KeyEvent::Special(Key::Named(Named::F2), _) => {
+                let cursor = self.can_edit_textbox()?.buffer().cursor.0;
+                self.request_rename(cursor, tasks);
+            }
+            KeyEvent::Special(Key::Named(Named::F12), modifiers) if modifiers.shift => {
+                let cursor = self.text_box.as_ref()?.buffer().cursor.0;
+                self.request_references(cursor);
+            }
+            // F4 and shift+F4 go through the references in the panel
+            KeyEvent::Special(Key::Named(Named::F4), modifiers) if self.references.is_some() => {
+                if modifiers.shift {
+                    self.open_reference(References::previous, tasks);
+                } else {
+                    self.open_reference(References::next, tasks);
+                }
+            }
+            // F8 and shift+F8 go through the problems of all the files
+            KeyEvent::Special(Key::Named(Named::F8), modifiers) => {
+                self.open_diagnostic(!modifiers.shift, tasks);
+            }
+            // Ctrl+shift+i formats the document, or the selection
+            KeyEvent::Special(Key::Character(key), modifiers)
+                if modifiers.ctrl && modifiers.shift && key.as_str().eq_ignore_ascii_case("i") =>
+            {
+                self.request_formatting(false, tasks);
+            }
+            // Ctrl+z undoes and ctrl+shift+z or ctrl+y redoes
+            KeyEvent::Special(Key::Character(key), modifiers)
+                if modifiers.ctrl && matches!(key.as_str(), "z" | "y") =>
+            {
+                let redo = key.as_str() == "y" || modifiers.shift;
+                self.can_edit_textbox()?.undo(redo);
+            }
+            // F12 goes to the definition and ctrl+F12 to the implementations
+            KeyEvent::Special(Key::Named(Named::F12), modifiers) => {
+                let kind = if modifiers.ctrl {
+                    GotoKind::Implementation
+                } else {
+                    GotoKind::Definition
+                };
+                let cursor = self.text_box.as_ref()?.buffer().cursor.0;
+                self.request_goto(kind, cursor);
+            }
+            // Tab and shift-tab go through the tab stops of a snippet
+            KeyEvent::Special(Key::Named(Named::Tab), modifiers)
+                if self.text_box.as_ref()?.buffer().snippet().is_some() =>
+            {
+                self.move_tab_stop(!modifiers.shift);
+            }
+            KeyEvent::Special(Key::Named(Named::Escape), _)
+                if self.text_box.as_ref()?.buffer().snippet().is_some() =>
+            {
+                self.can_edit_textbox()?.end_snippet();
+            }
             KeyEvent::CharacterReceived(character) => {
                 // Only process the event if the last key recieved wasn't a special key.last_event
                 // This is because they send the event of special keys twice
                 if !self.modifiers.ctrl {
                     self.can_edit_textbox()?.insert(character.to_string());
+                    self.complete_after_typing(character);
+                    self.signature_help_after_typing(character);
+                    self.format_after_typing(character);
                 }
             }
             _ => {}
@@ -566,6 +2567,24 @@ impl Editor {
         true
     }
 
+    /**
+     * What's shown in the status bar: the state of the language server of the open document, and
+     * the work the servers are doing.
+     */
+    fn status(&self) -> Vec<String> {
+        let server = self.server.as_ref().map(|server| {
+            let state = self
+                .servers
+                .state(server)
+                .map_or("Waiting", |state| state.label());
+            format!("{}: {state}", server.language)
+        });
+        server
+            .into_iter()
+            .chain(self.progress.works().iter().map(|work| work.label()))
+            .collect()
+    }
+
     // fn set_modal(&mut self, file: String) {
     //     self.modal = Some(Modal::FileSelector(file.to_owned()));
     //     if let Some(textbox) = self.text_box.as_mut() {
@@ -595,7 +2614,11 @@ impl Editor {
         None
     }
 
-    fn change_file(&mut self, file: String, tasks: &mut Vec<Task<Message>>) {
+    /**
+     * Opens another file in place of the open document, returning whether it was opened. It's
+     * refused while the document has unsaved changes, which would be lost.
+     */
+    fn change_file(&mut self, file: String, tasks: &mut Vec<Task<Message>>) -> bool {
         let old_file = self
             .text_box
             .as_ref()
@@ -604,8 +2627,14 @@ impl Editor {
             .map(Document::uri)
             .map(ToOwned::to_owned);
 
-        self.open(&file, old_file, tasks);
+        if let Err(e) = self.open(&file, old_file, tasks) {
+            warn!(%file, error = %e, "Couldn't open the file");
+            let message = format!("Couldn't open {file}: {e}");
+            self.show_message(MessageRequest::notice(MessageType::ERROR, message));
+            return false;
+        }
         self.modal = None;
+        true
     }
 
     // fn workspace(&self) -> Option<String> {
@@ -649,40 +2678,58 @@ impl Editor {
         let scroll_properties = Scrollbar::default();
 
         let second_scroll_id = iced::widget::scrollable::Id::new("2");
-        container(layout::layout(
-            row![
-                line_number(
-                    text_box.buffer().len(),
+        let editor = row![
+            line_number(
+                text_box.buffer().len(),
+                text_box.get_font_size(),
+                text_box.height() + text_box.window_height(),
+                second_scroll_id,
+                self.lightbulb
+            ),
+            ViewPort::new(
+                scrollable(TextboxContainer::new(
+                    text_box.view(),
+                    text_box,
+                    text_box.longest_line(),
+                    text_box.floating_element(&self.theme),
                     text_box.get_font_size(),
-                    text_box.height() + text_box.window_height(),
-                    second_scroll_id
-                ),
-                ViewPort::new(
-                    scrollable(TextboxContainer::new(
-                        text_box.view(),
-                        text_box,
-                        text_box.longest_line(),
-                        text_box.floating_element(),
-                        text_box.get_font_size(),
-                        text_box.get_font()
-                    ))
-                    .id(id)
-                    .on_scroll(|viewport| {
-                        Message::Offset(viewport.absolute_offset().x, viewport.absolute_offset().y)
-                    })
-                    .direction(scrollable::Direction::Both {
-                        vertical: scroll_properties,
-                        horizontal: scroll_properties
-                    })
-                    .into(),
-                    self.modal_view(),
-                    &text_box.buffer().window,
-                )
-            ]
-            .spacing(5)
-            .into(),
+                    text_box.get_font()
+                ))
+                .id(id)
+                .on_scroll(|viewport| {
+                    Message::Offset(viewport.absolute_offset().x, viewport.absolute_offset().y)
+                })
+                .direction(scrollable::Direction::Both {
+                    vertical: scroll_properties,
+                    horizontal: scroll_properties
+                })
+                .into(),
+                self.modal_view(),
+                &text_box.buffer().window,
+            )
+        ]
+        .spacing(5)
+        .height(Length::Fill);
+        let mut child = column![editor];
+        if let Some(references) = self.references.as_ref() {
+            child = child.push(references_panel(references, &self.theme));
+        }
+        if self.problems_open {
+            child = child.push(problems_panel(&self.diagnostics, &self.theme));
+        }
+        if self.output_open {
+            child = child.push(output_panel(
+                &self.output,
+                self.servers.trace(),
+                &self.theme,
+            ));
+        }
+
+        container(layout::layout(
+            child.into(),
             self.modal_view(),
             self.is_saved(),
+            self.status(),
         ))
         .width(iced::Length::Fill)
         .height(iced::Length::Fill)
@@ -719,6 +2766,176 @@ impl Editor {
         if let Some(text_box) = self.can_edit_textbox() {
             text_box.clear_floating_elements();
         }
+        self.close_rename();
+        self.close_completion();
+        self.close_signature_help();
         self.modal = None;
     }
 }
+
+#[cfg(test)]
+mod editor_tests {
+    use super::{Editor, Message, Modal, Modifiers};
+    use crate::core::{
+        buffer::Buffer, document::Document, jump_list::Jump, workspace_edit::FileChange,
+    };
+    use crate::widgets::textbox::Textbox;
+    use async_lsp::lsp_types::{self, OneOf, ServerCapabilities, Url};
+    use laurel_common::text::Position;
+    use laurel_lsp::{GotoKind, LspCommand, LspConnection, LspRequest, ServerId};
+    use pretty_assertions::assert_eq;
+    use ropey::Rope;
+    use std::{
+        path::PathBuf,
+        sync::{atomic::AtomicU64, Arc},
+    };
+    use tokio::sync::mpsc;
+
+    /// An editor with a Rust file open, connected to a server that goes to definitions
+    fn editor() -> (Editor, mpsc::UnboundedReceiver<LspCommand>) {
+        let (mut editor, _) = Editor::new(());
+        let document = Document::from_rope(Rope::from_str("fn main() {}\n"), "/tmp/main.rs");
+        let mut textbox = Textbox::new(Buffer::new(document, None));
+        // As the click focuses it
+        textbox.set_focus(true);
+        editor.text_box = Some(textbox);
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let capabilities = ServerCapabilities {
+            definition_provider: Some(OneOf::Left(true)),
+            ..ServerCapabilities::default()
+        };
+        let server = ServerId {
+            language: String::from("rust"),
+            root: PathBuf::from("/tmp"),
+        };
+        editor.lsp = Some(LspConnection::new(
+            server,
+            sender,
+            Arc::new(capabilities),
+            Arc::new(AtomicU64::new(0)),
+        ));
+        (editor, receiver)
+    }
+
+    fn cursor(editor: &Editor) -> Position {
+        editor.text_box.as_ref().unwrap().buffer().cursor.0
+    }
+
+    fn open_file(editor: &Editor) -> String {
+        let document = editor.text_box.as_ref().unwrap().buffer().document();
+        document.uri().path().to_owned()
+    }
+
+    #[test]
+    fn test_ctrl_click_goes_to_definition() {
+        let (mut editor, mut receiver) = editor();
+        let ctrl = Modifiers {
+            ctrl: true,
+            ..Modifiers::default()
+        };
+
+        editor.process_event(Message::ModifiersChanged(ctrl));
+        editor.process_event(Message::CursorEvent(Position::new(0, 4)));
+        match receiver.try_recv() {
+            Ok(LspCommand::Request(_, LspRequest::Goto(kind, uri, position))) => {
+                assert_eq!(kind, GotoKind::Definition);
+                assert_eq!(uri.path(), "/tmp/main.rs");
+                assert_eq!(position, lsp_types::Position::new(0, 4));
+            }
+            command => panic!("expected a definition request, got {command:?}"),
+        }
+        // The cursor stays where it was
+        assert_eq!(cursor(&editor), Position::new(0, 0));
+
+        // Once ctrl is released a click moves the cursor again
+        editor.process_event(Message::ModifiersChanged(Modifiers::default()));
+        editor.process_event(Message::CursorEvent(Position::new(0, 4)));
+        assert_eq!(cursor(&editor), Position::new(0, 4));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    /// A plain text file to go to, which has no language server to start
+    fn other_file(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("laurel-editor-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(name);
+        std::fs::write(&file, "other\n").unwrap();
+        file.to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn test_unsaved_changes_ask_before_leaving() {
+        let (mut editor, _receiver) = editor();
+        let other = other_file("unsaved.txt");
+        editor.text_box.as_mut().unwrap().insert(String::from("x"));
+
+        editor.process_event(Message::Open(other.clone()));
+        assert_eq!(open_file(&editor), "/tmp/main.rs");
+        assert!(matches!(editor.modal, Some(Modal::UnsavedChanges(_))));
+        assert!(!editor.is_saved());
+
+        // Cancelling stays on the changes
+        editor.process_event(Message::DismissModal);
+        assert_eq!(open_file(&editor), "/tmp/main.rs");
+        assert!(!editor.is_saved());
+
+        // Discarding them carries on to the file
+        editor.process_event(Message::Open(other.clone()));
+        editor.process_event(Message::LeaveUnsaved(false));
+        assert_eq!(open_file(&editor), other);
+        assert!(editor.modal.is_none());
+        assert!(editor.is_saved());
+    }
+
+    #[test]
+    fn test_renamed_file_that_cant_be_opened_fails_the_edit() {
+        let (mut editor, _receiver) = editor();
+        let old = other_file("renamed.txt");
+        let new = old.replace("renamed.txt", "renamed-new.txt");
+        // Invalid UTF-8, which can't be opened again
+        std::fs::write(&old, [0xff, 0xfe]).unwrap();
+        let document = Document::from_rope(Rope::from_str("other\n"), &old);
+        editor.text_box = Some(Textbox::new(Buffer::new(document, None)));
+
+        let change = FileChange::Rename {
+            old_uri: Url::from_file_path(&old).unwrap(),
+            new_uri: Url::from_file_path(&new).unwrap(),
+            overwrite: false,
+            ignore_if_exists: false,
+        };
+        let response = editor.apply_workspace_edit(vec![change], &mut Vec::new());
+        let renamed = std::path::Path::new(&new).exists();
+        std::fs::remove_file(&new).unwrap();
+
+        assert!(renamed);
+        assert!(!response.applied);
+        assert_eq!(response.failed_change, Some(0));
+        assert!(response.failure_reason.is_some());
+    }
+
+    #[test]
+    fn test_failed_jump_back_keeps_the_history() {
+        let (mut editor, _receiver) = editor();
+        let missing = Jump {
+            uri: Url::parse("file:///laurel/missing.rs").unwrap(),
+            position: Position::new(0, 0),
+        };
+        editor.jumps.push(missing.clone());
+
+        editor.process_event(Message::GoBack);
+        assert_eq!(open_file(&editor), "/tmp/main.rs");
+        assert!(matches!(editor.modal, Some(Modal::MessageRequest(_))));
+        assert_eq!(editor.jumps.peek(false), Some(&missing));
+        assert_eq!(editor.jumps.peek(true), None);
+    }
+
+    #[test]
+    fn test_unreadable_file_is_reported() {
+        let (mut editor, _receiver) = editor();
+
+        editor.process_event(Message::Open(String::from("/laurel/missing.rs")));
+        assert_eq!(open_file(&editor), "/tmp/main.rs");
+        assert!(matches!(editor.modal, Some(Modal::MessageRequest(_))));
+    }
+}
@@ -0,0 +1,141 @@
+use std::fs;
+
+use async_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, Documentation, InsertTextFormat, MarkupContent, MarkupKind,
+};
+use toml::{Table, Value};
+use tracing::warn;
+
+use crate::language::runtime::runtime_dir;
+
+/**
+ * A snippet the user wrote, offered along with the completions of the language server.
+ *
+ * They're kept per language in `snippets/<language>.toml` in the runtime directory, one table per
+ * snippet:
+ *
+ * ```toml
+ * [function]
+ * prefix = "fn"
+ * body = ["fn ${1:name}($2) {", "\t$0", "}"]
+ * description = "A function"
+ * ```
+ *
+ * The body is a string or a list of lines in the TextMate snippet syntax, and the prefix is the
+ * name of the table when it's left out.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserSnippet {
+    pub prefix: String,
+    pub body: String,
+    pub description: Option<String>,
+}
+
+impl UserSnippet {
+    pub fn completion_item(&self) -> CompletionItem {
+        CompletionItem {
+            label: self.prefix.clone(),
+            kind: Some(CompletionItemKind::SNIPPET),
+            detail: self.description.clone(),
+            documentation: Some(Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```\n{}\n```", self.body),
+            })),
+            insert_text: Some(self.body.clone()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..CompletionItem::default()
+        }
+    }
+}
+
+/**
+ * Reads the snippets of a snippets file, skipping the ones without a body.
+ */
+pub fn parse(source: &str) -> Result<Vec<UserSnippet>, toml::de::Error> {
+    let table: Table = source.parse()?;
+    let snippets = table
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let Value::Table(mut snippet) = value else {
+                warn!("The snippet `{name}` should be a table");
+                return None;
+            };
+            let body = match snippet.remove("body") {
+                Some(Value::String(body)) => body,
+                Some(Value::Array(lines)) => {
+                    let lines: Option<Vec<&str>> = lines.iter().map(Value::as_str).collect();
+                    lines?.join("\n")
+                }
+                _ => {
+                    warn!("The snippet `{name}` has no body");
+                    return None;
+                }
+            };
+            let text = |key: &str| snippet.get(key)?.as_str().map(ToOwned::to_owned);
+            Some(UserSnippet {
+                prefix: text("prefix").unwrap_or(name),
+                description: text("description"),
+                body,
+            })
+        })
+        .collect();
+    Ok(snippets)
+}
+
+/**
+ * Loads the user's snippets for a language, none when there's no snippets file.
+ */
+pub fn for_language(language: &str) -> Vec<UserSnippet> {
+    let Some(path) = runtime_dir().map(|dir| dir.join("snippets").join(format!("{language}.toml")))
+    else {
+        return Vec::new();
+    };
+    let Ok(source) = fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+    parse(&source).unwrap_or_else(|error| {
+        warn!(error = %error, "Couldn't read the snippets in {}", path.display());
+        Vec::new()
+    })
+}
+
+#[cfg(test)]
+mod snippets_tests {
+    use super::{parse, UserSnippet};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse() {
+        let snippets = parse(
+            r#"
+            [function]
+            prefix = "fn"
+            body = ["fn ${1:name}() {", "\t$0", "}"]
+            description = "A function"
+
+            [todo]
+            body = "// TODO: $0"
+
+            [broken]
+            prefix = "nothing"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            snippets,
+            vec![
+                UserSnippet {
+                    prefix: String::from("fn"),
+                    body: String::from("fn ${1:name}() {\n\t$0\n}"),
+                    description: Some(String::from("A function")),
+                },
+                UserSnippet {
+                    prefix: String::from("todo"),
+                    body: String::from("// TODO: $0"),
+                    description: None,
+                },
+            ]
+        );
+    }
+}
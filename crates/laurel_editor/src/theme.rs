@@ -0,0 +1,100 @@
+use std::{fs, path::PathBuf};
+
+use laurel_common::{
+    appearance::{
+        theme::{self, import, BUNDLED_THEMES},
+        ThemeError,
+    },
+    LaurelTheme,
+};
+use tracing::warn;
+
+use crate::language::runtime::runtime_dir;
+
+/**
+ * The user's themes, `.toml` files in Laurel's or Helix's format and `.json` VS Code themes.
+ */
+fn themes_dir() -> Option<PathBuf> {
+    runtime_dir().map(|dir| dir.join("themes"))
+}
+
+/**
+ * Lists the bundled themes followed by the user's.
+ */
+pub fn available() -> Vec<String> {
+    let mut names: Vec<String> = BUNDLED_THEMES
+        .iter()
+        .map(|(name, _)| (*name).to_owned())
+        .collect();
+
+    let entries = themes_dir().and_then(|dir| fs::read_dir(dir).ok());
+    let mut user: Vec<String> = entries
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "toml" || extension == "json")
+        })
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_owned()))
+        .filter(|name| !names.contains(name))
+        .collect();
+    user.sort();
+    user.dedup();
+
+    names.extend(user);
+    names
+}
+
+/**
+ * Loads the default theme, or the user's version of it.
+ */
+pub fn default_theme() -> LaurelTheme {
+    load(theme::DEFAULT_THEME).unwrap_or_else(|error| {
+        warn!(error = %error, "Couldn't load the default theme");
+        LaurelTheme::default()
+    })
+}
+
+/**
+ * Loads a theme by name, a user theme takes precedence over the bundled one of the same name.
+ */
+pub fn load(name: &str) -> Result<LaurelTheme, ThemeError> {
+    if let Some(dir) = themes_dir() {
+        let toml = dir.join(format!("{name}.toml"));
+        if toml.is_file() {
+            return LaurelTheme::from_toml(name, &fs::read_to_string(toml)?);
+        }
+        let json = dir.join(format!("{name}.json"));
+        if json.is_file() {
+            return import::vscode(name, &fs::read_to_string(json)?);
+        }
+    }
+    theme::bundled(name).ok_or_else(|| ThemeError::NotFound(name.to_owned()))
+}
+
+/**
+ * Loads the theme after `current`, skipping the ones that fail to load. The themes are read
+ * again every time, so changes to a theme show up when switching back to it.
+ */
+pub fn next(current: &str) -> Option<LaurelTheme> {
+    let names = available();
+    let start = names
+        .iter()
+        .position(|name| name == current)
+        .map_or(0, |index| index + 1);
+
+    names
+        .iter()
+        .cycle()
+        .skip(start)
+        .take(names.len())
+        .find_map(|name| match load(name) {
+            Ok(theme) => Some(theme),
+            Err(error) => {
+                warn!(error = %error, "Couldn't load the {name} theme");
+                None
+            }
+        })
+}
@@ -0,0 +1,76 @@
+use async_lsp::lsp_types::CodeActionOrCommand;
+use iced::{
+    widget::{button, column, container, text},
+    Length,
+};
+use laurel_common::{appearance, text::Position, Element, LaurelTheme};
+
+use crate::{
+    core::code_action::{self, CodeActions},
+    Message,
+};
+
+/**
+ * The code actions at the cursor, listed below it to pick one.
+ */
+#[derive(Debug, Clone)]
+pub struct CodeActionMenu {
+    actions: CodeActions,
+    /// Where the actions were asked for, in characters
+    position: Position,
+}
+
+impl CodeActionMenu {
+    pub fn new(actions: CodeActions, position: Position) -> Self {
+        Self { actions, position }
+    }
+
+    pub fn actions(&self) -> &CodeActions {
+        &self.actions
+    }
+
+    pub fn actions_mut(&mut self) -> &mut CodeActions {
+        &mut self.actions
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub fn take_selected(self) -> Option<CodeActionOrCommand> {
+        self.actions.take_selected()
+    }
+
+    pub fn view(&self, theme: &LaurelTheme) -> Element<Message> {
+        let selected = self.actions.selected_index();
+        let rows = self
+            .actions
+            .actions()
+            .iter()
+            .enumerate()
+            .map(|(index, action)| {
+                let style = if index == selected {
+                    appearance::button::selected
+                } else {
+                    appearance::button::transparent
+                };
+                button(
+                    text(code_action::title(action))
+                        .size(14.0)
+                        .color(theme.text),
+                )
+                .on_press(Message::PickCodeAction(index))
+                .style(style)
+                .padding([2, 6])
+                .width(Length::Fill)
+                .into()
+            });
+        container(column(rows))
+            .padding(4)
+            .width(420)
+            .max_height(300)
+            .clip(true)
+            .style(appearance::container::floating)
+            .into()
+    }
+}
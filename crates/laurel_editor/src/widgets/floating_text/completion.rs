@@ -0,0 +1,156 @@
+use async_lsp::lsp_types::{CompletionItem, CompletionItemKind};
+use iced::{
+    widget::{button, column, container, horizontal_space, row, text},
+    Font, Length,
+};
+use laurel_common::{appearance, Element, LaurelTheme};
+
+use super::markdown::{self, Block};
+use crate::{core::completion::Completion, Message};
+
+/// How many rows of the popup are shown at once.
+const VISIBLE_ROWS: usize = 12;
+const DETAIL_LENGTH: usize = 40;
+
+/**
+ * The completions at the cursor, along with the documentation of the selected one.
+ */
+#[derive(Debug, Clone)]
+pub struct CompletionPopup {
+    completion: Completion,
+    documentation: Vec<Block>,
+}
+
+impl CompletionPopup {
+    pub fn new(completion: Completion) -> Self {
+        let mut popup = Self {
+            completion,
+            documentation: Vec::new(),
+        };
+        popup.refresh_documentation();
+        popup
+    }
+
+    pub fn completion(&self) -> &Completion {
+        &self.completion
+    }
+
+    /**
+     * Changes the completion, e.g. to filter or to select another item, and shows the
+     * documentation of whatever is selected afterwards.
+     */
+    pub fn update(&mut self, update: impl FnOnce(&mut Completion)) {
+        update(&mut self.completion);
+        self.refresh_documentation();
+    }
+
+    fn refresh_documentation(&mut self) {
+        self.documentation = self
+            .completion
+            .selected()
+            .and_then(|item| item.documentation.clone())
+            .map(markdown::from_documentation)
+            .unwrap_or_default();
+    }
+
+    pub fn view(&self, theme: &LaurelTheme) -> Element<Message> {
+        let selected = self.completion.selected_index();
+        // Scrolls just enough to keep the selected row in view
+        let offset = selected.saturating_sub(VISIBLE_ROWS - 1);
+        let rows = self
+            .completion
+            .matches()
+            .enumerate()
+            .skip(offset)
+            .take(VISIBLE_ROWS)
+            .map(|(index, item)| {
+                let style = if index == selected {
+                    appearance::button::selected
+                } else {
+                    appearance::button::transparent
+                };
+                button(item_row(item, theme))
+                    .on_press(Message::AcceptCompletion(index))
+                    .style(style)
+                    .padding([2, 6])
+                    .width(Length::Fill)
+                    .into()
+            });
+        let list = container(column(rows))
+            .padding(4)
+            .width(360)
+            .style(appearance::container::floating);
+
+        if self.documentation.is_empty() {
+            return list.into();
+        }
+        let documentation = container(markdown::view(&self.documentation, theme))
+            .padding(12)
+            .max_width(420)
+            .max_height(300)
+            .clip(true)
+            .style(appearance::container::floating);
+        row![list, documentation].spacing(4).into()
+    }
+}
+
+fn item_row<'a>(item: &'a CompletionItem, theme: &LaurelTheme) -> Element<'a, Message> {
+    let (icon, capture) = kind_icon(item.kind);
+    let label_color = if item.deprecated == Some(true) {
+        theme.muted
+    } else {
+        theme.text
+    };
+    let detail: String = item
+        .label_details
+        .as_ref()
+        .and_then(|details| details.description.as_deref())
+        .or(item.detail.as_deref())
+        .unwrap_or_default()
+        .chars()
+        .take(DETAIL_LENGTH)
+        .collect();
+
+    row![
+        text(icon)
+            .font(Font::MONOSPACE)
+            .size(12.0)
+            .color(theme.syntax_color(capture))
+            .width(40),
+        text(item.label.as_str()).size(14.0).color(label_color),
+        horizontal_space(),
+        text(detail).size(12.0).color(theme.muted),
+    ]
+    .spacing(6)
+    .into()
+}
+
+/**
+ * The abbreviation shown for the kind of a completion, and the highlight capture it takes its
+ * colour from.
+ */
+fn kind_icon(kind: Option<CompletionItemKind>) -> (&'static str, &'static str) {
+    match kind {
+        Some(CompletionItemKind::METHOD) => ("fn", "function.method"),
+        Some(CompletionItemKind::FUNCTION) => ("fn", "function"),
+        Some(CompletionItemKind::CONSTRUCTOR) => ("new", "constructor"),
+        Some(CompletionItemKind::FIELD | CompletionItemKind::PROPERTY) => ("fld", "property"),
+        Some(CompletionItemKind::VARIABLE) => ("var", "variable"),
+        Some(CompletionItemKind::CLASS | CompletionItemKind::STRUCT) => ("type", "type"),
+        Some(CompletionItemKind::INTERFACE) => ("trait", "type"),
+        Some(CompletionItemKind::ENUM) => ("enum", "type"),
+        Some(CompletionItemKind::TYPE_PARAMETER) => ("T", "type.parameter"),
+        Some(CompletionItemKind::ENUM_MEMBER) => ("case", "constant"),
+        Some(CompletionItemKind::CONSTANT | CompletionItemKind::VALUE) => ("const", "constant"),
+        Some(CompletionItemKind::MODULE) => ("mod", "module"),
+        Some(CompletionItemKind::KEYWORD) => ("kw", "keyword"),
+        Some(CompletionItemKind::OPERATOR) => ("op", "operator"),
+        Some(CompletionItemKind::SNIPPET) => ("snip", "string.special"),
+        Some(CompletionItemKind::FILE | CompletionItemKind::FOLDER) => ("file", "string"),
+        Some(CompletionItemKind::EVENT) => ("evt", "variable"),
+        Some(CompletionItemKind::UNIT) => ("unit", "constant"),
+        Some(CompletionItemKind::COLOR) => ("col", "constant"),
+        Some(CompletionItemKind::REFERENCE) => ("ref", "variable"),
+        _ => ("txt", "text"),
+    }
+}
@@ -3,9 +3,9 @@ use iced::{
     advanced::{
         self,
         layout::{self, Layout},
-        overlay, widget,
+        overlay, widget, Clipboard, Shell,
     },
-    Vector,
+    Event, Vector,
 };
 use iced::{Length, Point, Rectangle, Size};
 use laurel_common::{Element, LaurelTheme};
@@ -17,6 +17,8 @@ pub struct FloatingOverlay<'a, 'b, Message, Renderer> {
     tree: &'b mut widget::Tree,
     size: Size,
     point: Point,
+    /// Whether the content ends at the point rather than starts at it
+    above: bool,
 }
 
 impl<'a, 'b, Message, Renderer> FloatingOverlay<'a, 'b, Message, Renderer> {
@@ -31,8 +33,17 @@ impl<'a, 'b, Message, Renderer> FloatingOverlay<'a, 'b, Message, Renderer> {
             tree,
             size,
             point,
+            above: false,
         }
     }
+
+    /**
+     * Places the content above the point rather than below it.
+     */
+    pub fn above(mut self, above: bool) -> Self {
+        self.above = above;
+        self
+    }
 }
 
 impl<'a, 'b, Message, Renderer> overlay::Overlay<Message, LaurelTheme, Renderer>
@@ -56,39 +67,36 @@ where
         //     y: position.y + self.point.y,
         // };
 
+        let point = if self.above {
+            Point::new(self.point.x, self.point.y - child.size().height)
+        } else {
+            self.point
+        };
         let node = layout::Node::with_children(self.size, vec![child]);
-        node.move_to(self.point)
+        node.move_to(point)
     }
 
-    // fn on_event(
-    //     &mut self,
-    //     event: Event,
-    //     layout: Layout<'_>,
-    //     cursor: mouse::Cursor,
-    //     renderer: &Renderer,
-    //     clipboard: &mut dyn Clipboard,
-    //     shell: &mut Shell<'_, Message>,
-    // ) -> event::Status {
-    //     let content_bounds = layout.children().next().unwrap().bounds();
-    //
-    //     if let Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) = &event {
-    //         if !cursor.is_over(content_bounds) {
-    //             shell.publish(Message::dismiss_modal());
-    //             return event::Status::Captured;
-    //         }
-    //     }
-    //
-    //     self.content.as_widget_mut().on_event(
-    //         self.tree,
-    //         event,
-    //         layout.children().next().unwrap(),
-    //         cursor,
-    //         renderer,
-    //         clipboard,
-    //         shell,
-    //         &layout.bounds(),
-    //     )
-    // }
+    fn update(
+        &mut self,
+        event: &Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+    ) {
+        // The content is interactive, e.g. the rows of the completion popup can be clicked
+        self.content.as_widget_mut().update(
+            self.tree,
+            event,
+            layout.children().next().unwrap(),
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            &layout.bounds(),
+        );
+    }
 
     fn draw(
         &self,
@@ -3,8 +3,9 @@ use iced::widget::{
     scrollable::{self, Scrollbar},
     text,
 };
-use laurel_common::{appearance, Element};
+use laurel_common::{appearance, Element, LaurelTheme};
 
+use super::markdown::{self, Block};
 use crate::Message;
 use laurel_common::text::Position;
 
@@ -16,30 +17,35 @@ pub struct FloatingElement {
 #[derive(Clone, Debug)]
 pub enum FloatingText {
     Diagnostic(String),
+    /**
+     * The hover information of the language server, already parsed from Markdown.
+     */
+    Hover(Vec<Block>),
 }
 
 impl FloatingText {
-    pub fn show(&self) -> Element<Message> {
-        match self {
+    pub fn show(&self, theme: &LaurelTheme) -> Element<Message> {
+        let content = match self {
             FloatingText::Diagnostic(value) => {
                 let mut text_lines = Vec::new();
                 for line in value.lines() {
                     let text = text(line).into();
                     text_lines.push(text);
                 }
-
-                container(
-                    iced::widget::scrollable(column(text_lines).width(iced::Length::Fill))
-                        .direction(scrollable::Direction::Vertical(
-                            Scrollbar::default().scroller_width(7.0).width(7.0),
-                        )),
-                )
-                .padding(20)
-                .style(appearance::container::floating)
-                .max_height(400)
-                .max_width(500)
-                .into()
+                column(text_lines).width(iced::Length::Fill).into()
             }
-        }
+            FloatingText::Hover(blocks) => markdown::view(blocks, theme),
+        };
+
+        container(
+            iced::widget::scrollable(content).direction(scrollable::Direction::Vertical(
+                Scrollbar::default().scroller_width(7.0).width(7.0),
+            )),
+        )
+        .padding(20)
+        .style(appearance::container::floating)
+        .max_height(400)
+        .max_width(500)
+        .into()
     }
 }
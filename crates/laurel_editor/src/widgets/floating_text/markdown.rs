@@ -0,0 +1,519 @@
+use async_lsp::lsp_types::{Documentation, HoverContents, MarkedString, MarkupKind};
+use iced::{
+    font::{Style as FontStyle, Weight},
+    widget::{column, container, horizontal_rule, rich_text, row, text, text::Span},
+    Font, Length,
+};
+use laurel_common::{appearance, Element, LaurelTheme};
+use ropey::Rope;
+
+use crate::{
+    highlighter::{Highlighter, HighlighterConfig},
+    language, Message,
+};
+
+/// How a piece of prose is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Plain,
+    Strong,
+    Emphasis,
+    Code,
+    Link,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inline {
+    pub text: String,
+    pub style: Style,
+}
+
+/// A line of code, split into pieces along with the capture name of each.
+pub type CodeLine = Vec<(String, Option<String>)>;
+
+/**
+ * The blocks of a Markdown document, as far as language servers use Markdown: headings,
+ * paragraphs, list items, fenced code and rules.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block {
+    Heading(Vec<Inline>),
+    Paragraph(Vec<Inline>),
+    Item(Vec<Inline>),
+    Code(Vec<CodeLine>),
+    Rule,
+}
+
+/**
+ * Parses Markdown into blocks, highlighting the fenced code of every language that has a grammar.
+ * Code blocks without a language are taken to be Rust, like in Rust doc comments.
+ */
+pub fn parse(source: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    // The fence, language and lines of the code block that's open
+    let mut code: Option<(&str, &str, Vec<&str>)> = None;
+
+    for line in source.lines() {
+        let trimmed = line.trim();
+        if let Some((fence, language, lines)) = &mut code {
+            if trimmed.starts_with(*fence) && trimmed.trim_start_matches(&fence[..1]).is_empty() {
+                blocks.push(Block::Code(highlight(language, &lines.join("\n"))));
+                code = None;
+            } else {
+                lines.push(line);
+            }
+            continue;
+        }
+
+        let block = if let Some(fence) = ["```", "~~~"]
+            .into_iter()
+            .find(|fence| trimmed.starts_with(fence))
+        {
+            let language = trimmed.trim_start_matches(&fence[..1]).trim();
+            code = Some((fence, language, Vec::new()));
+            None
+        } else if trimmed.is_empty() {
+            None
+        } else if is_rule(trimmed) {
+            Some(Block::Rule)
+        } else if let Some(heading) = heading(trimmed) {
+            Some(Block::Heading(inlines(heading)))
+        } else if let Some(item) = item(trimmed) {
+            Some(Block::Item(inlines(item)))
+        } else {
+            paragraph.push(trimmed);
+            continue;
+        };
+
+        if !paragraph.is_empty() {
+            blocks.push(Block::Paragraph(inlines(&paragraph.join(" "))));
+            paragraph.clear();
+        }
+        blocks.extend(block);
+    }
+
+    if !paragraph.is_empty() {
+        blocks.push(Block::Paragraph(inlines(&paragraph.join(" "))));
+    }
+    // A code block that's never closed runs to the end
+    if let Some((_, language, lines)) = code {
+        blocks.push(Block::Code(highlight(language, &lines.join("\n"))));
+    }
+    blocks
+}
+
+/**
+ * Keeps plain text as it is, one paragraph for every run of lines.
+ */
+pub fn plain(source: &str) -> Vec<Block> {
+    source
+        .split("\n\n")
+        .filter(|paragraph| !paragraph.trim().is_empty())
+        .map(|paragraph| {
+            Block::Paragraph(vec![Inline {
+                text: paragraph.trim().to_owned(),
+                style: Style::Plain,
+            }])
+        })
+        .collect()
+}
+
+/**
+ * Converts the contents of a hover response, whose marked strings are Markdown or code in a
+ * language. Several contents are separated by rules.
+ */
+pub fn from_hover(contents: HoverContents) -> Vec<Block> {
+    let marked = |marked: MarkedString| match marked {
+        MarkedString::String(markdown) => markdown,
+        MarkedString::LanguageString(code) => format!("```{}\n{}\n```", code.language, code.value),
+    };
+    match contents {
+        HoverContents::Scalar(contents) => parse(&marked(contents)),
+        HoverContents::Array(contents) => {
+            let contents: Vec<String> = contents.into_iter().map(marked).collect();
+            parse(&contents.join("\n\n---\n\n"))
+        }
+        HoverContents::Markup(markup) => match markup.kind {
+            MarkupKind::Markdown => parse(&markup.value),
+            MarkupKind::PlainText => plain(&markup.value),
+        },
+    }
+}
+
+/**
+ * Parses the documentation of a completion.
+ */
+pub fn from_documentation(documentation: Documentation) -> Vec<Block> {
+    match documentation {
+        Documentation::String(text) => plain(&text),
+        Documentation::MarkupContent(markup) => match markup.kind {
+            MarkupKind::Markdown => parse(&markup.value),
+            MarkupKind::PlainText => plain(&markup.value),
+        },
+    }
+}
+
+fn is_rule(line: &str) -> bool {
+    let line: String = line.split_whitespace().collect();
+    line.len() >= 3
+        && ['-', '*', '_']
+            .into_iter()
+            .any(|marker| line.chars().all(|c| c == marker))
+}
+
+fn heading(line: &str) -> Option<&str> {
+    let text = line.trim_start_matches('#');
+    let level = line.len() - text.len();
+    if (1..=6).contains(&level) && (text.is_empty() || text.starts_with(' ')) {
+        return Some(text.trim());
+    }
+    None
+}
+
+fn item(line: &str) -> Option<&str> {
+    if let Some(text) = ["- ", "* ", "+ "]
+        .into_iter()
+        .find_map(|marker| line.strip_prefix(marker))
+    {
+        return Some(text);
+    }
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits > 0 {
+        return line[digits..].strip_prefix(". ");
+    }
+    None
+}
+
+/**
+ * Splits a line of prose at its inline code, strong and emphasised text and links. Underscores
+ * are left alone, since they're common in identifiers.
+ */
+fn inlines(source: &str) -> Vec<Inline> {
+    let mut inlines: Vec<Inline> = Vec::new();
+    let mut push = |text: &str, style: Style| match inlines.last_mut() {
+        Some(last) if last.style == style => last.text.push_str(text),
+        _ if text.is_empty() => {}
+        _ => inlines.push(Inline {
+            text: text.to_owned(),
+            style,
+        }),
+    };
+
+    let mut strong = false;
+    let mut emphasis = false;
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        let style = match (strong, emphasis) {
+            (true, _) => Style::Strong,
+            (_, true) => Style::Emphasis,
+            _ => Style::Plain,
+        };
+
+        if c == '\\' && rest.len() > 1 {
+            let escaped = rest[1..].chars().next().unwrap_or('\\');
+            push(&escaped.to_string(), style);
+            rest = &rest[1 + escaped.len_utf8()..];
+        } else if c == '`' {
+            let ticks = rest.len() - rest.trim_start_matches('`').len();
+            let after = &rest[ticks..];
+            match after.find(&rest[..ticks]) {
+                Some(end) => {
+                    push(after[..end].trim(), Style::Code);
+                    rest = &after[end + ticks..];
+                }
+                None => {
+                    push(&rest[..ticks], style);
+                    rest = after;
+                }
+            }
+        } else if let Some(after) = rest.strip_prefix("**") {
+            strong = !strong;
+            rest = after;
+        } else if c == '*' {
+            emphasis = !emphasis;
+            rest = &rest[1..];
+        } else if let Some((label, after)) = link(rest) {
+            for inline in inlines_of_link(label) {
+                push(&inline.text, inline.style);
+            }
+            rest = after;
+        } else {
+            push(&rest[..c.len_utf8()], style);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    inlines
+}
+
+/**
+ * Splits `[label](target)` or `[label][reference]` off the start of `source`, keeping the label.
+ */
+fn link(source: &str) -> Option<(&str, &str)> {
+    let rest = source.strip_prefix('[')?;
+    let end = rest.find(']')?;
+    let (label, after) = (&rest[..end], &rest[end + 1..]);
+    let close = match after.chars().next() {
+        Some('(') => ')',
+        Some('[') => ']',
+        // Plain brackets, like in `slice[0]`
+        _ => return None,
+    };
+    let target_end = after.find(close)?;
+    Some((label, &after[target_end + 1..]))
+}
+
+fn inlines_of_link(label: &str) -> Vec<Inline> {
+    inlines(label)
+        .into_iter()
+        .map(|inline| match inline.style {
+            Style::Plain => Inline {
+                style: Style::Link,
+                ..inline
+            },
+            _ => inline,
+        })
+        .collect()
+}
+
+/**
+ * Highlights code with the grammar of its language, leaving it plain when there's no grammar.
+ */
+fn highlight(language: &str, code: &str) -> Vec<CodeLine> {
+    let plain = || {
+        code.lines()
+            .map(|line| vec![(line.to_owned(), None)])
+            .collect()
+    };
+    let name = language.split(',').next().unwrap_or_default();
+    let Some(language) = language::by_injection_name(if name.is_empty() { "rust" } else { name })
+    else {
+        return plain();
+    };
+
+    let rope = Rope::from_str(code);
+    let content = rope.slice(..);
+    let Some(highlights) = HighlighterConfig::for_language(language, &content)
+        .and_then(|config| Highlighter::new(&config, 0..rope.len_lines(), &content))
+    else {
+        return plain();
+    };
+
+    let mut captures = highlights.captures.into_iter().peekable();
+    code.lines()
+        .enumerate()
+        .map(|(row, line)| {
+            let mut pieces: CodeLine = Vec::new();
+            let mut column = 0;
+            let chars: Vec<char> = line.chars().collect();
+            while let Some(item) = captures.next_if(|item| item.range.start_point.row == row) {
+                let start = item.range.start_point.column.min(chars.len());
+                let end = item.range.end_point.column.min(chars.len());
+                if column < start {
+                    pieces.push((chars[column..start].iter().collect(), None));
+                }
+                if start < end {
+                    let text = chars[start..end].iter().collect();
+                    pieces.push((text, Some(item.capture_name)));
+                }
+                column = column.max(end);
+            }
+            if column < chars.len() || pieces.is_empty() {
+                pieces.push((chars[column..].iter().collect(), None));
+            }
+            pieces
+        })
+        .collect()
+}
+
+/**
+ * Draws the blocks with the theme's colours, code in the monospace font.
+ */
+pub fn view<'a>(blocks: &'a [Block], theme: &LaurelTheme) -> Element<'a, Message> {
+    let elements = blocks.iter().map(|block| match block {
+        Block::Heading(inlines) => {
+            let title = theme.syntax_color("text.title");
+            let spans = spans(inlines, theme, 16.0).into_iter();
+            rich_text(spans.map(|span| span.color(title)).collect::<Vec<_>>()).into()
+        }
+        Block::Paragraph(inlines) => rich_text(spans(inlines, theme, 14.0)).into(),
+        Block::Item(inlines) => row![text("•").size(14.0), rich_text(spans(inlines, theme, 14.0))]
+            .spacing(8)
+            .into(),
+        Block::Code(lines) => {
+            let lines = lines.iter().map(|line| {
+                let pieces: Vec<Span<'a, (), Font>> = line
+                    .iter()
+                    .map(|(text, capture)| {
+                        let color = match capture {
+                            Some(capture) => theme.syntax_color(capture),
+                            None => theme.text,
+                        };
+                        Span::new(text.as_str())
+                            .font(Font::MONOSPACE)
+                            .size(13.0)
+                            .color(color)
+                    })
+                    .collect();
+                rich_text(pieces).into()
+            });
+            container(column(lines))
+                .padding(8)
+                .width(Length::Fill)
+                .style(appearance::container::code)
+                .into()
+        }
+        Block::Rule => horizontal_rule(1).into(),
+    });
+    column(elements).spacing(10).into()
+}
+
+fn spans<'a>(inlines: &'a [Inline], theme: &LaurelTheme, size: f32) -> Vec<Span<'a, (), Font>> {
+    inlines
+        .iter()
+        .map(|inline| {
+            let span = Span::new(inline.text.as_str()).size(size);
+            match inline.style {
+                Style::Plain => span,
+                Style::Strong => span.font(Font {
+                    weight: Weight::Bold,
+                    ..Font::DEFAULT
+                }),
+                Style::Emphasis => span.font(Font {
+                    style: FontStyle::Italic,
+                    ..Font::DEFAULT
+                }),
+                Style::Code => span
+                    .font(Font::MONOSPACE)
+                    .color(theme.syntax_color("text.literal")),
+                Style::Link => span.color(theme.syntax_color("text.reference")),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod markdown_tests {
+    use super::{from_documentation, from_hover, parse, plain, Block, Inline, Style};
+    use async_lsp::lsp_types::{
+        Documentation, HoverContents, LanguageString, MarkedString, MarkupContent, MarkupKind,
+    };
+    use pretty_assertions::assert_eq;
+
+    fn inline(text: &str, style: Style) -> Inline {
+        Inline {
+            text: text.to_owned(),
+            style,
+        }
+    }
+
+    #[test]
+    fn test_inlines() {
+        let blocks = parse(
+            "Returns **the** `Vec`, see [`Vec::new`](https://doc.rust-lang.org) or *this_one*\\*",
+        );
+        assert_eq!(
+            blocks,
+            vec![Block::Paragraph(vec![
+                inline("Returns ", Style::Plain),
+                inline("the", Style::Strong),
+                inline(" ", Style::Plain),
+                inline("Vec", Style::Code),
+                inline(", see ", Style::Plain),
+                inline("Vec::new", Style::Code),
+                inline(" or ", Style::Plain),
+                inline("this_one", Style::Emphasis),
+                inline("*", Style::Plain),
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_blocks() {
+        let blocks = parse("# Title\n\nFirst\nline\n\n---\n- one\n2. two\n");
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Heading(vec![inline("Title", Style::Plain)]),
+                Block::Paragraph(vec![inline("First line", Style::Plain)]),
+                Block::Rule,
+                Block::Item(vec![inline("one", Style::Plain)]),
+                Block::Item(vec![inline("two", Style::Plain)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rust_code_is_highlighted() {
+        let blocks = parse("```rust\nfn main() {}\n```\nAfter");
+        let Block::Code(lines) = &blocks[0] else {
+            panic!("expected a code block, got {blocks:?}");
+        };
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0][0], ("fn".to_owned(), Some("keyword".to_owned())));
+        let text: String = lines[0].iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(text, "fn main() {}");
+        assert_eq!(
+            blocks[1],
+            Block::Paragraph(vec![inline("After", Style::Plain)])
+        );
+    }
+
+    #[test]
+    fn test_unknown_language_stays_plain() {
+        let blocks = parse("```text\n*not emphasis*\n```");
+        assert_eq!(
+            blocks,
+            vec![Block::Code(vec![vec![("*not emphasis*".to_owned(), None)]])]
+        );
+    }
+
+    #[test]
+    fn test_from_hover() {
+        let contents = HoverContents::Array(vec![
+            MarkedString::LanguageString(LanguageString {
+                language: "text".to_owned(),
+                value: "x: i32".to_owned(),
+            }),
+            MarkedString::String("A *number*".to_owned()),
+        ]);
+        assert_eq!(
+            from_hover(contents),
+            vec![
+                Block::Code(vec![vec![("x: i32".to_owned(), None)]]),
+                Block::Rule,
+                Block::Paragraph(vec![
+                    inline("A ", Style::Plain),
+                    inline("number", Style::Emphasis),
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plain() {
+        assert_eq!(
+            plain("a *b*\n\nc"),
+            vec![
+                Block::Paragraph(vec![inline("a *b*", Style::Plain)]),
+                Block::Paragraph(vec![inline("c", Style::Plain)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_documentation() {
+        let markdown = Documentation::MarkupContent(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: String::from("**a**"),
+        });
+        assert_eq!(
+            from_documentation(markdown),
+            vec![Block::Paragraph(vec![inline("a", Style::Strong)])]
+        );
+        assert_eq!(
+            from_documentation(Documentation::String(String::from("**a**"))),
+            plain("**a**")
+        );
+    }
+}
@@ -1,2 +1,7 @@
+pub mod code_actions;
+pub mod completion;
 pub mod floating_overlay;
 pub mod floating_text_element;
+pub mod markdown;
+pub mod rename;
+pub mod signature_help;
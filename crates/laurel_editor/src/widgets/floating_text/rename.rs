@@ -0,0 +1,57 @@
+use iced::{
+    widget::{container, text_input},
+    Font, Length,
+};
+use laurel_common::{appearance, text::Position, Element};
+
+use crate::Message;
+
+/**
+ * The id of the input of the new name, to focus it once it's shown.
+ */
+pub fn input_id() -> text_input::Id {
+    text_input::Id::new("rename")
+}
+
+/**
+ * The new name of the symbol that's renamed, typed below its start.
+ */
+#[derive(Debug, Clone)]
+pub struct RenameInput {
+    /// The start of the name, in characters
+    position: Position,
+    name: String,
+}
+
+impl RenameInput {
+    pub fn new(position: Position, name: String) -> Self {
+        Self { position, name }
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    pub fn view(&self) -> Element<Message> {
+        container(
+            text_input("New name", &self.name)
+                .id(input_id())
+                .font(Font::MONOSPACE)
+                .size(14.0)
+                .on_input(Message::RenameInput)
+                .on_submit(Message::SubmitRename),
+        )
+        .padding(4)
+        .width(Length::Fixed(240.0))
+        .style(appearance::container::floating)
+        .into()
+    }
+}
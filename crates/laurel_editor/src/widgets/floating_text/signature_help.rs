@@ -0,0 +1,131 @@
+use iced::{
+    font::Weight,
+    widget::{button, column, container, horizontal_rule, rich_text, row, text, text::Span},
+    Font,
+};
+use laurel_common::{appearance, Element, LaurelTheme};
+
+use super::markdown::{self, Block};
+use crate::{core::signature_help::Signatures, Message};
+
+/**
+ * The signatures of the call at the cursor with the active parameter in bold, along with the
+ * documentation of the parameter and of the signature.
+ */
+#[derive(Debug, Clone)]
+pub struct SignaturePopup {
+    signatures: Signatures,
+    parameter_documentation: Vec<Block>,
+    documentation: Vec<Block>,
+}
+
+impl SignaturePopup {
+    pub fn new(signatures: Signatures) -> Self {
+        let mut popup = Self {
+            signatures,
+            parameter_documentation: Vec::new(),
+            documentation: Vec::new(),
+        };
+        popup.refresh_documentation();
+        popup
+    }
+
+    pub fn signatures(&self) -> &Signatures {
+        &self.signatures
+    }
+
+    /**
+     * Changes the signatures, e.g. to show another overload, and shows the documentation of
+     * whatever is active afterwards.
+     */
+    pub fn update(&mut self, update: impl FnOnce(&mut Signatures)) {
+        update(&mut self.signatures);
+        self.refresh_documentation();
+    }
+
+    fn refresh_documentation(&mut self) {
+        self.parameter_documentation = self
+            .signatures
+            .active_parameter()
+            .and_then(|parameter| parameter.documentation.clone())
+            .map(markdown::from_documentation)
+            .unwrap_or_default();
+        self.documentation = self
+            .signatures
+            .active()
+            .and_then(|signature| signature.documentation.clone())
+            .map(markdown::from_documentation)
+            .unwrap_or_default();
+    }
+
+    pub fn view(&self, theme: &LaurelTheme) -> Element<Message> {
+        let mut content = column![].spacing(8);
+        if let Some(signature) = self.signatures.active() {
+            let label = signature.label.as_str();
+            let (before, parameter, after) = match self.signatures.parameter_range() {
+                Some(range) => (
+                    &label[..range.start],
+                    &label[range.clone()],
+                    &label[range.end..],
+                ),
+                None => (label, "", ""),
+            };
+            let spans: Vec<Span<'_, (), Font>> = vec![
+                Span::new(before).color(theme.text),
+                Span::new(parameter)
+                    .font(Font {
+                        weight: Weight::Bold,
+                        ..Font::MONOSPACE
+                    })
+                    .color(theme.syntax_color("variable.parameter")),
+                Span::new(after).color(theme.text),
+            ];
+            let label = rich_text(spans).font(Font::MONOSPACE).size(14.0);
+
+            let line: Element<Message> = if self.signatures.len() > 1 {
+                row![overloads(&self.signatures, theme), label]
+                    .spacing(8)
+                    .into()
+            } else {
+                label.into()
+            };
+            content = content.push(line);
+        }
+
+        if !self.parameter_documentation.is_empty() {
+            content = content.push(markdown::view(&self.parameter_documentation, theme));
+        }
+        if !self.documentation.is_empty() {
+            content = content
+                .push(horizontal_rule(1))
+                .push(markdown::view(&self.documentation, theme));
+        }
+        container(content)
+            .padding(8)
+            .max_width(600)
+            .max_height(300)
+            .clip(true)
+            .style(appearance::container::floating)
+            .into()
+    }
+}
+
+/**
+ * The buttons that go through the overloads, around which of them is shown.
+ */
+fn overloads<'a>(signatures: &Signatures, theme: &LaurelTheme) -> Element<'a, Message> {
+    let arrow = |arrow: &'a str, message: Message| {
+        button(text(arrow).size(12.0))
+            .on_press(message)
+            .style(appearance::button::transparent)
+            .padding([0, 4])
+    };
+    let shown = format!("{}/{}", signatures.active_index() + 1, signatures.len());
+    row![
+        arrow("▲", Message::PreviousSignature),
+        text(shown).size(12.0).color(theme.muted),
+        arrow("▼", Message::NextSignature),
+    ]
+    .spacing(2)
+    .into()
+}
@@ -1,25 +1,38 @@
 use iced::{
-    widget::{button, column, container, row, text},
+    widget::{button, column, container, row, text, Row},
     Length, Padding, Pixels,
 };
 
 use laurel_common::{appearance, Element};
+use laurel_lsp::GotoKind;
 
 use crate::{widgets::main_view::MainView, Message};
 
+/**
+ * The navbar above the view, and the status bar below it when there's something to show, e.g.
+ * `rust: Running` and `rust: Indexing 40%`.
+ */
 pub fn layout<'a>(
     child: Element<'a, Message>,
     modal: Option<Element<'a, Message>>,
     is_saved: bool,
+    status: Vec<String>,
 ) -> Element<'a, Message> {
     // return "Layout".into();
-    column!(
+    let mut layout = column!(
         container(navbar(is_saved))
             .width(Length::Fill)
             .style(appearance::container::menu),
         main_view(child, modal),
-    )
-    .into()
+    );
+    if !status.is_empty() {
+        layout = layout.push(
+            container(status_bar(status))
+                .width(Length::Fill)
+                .style(appearance::container::menu),
+        );
+    }
+    layout.into()
 }
 
 fn navbar(is_saved: bool) -> Element<'static, Message> {
@@ -42,6 +55,90 @@ fn navbar(is_saved: bool) -> Element<'static, Message> {
             .style(appearance::button::menu)
             .padding(padding)
             .on_press(Message::Save),
+        button(text("Undo").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::Undo),
+        button(text("Redo").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::Redo),
+        button(text("Reload Queries").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::ReloadQueries),
+        button(text("Next Theme").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::NextTheme),
+        button(text("Hover").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::Hover),
+        button(text("Complete").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::Complete),
+        button(text("Signature Help").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::SignatureHelp),
+        button(text("Definition").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::Goto(GotoKind::Definition)),
+        button(text("Declaration").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::Goto(GotoKind::Declaration)),
+        button(text("Type Definition").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::Goto(GotoKind::TypeDefinition)),
+        button(text("Implementation").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::Goto(GotoKind::Implementation)),
+        button(text("Back").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::GoBack),
+        button(text("Forward").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::GoForward),
+        button(text("References").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::FindReferences),
+        button(text("Rename").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::Rename),
+        button(text("Code Actions").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::CodeActions),
+        button(text("Format").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::Format),
+        button(text("Next Placeholder").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::NextTabStop),
+        button(text("Restart Server").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::RestartServer),
+        button(text("Output").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::ToggleOutput),
+        button(text("Problems").size(Pixels::from(14.0)))
+            .style(appearance::button::menu)
+            .padding(padding)
+            .on_press(Message::ToggleProblems),
     )
     .padding(Padding {
         right: 15.0,
@@ -64,6 +161,24 @@ fn navbar(is_saved: bool) -> Element<'static, Message> {
     row.into()
 }
 
+fn status_bar(status: Vec<String>) -> Element<'static, Message> {
+    let padding = Padding {
+        top: 4.0,
+        left: 12.0,
+        bottom: 4.0,
+        right: 12.0,
+    };
+    let mut row = Row::new().spacing(8);
+    for item in status {
+        row = row.push(
+            container(text(item).size(12.0))
+                .style(appearance::container::saved)
+                .padding(padding),
+        );
+    }
+    row.into()
+}
+
 fn main_view<'a>(
     child: Element<'a, Message>,
     modal: Option<Element<'a, Message>>,
@@ -1,18 +1,21 @@
 use crate::Message;
 use iced::widget::scrollable::{Id, Scrollbar};
-use iced::widget::{column, container, scrollable, text};
+use iced::widget::{button, column, container, row, scrollable, text};
 use iced::{alignment, Background, Border, Color, Length, Padding, Pixels};
-use laurel_common::{Element, LaurelTheme};
+use laurel_common::{appearance, Element, LaurelTheme};
 
 pub mod floating_text;
 pub mod layout;
 pub mod main_view;
 pub mod modal;
+pub mod output_panel;
+pub mod problems_panel;
+pub mod references_panel;
 pub mod textbox;
 pub mod textbox_container;
 pub mod view_port;
 
-fn code_line_style(_style: &LaurelTheme) -> container::Style {
+fn code_line_style(theme: &LaurelTheme) -> container::Style {
     container::Style {
         background: Some(Background::from(Color::TRANSPARENT)),
         border: Border {
@@ -20,16 +23,21 @@ fn code_line_style(_style: &LaurelTheme) -> container::Style {
             width: 0.0,
             color: Color::BLACK,
         },
-        text_color: Some(Color::from_rgb8(153, 153, 153)),
+        text_color: Some(theme.muted),
         ..Default::default()
     }
 }
 
+/**
+ * The numbers of the lines, with a lightbulb that opens the code actions on `lightbulb` when
+ * there are some.
+ */
 pub fn line_number(
     number_of_lines: usize,
     font_size: f32,
     height: f32,
     id: Id,
+    lightbulb: Option<usize>,
 ) -> Element<'static, Message> {
     let mut lines: Vec<Element<'static, Message>> = Vec::new();
     let box_height = text::LineHeight::default().to_absolute(Pixels(font_size)).0;
@@ -41,7 +49,20 @@ pub fn line_number(
             right: 5.0,
         };
 
-        let container = container(text(i).size(font_size))
+        let number: Element<'static, Message> = if lightbulb == Some(i - 1) {
+            row![
+                button(text("💡").size(font_size - 2.0))
+                    .style(appearance::button::transparent)
+                    .padding(0)
+                    .on_press(Message::CodeActions),
+                text(i).size(font_size),
+            ]
+            .spacing(4)
+            .into()
+        } else {
+            text(i).size(font_size).into()
+        };
+        let container = container(number)
             .center_x(Length::Shrink)
             .align_y(alignment::Vertical::Top)
             .width(Length::Fixed(80.0))
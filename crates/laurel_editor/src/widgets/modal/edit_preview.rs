@@ -0,0 +1,128 @@
+use async_lsp::lsp_types::Url;
+use iced::{
+    widget::{button, column, container, row, scrollable, text, Column},
+    Color, Font, Length, Padding,
+};
+use laurel_common::{appearance, Element, LaurelTheme};
+
+use crate::{
+    core::workspace_edit::{self, FileChange, LineChange},
+    Message,
+};
+
+/**
+ * A change of a workspace edit as it's previewed, with the lines it changes when it edits a file.
+ */
+#[derive(Debug, Clone)]
+pub struct PreviewItem {
+    pub title: String,
+    pub lines: Vec<LineChange>,
+}
+
+/**
+ * The changes of a workspace edit, e.g. of a rename, shown before they're made.
+ */
+#[derive(Debug, Clone)]
+pub struct EditPreview {
+    pub changes: Vec<FileChange>,
+    items: Vec<PreviewItem>,
+}
+
+fn path(uri: &Url) -> String {
+    uri.to_file_path()
+        .map_or_else(|_| uri.to_string(), |path| path.display().to_string())
+}
+
+impl EditPreview {
+    /**
+     * `text` gives the text of a file, from its buffer when it's open.
+     */
+    pub fn new(changes: Vec<FileChange>, text: impl Fn(&Url) -> Option<String>) -> Self {
+        let items = changes
+            .iter()
+            .map(|change| match change {
+                FileChange::Edit(uri, edits) => PreviewItem {
+                    title: path(uri),
+                    lines: text(uri)
+                        .map(|text| workspace_edit::line_changes(&text, edits))
+                        .unwrap_or_default(),
+                },
+                FileChange::Create { uri, .. } => PreviewItem {
+                    title: format!("Create {}", path(uri)),
+                    lines: Vec::new(),
+                },
+                FileChange::Rename {
+                    old_uri, new_uri, ..
+                } => PreviewItem {
+                    title: format!("Rename {} to {}", path(old_uri), path(new_uri)),
+                    lines: Vec::new(),
+                },
+                FileChange::Delete { uri, .. } => PreviewItem {
+                    title: format!("Delete {}", path(uri)),
+                    lines: Vec::new(),
+                },
+            })
+            .collect();
+        Self { changes, items }
+    }
+}
+
+/**
+ * A line of the preview in the colour `color` picks from the theme.
+ */
+fn line<'a>(content: String, color: fn(&LaurelTheme) -> Color) -> Element<'a, Message> {
+    text(content)
+        .font(Font::MONOSPACE)
+        .size(12.0)
+        .style(move |theme: &LaurelTheme| text::Style {
+            color: Some(color(theme)),
+        })
+        .into()
+}
+
+/**
+ * Lists the changes with the lines they remove and add, to apply them or cancel.
+ */
+pub fn edit_preview(preview: &EditPreview) -> Element<'static, Message> {
+    let mut list = Column::new().spacing(8);
+    for item in &preview.items {
+        let mut lines = Column::new().push(text(item.title.clone()).size(14.0));
+        for change in &item.lines {
+            for (index, removed) in change.before.lines().enumerate() {
+                let number = change.line + index + 1;
+                lines = lines.push(line(format!("{number:>5} - {removed}"), |theme| {
+                    theme.muted
+                }));
+            }
+            for added in change.after.lines() {
+                lines = lines.push(line(format!("{:>5} + {added}", ""), |theme| theme.accent));
+            }
+        }
+        list = list.push(lines);
+    }
+
+    let files = match preview.changes.len() {
+        1 => String::from("1 change"),
+        len => format!("{len} changes"),
+    };
+    container(
+        column![
+            text(files),
+            scrollable(list).width(Length::Fill).height(Length::Fill),
+            row![
+                button(text("Apply"))
+                    .style(appearance::button::menu)
+                    .on_press(Message::ApplyWorkspaceEdit),
+                button(text("Cancel"))
+                    .style(appearance::button::menu)
+                    .on_press(Message::DismissModal),
+            ]
+            .spacing(8),
+        ]
+        .spacing(10)
+        .padding(Padding::from([10, 0])),
+    )
+    .height(Length::Fixed(600.0))
+    .width(Length::Fixed(700.0))
+    .into()
+}
@@ -8,6 +8,10 @@ use iced::{
 use laurel_common::{appearance, Element};
 use std::{collections::VecDeque, fs, path::Path};
 
+use super::edit_preview::{edit_preview, EditPreview};
+use super::location_picker::{location_picker, LocationItem};
+use super::message_request::{message_request, MessageRequest};
+use super::unsaved_changes::{unsaved_changes, UnsavedChanges};
 use crate::Message;
 
 /**
@@ -17,17 +21,29 @@ use crate::Message;
 #[derive(Debug)]
 pub enum Modal {
     FileSelector(String),
+    /// A choice between the locations the language server found
+    Locations(Vec<LocationItem>),
+    /// The changes of a workspace edit, to be applied or cancelled
+    EditPreview(EditPreview),
+    /// A message of the language server with the actions to answer it
+    MessageRequest(MessageRequest),
+    /// Whether to save or discard the changes of the open document before leaving it
+    UnsavedChanges(UnsavedChanges),
 }
 
 impl Modal {
     pub fn show(&self, filter: &str) -> Element<'static, Message> {
         match self {
             Modal::FileSelector(file) => file_selector(file, filter),
+            Modal::Locations(items) => location_picker(items, filter),
+            Modal::EditPreview(preview) => edit_preview(preview),
+            Modal::MessageRequest(request) => message_request(request),
+            Modal::UnsavedChanges(prompt) => unsaved_changes(prompt),
         }
     }
 }
 
-fn matches(choice: &str, pattern: &str) -> bool {
+pub fn matches(choice: &str, pattern: &str) -> bool {
     let matcher = SkimMatcher::default();
     if pattern.is_empty() {
         return true;
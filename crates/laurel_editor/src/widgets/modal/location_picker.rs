@@ -0,0 +1,97 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+use async_lsp::lsp_types::Location;
+use iced::{
+    widget::{
+        button, column, container, scrollable, scrollable::Scrollbar, text, text_input, Column,
+    },
+    Font, Length, Padding,
+};
+use laurel_common::{appearance, Element};
+
+use super::file_selector::matches;
+use crate::Message;
+
+/**
+ * A location of the language server as it's listed, along with the line it points at.
+ */
+#[derive(Debug, Clone)]
+pub struct LocationItem {
+    pub location: Location,
+    /// The file and where in it the location is, counting from 1
+    pub label: String,
+    pub preview: String,
+}
+
+impl LocationItem {
+    /**
+     * Lists a location, `line` is the text it points at or `None` to read it from the file.
+     */
+    pub fn new(location: Location, line: Option<String>) -> Self {
+        let path = location.uri.to_file_path().map_or_else(
+            |_| location.uri.to_string(),
+            |path| path.display().to_string(),
+        );
+        let start = location.range.start;
+        let label = format!("{path}:{}:{}", start.line + 1, start.character + 1);
+        let preview = line
+            .or_else(|| line_on_disk(&location))
+            .unwrap_or_default()
+            .trim()
+            .to_owned();
+        Self {
+            location,
+            label,
+            preview,
+        }
+    }
+}
+
+/**
+ * The line a location starts on, read from its file.
+ */
+pub fn line_on_disk(location: &Location) -> Option<String> {
+    let file = File::open(location.uri.to_file_path().ok()?).ok()?;
+    let line = location.range.start.line as usize;
+    BufReader::new(file).lines().nth(line)?.ok()
+}
+
+/**
+ * Lists the locations whose file matches the filter, e.g. the definitions of a symbol.
+ */
+pub fn location_picker(items: &[LocationItem], filter: &str) -> Element<'static, Message> {
+    let mut buttons = Column::new();
+
+    for item in items {
+        if matches(&item.label, filter) {
+            buttons = buttons.push(
+                button(column![
+                    text(item.label.clone()).size(12.0),
+                    text(item.preview.clone()).font(Font::MONOSPACE),
+                ])
+                .width(Length::Fill)
+                .style(appearance::button::menu)
+                .on_press(Message::OpenLocation(item.location.clone())),
+            )
+        }
+    }
+
+    container(
+        column!(
+            text_input("Filter locations", filter).on_input(Message::FileFilter),
+            scrollable(buttons)
+                .width(Length::Fill)
+                .direction(scrollable::Direction::Vertical(
+                    Scrollbar::default().scroller_width(0.0).width(0.0),
+                ))
+        )
+        .spacing(0)
+        .padding(Padding::from([10, 0])),
+    )
+    .height(Length::Fixed(600.0))
+    .width(Length::Fixed(600.0))
+    .into()
+}
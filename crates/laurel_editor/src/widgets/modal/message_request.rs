@@ -0,0 +1,99 @@
+use async_lsp::lsp_types::{MessageActionItem, MessageType};
+use iced::{
+    widget::{button, column, container, text, Row},
+    Length, Padding,
+};
+use laurel_common::{appearance, Element};
+use laurel_lsp::Responder;
+
+use crate::Message;
+
+/**
+ * A message the language server asked to show, answered with one of its actions, or one of the
+ * editor's own. Dismissing it drops the responder, which answers with no action.
+ */
+#[derive(Debug)]
+pub struct MessageRequest {
+    typ: MessageType,
+    message: String,
+    actions: Vec<MessageActionItem>,
+    /// `None` for the editor's own messages, which aren't answered
+    responder: Option<Responder<Option<MessageActionItem>>>,
+}
+
+impl MessageRequest {
+    pub fn new(
+        typ: MessageType,
+        message: String,
+        actions: Vec<MessageActionItem>,
+        responder: Responder<Option<MessageActionItem>>,
+    ) -> Self {
+        Self {
+            typ,
+            message,
+            actions,
+            responder: Some(responder),
+        }
+    }
+
+    /**
+     * A message of the editor itself, which only has to be dismissed.
+     */
+    pub fn notice(typ: MessageType, message: String) -> Self {
+        Self {
+            typ,
+            message,
+            actions: Vec::new(),
+            responder: None,
+        }
+    }
+
+    /**
+     * Answers with the action at `index`, or with none when there's no such action.
+     */
+    pub fn answer(mut self, index: usize) {
+        let action = (index < self.actions.len()).then(|| self.actions.swap_remove(index));
+        if let Some(responder) = self.responder {
+            responder.respond(action);
+        }
+    }
+}
+
+fn heading(typ: MessageType) -> &'static str {
+    match typ {
+        MessageType::ERROR => "Error",
+        MessageType::WARNING => "Warning",
+        _ => "Language server",
+    }
+}
+
+/**
+ * Shows the message with a button for each of its actions.
+ */
+pub fn message_request(request: &MessageRequest) -> Element<'static, Message> {
+    let mut actions = Row::new().spacing(8);
+    for (index, action) in request.actions.iter().enumerate() {
+        actions = actions.push(
+            button(text(action.title.clone()))
+                .style(appearance::button::menu)
+                .on_press(Message::AnswerMessage(index)),
+        );
+    }
+    actions = actions.push(
+        button(text("Dismiss"))
+            .style(appearance::button::menu)
+            .on_press(Message::DismissModal),
+    );
+
+    container(
+        column![
+            text(heading(request.typ)).size(14.0),
+            text(request.message.clone()),
+            actions,
+        ]
+        .spacing(10)
+        .padding(Padding::from([10, 0])),
+    )
+    .width(Length::Fixed(500.0))
+    .into()
+}
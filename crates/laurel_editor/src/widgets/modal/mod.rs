@@ -1,2 +1,6 @@
+pub mod edit_preview;
 pub mod file_selector;
+pub mod location_picker;
+pub mod message_request;
 pub mod modal_overlay;
+pub mod unsaved_changes;
@@ -0,0 +1,46 @@
+use iced::{
+    widget::{button, column, container, row, text},
+    Length, Padding,
+};
+use laurel_common::{appearance, Element};
+
+use crate::{core::navigation::Navigation, Message};
+
+/**
+ * Asks what to do with the unsaved changes of the open document before going somewhere else.
+ */
+#[derive(Debug)]
+pub struct UnsavedChanges {
+    pub filename: String,
+    pub navigation: Navigation,
+}
+
+/**
+ * Shows the prompt with the buttons to save the changes, discard them or stay.
+ */
+pub fn unsaved_changes(prompt: &UnsavedChanges) -> Element<'static, Message> {
+    let actions = row![
+        button(text("Save"))
+            .style(appearance::button::menu)
+            .on_press(Message::LeaveUnsaved(true)),
+        button(text("Discard"))
+            .style(appearance::button::menu)
+            .on_press(Message::LeaveUnsaved(false)),
+        button(text("Cancel"))
+            .style(appearance::button::menu)
+            .on_press(Message::DismissModal),
+    ]
+    .spacing(8);
+
+    container(
+        column![
+            text("Unsaved changes").size(14.0),
+            text(format!("{} has unsaved changes.", prompt.filename)),
+            actions,
+        ]
+        .spacing(10)
+        .padding(Padding::from([10, 0])),
+    )
+    .width(Length::Fixed(500.0))
+    .into()
+}
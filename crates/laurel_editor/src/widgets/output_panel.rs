@@ -0,0 +1,101 @@
+use iced::{
+    widget::{button, column, container, row, scrollable, text, text_input, Column},
+    Font, Length,
+};
+use laurel_common::{appearance, Element, LaurelTheme};
+
+use crate::{
+    core::output::{OutputKind, OutputLog},
+    Message,
+};
+
+/**
+ * How many of the last entries are shown, the rest can still be copied.
+ */
+const SHOWN_ENTRIES: usize = 500;
+
+/**
+ * How much of an entry is shown, long messages of the trace are cut.
+ */
+const SHOWN_LENGTH: usize = 400;
+
+/**
+ * The output of the language servers, filtered by text and kind, newest at the bottom. Clicking
+ * an entry copies it.
+ */
+pub fn output_panel<'a>(
+    output: &'a OutputLog,
+    trace: bool,
+    theme: &LaurelTheme,
+) -> Element<'a, Message> {
+    let action = |label: &'a str, selected: bool, message: Message| {
+        let style = if selected {
+            appearance::button::selected
+        } else {
+            appearance::button::transparent
+        };
+        button(text(label).size(12.0))
+            .style(style)
+            .padding([0, 6])
+            .on_press(message)
+    };
+    let mut header = row![text_input("Filter output", output.filter())
+        .on_input(Message::OutputFilter)
+        .size(12.0)
+        .width(Length::Fill)]
+    .spacing(4);
+    for kind in OutputKind::ALL {
+        header = header.push(action(
+            kind.label(),
+            output.is_shown(kind),
+            Message::ToggleOutputKind(kind),
+        ));
+    }
+    header = header.push(action(
+        if trace { "Stop Trace" } else { "Start Trace" },
+        false,
+        Message::ToggleTrace,
+    ));
+    for (label, message) in [
+        ("Copy", Message::CopyOutput),
+        ("Clear", Message::ClearOutput),
+        ("Close", Message::ToggleOutput),
+    ] {
+        header = header.push(action(label, false, message));
+    }
+
+    let visible: Vec<_> = output.visible().rev().take(SHOWN_ENTRIES).collect();
+    let mut list = Column::new();
+    if visible.is_empty() {
+        list = list.push(text("No output").size(12.0).color(theme.muted));
+    }
+    for (index, entry) in visible.into_iter().rev() {
+        let line = match entry.text.char_indices().nth(SHOWN_LENGTH) {
+            Some((end, _)) => format!("{}…", &entry.text[..end]),
+            None => entry.text.clone(),
+        };
+        list = list.push(
+            button(text(line).size(12.0).font(Font::MONOSPACE))
+                .width(Length::Fill)
+                .padding([1, 12])
+                .style(appearance::button::transparent)
+                .on_press(Message::CopyOutputEntry(index)),
+        );
+    }
+
+    container(
+        column![
+            header,
+            scrollable(list)
+                .anchor_bottom()
+                .width(Length::Fill)
+                .height(Length::Fill)
+        ]
+        .spacing(6),
+    )
+    .padding(8)
+    .width(Length::Fill)
+    .height(Length::Fixed(240.0))
+    .style(appearance::container::menu)
+    .into()
+}
@@ -0,0 +1,104 @@
+use iced::{
+    widget::{button, column, container, row, scrollable, text, text_input, Column},
+    Length,
+};
+use laurel_common::{appearance, Element, LaurelTheme};
+use laurel_lsp::diagnostics::Severity;
+
+use crate::{
+    core::diagnostics::{severity_label, DiagnosticSort, WorkspaceDiagnostics},
+    Message,
+};
+
+/**
+ * Lists the diagnostics of every file, filtered by text and severity, with the file and line
+ * each of them is on. Clicking one goes to it.
+ */
+pub fn problems_panel<'a>(
+    diagnostics: &'a WorkspaceDiagnostics,
+    theme: &LaurelTheme,
+) -> Element<'a, Message> {
+    let action = |label: &'static str, selected: bool, message: Message| {
+        let style = if selected {
+            appearance::button::selected
+        } else {
+            appearance::button::transparent
+        };
+        button(text(label).size(12.0))
+            .style(style)
+            .padding([0, 6])
+            .on_press(message)
+    };
+    let problems = diagnostics.problems();
+    let count = match problems.len() {
+        0 => String::from("No problems"),
+        1 => String::from("1 problem"),
+        len => format!("{len} problems"),
+    };
+    let mut header = row![
+        text(count).size(12.0).width(Length::Fixed(90.0)),
+        text_input("Filter problems", diagnostics.filter())
+            .on_input(Message::DiagnosticFilter)
+            .size(12.0)
+            .width(Length::Fill),
+    ]
+    .spacing(4);
+    for severity in [
+        Severity::Error,
+        Severity::Warning,
+        Severity::Info,
+        Severity::Hint,
+    ] {
+        header = header.push(action(
+            severity_label(severity),
+            diagnostics.is_shown(severity),
+            Message::ToggleSeverity(severity),
+        ));
+    }
+    for sort in DiagnosticSort::ALL {
+        header = header.push(action(
+            sort.label(),
+            diagnostics.sort() == sort,
+            Message::SortDiagnostics(sort),
+        ));
+    }
+    header = header.push(action("Previous", false, Message::PreviousDiagnostic));
+    header = header.push(action("Next", false, Message::NextDiagnostic));
+    header = header.push(action("Close", false, Message::ToggleProblems));
+
+    let mut list = Column::new();
+    for problem in problems {
+        let start = problem.issue.diagnostic.range.start;
+        list = list.push(
+            button(
+                row![
+                    text("●").size(12.0).color(problem.issue.severity.color()),
+                    text(format!(
+                        "{}:{}:{}",
+                        problem.path(),
+                        start.line + 1,
+                        start.character + 1
+                    ))
+                    .size(12.0)
+                    .color(theme.muted)
+                    .width(Length::FillPortion(1)),
+                    text(problem.issue.message.lines().next().unwrap_or_default())
+                        .size(12.0)
+                        .width(Length::FillPortion(3)),
+                ]
+                .spacing(8),
+            )
+            .width(Length::Fill)
+            .padding([2, 12])
+            .style(appearance::button::transparent)
+            .on_press(Message::OpenLocation(problem.location())),
+        );
+    }
+
+    container(column![header, scrollable(list).width(Length::Fill)].spacing(6))
+        .padding(8)
+        .width(Length::Fill)
+        .height(Length::Fixed(200.0))
+        .style(appearance::container::menu)
+        .into()
+}
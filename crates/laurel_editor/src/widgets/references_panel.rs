@@ -0,0 +1,77 @@
+use iced::{
+    widget::{button, column, container, row, scrollable, text, Column},
+    Font, Length,
+};
+use laurel_common::{appearance, Element, LaurelTheme};
+
+use crate::{core::references::References, Message};
+
+/**
+ * Lists the references that were found under their files, with the line each of them is on.
+ * Clicking one goes to it.
+ */
+pub fn references_panel<'a>(
+    references: &'a References,
+    theme: &LaurelTheme,
+) -> Element<'a, Message> {
+    let count = match references.len() {
+        0 => String::from("No references"),
+        1 => String::from("1 reference"),
+        len => format!("{len} references"),
+    };
+    let action = |label: &'a str, message: Message| {
+        button(text(label).size(12.0))
+            .style(appearance::button::transparent)
+            .padding([0, 6])
+            .on_press(message)
+    };
+    let header = row![
+        text(count).size(12.0).width(Length::Fill),
+        action("Previous", Message::PreviousReference),
+        action("Next", Message::NextReference),
+        action("Close", Message::CloseReferences),
+    ]
+    .spacing(4);
+
+    let mut list = Column::new();
+    for (uri, indices) in references.files() {
+        let path = uri
+            .to_file_path()
+            .map_or_else(|_| uri.to_string(), |path| path.display().to_string());
+        list = list.push(text(path).size(12.0).color(theme.muted));
+
+        for index in indices {
+            let start = references.locations()[index].range.start;
+            let style = if references.current() == Some(index) {
+                appearance::button::selected
+            } else {
+                appearance::button::transparent
+            };
+            list = list.push(
+                button(
+                    row![
+                        text(format!("{}:{}", start.line + 1, start.character + 1))
+                            .size(12.0)
+                            .color(theme.muted)
+                            .width(Length::Fixed(60.0)),
+                        text(references.preview(index))
+                            .size(12.0)
+                            .font(Font::MONOSPACE),
+                    ]
+                    .spacing(8),
+                )
+                .width(Length::Fill)
+                .padding([2, 12])
+                .style(style)
+                .on_press(Message::OpenReference(index)),
+            );
+        }
+    }
+
+    container(column![header, scrollable(list).width(Length::Fill)].spacing(6))
+        .padding(8)
+        .width(Length::Fill)
+        .height(Length::Fixed(200.0))
+        .style(appearance::container::menu)
+        .into()
+}
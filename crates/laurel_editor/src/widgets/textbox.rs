@@ -7,12 +7,14 @@ use iced::mouse;
 use iced::widget::canvas;
 use iced::widget::canvas::Cache;
 use iced::widget::canvas::Text;
-use iced::{Color, Font, Length, Pixels, Point, Rectangle, Renderer, Size, Task};
+use iced::{Font, Length, Pixels, Point, Rectangle, Renderer, Size, Task};
 use laurel_common::LaurelTheme;
 
 use crate::core::buffer::Buffer;
 use crate::core::buffer::TextInfo;
 use crate::core::document_change::DocumentChange;
+use crate::core::inlay_hints::{self, InlayHints, LineHint, LineHints};
+use crate::core::snippet::Snippet;
 use crate::highlighter::HighlightItem;
 
 use crate::Message;
@@ -21,13 +23,19 @@ use crate::VirtualWindow;
 
 use crate::highlighter::color_selector::ColorSelector;
 
-use laurel_lsp::LspClientNotification;
+use async_lsp::lsp_types::{SemanticTokens, SemanticTokensDelta, SemanticTokensLegend, TextEdit};
+use laurel_lsp::{diagnostics::ClientDiagnostics, LspClientNotification, TextChange};
 
+use super::floating_text::code_actions::CodeActionMenu;
+use super::floating_text::completion::CompletionPopup;
 use super::floating_text::floating_text_element::FloatingElement;
 use super::floating_text::floating_text_element::FloatingText;
+use super::floating_text::markdown::Block;
+use super::floating_text::rename::RenameInput;
+use super::floating_text::signature_help::SignaturePopup;
 
 use laurel_common::{
-    text::{Cursor, Position},
+    text::{Cursor, Position, Range},
     Canvas, Element,
 };
 
@@ -40,6 +48,18 @@ pub struct Textbox {
     longest_line: Cell<usize>,
     text_width: Cell<f32>,
     floating_element: Option<FloatingElement>,
+    /// Kept apart from the floating element, as typing filters it rather than closing it
+    completion: Option<CompletionPopup>,
+    /// The signatures of the call at the cursor, which stay open while its arguments are typed
+    signature_help: Option<SignaturePopup>,
+    /// The references that are listed in the panel and are in this document, in characters
+    references: Vec<Range>,
+    /// The new name of the symbol at the cursor while it's typed, which takes the focus
+    rename: Option<RenameInput>,
+    /// The code actions at the cursor to pick from
+    code_actions: Option<CodeActionMenu>,
+    /// The inlay hints of the document, drawn between its characters
+    inlay_hints: InlayHints,
     is_focused: bool,
 }
 
@@ -57,6 +77,12 @@ impl Textbox {
             is_focused: false,
             // floating_element: Some(FloatingText::Diagnostic("Something\n".repeat(20).to_owned()))
             floating_element: None,
+            completion: None,
+            signature_help: None,
+            references: Vec::new(),
+            rename: None,
+            code_actions: None,
+            inlay_hints: InlayHints::default(),
         }
     }
 
@@ -169,10 +195,32 @@ impl Textbox {
         self.buffer.selection.set_end(pos);
     }
 
-    pub fn floating_element(&self) -> Option<Element<Message, Renderer>> {
-        self.floating_element
-            .as_ref()
-            .map(|element| element.view_box.show())
+    pub fn floating_element(&self, theme: &LaurelTheme) -> Option<Element<Message, Renderer>> {
+        if let Some(rename) = &self.rename {
+            return Some(rename.view());
+        }
+        if let Some(menu) = &self.code_actions {
+            return Some(menu.view(theme));
+        }
+        if let Some(completion) = &self.completion {
+            return Some(completion.view(theme));
+        }
+        if let Some(element) = &self.floating_element {
+            return Some(element.view_box.show(theme));
+        }
+        self.signature_help.as_ref().map(|popup| popup.view(theme))
+    }
+
+    /**
+     * Whether the floating element is shown above its position, which the signatures are so
+     * they don't cover the next lines of the call.
+     */
+    pub fn floats_above(&self) -> bool {
+        self.rename.is_none()
+            && self.code_actions.is_none()
+            && self.completion.is_none()
+            && self.floating_element.is_none()
+            && self.signature_help.is_some()
     }
 
     pub fn is_saved(&self) -> bool {
@@ -180,7 +228,20 @@ impl Textbox {
     }
 
     pub fn float_position(&self) -> Position {
-        let position = self.floating_element.clone().unwrap().position;
+        // The top of the line of the cursor
+        if self.floats_above() {
+            return self.buffer.cursor.0;
+        }
+        let position = self
+            .rename
+            .as_ref()
+            .map(RenameInput::position)
+            .or_else(|| self.code_actions.as_ref().map(CodeActionMenu::position))
+            .or_else(|| {
+                let completion = self.completion.as_ref()?;
+                Some(completion.completion().start())
+            })
+            .unwrap_or_else(|| self.floating_element.clone().unwrap().position);
         Position::new(position.line() + 1, position.character())
     }
 
@@ -194,6 +255,199 @@ impl Textbox {
         }
     }
 
+    /**
+     * Shows the hover information of the language server below `position`, which is in
+     * characters.
+     */
+    pub fn set_hover(&mut self, position: Position, blocks: Vec<Block>) {
+        self.floating_element = Some(FloatingElement {
+            view_box: FloatingText::Hover(blocks),
+            position,
+        });
+    }
+
+    pub fn is_showing_hover(&self) -> bool {
+        matches!(
+            self.floating_element,
+            Some(FloatingElement {
+                view_box: FloatingText::Hover(_),
+                ..
+            })
+        )
+    }
+
+    pub fn completion(&self) -> Option<&CompletionPopup> {
+        self.completion.as_ref()
+    }
+
+    pub fn completion_mut(&mut self) -> Option<&mut CompletionPopup> {
+        self.completion.as_mut()
+    }
+
+    /**
+     * Shows the completions below the start of the word they complete, or closes them.
+     */
+    pub fn set_completion(&mut self, completion: Option<CompletionPopup>) {
+        self.completion = completion;
+    }
+
+    pub fn signature_help(&self) -> Option<&SignaturePopup> {
+        self.signature_help.as_ref()
+    }
+
+    pub fn signature_help_mut(&mut self) -> Option<&mut SignaturePopup> {
+        self.signature_help.as_mut()
+    }
+
+    /**
+     * Shows the signatures above the cursor, or closes them.
+     */
+    pub fn set_signature_help(&mut self, signature_help: Option<SignaturePopup>) {
+        self.signature_help = signature_help;
+    }
+
+    pub fn rename(&self) -> Option<&RenameInput> {
+        self.rename.as_ref()
+    }
+
+    pub fn rename_mut(&mut self) -> Option<&mut RenameInput> {
+        self.rename.as_mut()
+    }
+
+    /**
+     * Shows the input of the new name below the symbol that's renamed, or closes it.
+     */
+    pub fn set_rename(&mut self, rename: Option<RenameInput>) {
+        self.rename = rename;
+    }
+
+    pub fn code_actions(&self) -> Option<&CodeActionMenu> {
+        self.code_actions.as_ref()
+    }
+
+    pub fn code_actions_mut(&mut self) -> Option<&mut CodeActionMenu> {
+        self.code_actions.as_mut()
+    }
+
+    /**
+     * Shows the code actions below the cursor, or closes them.
+     */
+    pub fn set_code_actions(&mut self, menu: Option<CodeActionMenu>) {
+        self.code_actions = menu;
+    }
+
+    pub fn take_code_actions(&mut self) -> Option<CodeActionMenu> {
+        self.code_actions.take()
+    }
+
+    /**
+     * Highlights the references of the symbol that were found, in characters.
+     */
+    pub fn set_references(&mut self, references: Vec<Range>) {
+        self.references = references;
+        self.clear();
+    }
+
+    pub fn inlay_hints(&self) -> &InlayHints {
+        &self.inlay_hints
+    }
+
+    /**
+     * The inlay hints, to move them along with the changes to the text.
+     */
+    pub fn set_diagnostics(&mut self, diagnostics: Option<ClientDiagnostics>) {
+        self.buffer.set_diagnostics(diagnostics);
+    }
+
+    pub fn inlay_hints_mut(&mut self) -> &mut InlayHints {
+        self.clear();
+        &mut self.inlay_hints
+    }
+
+    pub fn set_inlay_hints(&mut self, hints: InlayHints) {
+        self.inlay_hints = hints;
+        self.clear();
+    }
+
+    /**
+     * The inlay hints of a line at the characters they're drawn before.
+     */
+    pub fn line_hints(&self, line: usize) -> LineHints {
+        let document = self.buffer.document();
+        LineHints::new(
+            self.inlay_hints
+                .on_line(line as u32)
+                .map(|(index, hint)| LineHint {
+                    index,
+                    character: document
+                        .utf16_to_position(&hint.position.into())
+                        .character(),
+                    label: inlay_hints::label(hint),
+                })
+                .collect(),
+        )
+    }
+
+    /**
+     * Applies edits from the language server, see [`Buffer::apply_text_edits`].
+     */
+    pub fn apply_text_edits(&mut self, edits: Vec<TextEdit>, anchor: Option<usize>) {
+        self.buffer.apply_text_edits(edits, anchor);
+        self.clear_floating_elements();
+    }
+
+    /**
+     * Applies the edits of a formatter, see [`Buffer::apply_formatting`].
+     */
+    pub fn apply_formatting(&mut self, edits: Vec<TextEdit>) {
+        self.buffer.apply_formatting(edits);
+        self.clear_floating_elements();
+    }
+
+    pub fn close_undo_group(&mut self) {
+        self.buffer.close_undo_group();
+    }
+
+    /**
+     * Undoes the last group of changes, or redoes the last one that was undone.
+     */
+    pub fn undo(&mut self, redo: bool) {
+        let changed = if redo {
+            self.buffer.redo()
+        } else {
+            self.buffer.undo()
+        };
+        if changed {
+            self.clear_floating_elements();
+            self.completion = None;
+            self.clear();
+        }
+    }
+
+    /**
+     * Starts filling in a snippet whose text was just inserted before the cursor.
+     */
+    pub fn start_snippet(&mut self, snippet: Snippet) {
+        self.buffer.start_snippet(snippet);
+        self.clear_floating_elements();
+    }
+
+    /**
+     * Moves to the next or previous tab stop of the snippet, returning `false` when there's no
+     * snippet.
+     */
+    pub fn move_tab_stop(&mut self, forward: bool) -> bool {
+        if forward {
+            self.buffer.next_tab_stop()
+        } else {
+            self.buffer.previous_tab_stop()
+        }
+    }
+
+    pub fn end_snippet(&mut self) {
+        self.buffer.end_snippet();
+    }
+
     pub fn insert(&mut self, character: String) -> Option<DocumentChange> {
         let _character_len = character.len();
         let mut document_change: Option<DocumentChange> = None;
@@ -204,12 +458,15 @@ impl Textbox {
         document_change
     }
 
+    pub fn take_changes(&mut self) -> Vec<TextChange> {
+        self.buffer.take_changes()
+    }
+
     pub fn process_lsp_notification(&mut self, message: LspClientNotification) {
         match message {
             LspClientNotification::Diagnostics(diagnostic) => {
-                self.buffer.add_diagnostics(diagnostic)
+                self.buffer.set_diagnostics(Some(diagnostic))
             }
-            LspClientNotification::Progress => println!("Progress!!"),
             _ => (),
         }
     }
@@ -219,7 +476,8 @@ impl Textbox {
     }
 
     pub fn clear_floating_elements(&mut self) {
-        self.floating_element = None
+        self.floating_element = None;
+        self.code_actions = None;
     }
 
     pub fn longest_line(&self) -> usize {
@@ -278,6 +536,26 @@ impl Textbox {
             .into()
     }
 
+    pub fn reload_queries(&mut self) {
+        self.buffer.reload_queries();
+        self.clear();
+    }
+
+    pub fn set_semantic_tokens(&mut self, legend: &SemanticTokensLegend, tokens: SemanticTokens) {
+        self.buffer.set_semantic_tokens(legend, tokens);
+        self.clear();
+    }
+
+    pub fn apply_semantic_delta(
+        &mut self,
+        previous_result_id: &str,
+        delta: SemanticTokensDelta,
+    ) -> bool {
+        let applied = self.buffer.apply_semantic_delta(previous_result_id, delta);
+        self.clear();
+        applied
+    }
+
     pub fn clear(&self) {
         self.text_cache.clear()
     }
@@ -287,6 +565,17 @@ impl Textbox {
         self
     }
 
+    /**
+     * Puts the cursor at a position and scrolls to it, e.g. after going to a definition.
+     */
+    pub fn jump_to(&mut self, position: Position) {
+        self.set_selection(position).set_curor(position);
+        self.clear_floating_elements();
+        let (text_width, longest_line) = (self.text_width(), self.longest_line());
+        self.correct_position_to_cursor(text_width, longest_line);
+        self.clear();
+    }
+
     pub fn set_offset(&mut self, offset_x: f32, offset_y: f32) {
         self.buffer.window.set_offset(offset_x, offset_y)
     }
@@ -320,7 +609,11 @@ impl Textbox {
     }
 
     pub fn get_window_point_from_position(&self, position: Position) -> Point {
-        let x = position.character() as f32 * self.text_width.get();
+        // Characters are drawn half the measured width apart, see `draw_line`
+        let column = self
+            .line_hints(position.line())
+            .column(position.character(), false);
+        let x = column as f32 * self.text_width.get() / 2.0;
         let y = position.line() as f32 * self.line_height;
         Point { x, y }
     }
@@ -328,53 +621,67 @@ impl Textbox {
     fn draw_line(
         &self,
         frame: &mut iced::widget::canvas::Frame,
+        theme: &LaurelTheme,
         line_number: usize,
-        text_width: f32,
         content: &str,
         highlighter: &mut IntoIter<HighlightItem>,
         highlight_item: &mut Option<HighlightItem>,
     ) {
+        let text_width = self.text_width.get();
         let y = self.line_height * line_number as f32;
         if !self.buffer.window.within(y) {
             return;
         }
 
+        let hints = self.line_hints(line_number);
         if self.buffer.cursor.0.line() == line_number
             && self.buffer.cursor.0.character() == content.len()
         {
-            let point = Point::new(text_width * content.len() as f32, y);
+            let column = hints.column(content.len(), false);
+            let point = Point::new(text_width * column as f32, y);
             frame.fill_rectangle(
                 point,
                 // Size::new(text_width, self.height),
                 Size::new(2.0, self.line_height),
-                Color::from_rgba8(83, 83, 83, 0.2),
+                theme.cursor,
             )
         }
+        let color_selector = ColorSelector::new(theme);
         for (c_index, c) in content.chars().enumerate() {
-            let x = text_width / 2.0 * c_index as f32;
+            let x = text_width / 2.0 * hints.column(c_index, true) as f32;
             let point = Point::new(x, y);
 
-            // Draw Cursor
+            // Draw Cursor, before the hints at the character
             if self.buffer.cursor.0.line() == line_number
                 && self.buffer.cursor.0.character() == c_index
             {
+                let x = text_width / 2.0 * hints.column(c_index, false) as f32;
                 frame.fill_rectangle(
-                    point,
+                    Point::new(x, y),
                     // Size::new(text_width, self.height),
                     Size::new(2.0, self.line_height),
-                    Color::from_rgba8(83, 83, 83, 0.2),
+                    theme.cursor,
                 )
             }
 
-            // Draw selection
+            // Draw references
             let pos = Position::new(line_number, c_index);
+            if self.references.iter().any(|range| range.pos_in_range(pos)) {
+                frame.fill_rectangle(
+                    point,
+                    Size::new(text_width / 2.0, self.line_height),
+                    theme.highlight_med,
+                )
+            }
+
+            // Draw selection
             if self.buffer.selection.correct_position().is_within(&pos)
                 && !self.buffer.selection.is_empty()
             {
                 frame.fill_rectangle(
                     point,
                     Size::new(text_width / 2.0, self.line_height),
-                    Color::from_rgba8(83, 83, 83, 0.1),
+                    theme.selection,
                 )
             }
 
@@ -390,8 +697,6 @@ impl Textbox {
                 )
             }
 
-            let color_selector = ColorSelector;
-
             let text = Text {
                 position: point,
                 font: self.font,
@@ -407,6 +712,20 @@ impl Textbox {
 
             frame.fill_text(text);
         }
+
+        // Draw inlay hints
+        for (column, hint) in hints.placed() {
+            for (offset, c) in hint.label.chars().enumerate() {
+                frame.fill_text(Text {
+                    position: Point::new(text_width / 2.0 * (column + offset) as f32, y),
+                    font: self.font,
+                    content: String::from(c),
+                    size: self.font_size.into(),
+                    color: theme.muted,
+                    ..Text::default()
+                });
+            }
+        }
     }
 }
 
@@ -433,7 +752,7 @@ where
         &self,
         _state: &Self::State,
         renderer: &Renderer,
-        _theme: &LaurelTheme,
+        theme: &LaurelTheme,
         bounds: Rectangle,
         _cursor: iced::advanced::mouse::Cursor,
     ) -> Vec<canvas::Geometry<Renderer>> {
@@ -442,13 +761,12 @@ where
             let mut highlighter = self
                 .buffer
                 .get_highlighter()
-                .clone()
-                .unwrap()
+                .unwrap_or_default()
                 .captures
                 .into_iter();
             let mut highlight_item = highlighter.next();
             for (index, line) in self.buffer.lines().enumerate() {
-                let width = if self.text_width.get() == 0.0 {
+                if self.text_width.get() == 0.0 {
                     let width = renderer.default_size(
                         // "T",
                         // self.font_size,
@@ -456,18 +774,17 @@ where
                         // Shaping::Basic,
                     ).0;
                     self.text_width.set(width);
-                    width
-                } else {
-                    self.text_width.get()
-                };
+                }
 
-                if line.len_chars() > longest_line {
-                    longest_line = line.len_chars()
+                // The hints widen the line as much as its characters do
+                let width = self.line_hints(index).column(line.len_chars(), true);
+                if width > longest_line {
+                    longest_line = width
                 }
                 self.draw_line(
                     frame,
+                    theme,
                     index,
-                    width,
                     &line.to_string(),
                     &mut highlighter,
                     &mut highlight_item,
@@ -2,16 +2,16 @@ use iced::{
     advanced::{
         mouse, text,
         widget::{tree, Tree},
-        Layout, Widget,
+        Clipboard, Layout, Shell, Widget,
     },
-    overlay, Alignment, Font, Length, Padding, Pixels, Point, Size, Vector,
+    overlay, Alignment, Event, Font, Length, Padding, Pixels, Point, Rectangle, Size, Vector,
 };
 use laurel_common::{
     text::{CursorMessage, Position},
     Element, LaurelTheme,
 };
 
-use crate::core::document::Document;
+use crate::core::inlay_hints::LineHints;
 
 use super::{
     floating_text::floating_overlay::FloatingOverlay, textbox::Textbox, view_port::ViewPortMessage,
@@ -57,6 +57,10 @@ where
 #[derive(Default)]
 struct State {
     dragging: bool,
+    /// The text under the mouse, as it was last published
+    hovered: Option<Position>,
+    /// The inlay hint under the mouse, which isn't part of the text
+    hovered_hint: Option<usize>,
 }
 
 impl<'a, Message, Renderer> Widget<Message, LaurelTheme, Renderer>
@@ -92,12 +96,15 @@ where
             let point = self
                 .textbox
                 .get_window_point_from_position(self.textbox.float_position());
-            let overlay = overlay::Element::new(Box::new(FloatingOverlay::new(
-                element,
-                &mut tree.children[1],
-                layout.bounds().size(),
-                point,
-            )));
+            let overlay = overlay::Element::new(Box::new(
+                FloatingOverlay::new(
+                    element,
+                    &mut tree.children[1],
+                    layout.bounds().size(),
+                    point,
+                )
+                .above(self.textbox.floats_above()),
+            ));
             // let overlay = overlay::Element::new(
             //     layout.position(),
             //     Box::new(ModalOverlay::new(element, tree, layout.bounds().size(), None ))
@@ -170,6 +177,70 @@ where
         });
     }
 
+    fn update(
+        &mut self,
+        tree: &mut Tree,
+        event: &Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        viewport: &Rectangle,
+    ) {
+        self.child.as_widget_mut().update(
+            &mut tree.children[0],
+            event,
+            layout.children().next().unwrap(),
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            viewport,
+        );
+
+        let Event::Mouse(
+            mouse::Event::CursorMoved { .. }
+            | mouse::Event::CursorLeft
+            | mouse::Event::ButtonPressed(mouse::Button::Left),
+        ) = event
+        else {
+            return;
+        };
+        // Every character is drawn half the measured width apart
+        let glyph_width = self.textbox.text_width() / 2.0;
+        let bounds = layout.bounds();
+        let hit = cursor
+            .position_in(bounds)
+            .filter(|_| glyph_width > 0.0)
+            .map(|point| line_hit_test(self.textbox, self.line_height, glyph_width, point));
+
+        // A click moves the cursor, or goes to the definition with ctrl held
+        if let Event::Mouse(mouse::Event::ButtonPressed(_)) = event {
+            if let Some((position, _)) = hit {
+                shell.publish(Message::set_textbox_focus(true));
+                shell.publish(Message::from_cursor_position(position));
+            }
+            return;
+        }
+
+        let (hovered, hovered_hint) = match hit {
+            Some((_, Some(hint))) => (None, Some(hint)),
+            Some((position, None)) => (Some(position), None),
+            None => (None, None),
+        };
+
+        let state = tree.state.downcast_mut::<State>();
+        if state.hovered != hovered {
+            state.hovered = hovered;
+            shell.publish(Message::from_mouse_hover(hovered));
+        }
+        if state.hovered_hint != hovered_hint {
+            state.hovered_hint = hovered_hint;
+            shell.publish(Message::from_inlay_hint_hover(hovered_hint));
+        }
+    }
+
     // fn on_event(
     //     &mut self,
     //     tree: &mut Tree,
@@ -289,26 +360,40 @@ where
     }
 }
 
-fn monospace_hit_test(text: &str, glyph_width: f32, point: Point) -> f32 {
-    let result = (point.x / glyph_width).floor();
-    if result > text.len() as f32 {
-        return text.len() as f32;
-    }
-    result
+/**
+ * The character at the point and the index of the inlay hint when the point is on one, which
+ * moved the characters after it to the right.
+ */
+fn monospace_hit_test(
+    text: &str,
+    hints: &LineHints,
+    glyph_width: f32,
+    point: Point,
+) -> (usize, Option<usize>) {
+    let column = (point.x / glyph_width).floor() as usize;
+    let (character, hint) = hints.hit(column);
+    (character.min(text.len()), hint)
 }
 
-fn line_hit_test(text: &Document, line_height: f32, glyph_width: f32, point: Point) -> Position {
+fn line_hit_test(
+    textbox: &Textbox,
+    line_height: f32,
+    glyph_width: f32,
+    point: Point,
+) -> (Position, Option<usize>) {
+    let text = textbox.buffer().document();
     let mut line_num = (point.y / line_height).floor() as usize;
 
     let line = text.get_line(line_num).unwrap_or_else(|| {
         line_num = text.len();
         text.last_line().unwrap()
     });
-    let character = monospace_hit_test(
+    let (character, hint) = monospace_hit_test(
         line.as_str().unwrap_or(&line.to_string()),
+        &textbox.line_hints(line_num),
         glyph_width,
         point,
-    ) as usize;
+    );
 
-    Position::new(line_num, character)
+    (Position::new(line_num, character), hint)
 }
@@ -1,58 +1,148 @@
-use std::{ops::ControlFlow, path::Path, process::Stdio};
+use std::{
+    collections::HashMap,
+    ops::ControlFlow,
+    process::{ExitStatus, Stdio},
+    sync::{Arc, atomic::AtomicBool},
+    time::Duration,
+};
 
 use async_lsp::{
     LanguageClient, LanguageServer as _, ResponseError, ServerSocket,
     concurrency::ConcurrencyLayer,
     lsp_types::{
-        ClientCapabilities, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
-        DidOpenTextDocumentParams, DidSaveTextDocumentParams, HoverContents, HoverParams,
-        InitializeParams, InitializedParams, NumberOrString, Position, ProgressParams,
-        ProgressParamsValue, PublishDiagnosticsParams, Range, ShowMessageParams,
+        ApplyWorkspaceEditParams, ApplyWorkspaceEditResponse, CancelParams, ClientCapabilities,
+        CodeActionClientCapabilities, CodeActionContext, CodeActionKind,
+        CodeActionKindLiteralSupport, CodeActionLiteralSupport, CodeActionOrCommand,
+        CodeActionParams, CodeActionTriggerKind, CompletionClientCapabilities, CompletionContext,
+        CompletionItem, CompletionItemCapability, CompletionItemCapabilityResolveSupport,
+        CompletionItemKind, CompletionItemKindCapability, CompletionParams, CompletionResponse,
+        CompletionTriggerKind, ConfigurationParams, Diagnostic, DidChangeTextDocumentParams,
+        DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
+        DocumentFormattingParams, DocumentOnTypeFormattingParams, DocumentRangeFormattingParams,
+        DynamicRegistrationClientCapabilities, ExecuteCommandParams, FailureHandlingKind,
+        FormattingOptions, GotoCapability, GotoDefinitionParams, GotoDefinitionResponse,
+        HoverClientCapabilities, HoverContents, HoverParams, InitializeParams, InitializedParams,
+        InlayHint, InlayHintClientCapabilities, InlayHintParams,
+        InlayHintResolveClientCapabilities, InlayHintWorkspaceClientCapabilities, LSPAny,
+        LSPObject, Location, LogMessageParams, MarkupKind, MessageActionItem,
+        MessageActionItemCapabilities, NumberOrString, ParameterInformationSettings,
+        PartialResultParams, Position, PrepareRenameResponse, PrepareSupportDefaultBehavior,
+        ProgressParams, ProgressParamsValue, PublishDiagnosticsParams, Range,
+        ReferenceClientCapabilities, ReferenceContext, ReferenceParams, RegistrationParams,
+        RenameClientCapabilities, RenameParams, ResourceOperationKind, SemanticTokenModifier,
+        SemanticTokenType, SemanticTokens, SemanticTokensClientCapabilities,
+        SemanticTokensClientCapabilitiesRequests, SemanticTokensDeltaParams,
+        SemanticTokensFullDeltaResult, SemanticTokensFullOptions, SemanticTokensParams,
+        SemanticTokensResult, ServerCapabilities, ShowMessageParams,
+        ShowMessageRequestClientCapabilities, ShowMessageRequestParams, SignatureHelp,
+        SignatureHelpClientCapabilities, SignatureHelpContext, SignatureHelpParams,
+        SignatureHelpTriggerKind, SignatureInformationSettings, TextDocumentClientCapabilities,
         TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem,
-        TextDocumentPositionParams, TextDocumentSaveReason, Url, VersionedTextDocumentIdentifier,
-        WillSaveTextDocumentParams, WindowClientCapabilities, WorkDoneProgress,
-        WorkDoneProgressParams,
+        TextDocumentPositionParams, TextDocumentSaveReason, TextEdit, TokenFormat,
+        UnregistrationParams, Url, VersionedTextDocumentIdentifier, WillSaveTextDocumentParams,
+        WindowClientCapabilities, WorkDoneProgressCreateParams, WorkDoneProgressParams,
+        WorkspaceClientCapabilities, WorkspaceEdit, WorkspaceEditClientCapabilities,
+        notification::Cancel,
     },
     panic::CatchUnwindLayer,
     router::Router,
     tracing::TracingLayer,
 };
+use futures::{
+    FutureExt as _,
+    future::{self, BoxFuture},
+};
 use tokio::{
+    io::{AsyncBufReadExt as _, BufReader},
     process::{Child, Command},
     spawn,
     sync::{mpsc::UnboundedSender, oneshot},
+    task::{AbortHandle, JoinSet},
+    time::{Instant, timeout},
 };
 use tokio_util::compat::{TokioAsyncReadCompatExt as _, TokioAsyncWriteCompatExt as _};
 use tower::ServiceBuilder;
-use tracing::{debug, info, trace, warn};
+use tracing::{debug, error, info, trace, warn};
 
-use crate::diagnostics::{ClientDiagnostics, Issue};
+use crate::{
+    GotoKind, LspRequest, LspResponse, LspServerRequest, RequestId, Responder, ServerConfig,
+    ServerId, TextChange,
+    diagnostics::{ClientDiagnostics, Issue},
+    output::{ServerOutput, Tap},
+};
+
+use super::{
+    LspClientNotification,
+    error::{LspClientError, LspClientResult},
+};
 
-use super::{LspClientNotification, error::LspClientResult};
+/// How long changes are held back, so the changes that follow them can go out together
+pub const CHANGE_BATCH_DELAY: Duration = Duration::from_millis(50);
 
-static LANGUAGE: &str = "rust";
+/// How long the process gets to exit once the connection to it ended
+const EXIT_WAIT: Duration = Duration::from_secs(1);
 
 struct ServerStop;
 
+/// The connection to a language server, the requests of the gui are sent and answered here.
+///
+/// async-lsp numbers the requests to the server from 0 in the order they reach its main loop and
+/// doesn't expose the ids, yet they're needed to cancel a request. So the client counts them
+/// itself in `next_server_id`, which only holds as long as:
+///
+/// - every request to the server is sent through this client, the [`ServerSocket`] isn't shared;
+/// - each request is sent when its method on the socket is called, which the `LanguageServer`
+///   methods of the socket do, rather than when the returned future is first polled;
+/// - every request takes exactly one id with `take_server_id`, in the order they're sent.
+///   `initialize` is 0 and `shutdown` takes its id by hand, [`LspClient::request`] takes one for
+///   the rest, so each of its arms sends exactly one request.
 #[derive(Debug)]
 pub struct LspClient {
-    _process: Child,
+    process: Child,
     server: ServerSocket,
+    capabilities: Arc<ServerCapabilities>,
+    pending: Option<PendingChanges>,
+    /// The JSON-RPC id of the next request to the server, see the invariants above
+    next_server_id: i32,
+    /// The requests of the gui that are waiting for a response
+    requests: JoinSet<(RequestId, LspClientResult<LspResponse>)>,
+    running: HashMap<RequestId, RunningRequest>,
+}
+
+#[derive(Debug)]
+struct RunningRequest {
+    server_id: i32,
+    task: AbortHandle,
+}
+
+/// Changes to a document that haven't been sent yet
+#[derive(Debug)]
+struct PendingChanges {
+    uri: Url,
+    version: i32,
+    changes: Vec<TextDocumentContentChangeEvent>,
+    deadline: Instant,
 }
 
 struct LspClientState {
-    indexed_tx: Option<oneshot::Sender<()>>,
     lsp_sender: UnboundedSender<LspClientNotification>,
+    /// Where the requests of the server that the gui answers go
+    request_sender: UnboundedSender<LspServerRequest>,
+    /// The settings of the server, its initialization options in its section, which
+    /// `workspace/configuration` is answered from
+    settings: Option<LSPAny>,
 }
 
 impl LspClientState {
     fn new_router(
-        indexed_tx: oneshot::Sender<()>,
         lsp_sender: UnboundedSender<LspClientNotification>,
+        request_sender: UnboundedSender<LspServerRequest>,
+        settings: Option<LSPAny>,
     ) -> Router<Self> {
         let mut router = Router::from_language_client(LspClientState {
             lsp_sender,
-            indexed_tx: Some(indexed_tx),
+            request_sender,
+            settings,
         });
 
         router.event(Self::on_stop);
@@ -67,6 +157,16 @@ impl LspClientState {
     fn on_stop(&mut self, _: ServerStop) -> ControlFlow<async_lsp::Result<()>> {
         ControlFlow::Break(Ok(()))
     }
+
+    fn output(&self, output: ServerOutput) {
+        if self
+            .lsp_sender
+            .send(LspClientNotification::Output(output))
+            .is_err()
+        {
+            warn!("The gui is gone, the output of the server can't be shown");
+        }
+    }
 }
 
 impl LanguageClient for LspClientState {
@@ -75,20 +175,13 @@ impl LanguageClient for LspClientState {
 
     fn progress(&mut self, params: ProgressParams) -> Self::NotifyResult {
         debug!("{:?} {:?}", params.token, params.value);
-        // FIXME: Swap rustAnalyzer here for tinymist's name
-        if matches!(params.token, NumberOrString::String(s) if s == "rustAnalyzer/cachePriming")
-            && matches!(
-                params.value,
-                ProgressParamsValue::WorkDone(WorkDoneProgress::End(_))
-            )
+        let ProgressParamsValue::WorkDone(progress) = params.value;
+        if self
+            .lsp_sender
+            .send(LspClientNotification::Progress(params.token, progress))
+            .is_err()
         {
-            // Sometimes rust-analyzer auto-index multiple times?
-            if let Some(tx) = self.indexed_tx.take() {
-                let _: Result<_, _> = tx.send(());
-                info!("Sent indexing completion oneshot");
-            } else {
-                warn!("Indexing completion oneshot already sent");
-            }
+            warn!("The gui is gone, the progress can't be shown");
         }
         ControlFlow::Continue(())
     }
@@ -97,147 +190,418 @@ impl LanguageClient for LspClientState {
         let issues: Vec<Issue> = params.diagnostics.into_iter().map(Issue::from).collect();
         info!(issues = ?issues, "LSP diagnostics notification");
 
-        self.lsp_sender
+        if self
+            .lsp_sender
             .send(LspClientNotification::Diagnostics(ClientDiagnostics {
                 issues,
                 uri: params.uri,
             }))
-            .unwrap();
+            .is_err()
+        {
+            warn!("The gui is gone, the diagnostics can't be shown");
+        }
 
         ControlFlow::Continue(())
     }
 
     fn show_message(&mut self, params: ShowMessageParams) -> Self::NotifyResult {
         info!("Message {:?}: {}", params.typ, params.message);
+        self.output(ServerOutput::Message(params.typ, params.message));
         ControlFlow::Continue(())
     }
+
+    fn log_message(&mut self, params: LogMessageParams) -> Self::NotifyResult {
+        debug!("Log {:?}: {}", params.typ, params.message);
+        self.output(ServerOutput::Log(params.typ, params.message));
+        ControlFlow::Continue(())
+    }
+
+    fn work_done_progress_create(
+        &mut self,
+        params: WorkDoneProgressCreateParams,
+    ) -> BoxFuture<'static, Result<(), Self::Error>> {
+        // The progress itself comes as notifications with the token
+        debug!(token = ?params.token, "Progress created");
+        future::ready(Ok(())).boxed()
+    }
+
+    fn register_capability(
+        &mut self,
+        params: RegistrationParams,
+    ) -> BoxFuture<'static, Result<(), Self::Error>> {
+        // The client doesn't support registering anything after it's initialized, so the
+        // registrations are ignored and the server only gets what it said it supports then
+        let methods: Vec<String> = params
+            .registrations
+            .into_iter()
+            .map(|registration| registration.method)
+            .collect();
+        info!(?methods, "Ignored the capabilities the server registered");
+        future::ready(Ok(())).boxed()
+    }
+
+    fn unregister_capability(
+        &mut self,
+        params: UnregistrationParams,
+    ) -> BoxFuture<'static, Result<(), Self::Error>> {
+        for unregistration in params.unregisterations {
+            info!(
+                id = unregistration.id,
+                method = unregistration.method,
+                "Server unregistered a capability"
+            );
+        }
+        future::ready(Ok(())).boxed()
+    }
+
+    fn configuration(
+        &mut self,
+        params: ConfigurationParams,
+    ) -> BoxFuture<'static, Result<Vec<LSPAny>, Self::Error>> {
+        debug!(items = ?params.items, "Configuration requested");
+        let settings = params
+            .items
+            .iter()
+            .map(|item| settings_section(self.settings.as_ref(), item.section.as_deref()))
+            .collect();
+        future::ready(Ok(settings)).boxed()
+    }
+
+    fn show_message_request(
+        &mut self,
+        params: ShowMessageRequestParams,
+    ) -> BoxFuture<'static, Result<Option<MessageActionItem>, Self::Error>> {
+        let (responder, answer) = Responder::new();
+        let request = LspServerRequest::ShowMessage {
+            typ: params.typ,
+            message: params.message,
+            actions: params.actions.unwrap_or_default(),
+            responder,
+        };
+        if self.request_sender.send(request).is_err() {
+            warn!("The gui is gone, the message can't be shown");
+        }
+        // Nothing was picked when the message was dismissed
+        async move { Ok(answer.await.unwrap_or(None)) }.boxed()
+    }
+
+    fn inlay_hint_refresh(&mut self, (): ()) -> BoxFuture<'static, Result<(), Self::Error>> {
+        if self
+            .lsp_sender
+            .send(LspClientNotification::InlayHintRefresh)
+            .is_err()
+        {
+            warn!("The gui is gone, the inlay hints can't be refreshed");
+        }
+        future::ready(Ok(())).boxed()
+    }
+
+    fn apply_edit(
+        &mut self,
+        params: ApplyWorkspaceEditParams,
+    ) -> BoxFuture<'static, Result<ApplyWorkspaceEditResponse, Self::Error>> {
+        let (responder, answer) = Responder::new();
+        let request = LspServerRequest::ApplyEdit {
+            label: params.label,
+            edit: params.edit,
+            responder,
+        };
+        if self.request_sender.send(request).is_err() {
+            warn!("The gui is gone, the edit can't be applied");
+        }
+        async move {
+            Ok(answer.await.unwrap_or_else(|_| ApplyWorkspaceEditResponse {
+                applied: false,
+                failure_reason: Some(String::from("The editor didn't apply the edit")),
+                failed_change: None,
+            }))
+        }
+        .boxed()
+    }
 }
 
 impl LspClient {
+    /// Starts the server in the root of its workspace and initializes it. Once the connection to
+    /// the server ends, whether it was shut down or its process exited, `stopped` is sent why.
+    /// The messages to and from the server are traced while `trace` is on
     pub async fn initialize(
+        id: &ServerId,
+        config: &ServerConfig,
         lsp_sender: UnboundedSender<LspClientNotification>,
+        request_sender: UnboundedSender<LspServerRequest>,
+        stopped: oneshot::Sender<async_lsp::Result<()>>,
+        trace: Arc<AtomicBool>,
     ) -> LspClientResult<Self> {
-        // FIXME: Proper workspace recognition once the fs is in
-        let root_dir = Path::new(".")
-            .canonicalize()
-            .expect("Workspace root should be valid");
-
-        let (indexed_tx, indexed_rx) = oneshot::channel();
+        let root_uri = Url::from_file_path(&id.root).map_err(|()| {
+            LspClientError::FailedInitialization(format!(
+                "The root {} isn't an absolute path",
+                id.root.display()
+            ))
+        })?;
 
+        let output = lsp_sender.clone();
         let (mainloop, mut server) = async_lsp::MainLoop::new_client(|_server| {
             ServiceBuilder::new()
                 .layer(TracingLayer::default())
                 .layer(CatchUnwindLayer::default())
                 .layer(ConcurrencyLayer::default())
-                .service(LspClientState::new_router(indexed_tx, lsp_sender))
+                .service(LspClientState::new_router(
+                    lsp_sender,
+                    request_sender,
+                    settings(config),
+                ))
         });
 
-        let mut process = Command::new("rust-analyzer")
-            .current_dir(&root_dir)
+        let mut process = Command::new(&config.command)
+            .args(&config.args)
+            .current_dir(&id.root)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::inherit())
+            .stderr(Stdio::piped())
             .kill_on_drop(true)
             .spawn()
-            .expect("Failed to run rust-analyzer");
+            .map_err(|e| {
+                LspClientError::ProcessFailure(format!("Couldn't run `{}`: {e}", config.command))
+            })?;
+
+        let (Some(stdout), Some(stdin), Some(stderr)) = (
+            process.stdout.take(),
+            process.stdin.take(),
+            process.stderr.take(),
+        ) else {
+            return Err(LspClientError::ProcessFailure(String::from(
+                "The server has no pipes",
+            )));
+        };
+        let stdout = Tap::new(stdout.compat(), false, trace.clone(), output.clone());
+        let stdin = Tap::new(stdin.compat_write(), true, trace, output.clone());
 
-        let stdout = process.stdout.take().unwrap().compat();
-        let stdin = process.stdin.take().unwrap().compat_write();
+        // The server would block once the pipe is full, so it's read until it's closed
+        spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                trace!(line, "stderr");
+                let _ = output.send(LspClientNotification::Output(ServerOutput::Stderr(line)));
+            }
+        });
 
-        let _mainloop_future =
-            spawn(async move { mainloop.run_buffered(stdout, stdin).await.unwrap() });
+        spawn(async move {
+            let _ = stopped.send(mainloop.run_buffered(stdout, stdin).await);
+        });
 
         // Initialize
-        let root_uri = Url::from_file_path(&root_dir).unwrap();
         let init_ret = server
             .initialize(InitializeParams {
                 root_uri: Some(root_uri),
+                initialization_options: config.initialization_options.clone(),
                 capabilities: ClientCapabilities {
+                    workspace: Some(WorkspaceClientCapabilities {
+                        apply_edit: Some(true),
+                        workspace_edit: Some(workspace_edit_capabilities()),
+                        configuration: Some(true),
+                        inlay_hint: Some(InlayHintWorkspaceClientCapabilities {
+                            refresh_support: Some(true),
+                        }),
+                        execute_command: Some(DynamicRegistrationClientCapabilities {
+                            dynamic_registration: Some(false),
+                        }),
+                        ..WorkspaceClientCapabilities::default()
+                    }),
                     window: Some(WindowClientCapabilities {
                         work_done_progress: Some(true),
+                        show_message: Some(ShowMessageRequestClientCapabilities {
+                            message_action_item: Some(MessageActionItemCapabilities {
+                                additional_properties_support: Some(false),
+                            }),
+                        }),
                         ..WindowClientCapabilities::default()
                     }),
+                    text_document: Some(TextDocumentClientCapabilities {
+                        semantic_tokens: Some(semantic_tokens_capabilities()),
+                        hover: Some(HoverClientCapabilities {
+                            dynamic_registration: Some(false),
+                            content_format: Some(vec![MarkupKind::Markdown, MarkupKind::PlainText]),
+                        }),
+                        completion: Some(completion_capabilities()),
+                        signature_help: Some(signature_help_capabilities()),
+                        definition: Some(goto_capabilities()),
+                        declaration: Some(goto_capabilities()),
+                        type_definition: Some(goto_capabilities()),
+                        implementation: Some(goto_capabilities()),
+                        references: Some(ReferenceClientCapabilities {
+                            dynamic_registration: Some(false),
+                        }),
+                        rename: Some(RenameClientCapabilities {
+                            dynamic_registration: Some(false),
+                            prepare_support: Some(true),
+                            prepare_support_default_behavior: Some(
+                                PrepareSupportDefaultBehavior::IDENTIFIER,
+                            ),
+                            honors_change_annotations: Some(false),
+                        }),
+                        code_action: Some(code_action_capabilities()),
+                        formatting: Some(DynamicRegistrationClientCapabilities {
+                            dynamic_registration: Some(false),
+                        }),
+                        range_formatting: Some(DynamicRegistrationClientCapabilities {
+                            dynamic_registration: Some(false),
+                        }),
+                        on_type_formatting: Some(DynamicRegistrationClientCapabilities {
+                            dynamic_registration: Some(false),
+                        }),
+                        inlay_hint: Some(InlayHintClientCapabilities {
+                            dynamic_registration: Some(false),
+                            resolve_support: Some(InlayHintResolveClientCapabilities {
+                                properties: vec![
+                                    String::from("tooltip"),
+                                    String::from("label.tooltip"),
+                                ],
+                            }),
+                        }),
+                        ..TextDocumentClientCapabilities::default()
+                    }),
                     ..ClientCapabilities::default()
                 },
                 ..InitializeParams::default()
             })
             .await
-            .unwrap();
+            .map_err(|e| LspClientError::FailedInitialization(e.to_string()))?;
 
         info!("Initialized: {init_ret:?}");
-        server.initialized(InitializedParams {}).unwrap();
-
-        info!("Waiting for the LSP to index");
-
-        // Wait until indexed.
-        indexed_rx.await.unwrap();
-
-        info!("LSP indexed");
+        server
+            .initialized(InitializedParams {})
+            .map_err(|e| LspClientError::ChannelClosed(e.to_string()))?;
 
         Ok(Self {
-            _process: process,
+            process,
             server,
+            capabilities: Arc::new(init_ret.capabilities),
+            pending: None,
+            // `initialize` was the first request
+            next_server_id: 1,
+            requests: JoinSet::new(),
+            running: HashMap::new(),
         })
     }
 
+    pub fn capabilities(&self) -> Arc<ServerCapabilities> {
+        self.capabilities.clone()
+    }
+
     /// Shutdowns server, this method is not run on process termination.
     /// Only on user requested an explicit termination.
     pub async fn shutdown(&mut self) -> LspClientResult<()> {
         info!("LSP Client shutting down");
-        self.server.shutdown(()).await.unwrap();
-        self.server.exit(()).unwrap();
-        self.server.emit(ServerStop).unwrap();
+        self.take_server_id();
+        self.server
+            .shutdown(())
+            .await
+            .map_err(|e| LspClientError::RequestFailed(e.to_string()))?;
+        self.server
+            .exit(())
+            .map_err(|e| LspClientError::ChannelClosed(e.to_string()))?;
+        self.server
+            .emit(ServerStop)
+            .map_err(|e| LspClientError::ChannelClosed(e.to_string()))?;
         trace!("LSP Client shutdown complete");
         Ok(())
     }
 
-    pub async fn did_change(
+    /// How the process of the server ended, `None` when it's still running
+    pub async fn exit_status(&mut self) -> Option<ExitStatus> {
+        // The pipe breaks a moment before the process is gone
+        timeout(EXIT_WAIT, self.process.wait()).await.ok()?.ok()
+    }
+
+    /// Drops every request that's waiting for a response, returning their ids, since the server
+    /// won't answer them anymore
+    pub fn abort_requests(&mut self) -> Vec<RequestId> {
+        self.requests.abort_all();
+        self.running.drain().map(|(id, _)| id).collect()
+    }
+
+    /// Queues changes to a document. They're sent once [`CHANGE_BATCH_DELAY`] has passed since the
+    /// first of them, or before anything else is sent, so rapid typing goes out as one
+    /// notification.
+    pub fn queue_changes(
         &mut self,
         uri: Url,
-        range: Range,
-        changed_to: String,
+        changes: Vec<TextChange>,
+        version: i32,
     ) -> LspClientResult<()> {
-        trace!("didchange");
+        // The changes of another document can't share the notification
+        if self
+            .pending
+            .as_ref()
+            .is_some_and(|pending| pending.uri != uri)
+        {
+            self.flush_changes()?;
+        }
+
+        let pending = self.pending.get_or_insert_with(|| PendingChanges {
+            uri,
+            version,
+            changes: Vec::new(),
+            deadline: Instant::now() + CHANGE_BATCH_DELAY,
+        });
+        for change in changes {
+            // The whole text makes every change before it redundant
+            if change.range.is_none() {
+                pending.changes.clear();
+            }
+            pending.changes.push(TextDocumentContentChangeEvent {
+                range: change.range.map(Range::from),
+                // range_length is deprecated, keep as none.
+                range_length: None,
+                text: change.text,
+            });
+        }
+        pending.version = version;
+
+        Ok(())
+    }
+
+    /// When the queued changes have to be sent
+    pub fn changes_deadline(&self) -> Option<Instant> {
+        self.pending.as_ref().map(|pending| pending.deadline)
+    }
+
+    /// Sends the queued changes straight away
+    pub fn flush_changes(&mut self) -> LspClientResult<()> {
+        let Some(pending) = self.pending.take() else {
+            return Ok(());
+        };
+        trace!(version = pending.version, "didchange");
 
         self.server
             .did_change(DidChangeTextDocumentParams {
                 text_document: VersionedTextDocumentIdentifier {
-                    uri,
-                    // TODO: Increase the version number before we do this
-                    version: 1,
+                    uri: pending.uri,
+                    version: pending.version,
                 },
-                content_changes: vec![TextDocumentContentChangeEvent {
-                    // OPTIMIZATION we need to send only the sections that have changed.
-                    // range is the starting..ending lines that have changed.
-                    // text is the text from the sections that have changed.
-                    // range_length is deprecated, keep as none.
-                    range: Some(range),
-                    range_length: None,
-                    text: changed_to,
-                }],
+                content_changes: pending.changes,
             })
-            .unwrap();
-
-        trace!("didchanged");
-
-        Ok(())
+            .map_err(|e| LspClientError::ChannelClosed(e.to_string()))
     }
 
-    pub async fn did_open(&mut self, uri: Url, text: String) -> LspClientResult<()> {
+    pub async fn did_open(
+        &mut self,
+        uri: Url,
+        text: String,
+        language_id: String,
+    ) -> LspClientResult<()> {
         trace!("didopen");
 
         self.server
             .did_open(DidOpenTextDocumentParams {
                 text_document: TextDocumentItem {
                     uri,
-                    language_id: LANGUAGE.into(),
+                    language_id,
                     version: 0,
                     text,
                 },
             })
-            .unwrap();
-
-        Ok(())
+            .map_err(|e| LspClientError::ChannelClosed(e.to_string()))
     }
 
     pub async fn did_save(&mut self, uri: Url, text: Option<String>) -> LspClientResult<()> {
@@ -246,9 +610,7 @@ impl LspClient {
                 text_document: TextDocumentIdentifier { uri },
                 text,
             })
-            .unwrap();
-
-        Ok(())
+            .map_err(|e| LspClientError::ChannelClosed(e.to_string()))
     }
 
     pub async fn did_close(&mut self, uri: Url) -> LspClientResult<()> {
@@ -256,9 +618,7 @@ impl LspClient {
             .did_close(DidCloseTextDocumentParams {
                 text_document: TextDocumentIdentifier { uri },
             })
-            .unwrap();
-
-        Ok(())
+            .map_err(|e| LspClientError::ChannelClosed(e.to_string()))
     }
 
     pub async fn will_save(&mut self, uri: Url) -> LspClientResult<()> {
@@ -267,28 +627,814 @@ impl LspClient {
                 text_document: TextDocumentIdentifier { uri },
                 reason: TextDocumentSaveReason::MANUAL,
             })
-            .unwrap();
+            .map_err(|e| LspClientError::ChannelClosed(e.to_string()))
+    }
 
-        Ok(())
+    /// Sends a request of the gui without waiting for its response, which comes out of
+    /// [`LspClient::next_response`]
+    pub fn request(&mut self, id: RequestId, request: LspRequest) {
+        let response: BoxFuture<'static, LspClientResult<LspResponse>> = match request {
+            LspRequest::Shutdown => {
+                error!("Shutdown has to be awaited, it isn't sent as a request");
+                return;
+            }
+            LspRequest::SemanticTokens(uri) => self
+                .semantic_tokens(uri.clone())
+                .map(|tokens| tokens.map(|tokens| LspResponse::SemanticTokens(uri, tokens)))
+                .boxed(),
+            LspRequest::SemanticTokensDelta(uri, previous_result_id) => self
+                .semantic_tokens_delta(uri.clone(), previous_result_id.clone())
+                .map(|delta| {
+                    delta.map(|delta| {
+                        LspResponse::SemanticTokensDelta(uri, previous_result_id, delta)
+                    })
+                })
+                .boxed(),
+            LspRequest::Completion(uri, position, trigger) => self
+                .completion(uri.clone(), position, trigger)
+                .map(|completions| {
+                    completions.map(|(items, is_incomplete)| {
+                        LspResponse::Completion(uri, items, is_incomplete)
+                    })
+                })
+                .boxed(),
+            LspRequest::ResolveCompletion(item) => self
+                .resolve_completion(item)
+                .map(|item| item.map(LspResponse::ResolvedCompletion))
+                .boxed(),
+            LspRequest::SignatureHelp(uri, position, trigger, active) => self
+                .signature_help(uri.clone(), position, trigger, active)
+                .map(|help| help.map(|help| LspResponse::SignatureHelp(uri, help)))
+                .boxed(),
+            LspRequest::Goto(kind, uri, position) => self
+                .goto(kind, uri, position)
+                .map(move |locations| {
+                    locations.map(|locations| LspResponse::Locations(kind, locations))
+                })
+                .boxed(),
+            LspRequest::References(uri, position) => self
+                .references(uri, position)
+                .map(|locations| locations.map(LspResponse::References))
+                .boxed(),
+            LspRequest::PrepareRename(uri, position) => self
+                .prepare_rename(uri.clone(), position)
+                .map(move |response| {
+                    response.map(|response| LspResponse::PrepareRename(uri, position, response))
+                })
+                .boxed(),
+            LspRequest::Rename(uri, position, new_name) => self
+                .rename(uri, position, new_name)
+                .map(|edit| edit.map(LspResponse::Rename))
+                .boxed(),
+            LspRequest::CodeActions(uri, range, diagnostics, invoked) => self
+                .code_actions(uri, range, diagnostics, invoked)
+                .map(|actions| actions.map(LspResponse::CodeActions))
+                .boxed(),
+            LspRequest::ExecuteCommand(command) => self
+                .execute_command(command)
+                .map(|result| result.map(|()| LspResponse::None))
+                .boxed(),
+            LspRequest::Formatting(uri, options) => self
+                .formatting(uri, options)
+                .map(|edits| edits.map(LspResponse::Formatting))
+                .boxed(),
+            LspRequest::RangeFormatting(uri, range, options) => self
+                .range_formatting(uri, range, options)
+                .map(|edits| edits.map(LspResponse::Formatting))
+                .boxed(),
+            LspRequest::OnTypeFormatting(uri, position, character, options) => self
+                .on_type_formatting(uri, position, character, options)
+                .map(|edits| edits.map(LspResponse::Formatting))
+                .boxed(),
+            LspRequest::InlayHints(uri, range) => self
+                .inlay_hints(uri.clone(), range)
+                .map(move |hints| hints.map(|hints| LspResponse::InlayHints(uri, hints)))
+                .boxed(),
+            LspRequest::ResolveInlayHint(hint) => self
+                .resolve_inlay_hint(hint)
+                .map(|hint| hint.map(LspResponse::ResolvedInlayHint))
+                .boxed(),
+            LspRequest::Hover(uri, position) => self
+                .hover(uri.clone(), position)
+                .map(move |contents| {
+                    contents.map(|contents| LspResponse::Hover(uri, position, contents))
+                })
+                .boxed(),
+        };
+        // The arm above sent the request
+        let server_id = self.take_server_id();
+
+        let task = self.requests.spawn(async move { (id, response.await) });
+        self.running.insert(id, RunningRequest { server_id, task });
+    }
+
+    /// Drops a request and tells the server it doesn't have to answer it
+    pub fn cancel(&mut self, id: RequestId) -> LspClientResult<()> {
+        // It was already answered
+        let Some(request) = self.running.remove(&id) else {
+            return Ok(());
+        };
+        request.task.abort();
+        trace!(?id, server_id = request.server_id, "cancelrequest");
+
+        self.server
+            .notify::<Cancel>(CancelParams {
+                id: NumberOrString::Number(request.server_id),
+            })
+            .map_err(|e| LspClientError::ChannelClosed(e.to_string()))
     }
 
-    pub async fn hover(
+    /// Waits for the response to any of the requests, `None` when there are none
+    pub async fn next_response(&mut self) -> Option<(RequestId, LspClientResult<LspResponse>)> {
+        while let Some(joined) = self.requests.join_next().await {
+            match joined {
+                Ok((id, result)) => {
+                    // Otherwise it was cancelled after it was answered
+                    if self.running.remove(&id).is_some() {
+                        return Some((id, result));
+                    }
+                }
+                Err(e) if e.is_cancelled() => {}
+                Err(e) => error!(error = ?e, "A request panicked"),
+            }
+        }
+        None
+    }
+
+    /// Counts a request to the server, returning its JSON-RPC id. It has to be called once for
+    /// every request, right after it's sent
+    fn take_server_id(&mut self) -> i32 {
+        let id = self.next_server_id;
+        self.next_server_id += 1;
+        id
+    }
+
+    fn hover(
+        &mut self,
+        uri: Url,
+        position: Position,
+    ) -> impl Future<Output = LspClientResult<Option<HoverContents>>> + use<> {
+        let hover = self.server.hover(HoverParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        });
+
+        async move {
+            let hover = hover
+                .await
+                .map_err(|e| LspClientError::RequestFailed(e.to_string()))?;
+            Ok(hover.map(|h| h.contents))
+        }
+    }
+
+    /// Requests the completions at a position, returning them with whether they're incomplete
+    fn completion(
+        &mut self,
+        uri: Url,
+        position: Position,
+        trigger: Option<String>,
+    ) -> impl Future<Output = LspClientResult<(Vec<CompletionItem>, bool)>> + use<> {
+        let context = CompletionContext {
+            trigger_kind: match trigger {
+                Some(_) => CompletionTriggerKind::TRIGGER_CHARACTER,
+                None => CompletionTriggerKind::INVOKED,
+            },
+            trigger_character: trigger,
+        };
+        let result = self.server.completion(CompletionParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+            context: Some(context),
+        });
+
+        async move {
+            let result = result
+                .await
+                .map_err(|e| LspClientError::RequestFailed(e.to_string()))?;
+
+            Ok(match result {
+                Some(CompletionResponse::Array(items)) => (items, false),
+                Some(CompletionResponse::List(list)) => (list.items, list.is_incomplete),
+                None => (Vec::new(), false),
+            })
+        }
+    }
+
+    fn resolve_completion(
+        &mut self,
+        item: CompletionItem,
+    ) -> impl Future<Output = LspClientResult<CompletionItem>> + use<> {
+        let result = self.server.completion_item_resolve(item);
+        async move {
+            result
+                .await
+                .map_err(|e| LspClientError::RequestFailed(e.to_string()))
+        }
+    }
+
+    /// Requests the signatures of the call at a position. Asking again while signatures are
+    /// shown lets the server keep the overload that was picked.
+    fn signature_help(
         &mut self,
         uri: Url,
         position: Position,
-    ) -> LspClientResult<Option<HoverContents>> {
-        let hover = self
+        trigger: Option<String>,
+        active: Option<SignatureHelp>,
+    ) -> impl Future<Output = LspClientResult<Option<SignatureHelp>>> + use<> {
+        let trigger_kind = match (&trigger, &active) {
+            (Some(_), _) => SignatureHelpTriggerKind::TRIGGER_CHARACTER,
+            (None, Some(_)) => SignatureHelpTriggerKind::CONTENT_CHANGE,
+            (None, None) => SignatureHelpTriggerKind::INVOKED,
+        };
+        let result = self.server.signature_help(SignatureHelpParams {
+            context: Some(SignatureHelpContext {
+                trigger_kind,
+                trigger_character: trigger,
+                is_retrigger: active.is_some(),
+                active_signature_help: active,
+            }),
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        });
+
+        async move {
+            result
+                .await
+                .map_err(|e| LspClientError::RequestFailed(e.to_string()))
+        }
+    }
+
+    /// Requests the locations of the symbol at a position, turning links into the range of the
+    /// name they point at
+    fn goto(
+        &mut self,
+        kind: GotoKind,
+        uri: Url,
+        position: Position,
+    ) -> impl Future<Output = LspClientResult<Vec<Location>>> + use<> {
+        let params = GotoDefinitionParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let result = match kind {
+            GotoKind::Definition => self.server.definition(params),
+            GotoKind::Declaration => self.server.declaration(params),
+            GotoKind::TypeDefinition => self.server.type_definition(params),
+            GotoKind::Implementation => self.server.implementation(params),
+        };
+
+        async move {
+            let result = result
+                .await
+                .map_err(|e| LspClientError::RequestFailed(e.to_string()))?;
+
+            Ok(match result {
+                Some(GotoDefinitionResponse::Scalar(location)) => vec![location],
+                Some(GotoDefinitionResponse::Array(locations)) => locations,
+                Some(GotoDefinitionResponse::Link(links)) => links
+                    .into_iter()
+                    .map(|link| Location::new(link.target_uri, link.target_selection_range))
+                    .collect(),
+                None => Vec::new(),
+            })
+        }
+    }
+
+    /// Requests the references to the symbol at a position, along with its declaration
+    fn references(
+        &mut self,
+        uri: Url,
+        position: Position,
+    ) -> impl Future<Output = LspClientResult<Vec<Location>>> + use<> {
+        let result = self.server.references(ReferenceParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+            context: ReferenceContext {
+                include_declaration: true,
+            },
+        });
+
+        async move {
+            let result = result
+                .await
+                .map_err(|e| LspClientError::RequestFailed(e.to_string()))?;
+            Ok(result.unwrap_or_default())
+        }
+    }
+
+    /// Asks whether the symbol at a position can be renamed
+    fn prepare_rename(
+        &mut self,
+        uri: Url,
+        position: Position,
+    ) -> impl Future<Output = LspClientResult<Option<PrepareRenameResponse>>> + use<> {
+        let result = self.server.prepare_rename(TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri },
+            position,
+        });
+
+        async move {
+            result
+                .await
+                .map_err(|e| LspClientError::RequestFailed(e.to_string()))
+        }
+    }
+
+    /// Requests the changes that rename the symbol at a position
+    fn rename(
+        &mut self,
+        uri: Url,
+        position: Position,
+        new_name: String,
+    ) -> impl Future<Output = LspClientResult<Option<WorkspaceEdit>>> + use<> {
+        let result = self.server.rename(RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            new_name,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        });
+
+        async move {
+            result
+                .await
+                .map_err(|e| LspClientError::RequestFailed(e.to_string()))
+        }
+    }
+
+    /// Requests the code actions of a range, along with the fixes of the diagnostics in it
+    fn code_actions(
+        &mut self,
+        uri: Url,
+        range: Range,
+        diagnostics: Vec<Diagnostic>,
+        invoked: bool,
+    ) -> impl Future<Output = LspClientResult<Vec<CodeActionOrCommand>>> + use<> {
+        let trigger_kind = if invoked {
+            CodeActionTriggerKind::INVOKED
+        } else {
+            CodeActionTriggerKind::AUTOMATIC
+        };
+        let result = self.server.code_action(CodeActionParams {
+            text_document: TextDocumentIdentifier { uri },
+            range,
+            context: CodeActionContext {
+                diagnostics,
+                only: None,
+                trigger_kind: Some(trigger_kind),
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        });
+
+        async move {
+            let result = result
+                .await
+                .map_err(|e| LspClientError::RequestFailed(e.to_string()))?;
+            Ok(result.unwrap_or_default())
+        }
+    }
+
+    /// Runs a command of a code action on the server
+    fn execute_command(
+        &mut self,
+        command: async_lsp::lsp_types::Command,
+    ) -> impl Future<Output = LspClientResult<()>> + use<> {
+        let result = self.server.execute_command(ExecuteCommandParams {
+            command: command.command,
+            arguments: command.arguments.unwrap_or_default(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        });
+
+        async move {
+            result
+                .await
+                .map_err(|e| LspClientError::RequestFailed(e.to_string()))?;
+            Ok(())
+        }
+    }
+
+    /// Requests the edits that format the whole document
+    fn formatting(
+        &mut self,
+        uri: Url,
+        options: FormattingOptions,
+    ) -> impl Future<Output = LspClientResult<Vec<TextEdit>>> + use<> {
+        let result = self.server.formatting(DocumentFormattingParams {
+            text_document: TextDocumentIdentifier { uri },
+            options,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        });
+
+        async move {
+            let result = result
+                .await
+                .map_err(|e| LspClientError::RequestFailed(e.to_string()))?;
+            Ok(result.unwrap_or_default())
+        }
+    }
+
+    /// Requests the edits that format a range of the document
+    fn range_formatting(
+        &mut self,
+        uri: Url,
+        range: Range,
+        options: FormattingOptions,
+    ) -> impl Future<Output = LspClientResult<Vec<TextEdit>>> + use<> {
+        let result = self.server.range_formatting(DocumentRangeFormattingParams {
+            text_document: TextDocumentIdentifier { uri },
+            range,
+            options,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        });
+
+        async move {
+            let result = result
+                .await
+                .map_err(|e| LspClientError::RequestFailed(e.to_string()))?;
+            Ok(result.unwrap_or_default())
+        }
+    }
+
+    /// Requests the edits that format the code around a position once a character was typed
+    fn on_type_formatting(
+        &mut self,
+        uri: Url,
+        position: Position,
+        ch: String,
+        options: FormattingOptions,
+    ) -> impl Future<Output = LspClientResult<Vec<TextEdit>>> + use<> {
+        let result = self
             .server
-            .hover(HoverParams {
-                text_document_position_params: TextDocumentPositionParams {
+            .on_type_formatting(DocumentOnTypeFormattingParams {
+                text_document_position: TextDocumentPositionParams {
                     text_document: TextDocumentIdentifier { uri },
                     position,
                 },
-                work_done_progress_params: WorkDoneProgressParams::default(),
+                ch,
+                options,
+            });
+
+        async move {
+            let result = result
+                .await
+                .map_err(|e| LspClientError::RequestFailed(e.to_string()))?;
+            Ok(result.unwrap_or_default())
+        }
+    }
+
+    /// Requests the inlay hints of a range of the document
+    fn inlay_hints(
+        &mut self,
+        uri: Url,
+        range: Range,
+    ) -> impl Future<Output = LspClientResult<Vec<InlayHint>>> + use<> {
+        let result = self.server.inlay_hint(InlayHintParams {
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            text_document: TextDocumentIdentifier { uri },
+            range,
+        });
+
+        async move {
+            let result = result
+                .await
+                .map_err(|e| LspClientError::RequestFailed(e.to_string()))?;
+            Ok(result.unwrap_or_default())
+        }
+    }
+
+    fn resolve_inlay_hint(
+        &mut self,
+        hint: InlayHint,
+    ) -> impl Future<Output = LspClientResult<InlayHint>> + use<> {
+        let result = self.server.inlay_hint_resolve(hint);
+        async move {
+            result
+                .await
+                .map_err(|e| LspClientError::RequestFailed(e.to_string()))
+        }
+    }
+
+    /// Requests all the semantic tokens of the document
+    fn semantic_tokens(
+        &mut self,
+        uri: Url,
+    ) -> impl Future<Output = LspClientResult<SemanticTokens>> + use<> {
+        let result = self.server.semantic_tokens_full(SemanticTokensParams {
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+            text_document: TextDocumentIdentifier { uri },
+        });
+
+        async move {
+            let result = result
+                .await
+                .map_err(|e| LspClientError::RequestFailed(e.to_string()))?;
+
+            Ok(match result {
+                Some(SemanticTokensResult::Tokens(tokens)) => tokens,
+                Some(SemanticTokensResult::Partial(partial)) => SemanticTokens {
+                    result_id: None,
+                    data: partial.data,
+                },
+                None => SemanticTokens::default(),
             })
-            .await
-            .unwrap();
+        }
+    }
+
+    /// Requests the changes to the semantic tokens of the document since `previous_result_id`
+    fn semantic_tokens_delta(
+        &mut self,
+        uri: Url,
+        previous_result_id: String,
+    ) -> impl Future<Output = LspClientResult<SemanticTokensFullDeltaResult>> + use<> {
+        let result = self
+            .server
+            .semantic_tokens_full_delta(SemanticTokensDeltaParams {
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+                text_document: TextDocumentIdentifier { uri },
+                previous_result_id,
+            });
+
+        async move {
+            let result = result
+                .await
+                .map_err(|e| LspClientError::RequestFailed(e.to_string()))?;
+
+            Ok(result.unwrap_or(SemanticTokensFullDeltaResult::Tokens(
+                SemanticTokens::default(),
+            )))
+        }
+    }
+}
+
+/// The settings of the server: its initialization options, in the section the server asks for
+/// them in when it has one
+fn settings(config: &ServerConfig) -> Option<LSPAny> {
+    let options = config.initialization_options.clone()?;
+    let Some(section) = &config.settings_section else {
+        return Some(options);
+    };
+    let mut settings = LSPObject::new();
+    settings.insert(section.clone(), options);
+    Some(LSPAny::Object(settings))
+}
+
+/// A section of the settings of the server, e.g. `rust-analyzer.cargo`, or all of them without
+/// one. The server uses its defaults for the sections that aren't set, which are `null`
+fn settings_section(settings: Option<&LSPAny>, section: Option<&str>) -> LSPAny {
+    let Some(section) = section else {
+        return settings.cloned().unwrap_or(LSPAny::Null);
+    };
+    settings
+        .and_then(|settings| {
+            section
+                .split('.')
+                .try_fold(settings, |settings, key| settings.get(key))
+        })
+        .cloned()
+        .unwrap_or(LSPAny::Null)
+}
+
+/// Workspace edits as document changes, which can create, rename and delete files too
+fn workspace_edit_capabilities() -> WorkspaceEditClientCapabilities {
+    WorkspaceEditClientCapabilities {
+        document_changes: Some(true),
+        resource_operations: Some(vec![
+            ResourceOperationKind::Create,
+            ResourceOperationKind::Rename,
+            ResourceOperationKind::Delete,
+        ]),
+        // The changes are made in order until one fails
+        failure_handling: Some(FailureHandlingKind::Abort),
+        ..WorkspaceEditClientCapabilities::default()
+    }
+}
+
+/// Code actions as literals with their kind, so quick fixes can be told apart and the preferred
+/// ones listed first
+fn code_action_capabilities() -> CodeActionClientCapabilities {
+    let kinds = [
+        CodeActionKind::EMPTY,
+        CodeActionKind::QUICKFIX,
+        CodeActionKind::REFACTOR,
+        CodeActionKind::REFACTOR_EXTRACT,
+        CodeActionKind::REFACTOR_INLINE,
+        CodeActionKind::REFACTOR_REWRITE,
+        CodeActionKind::SOURCE,
+        CodeActionKind::SOURCE_ORGANIZE_IMPORTS,
+    ];
+    CodeActionClientCapabilities {
+        dynamic_registration: Some(false),
+        code_action_literal_support: Some(CodeActionLiteralSupport {
+            code_action_kind: CodeActionKindLiteralSupport {
+                value_set: kinds.iter().map(|kind| kind.as_str().to_owned()).collect(),
+            },
+        }),
+        is_preferred_support: Some(true),
+        ..CodeActionClientCapabilities::default()
+    }
+}
+
+/// Locations of a symbol, which may be links to the range of the name they point at
+fn goto_capabilities() -> GotoCapability {
+    GotoCapability {
+        dynamic_registration: Some(false),
+        link_support: Some(true),
+    }
+}
+
+/// Signatures with their documentation, whose parameters can be given as offsets into the label
+fn signature_help_capabilities() -> SignatureHelpClientCapabilities {
+    SignatureHelpClientCapabilities {
+        dynamic_registration: Some(false),
+        signature_information: Some(SignatureInformationSettings {
+            documentation_format: Some(vec![MarkupKind::Markdown, MarkupKind::PlainText]),
+            parameter_information: Some(ParameterInformationSettings {
+                label_offset_support: Some(true),
+            }),
+            active_parameter_support: Some(true),
+        }),
+        context_support: Some(true),
+    }
+}
+
+/// Completions that may be snippets, whose documentation, details and additional edits can be
+/// resolved once an item is selected.
+fn completion_capabilities() -> CompletionClientCapabilities {
+    CompletionClientCapabilities {
+        dynamic_registration: Some(false),
+        completion_item: Some(CompletionItemCapability {
+            snippet_support: Some(true),
+            documentation_format: Some(vec![MarkupKind::Markdown, MarkupKind::PlainText]),
+            deprecated_support: Some(true),
+            insert_replace_support: Some(false),
+            resolve_support: Some(CompletionItemCapabilityResolveSupport {
+                properties: ["documentation", "detail", "additionalTextEdits"]
+                    .map(String::from)
+                    .to_vec(),
+            }),
+            label_details_support: Some(true),
+            ..CompletionItemCapability::default()
+        }),
+        completion_item_kind: Some(CompletionItemKindCapability {
+            value_set: Some(vec![
+                CompletionItemKind::TEXT,
+                CompletionItemKind::METHOD,
+                CompletionItemKind::FUNCTION,
+                CompletionItemKind::CONSTRUCTOR,
+                CompletionItemKind::FIELD,
+                CompletionItemKind::VARIABLE,
+                CompletionItemKind::CLASS,
+                CompletionItemKind::INTERFACE,
+                CompletionItemKind::MODULE,
+                CompletionItemKind::PROPERTY,
+                CompletionItemKind::UNIT,
+                CompletionItemKind::VALUE,
+                CompletionItemKind::ENUM,
+                CompletionItemKind::KEYWORD,
+                CompletionItemKind::SNIPPET,
+                CompletionItemKind::COLOR,
+                CompletionItemKind::FILE,
+                CompletionItemKind::REFERENCE,
+                CompletionItemKind::FOLDER,
+                CompletionItemKind::ENUM_MEMBER,
+                CompletionItemKind::CONSTANT,
+                CompletionItemKind::STRUCT,
+                CompletionItemKind::EVENT,
+                CompletionItemKind::OPERATOR,
+                CompletionItemKind::TYPE_PARAMETER,
+            ]),
+        }),
+        context_support: Some(true),
+        ..CompletionClientCapabilities::default()
+    }
+}
+
+/// Full and delta semantic tokens, in the relative format with the standard types and modifiers.
+/// The editor draws one token at a time, so tokens can't overlap or span several lines.
+fn semantic_tokens_capabilities() -> SemanticTokensClientCapabilities {
+    SemanticTokensClientCapabilities {
+        dynamic_registration: Some(false),
+        requests: SemanticTokensClientCapabilitiesRequests {
+            range: Some(false),
+            full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
+        },
+        token_types: vec![
+            SemanticTokenType::NAMESPACE,
+            SemanticTokenType::TYPE,
+            SemanticTokenType::CLASS,
+            SemanticTokenType::ENUM,
+            SemanticTokenType::INTERFACE,
+            SemanticTokenType::STRUCT,
+            SemanticTokenType::TYPE_PARAMETER,
+            SemanticTokenType::PARAMETER,
+            SemanticTokenType::VARIABLE,
+            SemanticTokenType::PROPERTY,
+            SemanticTokenType::ENUM_MEMBER,
+            SemanticTokenType::EVENT,
+            SemanticTokenType::FUNCTION,
+            SemanticTokenType::METHOD,
+            SemanticTokenType::MACRO,
+            SemanticTokenType::KEYWORD,
+            SemanticTokenType::MODIFIER,
+            SemanticTokenType::COMMENT,
+            SemanticTokenType::STRING,
+            SemanticTokenType::NUMBER,
+            SemanticTokenType::REGEXP,
+            SemanticTokenType::OPERATOR,
+            SemanticTokenType::DECORATOR,
+        ],
+        token_modifiers: vec![
+            SemanticTokenModifier::DECLARATION,
+            SemanticTokenModifier::DEFINITION,
+            SemanticTokenModifier::READONLY,
+            SemanticTokenModifier::STATIC,
+            SemanticTokenModifier::DEPRECATED,
+            SemanticTokenModifier::ABSTRACT,
+            SemanticTokenModifier::ASYNC,
+            SemanticTokenModifier::MODIFICATION,
+            SemanticTokenModifier::DOCUMENTATION,
+            SemanticTokenModifier::DEFAULT_LIBRARY,
+        ],
+        formats: vec![TokenFormat::RELATIVE],
+        overlapping_token_support: Some(false),
+        multiline_token_support: Some(false),
+        server_cancel_support: Some(false),
+        augments_syntax_tokens: Some(true),
+    }
+}
+
+#[cfg(test)]
+mod client_tests {
+    use async_lsp::lsp_types::LSPAny;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::{settings, settings_section};
+    use crate::ServerConfig;
+
+    fn config(section: Option<&str>) -> ServerConfig {
+        ServerConfig {
+            command: String::from("rust-analyzer"),
+            args: Vec::new(),
+            initialization_options: Some(json!({ "cargo": { "features": "all" } })),
+            root_markers: Vec::new(),
+            settings_section: section.map(ToOwned::to_owned),
+        }
+    }
+
+    #[test]
+    fn test_settings_are_in_the_server_section() {
+        let settings = settings(&config(Some("rust-analyzer")));
+        let section = |section| settings_section(settings.as_ref(), section);
+
+        assert_eq!(
+            section(Some("rust-analyzer")),
+            json!({ "cargo": { "features": "all" } })
+        );
+        assert_eq!(section(Some("rust-analyzer.cargo.features")), json!("all"));
+        assert_eq!(
+            section(None),
+            json!({ "rust-analyzer": { "cargo": { "features": "all" } } })
+        );
+        // What isn't set is left to the server
+        assert_eq!(section(Some("rust-analyzer.checkOnSave")), LSPAny::Null);
+        assert_eq!(section(Some("cargo")), LSPAny::Null);
+        assert_eq!(
+            section(Some("rust-analyzer.cargo.features.all")),
+            LSPAny::Null
+        );
+    }
 
-        Ok(hover.map(|h| h.contents))
+    #[test]
+    fn test_settings_without_a_section() {
+        let settings = settings(&config(None));
+        assert_eq!(
+            settings_section(settings.as_ref(), Some("cargo.features")),
+            json!("all")
+        );
+        assert_eq!(settings_section(None, Some("rust-analyzer")), LSPAny::Null);
+        assert_eq!(settings_section(None, None), LSPAny::Null);
     }
 }
@@ -1,98 +1,338 @@
+use std::{
+    collections::HashMap,
+    future,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64},
+    },
+    time::Duration,
+};
+
 use futures::{SinkExt as _, Stream, channel::mpsc::Sender as FuturesSender};
 use iced::stream;
-use tokio::sync::mpsc::{self, UnboundedReceiver};
-use tracing::{error, info};
+use tokio::{
+    spawn,
+    sync::{
+        mpsc::{self, UnboundedReceiver, UnboundedSender, error::SendError},
+        oneshot,
+    },
+    time::{Instant, sleep, sleep_until, timeout},
+};
+use tracing::{error, info, warn};
 
-use crate::{LspConnection, LspServerNotification, Synchronise};
+use crate::{
+    LspConnection, LspServerNotification, LspServerRequest, LspServers, ServerConfig, ServerId,
+    Synchronise, error::LspClientError,
+};
 
 use super::{LspClientNotification, LspCommand, LspMessage, LspRequest, client::LspClient};
 
-/// How the internals of the stream are handled. This enum never leaves the stream and never is
-/// touched by the gui. It is self managed state by the [`client`] stream.
-#[derive(Debug, Default)]
-enum State {
-    Connected(
-        LspClient,
-        UnboundedReceiver<LspCommand>,
-        UnboundedReceiver<LspClientNotification>,
-    ),
-    #[default]
-    Disconnected,
-}
+/// How long a server that stopped waits to be restarted, doubled for every restart in a row
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+/// How many times in a row a server is restarted before it's left stopped
+const MAX_RESTARTS: u32 = 5;
+/// How long a server has to run for its next stop to count as the first in a row again
+const STABLE_RUN: Duration = Duration::from_secs(60);
+/// How long a server gets to shut down before it's killed
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
+/// Runs the language servers the gui starts through the [`LspServers`] it's handed. Every server
+/// runs on its own, so one that's slow to start or answer doesn't hold up the others.
 pub fn connect() -> impl Stream<Item = LspMessage> {
     const CHANNEL_SIZE: usize = 1024;
     stream::channel(CHANNEL_SIZE, async |mut output| {
-        let mut state = State::default();
+        let (start_sender, mut start_receiver) = mpsc::unbounded_channel();
+        let trace = Arc::new(AtomicBool::new(false));
+        let _ = output
+            .send(LspMessage::Ready(LspServers::new(
+                start_sender,
+                trace.clone(),
+            )))
+            .await;
 
-        loop {
-            match state {
-                State::Connected(ref mut client, ref mut gui_rcv, ref mut lsp_rcv) => {
-                    receive_message(client, &mut output, gui_rcv, lsp_rcv).await
-                }
-                State::Disconnected => {
-                    let (lsp_sender, lsp_receiver) = mpsc::unbounded_channel();
-                    match LspClient::initialize(lsp_sender).await {
-                        Ok(client) => {
-                            info!("Successfully initialized LSP");
-                            let (gui_sender, gui_receiver) =
-                                mpsc::unbounded_channel::<LspCommand>();
-                            let _ = output
-                                .send(LspMessage::Initialized(LspConnection(gui_sender)))
-                                .await;
-                            state = State::Connected(client, gui_receiver, lsp_receiver);
-                        }
-                        Err(e) => {
-                            error!(error = ?e, "Error whilst initializing the language server client");
-                            let _ = output.send(LspMessage::Shutdown).await;
-                        }
+        let next_request = Arc::new(AtomicU64::new(0));
+        // Restarts the servers that are running or waiting to be restarted
+        let mut restarts: HashMap<ServerId, UnboundedSender<ServerConfig>> = HashMap::new();
+        while let Some((id, config)) = start_receiver.recv().await {
+            // Otherwise the server was left stopped, and it's started all over
+            let config = match restarts.get(&id) {
+                Some(restart) => match restart.send(config) {
+                    Ok(()) => continue,
+                    Err(SendError(config)) => config,
+                },
+                None => config,
+            };
+            let (restart_sender, restart_receiver) = mpsc::unbounded_channel();
+            restarts.insert(id.clone(), restart_sender);
+            spawn(run_server(
+                id,
+                config,
+                restart_receiver,
+                next_request.clone(),
+                trace.clone(),
+                output.clone(),
+            ));
+        }
+    })
+}
+
+/// Why a server stopped running
+enum Stop {
+    /// The gui asked for it to be restarted with the config
+    Restart(ServerConfig),
+    /// It was shut down, so it isn't restarted
+    Shutdown,
+    /// It couldn't be started or it stopped on its own
+    Failed(LspClientError),
+}
+
+/// Runs a server, starting it again when it stops with a delay that grows with every restart in
+/// a row, until it stopped too often. A restart from the gui starts it again straight away.
+async fn run_server(
+    id: ServerId,
+    mut config: ServerConfig,
+    mut restarts: UnboundedReceiver<ServerConfig>,
+    next_request: Arc<AtomicU64>,
+    trace: Arc<AtomicBool>,
+    mut output: FuturesSender<LspMessage>,
+) {
+    let mut attempt = 0;
+    loop {
+        let _ = output.send(LspMessage::Starting(id.clone())).await;
+        let started = Instant::now();
+        let served = serve(
+            &id,
+            &config,
+            &mut restarts,
+            &next_request,
+            &trace,
+            &mut output,
+        );
+        let error = match served.await {
+            Stop::Restart(restart) => {
+                info!(server = %id, "Restarting the language server");
+                config = restart;
+                attempt = 0;
+                continue;
+            }
+            Stop::Shutdown => {
+                info!(server = %id, "The language server was shut down");
+                let error = LspClientError::ServerStopped(String::from("It was shut down"));
+                let _ = output.send(LspMessage::Stopped(id, error, None)).await;
+                return;
+            }
+            Stop::Failed(error) => error,
+        };
+
+        if started.elapsed() >= STABLE_RUN {
+            attempt = 0;
+        }
+        // A command that can't be run won't run the next time either
+        let delay = (attempt < MAX_RESTARTS && !matches!(error, LspClientError::ProcessFailure(_)))
+            .then(|| RESTART_DELAY * 2_u32.pow(attempt));
+        error!(
+            error = %error,
+            server = %id,
+            ?delay,
+            "The language server stopped"
+        );
+        let _ = output
+            .send(LspMessage::Stopped(id.clone(), error, delay))
+            .await;
+        let Some(delay) = delay else {
+            return;
+        };
+
+        attempt += 1;
+        tokio::select! {
+            () = sleep(delay) => {}
+            Some(restart) = restarts.recv() => {
+                config = restart;
+                attempt = 0;
+            }
+        }
+    }
+}
+
+/// Starts a server and passes messages between it and the gui until it stops
+async fn serve(
+    id: &ServerId,
+    config: &ServerConfig,
+    restarts: &mut UnboundedReceiver<ServerConfig>,
+    next_request: &Arc<AtomicU64>,
+    trace: &Arc<AtomicBool>,
+    output: &mut FuturesSender<LspMessage>,
+) -> Stop {
+    let (lsp_sender, mut lsp_rcv) = mpsc::unbounded_channel();
+    let (request_sender, mut request_rcv) = mpsc::unbounded_channel();
+    let (stopped_sender, mut stopped_rcv) = oneshot::channel();
+    let initialize = LspClient::initialize(
+        id,
+        config,
+        lsp_sender,
+        request_sender,
+        stopped_sender,
+        trace.clone(),
+    );
+    // The process is killed along with the client when it's restarted before it's initialized
+    let mut client = tokio::select! {
+        client = initialize => match client {
+            Ok(client) => client,
+            Err(e) => return Stop::Failed(e),
+        },
+        Some(restart) = restarts.recv() => return Stop::Restart(restart),
+    };
+    info!(server = %id, "Successfully initialized LSP");
+
+    let (gui_sender, mut gui_rcv) = mpsc::unbounded_channel::<LspCommand>();
+    let capabilities = client.capabilities();
+    let connection = LspConnection::new(id.clone(), gui_sender, capabilities, next_request.clone());
+    let _ = output.send(LspMessage::Initialized(connection)).await;
+    let stop = loop {
+        tokio::select! {
+            biased;
+            Some(restart) = restarts.recv() => {
+                shutdown(id, &mut client).await;
+                break Stop::Restart(restart);
+            }
+            stopped = &mut stopped_rcv => {
+                break match stopped {
+                    Ok(Ok(())) => Stop::Shutdown,
+                    Ok(Err(e)) => {
+                        let reason = match client.exit_status().await {
+                            Some(status) => format!("{e} ({status})"),
+                            None => e.to_string(),
+                        };
+                        Stop::Failed(LspClientError::ServerStopped(reason))
                     }
-                }
+                    Err(_) => Stop::Failed(LspClientError::ServerStopped(String::from(
+                        "The connection was dropped",
+                    ))),
+                };
             }
+            () = receive_message(
+                id,
+                &mut client,
+                output,
+                &mut gui_rcv,
+                &mut lsp_rcv,
+                &mut request_rcv,
+            ) => {}
         }
-    })
+    };
+
+    // The requests won't be answered by the server, and the gui would wait for them forever
+    for request in client.abort_requests() {
+        let error = LspClientError::ServerStopped(String::from("The server stopped"));
+        let _ = output.send(LspMessage::RequestFailed(request, error)).await;
+    }
+    stop
+}
+
+/// Shuts a server down to restart it, it's killed when it doesn't in time
+async fn shutdown(id: &ServerId, client: &mut LspClient) {
+    match timeout(SHUTDOWN_TIMEOUT, client.shutdown()).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => warn!(error = %e, server = %id, "Couldn't shut the language server down"),
+        Err(_) => warn!(server = %id, "The language server took too long to shut down"),
+    }
 }
 
 async fn receive_message(
+    server: &ServerId,
     client: &mut LspClient,
     output: &mut FuturesSender<LspMessage>,
     gui_rcv: &mut UnboundedReceiver<LspCommand>,
     lsp_rcv: &mut UnboundedReceiver<LspClientNotification>,
+    request_rcv: &mut UnboundedReceiver<LspServerRequest>,
 ) {
+    let deadline = client.changes_deadline();
     tokio::select! {
         biased;
         // LspCommand -> LspRequest | LspServerNotification
-        Some(msg) = gui_rcv.recv() => match msg {
-            LspCommand::Request(r) => match r {
-                LspRequest::Shutdown => {
-                    let _ = client.shutdown().await;
-                }
-            },
-            LspCommand::Notification(n) => match n {
-                LspServerNotification::Synchronise(s, uri) => {
-                    match s {
-                        Synchronise::DidChange(string, range) => {
-                            let _ = client.did_change(uri, range.into(), string).await;
-                        },
-                        Synchronise::DidClose => {
-                            let _ = client.did_close(uri).await;
-                        },
-                        Synchronise::DidOpen(string) => {
-                            let _ = client.did_open(uri, string).await;
-                        }
-                        Synchronise::DidSave(string) => {
-                            let _ = client.did_save(uri, string).await;
-                        },
-                        Synchronise::WillSave => {
-                            let _ = client.will_save(uri).await;
-                        }
-                    }
-                }
+        Some(msg) = gui_rcv.recv() => {
+            // Everything else has to reach the server after the changes that came before it
+            if !is_change(&msg) {
+                flush_changes(client);
             }
+            handle_command(client, msg).await;
         },
+        // The queued changes have waited long enough
+        _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+            flush_changes(client);
+        }
+        // The response to a request of the gui
+        Some((id, result)) = client.next_response() => {
+            let message = match result {
+                Ok(response) => LspMessage::Response(id, response),
+                Err(e) => {
+                    error!(error = ?e, ?id, "Request failed");
+                    LspMessage::RequestFailed(id, e)
+                }
+            };
+            let _ = output.send(message).await;
+        }
         // LspMessage
         Some(notification) = lsp_rcv.recv() => {
-            let _ = output.send(LspMessage::Notification(notification)).await;
+            let _ = output
+                .send(LspMessage::Notification(server.clone(), notification))
+                .await;
+        }
+        // A request of the server that the gui answers
+        Some(request) = request_rcv.recv() => {
+            let _ = output.send(LspMessage::Request(request)).await;
         }
+        // Nothing can come anymore, until the server is restarted or its stop is noticed
+        else => future::pending().await,
     };
 }
+
+fn is_change(command: &LspCommand) -> bool {
+    matches!(
+        command,
+        LspCommand::Notification(LspServerNotification::Synchronise(
+            Synchronise::DidChange(..),
+            _
+        ))
+    )
+}
+
+fn flush_changes(client: &mut LspClient) {
+    if let Err(e) = client.flush_changes() {
+        error!(error = ?e, "Couldn't send the document changes");
+    }
+}
+
+async fn handle_command(client: &mut LspClient, command: LspCommand) {
+    match command {
+        LspCommand::Request(_, LspRequest::Shutdown) => {
+            let _ = client.shutdown().await;
+        }
+        // Requests run alongside everything else, so a slow one doesn't hold up the rest
+        LspCommand::Request(id, request) => client.request(id, request),
+        LspCommand::Cancel(id) => {
+            if let Err(e) = client.cancel(id) {
+                error!(error = ?e, "Couldn't cancel the request");
+            }
+        }
+        LspCommand::Notification(n) => match n {
+            LspServerNotification::Synchronise(s, uri) => match s {
+                Synchronise::DidChange(changes, version) => {
+                    let _ = client.queue_changes(uri, changes, version);
+                }
+                Synchronise::DidClose => {
+                    let _ = client.did_close(uri).await;
+                }
+                Synchronise::DidOpen(string, language_id) => {
+                    let _ = client.did_open(uri, string, language_id).await;
+                }
+                Synchronise::DidSave(string) => {
+                    let _ = client.did_save(uri, string).await;
+                }
+                Synchronise::WillSave => {
+                    let _ = client.will_save(uri).await;
+                }
+            },
+        },
+    }
+}
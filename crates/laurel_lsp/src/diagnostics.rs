@@ -18,7 +18,10 @@ impl ClientDiagnostics {
     }
 }
 
-#[derive(Debug, Clone)]
+/**
+ * How severe an issue is, the most severe comes first so they can be sorted by it.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Severity {
     Error,
     Warning,
@@ -58,6 +61,8 @@ pub struct Issue {
     pub code_description: Option<lsp_types::CodeDescription>,
     pub message: String,
     pub severity: Severity,
+    /// The diagnostic as the server sent it, which code actions are asked for with
+    pub diagnostic: lsp_types::Diagnostic,
 }
 
 impl From<lsp_types::Diagnostic> for Issue {
@@ -67,9 +72,10 @@ impl From<lsp_types::Diagnostic> for Issue {
 
         Self {
             range: Range::from(value.range),
-            code_description: value.code_description,
-            message: value.message,
+            code_description: value.code_description.clone(),
+            message: value.message.clone(),
             severity,
+            diagnostic: value,
         }
     }
 }
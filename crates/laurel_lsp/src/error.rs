@@ -6,6 +6,10 @@ pub enum LspClientError {
     ProcessFailure(String),
     #[error("Process Failure: `{0}`")]
     ChannelClosed(String),
+    #[error("Request Failed: `{0}`")]
+    RequestFailed(String),
+    #[error("Server Stopped: `{0}`")]
+    ServerStopped(String),
 }
 
 pub type LspClientResult<T> = Result<T, LspClientError>;
@@ -1,57 +1,437 @@
-use std::{fs, path::PathBuf};
+use std::{
+    fmt, fs,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::Duration,
+};
 
-use async_lsp::lsp_types::Url;
+use async_lsp::lsp_types::{
+    self, ApplyWorkspaceEditResponse, CodeActionOrCommand, CodeActionProviderCapability, Command,
+    CompletionItem, DeclarationCapability, Diagnostic, FormattingOptions, HoverContents,
+    HoverProviderCapability, ImplementationProviderCapability, InlayHint,
+    InlayHintServerCapabilities, Location, MessageActionItem, MessageType, NumberOrString, OneOf,
+    Position, PrepareRenameResponse, SemanticTokens, SemanticTokensFullDeltaResult,
+    SemanticTokensFullOptions, SemanticTokensLegend, SemanticTokensServerCapabilities,
+    ServerCapabilities, SignatureHelp, TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit,
+    TypeDefinitionProviderCapability, Url, WorkDoneProgress, WorkspaceEdit,
+};
 pub use diagnostics::{ClientDiagnostics, Issue, Severity};
-use tokio::sync::mpsc::{self};
+use error::LspClientError;
+use tokio::sync::{
+    mpsc::{self},
+    oneshot,
+};
+use tracing::{error, warn};
 
 use laurel_common::text::Range;
 
 pub use connect::connect;
+pub use output::{ServerOutput, TraceKind, TraceMessage};
+pub use server::{ServerConfig, ServerId};
 
 pub mod client;
 pub mod connect;
 pub mod diagnostics;
 pub mod error;
+pub mod output;
+pub mod server;
 
+/// Starts language servers, each of which sends an [`LspMessage::Initialized`] with its
+/// connection once it's ready, and again whenever it was restarted
 #[derive(Debug, Clone)]
-pub struct LspConnection(mpsc::UnboundedSender<LspCommand>);
+pub struct LspServers {
+    sender: mpsc::UnboundedSender<(ServerId, ServerConfig)>,
+    /// Whether the messages to and from every server are traced
+    trace: Arc<AtomicBool>,
+}
+
+impl LspServers {
+    pub fn new(
+        sender: mpsc::UnboundedSender<(ServerId, ServerConfig)>,
+        trace: Arc<AtomicBool>,
+    ) -> Self {
+        Self { sender, trace }
+    }
+
+    /// Turns the trace of the messages to and from the servers on or off, they come as
+    /// [`ServerOutput::Trace`]
+    pub fn set_trace(&self, trace: bool) {
+        self.trace.store(trace, Ordering::Relaxed);
+    }
+
+    /// Starts the server, or restarts it with the config when it's already running or waiting to
+    /// be restarted
+    pub fn start(&self, id: ServerId, config: ServerConfig) {
+        if self.sender.send((id, config)).is_err() {
+            error!("The language server stream is gone, no server can be started");
+        }
+    }
+}
+
+/// The connection to one of the language servers
+#[derive(Debug, Clone)]
+pub struct LspConnection {
+    server: ServerId,
+    sender: mpsc::UnboundedSender<LspCommand>,
+    /// What the server said it supports when it was initialized
+    capabilities: Arc<ServerCapabilities>,
+    /// The id of the next request, shared by every connection so the ids of their responses
+    /// can't be mixed up
+    next_request: Arc<AtomicU64>,
+}
 
 impl LspConnection {
+    pub fn new(
+        server: ServerId,
+        sender: mpsc::UnboundedSender<LspCommand>,
+        capabilities: Arc<ServerCapabilities>,
+        next_request: Arc<AtomicU64>,
+    ) -> Self {
+        Self {
+            server,
+            sender,
+            capabilities,
+            next_request,
+        }
+    }
+
+    pub fn server(&self) -> &ServerId {
+        &self.server
+    }
+
+    /// Sends a command to the server, which is dropped when the server stopped. A restarted
+    /// server comes with a new connection
     pub fn send(&mut self, item: LspCommand) {
-        // FIXME: This will crash the program if the stream has to reinit
-        self.0.send(item).unwrap()
+        if self.sender.send(item).is_err() {
+            warn!(server = %self.server, "The language server stopped, the command was dropped");
+        }
+    }
+
+    /// Sends a request, its response comes back as an [`LspMessage::Response`] with the
+    /// returned id
+    pub fn request(&mut self, request: LspRequest) -> RequestId {
+        let id = RequestId(self.next_request.fetch_add(1, Ordering::Relaxed));
+        self.send(LspCommand::Request(id, request));
+        id
+    }
+
+    /// Cancels a request that was superseded, so it never gets a response
+    pub fn cancel(&mut self, id: RequestId) {
+        self.send(LspCommand::Cancel(id));
+    }
+
+    pub fn capabilities(&self) -> &ServerCapabilities {
+        &self.capabilities
+    }
+
+    /// The legend of the server's semantic tokens, `None` when it doesn't provide any
+    pub fn semantic_tokens_legend(&self) -> Option<&SemanticTokensLegend> {
+        match self.capabilities.semantic_tokens_provider.as_ref()? {
+            SemanticTokensServerCapabilities::SemanticTokensOptions(options) => {
+                Some(&options.legend)
+            }
+            SemanticTokensServerCapabilities::SemanticTokensRegistrationOptions(options) => {
+                Some(&options.semantic_tokens_options.legend)
+            }
+        }
+    }
+
+    /// How the server wants to be told about changes to a document, `NONE` when it doesn't say
+    pub fn text_document_sync_kind(&self) -> TextDocumentSyncKind {
+        match &self.capabilities.text_document_sync {
+            Some(TextDocumentSyncCapability::Kind(kind)) => *kind,
+            Some(TextDocumentSyncCapability::Options(options)) => {
+                options.change.unwrap_or(TextDocumentSyncKind::NONE)
+            }
+            None => TextDocumentSyncKind::NONE,
+        }
+    }
+
+    /// Whether the server can send information about the symbol under a position
+    pub fn supports_hover(&self) -> bool {
+        match self.capabilities.hover_provider {
+            Some(HoverProviderCapability::Simple(supported)) => supported,
+            Some(HoverProviderCapability::Options(_)) => true,
+            None => false,
+        }
+    }
+
+    /// The characters that ask for completions when they're typed, `None` when the server doesn't
+    /// complete at all
+    pub fn completion_trigger_characters(&self) -> Option<&[String]> {
+        let options = self.capabilities.completion_provider.as_ref()?;
+        Some(options.trigger_characters.as_deref().unwrap_or_default())
+    }
+
+    /// Whether the server fills in the details of a completion item when it's asked to
+    pub fn supports_completion_resolve(&self) -> bool {
+        self.capabilities
+            .completion_provider
+            .as_ref()
+            .and_then(|options| options.resolve_provider)
+            .unwrap_or(false)
+    }
+
+    /// Whether the server can find this kind of location of a symbol
+    pub fn supports_goto(&self, kind: GotoKind) -> bool {
+        let capabilities = &self.capabilities;
+        match kind {
+            GotoKind::Definition => match capabilities.definition_provider {
+                Some(OneOf::Left(supported)) => supported,
+                Some(OneOf::Right(_)) => true,
+                None => false,
+            },
+            GotoKind::Declaration => match capabilities.declaration_provider {
+                Some(DeclarationCapability::Simple(supported)) => supported,
+                Some(_) => true,
+                None => false,
+            },
+            GotoKind::TypeDefinition => match capabilities.type_definition_provider {
+                Some(TypeDefinitionProviderCapability::Simple(supported)) => supported,
+                Some(TypeDefinitionProviderCapability::Options(_)) => true,
+                None => false,
+            },
+            GotoKind::Implementation => match capabilities.implementation_provider {
+                Some(ImplementationProviderCapability::Simple(supported)) => supported,
+                Some(ImplementationProviderCapability::Options(_)) => true,
+                None => false,
+            },
+        }
+    }
+
+    /// Whether the server can find the references to a symbol
+    pub fn supports_references(&self) -> bool {
+        match self.capabilities.references_provider {
+            Some(OneOf::Left(supported)) => supported,
+            Some(OneOf::Right(_)) => true,
+            None => false,
+        }
+    }
+
+    /// Whether the server can rename symbols
+    pub fn supports_rename(&self) -> bool {
+        match self.capabilities.rename_provider {
+            Some(OneOf::Left(supported)) => supported,
+            Some(OneOf::Right(_)) => true,
+            None => false,
+        }
+    }
+
+    /// Whether the server can tell if the symbol at a position can be renamed before it is
+    pub fn supports_prepare_rename(&self) -> bool {
+        match &self.capabilities.rename_provider {
+            Some(OneOf::Right(options)) => options.prepare_provider.unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    /// Whether the server can list the code actions of a range, e.g. the fixes of its diagnostics
+    pub fn supports_code_actions(&self) -> bool {
+        match self.capabilities.code_action_provider {
+            Some(CodeActionProviderCapability::Simple(supported)) => supported,
+            Some(CodeActionProviderCapability::Options(_)) => true,
+            None => false,
+        }
+    }
+
+    /// Whether the server can format a whole document
+    pub fn supports_formatting(&self) -> bool {
+        match self.capabilities.document_formatting_provider {
+            Some(OneOf::Left(supported)) => supported,
+            Some(OneOf::Right(_)) => true,
+            None => false,
+        }
+    }
+
+    /// Whether the server can format a range of a document, e.g. the selection
+    pub fn supports_range_formatting(&self) -> bool {
+        match self.capabilities.document_range_formatting_provider {
+            Some(OneOf::Left(supported)) => supported,
+            Some(OneOf::Right(_)) => true,
+            None => false,
+        }
+    }
+
+    /// Whether typing the character formats the code around it
+    pub fn formats_on_type(&self, character: &str) -> bool {
+        let Some(options) = &self.capabilities.document_on_type_formatting_provider else {
+            return false;
+        };
+        options.first_trigger_character == character
+            || options
+                .more_trigger_character
+                .as_ref()
+                .is_some_and(|more| more.iter().any(|trigger| trigger == character))
+    }
+
+    /// Whether the server can list the inlay hints of a range, e.g. the types of variables
+    pub fn supports_inlay_hints(&self) -> bool {
+        match self.capabilities.inlay_hint_provider {
+            Some(OneOf::Left(supported)) => supported,
+            Some(OneOf::Right(_)) => true,
+            None => false,
+        }
+    }
+
+    /// Whether the server fills in the details of an inlay hint, like its tooltip, when asked to
+    pub fn supports_inlay_hint_resolve(&self) -> bool {
+        let options = match &self.capabilities.inlay_hint_provider {
+            Some(OneOf::Right(InlayHintServerCapabilities::Options(options))) => options,
+            Some(OneOf::Right(InlayHintServerCapabilities::RegistrationOptions(options))) => {
+                &options.inlay_hint_options
+            }
+            _ => return false,
+        };
+        options.resolve_provider.unwrap_or(false)
+    }
+
+    /// Whether the server runs a command, rather than leaving it to the client
+    pub fn executes_command(&self, command: &str) -> bool {
+        self.capabilities
+            .execute_command_provider
+            .as_ref()
+            .is_some_and(|options| options.commands.iter().any(|known| known == command))
+    }
+
+    /// The characters that ask for the signatures of a call when they're typed, `None` when the
+    /// server doesn't provide signatures at all
+    pub fn signature_help_trigger_characters(&self) -> Option<&[String]> {
+        let options = self.capabilities.signature_help_provider.as_ref()?;
+        Some(options.trigger_characters.as_deref().unwrap_or_default())
+    }
+
+    /// The characters that ask for the signatures again when they're typed while they're shown
+    pub fn signature_help_retrigger_characters(&self) -> &[String] {
+        self.capabilities
+            .signature_help_provider
+            .as_ref()
+            .and_then(|options| options.retrigger_characters.as_deref())
+            .unwrap_or_default()
+    }
+
+    /// Whether the server can send the changes to the semantic tokens of a document
+    pub fn supports_semantic_tokens_delta(&self) -> bool {
+        let options = match self.capabilities.semantic_tokens_provider.as_ref() {
+            Some(SemanticTokensServerCapabilities::SemanticTokensOptions(options)) => options,
+            Some(SemanticTokensServerCapabilities::SemanticTokensRegistrationOptions(options)) => {
+                &options.semantic_tokens_options
+            }
+            None => return false,
+        };
+        matches!(
+            options.full,
+            Some(SemanticTokensFullOptions::Delta { delta: Some(true) })
+        )
     }
 }
 
 /// A message from the Stream to the gui
 #[derive(Debug, Clone)]
 pub enum LspMessage {
+    /// Language servers can be started with the handle
+    Ready(LspServers),
+    /// The server is being started, the first time or after it stopped
+    Starting(ServerId),
+    /// The server was started and initialized
     Initialized(LspConnection),
-    Shutdown,
+    /// The server stopped or couldn't be started, e.g. because its process exited or its command
+    /// isn't installed. It's started again after the delay, `None` when it's left stopped
+    Stopped(ServerId, LspClientError, Option<Duration>),
 
-    Response(LspResponse),
-    Notification(LspClientNotification),
+    Response(RequestId, LspResponse),
+    /// The request with the id failed, or the server answered it with an error
+    RequestFailed(RequestId, LspClientError),
+    /// A notification of the server
+    Notification(ServerId, LspClientNotification),
+    /// A request of the server that needs the user or the state of the gui to be answered
+    Request(LspServerRequest),
 }
 
 /// A message from the gui to the Stream
 #[derive(Debug, Clone)]
 pub enum LspCommand {
-    Request(LspRequest),
+    Request(RequestId, LspRequest),
+    /// Cancels the request with the id, if it's still waiting for a response
+    Cancel(RequestId),
     Notification(LspServerNotification),
 }
 
+/// Identifies a request of the gui, so its response can be matched to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RequestId(u64);
+
 /// A notification sent from the server to the client
 #[derive(Debug, Clone, Default)]
 pub enum LspClientNotification {
     Diagnostics(ClientDiagnostics),
-    Progress,
+    /// The progress of work the server is doing, e.g. indexing, with the token that tells the
+    /// works apart
+    Progress(NumberOrString, WorkDoneProgress),
     ErrorMessage(String),
     Initialized,
+    /// The inlay hints changed, e.g. once the project was loaded, so they're asked for again
+    InlayHintRefresh,
+    /// What the server said besides its answers, or a message of the trace
+    Output(ServerOutput),
 
     #[default]
     UnknownMessage,
 }
 
+/// A request sent from the server to the client, which the gui answers through its responder
+#[derive(Debug, Clone)]
+pub enum LspServerRequest {
+    /// Changes to make, answered with whether they were made
+    ApplyEdit {
+        label: Option<String>,
+        edit: WorkspaceEdit,
+        responder: Responder<ApplyWorkspaceEditResponse>,
+    },
+    /// A message with actions to pick from, answered with the action that was picked or `None`
+    /// when it was dismissed
+    ShowMessage {
+        typ: MessageType,
+        message: String,
+        actions: Vec<MessageActionItem>,
+        responder: Responder<Option<MessageActionItem>>,
+    },
+}
+
+/// Answers a request of the server once. Dropping every clone without answering leaves the
+/// default answer to the client
+pub struct Responder<T>(Arc<Mutex<Option<oneshot::Sender<T>>>>);
+
+impl<T> Responder<T> {
+    pub fn new() -> (Self, oneshot::Receiver<T>) {
+        let (sender, receiver) = oneshot::channel();
+        (Self(Arc::new(Mutex::new(Some(sender)))), receiver)
+    }
+
+    /// Sends the answer, unless a clone of the responder already did
+    pub fn respond(&self, answer: T) {
+        let sender = self.0.lock().ok().and_then(|mut sender| sender.take());
+        if let Some(sender) = sender {
+            // The server may have stopped waiting
+            let _ = sender.send(answer);
+        }
+    }
+}
+
+impl<T> Clone for Responder<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> fmt::Debug for Responder<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Responder")
+    }
+}
+
 /// A notification sent from the client to the server
 #[derive(Debug, Clone)]
 pub enum LspServerNotification {
@@ -62,12 +442,80 @@ pub enum LspServerNotification {
 #[derive(Debug, Clone)]
 pub enum LspRequest {
     Shutdown,
+    /// All the semantic tokens of a document
+    SemanticTokens(Url),
+    /// The changes to the semantic tokens of a document since the response with the given id
+    SemanticTokensDelta(Url, String),
+    /// Information about the symbol at a position, with its column in UTF-16 code units
+    Hover(Url, Position),
+    /// The completions at a position, along with the character that triggered them
+    Completion(Url, Position, Option<String>),
+    /// The details of a completion item that were left out of the completions
+    ResolveCompletion(CompletionItem),
+    /// The signatures of the call at a position, along with the character that triggered them
+    /// and the signatures that are already shown
+    SignatureHelp(Url, Position, Option<String>, Option<SignatureHelp>),
+    /// Where the symbol at a position is defined, declared, etc.
+    Goto(GotoKind, Url, Position),
+    /// Everywhere the symbol at a position is used, including its declaration
+    References(Url, Position),
+    /// Whether the symbol at a position can be renamed, and the range of its name
+    PrepareRename(Url, Position),
+    /// The edit that renames the symbol at a position to the new name
+    Rename(Url, Position, String),
+    /// The code actions of a range and of the diagnostics in it, and whether the user asked for
+    /// them rather than the editor
+    CodeActions(Url, lsp_types::Range, Vec<Diagnostic>, bool),
+    /// Runs a command of a code action on the server
+    ExecuteCommand(Command),
+    /// The edits that format a whole document
+    Formatting(Url, FormattingOptions),
+    /// The edits that format a range of a document
+    RangeFormatting(Url, lsp_types::Range, FormattingOptions),
+    /// The edits that format the code around a position after the character was typed there
+    OnTypeFormatting(Url, Position, String, FormattingOptions),
+    /// The inlay hints of a range of a document
+    InlayHints(Url, lsp_types::Range),
+    /// The details of an inlay hint that were left out of the hints, like its tooltip
+    ResolveInlayHint(InlayHint),
+}
+
+/// The locations of a symbol that can be gone to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GotoKind {
+    Definition,
+    Declaration,
+    TypeDefinition,
+    Implementation,
 }
 
 /// The response to an [`LspRequest`]
 #[derive(Debug, Clone)]
 pub enum LspResponse {
     None,
+    SemanticTokens(Url, SemanticTokens),
+    /// The changes since the response with the given id, which the delta applies to
+    SemanticTokensDelta(Url, String, SemanticTokensFullDeltaResult),
+    /// The hover at the requested position, `None` when there's nothing to show
+    Hover(Url, Position, Option<HoverContents>),
+    /// The completions, and whether typing further should ask for them again rather than filter
+    /// them
+    Completion(Url, Vec<CompletionItem>, bool),
+    ResolvedCompletion(CompletionItem),
+    /// The signatures of the call, `None` when the position isn't in one
+    SignatureHelp(Url, Option<SignatureHelp>),
+    /// The locations that were asked for, none when the server doesn't know any
+    Locations(GotoKind, Vec<Location>),
+    References(Vec<Location>),
+    /// Where the rename was asked for, and what can be renamed there, `None` when nothing can
+    PrepareRename(Url, Position, Option<PrepareRenameResponse>),
+    /// The changes that rename the symbol, `None` when there's nothing to change
+    Rename(Option<WorkspaceEdit>),
+    CodeActions(Vec<CodeActionOrCommand>),
+    /// The edits that format the document, of any of the formatting requests
+    Formatting(Vec<TextEdit>),
+    InlayHints(Url, Vec<InlayHint>),
+    ResolvedInlayHint(InlayHint),
 }
 
 //
@@ -75,13 +523,38 @@ pub enum LspResponse {
 /// Messages for document synchronisation
 #[derive(Debug, Clone)]
 pub enum Synchronise {
-    DidChange(String, Range),
+    /// The changes in the order they were made, and the version of the document after them
+    DidChange(Vec<TextChange>, i32),
     DidClose,
-    DidOpen(String),
+    /// The text of the document and its language identifier
+    DidOpen(String, String),
     DidSave(Option<String>),
     WillSave,
 }
 
+/// A change to the text of a document
+#[derive(Debug, Clone)]
+pub struct TextChange {
+    /// The range that was replaced, with its columns in UTF-16 code units. `None` replaces the
+    /// whole text
+    pub range: Option<Range>,
+    pub text: String,
+}
+
+impl TextChange {
+    pub fn new(range: Range, text: String) -> Self {
+        Self {
+            range: Some(range),
+            text,
+        }
+    }
+
+    /// Replaces the whole text, for servers that only take full text
+    pub fn full(text: String) -> Self {
+        Self { range: None, text }
+    }
+}
+
 pub fn file_path(relative_path: &str) -> String {
     let path = PathBuf::from(relative_path);
     let absolute_path = fs::canonicalize(path).unwrap();
@@ -0,0 +1,203 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    task::{Context, Poll, ready},
+    time::Instant,
+};
+
+use async_lsp::lsp_types::MessageType;
+use futures::io::{AsyncRead, AsyncWrite};
+use serde_json::Value;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::LspClientNotification;
+
+/// What a server said besides its answers, which is shown in the output panel
+#[derive(Debug, Clone)]
+pub enum ServerOutput {
+    /// A line the server wrote to its stderr
+    Stderr(String),
+    /// A `window/logMessage`
+    Log(MessageType, String),
+    /// A `window/showMessage`
+    Message(MessageType, String),
+    /// A message that went between the server and the editor, while the trace is on
+    Trace(TraceMessage),
+}
+
+/// A JSON-RPC message as it went over the pipe
+#[derive(Debug, Clone)]
+pub struct TraceMessage {
+    /// Whether the editor sent it, rather than the server
+    pub sent: bool,
+    pub at: Instant,
+    pub kind: TraceKind,
+    /// The id of the request, or of the request the response answers
+    pub id: Option<String>,
+    /// The method of the request or notification
+    pub method: Option<String>,
+    pub json: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceKind {
+    Request,
+    Response,
+    Notification,
+}
+
+impl TraceMessage {
+    /// Reads what kind of message the JSON is, `None` when it isn't a JSON-RPC message
+    pub fn parse(sent: bool, at: Instant, json: String) -> Option<Self> {
+        let value: Value = serde_json::from_str(&json).ok()?;
+        let id = value.get("id").map(|id| match id {
+            Value::String(id) => id.clone(),
+            id => id.to_string(),
+        });
+        let method = value
+            .get("method")
+            .and_then(Value::as_str)
+            .map(ToOwned::to_owned);
+        let kind = match (&id, &method) {
+            (Some(_), Some(_)) => TraceKind::Request,
+            (Some(_), None) => TraceKind::Response,
+            (None, Some(_)) => TraceKind::Notification,
+            (None, None) => return None,
+        };
+        Some(Self {
+            sent,
+            at,
+            kind,
+            id,
+            method,
+            json,
+        })
+    }
+}
+
+/// Cuts the messages out of the bytes going one way over the pipe, which come in pieces of any
+/// size
+#[derive(Debug, Default)]
+pub struct Frames {
+    buffer: Vec<u8>,
+}
+
+impl Frames {
+    /// Adds the bytes, returning the content of the messages they complete
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(bytes);
+        let mut messages = Vec::new();
+        while let Some(header_end) = self
+            .buffer
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .map(|position| position + 4)
+        {
+            // A header without the length can't be made sense of, so it's skipped
+            let Some(length) = content_length(&self.buffer[..header_end]) else {
+                self.buffer.drain(..header_end);
+                continue;
+            };
+            let end = header_end + length;
+            if self.buffer.len() < end {
+                break;
+            }
+            messages.push(String::from_utf8_lossy(&self.buffer[header_end..end]).into_owned());
+            self.buffer.drain(..end);
+        }
+        messages
+    }
+}
+
+fn content_length(header: &[u8]) -> Option<usize> {
+    String::from_utf8_lossy(header).lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if !name.trim().eq_ignore_ascii_case("content-length") {
+            return None;
+        }
+        value.trim().parse().ok()
+    })
+}
+
+/// Passes the bytes of a pipe to the server through, tracing the messages in them while the
+/// trace is on
+pub struct Tap<T> {
+    inner: T,
+    /// Whether the editor writes to the pipe, rather than the server
+    sent: bool,
+    frames: Frames,
+    trace: Arc<AtomicBool>,
+    sender: UnboundedSender<LspClientNotification>,
+}
+
+impl<T> Tap<T> {
+    pub fn new(
+        inner: T,
+        sent: bool,
+        trace: Arc<AtomicBool>,
+        sender: UnboundedSender<LspClientNotification>,
+    ) -> Self {
+        Self {
+            inner,
+            sent,
+            frames: Frames::default(),
+            trace,
+            sender,
+        }
+    }
+
+    fn record(&mut self, bytes: &[u8]) {
+        // The messages are cut out while the trace is off too, so it starts at a message once
+        // it's turned on
+        let messages = self.frames.push(bytes);
+        if !self.trace.load(Ordering::Relaxed) {
+            return;
+        }
+        let at = Instant::now();
+        for json in messages {
+            let Some(message) = TraceMessage::parse(self.sent, at, json) else {
+                continue;
+            };
+            // The gui may be gone, the server goes on anyway
+            let _ = self
+                .sender
+                .send(LspClientNotification::Output(ServerOutput::Trace(message)));
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Tap<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let read = ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        self.record(&buf[..read]);
+        Poll::Ready(Ok(read))
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Tap<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let written = ready!(Pin::new(&mut self.inner).poll_write(cx, buf))?;
+        self.record(&buf[..written]);
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
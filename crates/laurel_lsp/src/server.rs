@@ -0,0 +1,33 @@
+use std::{fmt, path::PathBuf};
+
+use async_lsp::lsp_types::LSPAny;
+
+/// Identifies a language server, one runs for each language and workspace root
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ServerId {
+    /// The language identifier of the documents the server is for, e.g. `rust`
+    pub language: String,
+    /// The root of the workspace the server was started in
+    pub root: PathBuf,
+}
+
+impl fmt::Display for ServerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in {}", self.language, self.root.display())
+    }
+}
+
+/// How a language server is started
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerConfig {
+    pub command: String,
+    pub args: Vec<String>,
+    /// Sent as the `initializationOptions` of the `initialize` request, and the settings that
+    /// answer the server when it asks for its `workspace/configuration`
+    pub initialization_options: Option<LSPAny>,
+    /// The section the settings are in when the server asks for them, e.g. `rust-analyzer` for
+    /// `rust-analyzer.cargo.features`. Without it the sections are looked up from the top
+    pub settings_section: Option<String>,
+    /// Files or directories that mark the root of a workspace, e.g. `Cargo.toml`
+    pub root_markers: Vec<String>,
+}